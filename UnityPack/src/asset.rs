@@ -12,17 +12,81 @@ use binaryreader::*;
 use error::{Error, Result};
 use lzma;
 use object::ObjectInfo;
-use resources::default_type_metadata;
+use resources::default_type_lookup;
 use std::collections::HashMap;
+use std::fmt;
 use std::io;
 use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::sync::Arc;
 use typetree::{TypeMetadata, TypeNode};
 use uuid::Uuid;
+use version::UnityVersion;
+
+/// Problem found while loading an asset which does not prevent loading it
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetWarning {
+    /// No type tree is known for the class, its objects cannot be decoded
+    MissingTypeTree { class_id: i16, version: String },
+    /// The fallback type tree of a class does not match the hash stored in the
+    /// asset, its objects will most likely not decode correctly
+    TypeTreeHashMismatch {
+        class_id: i16,
+        version: String,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    /// The fallback type tree of a class was taken from another Unity version
+    /// and the asset stores no hash to verify it against
+    FallbackTypeTree {
+        class_id: i16,
+        version: String,
+        fallback_version: String,
+    },
+}
+
+impl fmt::Display for AssetWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = |hash: &[u8]| -> String { hash.iter().map(|b| format!("{:02x}", b)).collect() };
+        match *self {
+            AssetWarning::MissingTypeTree {
+                class_id,
+                ref version,
+            } => write!(
+                f,
+                "{:?} is absent from the type database for {}",
+                class_id, version
+            ),
+            AssetWarning::TypeTreeHashMismatch {
+                class_id,
+                ref version,
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "Type tree of {:?} for {} has hash {} but the asset expects {}",
+                class_id,
+                version,
+                hex(actual),
+                hex(expected)
+            ),
+            AssetWarning::FallbackTypeTree {
+                class_id,
+                ref version,
+                ref fallback_version,
+            } => write!(
+                f,
+                "{:?} is absent from the type database for {}, using the type tree of {}",
+                class_id, version, fallback_version
+            ),
+        }
+    }
+}
 
 pub struct Asset {
     pub name: String,
     pub bundle_offset: u64,
+    /// Size of the bundle node holding the asset, 0 if the bundle does not store it
+    pub node_size: u64,
     pub objects: HashMap<i64, ObjectInfo>,
     pub is_loaded: bool,
     pub endianness: Endianness,
@@ -37,6 +101,12 @@ pub struct Asset {
     pub format: u32,
     pub data_offset: u32,
     pub long_object_ids: bool,
+    /// Unity version of the bundle the asset belongs to, see `AssetBundle::unity_version`
+    pub target_version: String,
+    /// Problems found while loading the objects
+    pub warnings: Vec<AssetWarning>,
+    /// Fail loading instead of warning when a fallback type tree does not match the stored hash
+    pub strict_type_hashes: bool,
 }
 
 impl Asset {
@@ -48,6 +118,7 @@ impl Asset {
 
         let mut asset = Asset {
             bundle_offset: 0,
+            node_size: 0,
             name: String::new(),
             objects: HashMap::new(),
             is_loaded: false,
@@ -63,6 +134,9 @@ impl Asset {
             format: 0,
             data_offset: 0,
             long_object_ids: false,
+            target_version: bundle.unity_version().to_string(),
+            warnings: Vec::new(),
+            strict_type_hashes: false,
         };
 
         {
@@ -122,8 +196,27 @@ impl Asset {
         Ok(asset)
     }
 
+    /// Unity version which generated the asset. Falls back to the version of the
+    /// bundle if the asset does not specify it or the version is stripped.
+    pub fn unity_version(&self) -> &str {
+        match self.tree {
+            Some(ref tree) if tree.version().is_some() => &tree.generator_version,
+            _ => &self.target_version,
+        }
+    }
+
+    /// Parsed `unity_version`, `None` if neither the asset nor the bundle store a valid version
+    pub fn version(&self) -> Option<UnityVersion> {
+        match UnityVersion::parse(self.unity_version()) {
+            Ok(version) if !version.is_stripped() => Some(version),
+            _ => None,
+        }
+    }
+
+    /// Returns true for nodes holding raw data such as streamed textures
+    /// (`.resS`) and audio (`.resource`) instead of objects
     pub fn is_resource(&self) -> bool {
-        self.name.as_str().ends_with(".resource")
+        self.name.as_str().ends_with(".resource") || self.name.as_str().ends_with(".resS")
     }
 
     pub fn load_objects(&mut self, signature: &mut Signature) -> io::Result<()> {
@@ -241,35 +334,64 @@ impl Asset {
     }
 
     fn register_object(&mut self, obj: ObjectInfo) -> Result<()> {
-        let tree = match self.tree {
-            Some(ref t) => t,
-            None => return Ok(()),
-        };
+        let version = self.unity_version().to_string();
+        let warning = {
+            let tree = match self.tree {
+                Some(ref t) => t,
+                None => return Ok(()),
+            };
 
-        match tree.type_trees.get(&obj.type_id) {
-            Some(o_type) => {
-                self.types.insert(obj.type_id, o_type.clone());
-            }
-            None => {
-                match self.types.get(&obj.type_id) {
-                    Some(_) => {}
-                    None => {
-                        let trees = &default_type_metadata()?.type_trees;
-                        match trees.get(&(obj.class_id.into())) {
-                            Some(o) => {
-                                self.types.insert(obj.type_id, o.clone());
+            match tree.type_trees.get(&obj.type_id) {
+                Some(o_type) => {
+                    self.types.insert(obj.type_id, o_type.clone());
+                    None
+                }
+                None if self.types.contains_key(&obj.type_id) => None,
+                None => match default_type_lookup(&version, obj.class_id.into()) {
+                    Some(found) => {
+                        let warning = match tree.type_hash(obj.type_id as i32) {
+                            Some(expected) => {
+                                let actual = found.tree.compute_hash();
+                                if expected == actual {
+                                    None
+                                } else {
+                                    Some(AssetWarning::TypeTreeHashMismatch {
+                                        class_id: obj.class_id,
+                                        version: version.clone(),
+                                        expected: expected.to_vec(),
+                                        actual: actual.to_vec(),
+                                    })
+                                }
                             }
-                            None => {
-                                // log warning
-                                println!("Warning: {:?} is absent from structs.dat", obj.class_id);
-                                // self.types.insert(obj.type_id, None)
+                            None if found.is_fallback(&version) => {
+                                Some(AssetWarning::FallbackTypeTree {
+                                    class_id: obj.class_id,
+                                    version: version.clone(),
+                                    fallback_version: found.version.clone(),
+                                })
                             }
+                            None => None,
                         };
+                        self.types.insert(obj.type_id, found.tree);
+                        warning
                     }
-                };
+                    None => Some(AssetWarning::MissingTypeTree {
+                        class_id: obj.class_id,
+                        version: version.clone(),
+                    }),
+                },
             }
         };
 
+        if let Some(warning) = warning {
+            if self.strict_type_hashes {
+                if let AssetWarning::TypeTreeHashMismatch { .. } = warning {
+                    return Err(Error::AssetError(warning.to_string()));
+                }
+            }
+            self.warnings.push(warning);
+        }
+
         if self.objects.get(&obj.path_id).is_some() {
             return Err(Error::AssetError(format!(
                 "Duplicate asset object: {} (path_id={})",
@@ -281,6 +403,15 @@ impl Asset {
         Ok(())
     }
 
+    /// Compares a type tree with the hash the asset stores for `type_id`.
+    /// Returns `None` if the asset has no hash to compare with.
+    pub fn verify_type_tree(&self, type_id: i64, tree: &TypeNode) -> Option<bool> {
+        match self.tree {
+            Some(ref metadata) => metadata.verify_type_tree(type_id as i32, tree),
+            None => None,
+        }
+    }
+
     pub fn read_id<R: Read + Seek + Teller>(&self, buffer: &mut R) -> io::Result<i64> {
         if self.format >= 14 {
             return buffer.read_i64(self.endianness);
@@ -15,6 +15,7 @@ use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::{BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom};
+use version::UnityVersion;
 
 fn decompress_data(data: &[u8], compression_type: &CompressionType) -> Result<Vec<u8>> {
     match *compression_type {
@@ -110,6 +111,28 @@ pub struct AssetBundle {
 }
 
 impl AssetBundle {
+    /// Unity version the bundle was built with. The header stores a generic
+    /// version such as `5.x.x` followed by the exact revision, the revision is
+    /// preferred unless it is stripped.
+    pub fn unity_version(&self) -> &str {
+        match UnityVersion::parse(&self.generator_version) {
+            Ok(ref version) if !version.is_stripped() => &self.generator_version,
+            _ => &self.target_version,
+        }
+    }
+
+    /// Parsed `unity_version`, `None` if it is stripped or invalid
+    pub fn version(&self) -> Option<UnityVersion> {
+        match UnityVersion::parse(self.unity_version()) {
+            Ok(version) if !version.is_stripped() => Some(version),
+            _ => None,
+        }
+    }
+
+    pub fn generator_version(&self) -> &str {
+        &self.generator_version
+    }
+
     pub fn load_from_file(file_path: &str) -> Result<AssetBundle> {
         // open file
         let file = File::open(file_path)?;
@@ -206,10 +229,11 @@ impl AssetBundle {
         self.signature =
             Signature::UnityFS(ArchiveBlockStorageReader::new(buffer.take_buffer(), blocks));
 
-        for (n_offset, _, _, n_name) in nodes {
+        for (n_offset, n_size, _, n_name) in nodes {
             self.signature.seek(SeekFrom::Start(n_offset))?;
             let mut asset = Asset::new(self)?;
             asset.name = n_name;
+            asset.node_size = n_size;
             self.assets.push(asset);
         }
 
@@ -279,6 +303,22 @@ impl AssetBundle {
         self.assets.len()
     }
 
+    /// Returns a reader over the raw bytes of node `idx`, such as the data
+    /// of a resource node. Only UnityFS bundles store the node sizes.
+    pub fn node_reader(
+        &mut self,
+        idx: usize,
+    ) -> Result<io::Take<&mut ArchiveBlockStorageReader<File>>> {
+        let (offset, size) = (self.assets[idx].bundle_offset, self.assets[idx].node_size);
+        match self.signature {
+            Signature::UnityFS(ref mut buf) => {
+                buf.seek(SeekFrom::Start(offset))?;
+                Ok(buf.take(size))
+            }
+            _ => Err(Error::FeatureNotImplementedError),
+        }
+    }
+
     pub fn resolve_asset(&mut self, idx: usize) -> Result<()> {
         if !self.assets[idx].is_loaded {
             self.assets[idx].load_objects(&mut self.signature)?;
@@ -108,6 +108,13 @@ pub trait ReadExtras: io::Read {
             Endianness::Big => ReadBytesExt::read_f32::<BigEndian>(self),
         }
     }
+
+    fn read_f64(&mut self, endiannes: Endianness) -> io::Result<f64> {
+        match endiannes {
+            Endianness::Little => ReadBytesExt::read_f64::<LittleEndian>(self),
+            Endianness::Big => ReadBytesExt::read_f64::<BigEndian>(self),
+        }
+    }
 }
 impl<R: io::Read + ?Sized> ReadExtras for R {}
 
@@ -206,6 +213,11 @@ where
         self.cursor += 4;
         ReadExtras::read_f32(&mut self.buffer, self.endianness)
     }
+
+    pub fn read_f64(&mut self) -> io::Result<f64> {
+        self.cursor += 8;
+        ReadExtras::read_f64(&mut self.buffer, self.endianness)
+    }
 }
 
 impl<R> Teller for BinaryReader<R>
@@ -220,7 +232,7 @@ where
         let old = self.tell() as i64;
         let new = (old + 3) & -4;
         if new > old {
-            let _ = self.seek(SeekFrom::Start(new as u64));
+            let _ = self.seek(SeekFrom::Current(new - old));
         }
     }
 }
@@ -248,7 +260,14 @@ impl<R> Seek for BinaryReader<R>
 where
     R: Read + Seek,
 {
+    /// Relative seeks keep the buffered data, so that skipping short
+    /// values does not refill the buffer
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        if let SeekFrom::Current(offset) = pos {
+            self.buffer.seek_relative(offset)?;
+            self.cursor = (self.cursor as i64 + offset) as u64;
+            return Ok(self.cursor);
+        }
         match self.buffer.seek(pos) {
             Ok(p) => {
                 self.cursor = p;
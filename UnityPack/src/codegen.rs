@@ -0,0 +1,521 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Generation of Rust structs with readers from type trees.
+//!
+//! The generated structs implement `ReadTypeTree` and decode object data
+//! straight from a `BinaryReader`, without building an `ObjectValue` tree.
+//! Generated code is usually written by a build script:
+//!
+//! ```ignore
+//! // build.rs
+//! let mut generator = unitypack::codegen::CodeGenerator::new();
+//! generator.add_class("5.5.2f1", 28).unwrap();
+//! for warning in generator.warnings() {
+//!     println!("cargo:warning={}", warning);
+//! }
+//! generator.write_to_out_dir("unity_types.rs").unwrap();
+//!
+//! // src/unity_types.rs
+//! include!(concat!(env!("OUT_DIR"), "/unity_types.rs"));
+//! ```
+//!
+//! The generated file contains `use` declarations, so it should be included
+//! into a module of its own.
+
+use asset::Asset;
+use binaryreader::{BinaryReader, ReadExtras};
+use error::{Error, Result};
+use resources::default_type_lookup;
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use typedb::{TypeDatabase, TypeLookup};
+use typeexport::{primitive_type, rust_field_identifier, type_identifier, Language, StructNames};
+use typetree::{TypeMetadata, TypeNode};
+
+/// Values which can be decoded from object data. Implemented by the primitive
+/// types and by every generated struct.
+pub trait ReadTypeTree: Sized {
+    fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<Self>;
+}
+
+macro_rules! impl_read_type_tree {
+    ($t:ty, $read:ident) => {
+        impl ReadTypeTree for $t {
+            fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<$t> {
+                Ok(reader.$read()?)
+            }
+        }
+    };
+}
+
+impl_read_type_tree!(i8, read_i8);
+impl_read_type_tree!(u16, read_u16);
+impl_read_type_tree!(i16, read_i16);
+impl_read_type_tree!(u32, read_u32);
+impl_read_type_tree!(i32, read_i32);
+impl_read_type_tree!(u64, read_u64);
+impl_read_type_tree!(i64, read_i64);
+impl_read_type_tree!(f32, read_f32);
+impl_read_type_tree!(f64, read_f64);
+
+impl ReadTypeTree for u8 {
+    fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<u8> {
+        Ok(ReadExtras::read_u8(reader)?)
+    }
+}
+
+impl ReadTypeTree for bool {
+    fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<bool> {
+        Ok(ReadExtras::read_u8(reader)? != 0)
+    }
+}
+
+/// Strings which are not valid utf-8 are decoded lossily
+impl ReadTypeTree for String {
+    fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<String> {
+        let data = read_byte_array(reader)?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+}
+
+/// Reads a length prefixed array, `read_element` decodes a single element
+pub fn read_array<R, T, F>(reader: &mut BinaryReader<R>, mut read_element: F) -> Result<Vec<T>>
+where
+    R: Read + Seek,
+    F: FnMut(&mut BinaryReader<R>) -> Result<T>,
+{
+    let len = reader.read_u32()? as usize;
+    let mut result = Vec::with_capacity(len);
+    for _ in 0..len {
+        result.push(read_element(reader)?);
+    }
+    Ok(result)
+}
+
+/// Reads a length prefixed array of bytes
+pub fn read_byte_array<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<Vec<u8>> {
+    let len = reader.read_u32()? as usize;
+    Ok(reader.read_bytes(len)?)
+}
+
+/// Collects type trees and generates Rust structs with readers for them.
+/// Types which are shared between type trees are generated only once, types
+/// with the same name but a different layout get a numbered suffix.
+pub struct CodeGenerator {
+    crate_path: String,
+    names: StructNames,
+    definitions: String,
+    warnings: Vec<String>,
+}
+
+impl Default for CodeGenerator {
+    fn default() -> CodeGenerator {
+        CodeGenerator::new()
+    }
+}
+
+impl CodeGenerator {
+    pub fn new() -> CodeGenerator {
+        CodeGenerator {
+            crate_path: "unitypack".to_string(),
+            names: StructNames::default(),
+            definitions: String::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Sets the path under which the generated code refers to this crate, `unitypack` by default
+    pub fn crate_path(mut self, path: &str) -> CodeGenerator {
+        self.crate_path = path.to_string();
+        self
+    }
+
+    /// Generates the struct of a type tree and of the types it uses, returns its name
+    pub fn add_type_tree(&mut self, tree: &TypeNode) -> String {
+        self.type_ref(tree)
+    }
+
+    /// Generates structs for every type tree of the metadata
+    pub fn add_metadata(&mut self, metadata: &TypeMetadata) {
+        let mut class_ids: Vec<&i64> = metadata.type_trees.keys().collect();
+        class_ids.sort();
+        for class_id in class_ids {
+            self.add_type_tree(&metadata.type_trees[class_id]);
+        }
+    }
+
+    /// Generates structs for the type trees stored in an asset
+    pub fn add_asset(&mut self, asset: &Asset) {
+        if let Some(ref tree) = asset.tree {
+            self.add_metadata(tree);
+        }
+        let mut type_ids: Vec<&i64> = asset.types.keys().collect();
+        type_ids.sort();
+        for type_id in type_ids {
+            self.add_type_tree(&asset.types[type_id]);
+        }
+    }
+
+    /// Generates the struct of a class from the process wide type tree database
+    pub fn add_class(&mut self, version: &str, class_id: i64) -> Result<String> {
+        let found = default_type_lookup(version, class_id);
+        self.add_lookup(found, version, class_id)
+    }
+
+    /// Generates the struct of a class from a type database
+    pub fn add_database_class(
+        &mut self,
+        database: &TypeDatabase,
+        version: &str,
+        class_id: i64,
+    ) -> Result<String> {
+        self.add_lookup(database.lookup(version, class_id), version, class_id)
+    }
+
+    /// Problems found while adding classes, e.g. type trees which were taken
+    /// from another Unity version than the requested one
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns the generated source code
+    pub fn generate(&self) -> String {
+        let mut output = String::new();
+        let _ = writeln!(
+            output,
+            "// Generated by unitypack::codegen from Unity type trees, do not edit."
+        );
+        let _ = writeln!(output, "#[allow(unused_imports)]");
+        let _ = writeln!(
+            output,
+            "use {}::binaryreader::{{BinaryReader, Teller}};",
+            self.crate_path
+        );
+        let _ = writeln!(
+            output,
+            "#[allow(unused_imports)]\nuse {}::codegen::{{read_array, read_byte_array, ReadTypeTree}};",
+            self.crate_path
+        );
+        let _ = writeln!(output, "use {}::error::Result;", self.crate_path);
+        let _ = writeln!(output, "use std::io::{{Read, Seek}};");
+        output.push_str(&self.definitions);
+        output
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        File::create(path)?.write_all(self.generate().as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the generated code into the `OUT_DIR` of the running build script
+    pub fn write_to_out_dir(&self, file_name: &str) -> Result<PathBuf> {
+        let out_dir =
+            match env::var_os("OUT_DIR") {
+                Some(out_dir) => out_dir,
+                None => return Err(Error::ResourceError(
+                    "OUT_DIR is not set, generated code can only be written from a build script"
+                        .to_string(),
+                )),
+            };
+        let path = Path::new(&out_dir).join(file_name);
+        self.write_to_file(&path)?;
+        Ok(path)
+    }
+
+    fn add_lookup(
+        &mut self,
+        found: Option<TypeLookup>,
+        version: &str,
+        class_id: i64,
+    ) -> Result<String> {
+        let found = match found {
+            Some(found) => found,
+            None => return Err(missing_class(version, class_id)),
+        };
+        if found.is_fallback(version) {
+            self.warnings.push(format!(
+                "No type tree for class {} in Unity {}, using the one of Unity {}",
+                class_id, version, found.version
+            ));
+        }
+        Ok(self.add_type_tree(&found.tree))
+    }
+
+    /// Returns the Rust type of a node, generating the structs it depends on
+    fn type_ref(&mut self, node: &TypeNode) -> String {
+        let t = node.type_name.as_str();
+        if let Some(primitive) = primitive_type(t, Language::Rust) {
+            return primitive.to_string();
+        }
+        if let Some(element) = array_element(node) {
+            if is_byte(element) {
+                return "Vec<u8>".to_string();
+            }
+            return format!("Vec<{}>", self.type_ref(element));
+        }
+        if t == "pair" && node.children.len() == 2 {
+            let first = self.type_ref(&node.children[0]);
+            let second = self.type_ref(&node.children[1]);
+            return format!("({}, {})", first, second);
+        }
+        let base_name = if t.starts_with("PPtr<") {
+            "PPtr".to_string()
+        } else {
+            type_identifier(t)
+        };
+        self.emit_struct(base_name, node)
+    }
+
+    /// Returns an expression which reads the value of a node and whether the
+    /// reader has to be aligned after it
+    fn read_expr(&mut self, node: &TypeNode) -> (String, bool) {
+        let t = node.type_name.as_str();
+        let mut align = node.post_align();
+        let expr = if primitive_type(t, Language::Rust).is_none() && array_element(node).is_some() {
+            let array = if node.is_array {
+                node
+            } else {
+                &node.children[0]
+            };
+            align |= array.post_align();
+            let element = &array.children[1];
+            if is_byte(element) {
+                "read_byte_array(reader)?".to_string()
+            } else {
+                let (element_expr, element_align) = self.read_expr(element);
+                let read_element = match element_expr.trim_end_matches("(reader)?") {
+                    // plain reads are passed as a function
+                    path if !element_align && path.ends_with("::read_type_tree") => {
+                        path.to_string()
+                    }
+                    _ => format!(
+                        "|reader| Ok({})",
+                        aligned_expr(element_expr.clone(), element_align)
+                    ),
+                };
+                format!("read_array(reader, {})?", read_element)
+            }
+        } else if t == "pair" && node.children.len() == 2 {
+            let (first, first_align) = self.read_expr(&node.children[0]);
+            let (second, second_align) = self.read_expr(&node.children[1]);
+            format!(
+                "({}, {})",
+                aligned_expr(first, first_align),
+                aligned_expr(second, second_align)
+            )
+        } else {
+            if t == "string" {
+                align |= node.children.first().is_some_and(|c| c.post_align());
+            }
+            format!("{}::read_type_tree(reader)?", self.type_ref(node))
+        };
+        (expr, align)
+    }
+
+    fn emit_struct(&mut self, base_name: String, node: &TypeNode) -> String {
+        let (name, generated) = self.names.name(base_name, node);
+        if generated {
+            return name;
+        }
+
+        // field types are resolved before the struct is written out
+        let mut fields = Vec::with_capacity(node.children.len());
+        for child in &node.children {
+            let type_ref = self.type_ref(child);
+            let (expr, align) = self.read_expr(child);
+            fields.push((
+                rust_field_identifier(&child.field_name),
+                type_ref,
+                expr,
+                align,
+            ));
+        }
+
+        let out = &mut self.definitions;
+        let _ = writeln!(out);
+        let _ = writeln!(out, "/// {} (version {})", node.type_name, node.version());
+        // fields keep their serialized names and not every field is read
+        let _ = writeln!(out, "#[allow(dead_code, non_snake_case)]");
+        let _ = writeln!(out, "#[derive(Debug, Clone)]");
+        let _ = writeln!(out, "pub struct {} {{", name);
+        for (field, type_ref, _, _) in &fields {
+            let _ = writeln!(out, "    pub {}: {},", field, type_ref);
+        }
+        let _ = writeln!(out, "}}\n");
+
+        let reader = if fields.is_empty() {
+            "_reader"
+        } else {
+            "reader"
+        };
+        let _ = writeln!(out, "#[allow(non_snake_case)]");
+        let _ = writeln!(out, "impl ReadTypeTree for {} {{", name);
+        let _ = writeln!(
+            out,
+            "    fn read_type_tree<R: Read + Seek>({}: &mut BinaryReader<R>) -> Result<{}> {{",
+            reader, name
+        );
+        for &(ref field, _, ref expr, align) in &fields {
+            let _ = writeln!(out, "        let {} = {};", field, expr);
+            if align {
+                let _ = writeln!(out, "        reader.align();");
+            }
+        }
+        let field_names: Vec<&str> = fields.iter().map(|f| f.0.as_str()).collect();
+        if field_names.is_empty() {
+            let _ = writeln!(out, "        Ok({} {{}})", name);
+        } else {
+            let _ = writeln!(out, "        Ok({} {{ {} }})", name, field_names.join(", "));
+        }
+        let _ = writeln!(out, "    }}\n}}");
+        name
+    }
+}
+
+fn missing_class(version: &str, class_id: i64) -> Error {
+    Error::ResourceError(format!(
+        "No type tree for class {} in Unity {}",
+        class_id, version
+    ))
+}
+
+/// Returns the element type of array nodes and of nodes wrapping an array
+fn array_element(node: &TypeNode) -> Option<&TypeNode> {
+    let array = if node.is_array {
+        node
+    } else {
+        match node.children.first() {
+            Some(child) if child.is_array => child,
+            _ => return None,
+        }
+    };
+    array.children.get(1)
+}
+
+fn is_byte(node: &TypeNode) -> bool {
+    node.type_name == "UInt8" || node.type_name == "char"
+}
+
+fn aligned_expr(expr: String, align: bool) -> String {
+    if align {
+        format!("{{ let value = {}; reader.align(); value }}", expr)
+    } else {
+        expr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binaryreader::Endianness;
+    use std::io::{BufReader, Cursor};
+    use typetree::tests::{node, pointer, sample_tree};
+
+    mod generated {
+        include!("../test_data/codegen_sample.rs");
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut generator = CodeGenerator::new().crate_path("crate");
+        assert_eq!(generator.add_type_tree(&sample_tree()), "Sample");
+        // the same layout is generated only once
+        assert_eq!(generator.add_type_tree(&sample_tree()), "Sample");
+        assert_eq!(
+            generator.generate(),
+            include_str!("../test_data/codegen_sample.rs")
+        );
+    }
+
+    #[test]
+    fn test_generate_shared_structs() {
+        let float = |name: &str| node("float", name, 4, Vec::new());
+        let vector = |name: &str| {
+            node(
+                "Vector3f",
+                name,
+                12,
+                vec![float("x"), float("y"), float("z")],
+            )
+        };
+        let tree = node(
+            "Bounds",
+            "Base",
+            -1,
+            vec![
+                vector("m_Center"),
+                vector("m_Extent"),
+                pointer("PPtr<Texture2D>", "m_Texture"),
+                pointer("PPtr<Mesh>", "m_Mesh"),
+            ],
+        );
+        let mut generator = CodeGenerator::new();
+        assert_eq!(generator.add_type_tree(&tree), "Bounds");
+        assert_eq!(generator.add_type_tree(&vector("m_Size")), "Vector3f");
+        assert_eq!(
+            generator.add_type_tree(&pointer("PPtr<GameObject>", "m_GameObject")),
+            "PPtr"
+        );
+        let code = generator.generate();
+        assert_eq!(code.matches("pub struct Vector3f").count(), 1);
+        assert_eq!(code.matches("pub struct PPtr").count(), 1);
+        assert!(code.contains("    pub m_Extent: Vector3f,"));
+        assert!(code.contains("    pub m_Mesh: PPtr,"));
+    }
+
+    #[test]
+    fn test_read_generated() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&[3, 0, 0, 0, b'a', b'b', b'c', 0]); // m_Name, aligned
+        data.extend_from_slice(&[1, 0, 0, 0]); // m_Enabled, aligned
+        data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0x80, 0x3f]); // m_Weights
+        data.extend_from_slice(&[1, 0, 0, 0, 7, 0, 0, 0, 1, 0, 0, 0, b'x', 0, 0, 0]); // m_Table
+        data.extend_from_slice(&[0, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0]); // m_Texture
+
+        let mut reader = BinaryReader::new(BufReader::new(Cursor::new(data)), Endianness::Little);
+        let sample = generated::Sample::read_type_tree(&mut reader).unwrap();
+        assert_eq!(sample.m_Name, "abc");
+        assert!(sample.m_Enabled);
+        assert_eq!(sample.m_Weights, vec![1.0]);
+        assert_eq!(sample.m_Table, vec![(7, "x".to_string())]);
+        assert_eq!(sample.m_Texture.m_PathID, 42);
+    }
+
+    #[test]
+    fn test_generate_builtin_class() {
+        let mut generator = CodeGenerator::new();
+        assert_eq!(generator.add_class("5.5.2f1", 28).unwrap(), "Texture2D");
+        let code = generator.generate();
+        assert!(code.contains("pub struct Texture2D {"));
+        assert!(code.contains("impl ReadTypeTree for Texture2D {"));
+        assert!(generator.add_class("5.5.2f1", 1_000_000).is_err());
+        assert!(generator.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_generate_fallback_class() {
+        let mut database = TypeDatabase::new();
+        database
+            .load_from_bytes(include_bytes!("../res/structs.dat"))
+            .unwrap();
+        let mut generator = CodeGenerator::new();
+        assert_eq!(
+            generator
+                .add_database_class(&database, "2019.4.31f1", 28)
+                .unwrap(),
+            "Texture2D"
+        );
+        assert_eq!(
+            generator.warnings(),
+            ["No type tree for class 28 in Unity 2019.4.31f1, using the one of Unity 5.5.2f1"]
+        );
+    }
+}
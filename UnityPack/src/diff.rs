@@ -0,0 +1,612 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Structural comparison of object trees, assets and asset bundles.
+//!
+//! Paths in the reported changes use the syntax of the `query` module, so a
+//! change can be looked up again with `ObjectValue::query`. Field names such
+//! as `weight[0]` are quoted.
+
+use asset::Asset;
+use assetbundle::{AssetBundle, Signature};
+use error::Result;
+use extras::containers::OrderedMap;
+use object::ObjectValue;
+use query::join_field;
+use serde_json;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::Read;
+
+/// Size of the chunks in which resource nodes are compared
+const RESOURCE_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Modified => write!(f, "modified"),
+        }
+    }
+}
+
+/// A single difference between two values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+    /// summary of the old value, `None` if it was added
+    pub old: Option<String>,
+    /// summary of the new value, `None` if it was removed
+    pub new: Option<String>,
+}
+
+impl Change {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "path": self.path,
+            "kind": self.kind.to_string(),
+            "old": self.old,
+            "new": self.new,
+        })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ChangeKind::Added => write!(
+                f,
+                "+ {}: {}",
+                self.path,
+                self.new.as_ref().map_or("", |s| s.as_str())
+            ),
+            ChangeKind::Removed => write!(
+                f,
+                "- {}: {}",
+                self.path,
+                self.old.as_ref().map_or("", |s| s.as_str())
+            ),
+            ChangeKind::Modified => write!(
+                f,
+                "~ {}: {} -> {}",
+                self.path,
+                self.old.as_ref().map_or("", |s| s.as_str()),
+                self.new.as_ref().map_or("", |s| s.as_str())
+            ),
+        }
+    }
+}
+
+/// Compares two values field by field and returns the list of differences
+pub fn diff_values(old: &ObjectValue, new: &ObjectValue) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_value(String::new(), old, new, &mut changes);
+    changes
+}
+
+fn push_change(
+    changes: &mut Vec<Change>,
+    path: String,
+    kind: ChangeKind,
+    old: Option<&ObjectValue>,
+    new: Option<&ObjectValue>,
+) {
+    changes.push(Change {
+        path,
+        kind,
+        old: old.map(summarize),
+        new: new.map(summarize),
+    });
+}
+
+fn as_map(value: &ObjectValue) -> Option<&OrderedMap<String, ObjectValue>> {
+    match value {
+        ObjectValue::Map(ref map) => Some(map),
+        ObjectValue::EngineObject(ref engine_object) => Some(&engine_object.map),
+        _ => None,
+    }
+}
+
+fn diff_value(path: String, old: &ObjectValue, new: &ObjectValue, changes: &mut Vec<Change>) {
+    if let (Some(old_map), Some(new_map)) = (as_map(old), as_map(new)) {
+        diff_maps(&path, old_map, new_map, changes);
+        return;
+    }
+
+    match (old, new) {
+        (ObjectValue::Array(ref a), ObjectValue::Array(ref b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let item_path = format!("{}[{}]", path, i);
+                match (a.get(i), b.get(i)) {
+                    (Some(x), Some(y)) => diff_value(item_path, x, y, changes),
+                    (Some(x), None) => {
+                        push_change(changes, item_path, ChangeKind::Removed, Some(x), None)
+                    }
+                    (None, Some(y)) => {
+                        push_change(changes, item_path, ChangeKind::Added, None, Some(y))
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        (ObjectValue::Pair((ref a1, ref a2)), ObjectValue::Pair((ref b1, ref b2))) => {
+            diff_value(join_field(&path, "first"), a1, b1, changes);
+            diff_value(join_field(&path, "second"), a2, b2, changes);
+        }
+        _ => {
+            if !values_equal(old, new) {
+                push_change(changes, path, ChangeKind::Modified, Some(old), Some(new));
+            }
+        }
+    }
+}
+
+fn diff_maps(
+    path: &str,
+    old: &OrderedMap<String, ObjectValue>,
+    new: &OrderedMap<String, ObjectValue>,
+    changes: &mut Vec<Change>,
+) {
+    for (key, old_value) in old.iter() {
+        let field_path = join_field(path, key);
+        match new.get(key) {
+            Some(new_value) => diff_value(field_path, old_value, new_value, changes),
+            None => push_change(
+                changes,
+                field_path,
+                ChangeKind::Removed,
+                Some(old_value),
+                None,
+            ),
+        }
+    }
+    for (key, new_value) in new.iter() {
+        if old.get(key).is_none() {
+            push_change(
+                changes,
+                join_field(path, key),
+                ChangeKind::Added,
+                None,
+                Some(new_value),
+            );
+        }
+    }
+}
+
+/// Compares two leaf values. Containers are never equal here, they are
+/// handled by `diff_value`.
+fn values_equal(old: &ObjectValue, new: &ObjectValue) -> bool {
+    match (old, new) {
+        (ObjectValue::Bool(a), ObjectValue::Bool(b)) => a == b,
+        (ObjectValue::U8(a), ObjectValue::U8(b)) => a == b,
+        (ObjectValue::I8(a), ObjectValue::I8(b)) => a == b,
+        (ObjectValue::U16(a), ObjectValue::U16(b)) => a == b,
+        (ObjectValue::I16(a), ObjectValue::I16(b)) => a == b,
+        (ObjectValue::U32(a), ObjectValue::U32(b)) => a == b,
+        (ObjectValue::I32(a), ObjectValue::I32(b)) => a == b,
+        (ObjectValue::U64(a), ObjectValue::U64(b)) => a == b,
+        (ObjectValue::I64(a), ObjectValue::I64(b)) => a == b,
+        // compare bit patterns so that NaN values do not show up as changes
+        (ObjectValue::Float(a), ObjectValue::Float(b)) => a.to_bits() == b.to_bits(),
+        (ObjectValue::String(ref a), ObjectValue::String(ref b)) => a == b,
+        (ObjectValue::ObjectPointer(ref a), ObjectValue::ObjectPointer(ref b)) => {
+            a.file_id == b.file_id && a.path_id == b.path_id
+        }
+        (ObjectValue::U8Array(ref a), ObjectValue::U8Array(ref b)) => a == b,
+        (ObjectValue::None, ObjectValue::None) => true,
+        _ => false,
+    }
+}
+
+/// 64-bit FNV-1a hash, stable across runs and platforms. Reports use it to
+/// tell byte arrays apart, comparisons use the bytes themselves.
+pub fn hash_bytes(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in data {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Short, human readable description of a value used in change lists
+pub fn summarize(value: &ObjectValue) -> String {
+    match value {
+        ObjectValue::Bool(v) => format!("{}", v),
+        ObjectValue::U8(v) => format!("{}", v),
+        ObjectValue::I8(v) => format!("{}", v),
+        ObjectValue::U16(v) => format!("{}", v),
+        ObjectValue::I16(v) => format!("{}", v),
+        ObjectValue::U32(v) => format!("{}", v),
+        ObjectValue::I32(v) => format!("{}", v),
+        ObjectValue::U64(v) => format!("{}", v),
+        ObjectValue::I64(v) => format!("{}", v),
+        ObjectValue::Float(v) => format!("{}", v),
+        ObjectValue::String(ref s) => format!("{:?}", s.to_string_lossy()),
+        ObjectValue::ObjectPointer(ref p) => format!(
+            "{}(file_id={}, path_id={})",
+            p.type_name, p.file_id, p.path_id
+        ),
+        ObjectValue::U8Array(ref data) => {
+            format!("<{} bytes, hash={:016x}>", data.len(), hash_bytes(data))
+        }
+        ObjectValue::Array(ref array) => format!("<array of {} items>", array.len()),
+        ObjectValue::Pair(_) => "<pair>".to_string(),
+        ObjectValue::Map(ref map) => format!("<map of {} fields>", map.len()),
+        ObjectValue::EngineObject(ref engine_object) => {
+            format!("<object of {} fields>", engine_object.map.len())
+        }
+        ObjectValue::None => "null".to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectStatus {
+    Added,
+    Removed,
+    Changed(Vec<Change>),
+}
+
+/// Difference of a single object, identified by its path id
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectDiff {
+    pub path_id: i64,
+    pub type_name: String,
+    pub status: ObjectStatus,
+}
+
+impl ObjectDiff {
+    pub fn to_json(&self) -> serde_json::Value {
+        let (status, changes) = match self.status {
+            ObjectStatus::Added => ("added", Vec::new()),
+            ObjectStatus::Removed => ("removed", Vec::new()),
+            ObjectStatus::Changed(ref changes) => {
+                ("changed", changes.iter().map(Change::to_json).collect())
+            }
+        };
+        json!({
+            "path_id": self.path_id,
+            "type": self.type_name,
+            "status": status,
+            "changes": changes,
+        })
+    }
+}
+
+/// List of objects which differ between two assets
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetDiff {
+    pub name: String,
+    pub objects: Vec<ObjectDiff>,
+}
+
+impl AssetDiff {
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "objects": self.objects.iter().map(ObjectDiff::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for AssetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Asset {}", self.name)?;
+        for object in &self.objects {
+            match object.status {
+                ObjectStatus::Added => {
+                    writeln!(f, "  + {} (path_id={})", object.type_name, object.path_id)?
+                }
+                ObjectStatus::Removed => {
+                    writeln!(f, "  - {} (path_id={})", object.type_name, object.path_id)?
+                }
+                ObjectStatus::Changed(ref changes) => {
+                    writeln!(
+                        f,
+                        "  ~ {} (path_id={}), {} change(s)",
+                        object.type_name,
+                        object.path_id,
+                        changes.len()
+                    )?;
+                    for change in changes {
+                        writeln!(f, "      {}", change)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares two loaded assets object by object, matching objects by their path id
+pub fn diff_assets(
+    old: &Asset,
+    old_signature: &mut Signature,
+    new: &Asset,
+    new_signature: &mut Signature,
+) -> Result<AssetDiff> {
+    let mut result = AssetDiff {
+        name: new.name.clone(),
+        objects: Vec::new(),
+    };
+
+    let path_ids: BTreeSet<i64> = old
+        .objects
+        .keys()
+        .chain(new.objects.keys())
+        .cloned()
+        .collect();
+
+    for path_id in path_ids {
+        match (old.objects.get(&path_id), new.objects.get(&path_id)) {
+            (Some(old_obj), Some(new_obj)) => {
+                let old_value = old_obj.read_signature(old, old_signature)?;
+                let new_value = new_obj.read_signature(new, new_signature)?;
+                let mut changes = Vec::new();
+                if old_obj.type_name != new_obj.type_name {
+                    changes.push(Change {
+                        path: String::new(),
+                        kind: ChangeKind::Modified,
+                        old: Some(old_obj.type_name.clone()),
+                        new: Some(new_obj.type_name.clone()),
+                    });
+                }
+                changes.extend(diff_values(&old_value, &new_value));
+                if !changes.is_empty() {
+                    result.objects.push(ObjectDiff {
+                        path_id,
+                        type_name: new_obj.type_name.clone(),
+                        status: ObjectStatus::Changed(changes),
+                    });
+                }
+            }
+            (Some(old_obj), None) => result.objects.push(ObjectDiff {
+                path_id,
+                type_name: old_obj.type_name.clone(),
+                status: ObjectStatus::Removed,
+            }),
+            (None, Some(new_obj)) => result.objects.push(ObjectDiff {
+                path_id,
+                type_name: new_obj.type_name.clone(),
+                status: ObjectStatus::Added,
+            }),
+            (None, None) => {}
+        }
+    }
+
+    Ok(result)
+}
+
+/// A resource node (`.resS`, `.resource`) whose raw data differs
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceDiff {
+    pub name: String,
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+/// Differences between two asset bundles, assets are matched by their node name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BundleDiff {
+    pub added_nodes: Vec<String>,
+    pub removed_nodes: Vec<String>,
+    pub changed_nodes: Vec<AssetDiff>,
+    pub changed_resources: Vec<ResourceDiff>,
+}
+
+impl BundleDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_nodes.is_empty()
+            && self.changed_resources.is_empty()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "added_nodes": self.added_nodes,
+            "removed_nodes": self.removed_nodes,
+            "changed_nodes": self.changed_nodes.iter().map(AssetDiff::to_json).collect::<Vec<_>>(),
+            "changed_resources": self.changed_resources.iter().map(|r| json!({
+                "name": r.name,
+                "old_size": r.old_size,
+                "new_size": r.new_size,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for BundleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for name in &self.added_nodes {
+            writeln!(f, "+ node {}", name)?;
+        }
+        for name in &self.removed_nodes {
+            writeln!(f, "- node {}", name)?;
+        }
+        for resource in &self.changed_resources {
+            writeln!(
+                f,
+                "~ resource {} ({} -> {} bytes)",
+                resource.name, resource.old_size, resource.new_size
+            )?;
+        }
+        for asset_diff in &self.changed_nodes {
+            write!(f, "{}", asset_diff)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two asset bundles node by node. Assets are resolved as needed,
+/// resource nodes are compared by size and contents.
+pub fn diff_bundles(old: &mut AssetBundle, new: &mut AssetBundle) -> Result<BundleDiff> {
+    let mut result = BundleDiff::default();
+
+    for asset in &new.assets {
+        if !old.assets.iter().any(|a| a.name == asset.name) {
+            result.added_nodes.push(asset.name.clone());
+        }
+    }
+
+    for old_idx in 0..old.assets.len() {
+        let name = old.assets[old_idx].name.clone();
+        let new_idx = match new.assets.iter().position(|a| a.name == name) {
+            Some(idx) => idx,
+            None => {
+                result.removed_nodes.push(name);
+                continue;
+            }
+        };
+
+        if old.assets[old_idx].is_resource() {
+            let old_size = old.assets[old_idx].node_size;
+            let new_size = new.assets[new_idx].node_size;
+            if old_size != new_size
+                || !same_contents(old.node_reader(old_idx)?, new.node_reader(new_idx)?)?
+            {
+                result.changed_resources.push(ResourceDiff {
+                    name,
+                    old_size,
+                    new_size,
+                });
+            }
+            continue;
+        }
+
+        old.resolve_asset(old_idx)?;
+        new.resolve_asset(new_idx)?;
+
+        let asset_diff = diff_assets(
+            &old.assets[old_idx],
+            &mut old.signature,
+            &new.assets[new_idx],
+            &mut new.signature,
+        )?;
+        if !asset_diff.is_empty() {
+            result.changed_nodes.push(asset_diff);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Compares two streams chunk by chunk, without reading them into memory
+fn same_contents<A: Read, B: Read>(mut old: A, mut new: B) -> Result<bool> {
+    let mut old_chunk = vec![0; RESOURCE_CHUNK_SIZE];
+    let mut new_chunk = vec![0; RESOURCE_CHUNK_SIZE];
+    loop {
+        let old_len = read_chunk(&mut old, &mut old_chunk)?;
+        let new_len = read_chunk(&mut new, &mut new_chunk)?;
+        if old_chunk[..old_len] != new_chunk[..new_len] {
+            return Ok(false);
+        }
+        if old_len < RESOURCE_CHUNK_SIZE {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fills `chunk` unless the stream ends first, returns the bytes read
+fn read_chunk<R: Read>(reader: &mut R, chunk: &mut [u8]) -> Result<usize> {
+    let mut len = 0;
+    while len < chunk.len() {
+        match reader.read(&mut chunk[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn object(name: &str, data: Vec<u8>, items: Vec<i32>) -> ObjectValue {
+        let mut map = OrderedMap::default();
+        map.insert(
+            "m_Name".to_string(),
+            ObjectValue::String(OsString::from(name)),
+        );
+        map.insert("image data".to_string(), ObjectValue::U8Array(data));
+        map.insert(
+            "m_Items".to_string(),
+            ObjectValue::Array(items.into_iter().map(ObjectValue::I32).collect()),
+        );
+        ObjectValue::Map(map)
+    }
+
+    #[test]
+    fn test_diff_values() {
+        let old = object("a", vec![0; 128], vec![1, 2, 3]);
+        assert!(diff_values(&old, &object("a", vec![0; 128], vec![1, 2, 3])).is_empty());
+
+        let mut data = vec![0; 128];
+        data[100] = 1;
+        let changes = diff_values(&old, &object("b", data, vec![1, 5]));
+        let paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["m_Name", "image data", "m_Items[1]", "m_Items[2]"]
+        );
+        assert_eq!(changes[0].new, Some("\"b\"".to_string()));
+        assert_eq!(changes[2].kind, ChangeKind::Modified);
+        assert_eq!(changes[3].kind, ChangeKind::Removed);
+    }
+
+    #[test]
+    fn test_bracketed_fields() {
+        let object = |weight: f32, guid: u32| {
+            let mut influence = OrderedMap::default();
+            influence.insert("weight[0]".to_string(), ObjectValue::Float(weight));
+            let mut map = OrderedMap::default();
+            map.insert(
+                "m_Skin".to_string(),
+                ObjectValue::Array(vec![ObjectValue::Map(influence)]),
+            );
+            map.insert("data[0]".to_string(), ObjectValue::U32(guid));
+            ObjectValue::Map(map)
+        };
+        let new = object(0.5, 2);
+        let changes = diff_values(&object(1.0, 1), &new);
+        let paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec![r#"m_Skin[0]["weight[0]"]"#, r#"["data[0]"]"#]);
+        assert_eq!(new.get::<f32>(&changes[0].path).unwrap(), 0.5);
+        assert_eq!(new.get::<u32>(&changes[1].path).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_same_contents() {
+        use std::io::Cursor;
+
+        let data: Vec<u8> = (0..RESOURCE_CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        assert!(same_contents(Cursor::new(&data), Cursor::new(&data)).unwrap());
+
+        let mut changed = data.clone();
+        changed[RESOURCE_CHUNK_SIZE + 3] ^= 1;
+        assert!(!same_contents(Cursor::new(&data), Cursor::new(&changed)).unwrap());
+        assert!(!same_contents(Cursor::new(&data), Cursor::new(&data[..100])).unwrap());
+    }
+}
@@ -0,0 +1,263 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+use engine::{as_map, read_floats};
+use error::{Error, Result};
+use extras::containers::OrderedMap;
+use object::ObjectValue;
+
+/// Offset of a single vertex in a blend shape frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlendShapeVertex {
+    pub vertex: [f32; 3],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 3],
+    pub index: u32,
+}
+
+/// A frame of a blend shape channel, a run of `vertex_count` entries of
+/// the blend shape vertices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshBlendShape {
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+    pub has_normals: bool,
+    pub has_tangents: bool,
+}
+
+/// A named blend shape with `frame_count` frames starting at
+/// `frame_index` of the shapes and full weights
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshBlendShapeChannel {
+    pub name: String,
+    pub name_hash: u32,
+    pub frame_index: i32,
+    pub frame_count: i32,
+}
+
+/// The blend shapes of a mesh as Unity stores them in `m_Shapes`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlendShapeData {
+    pub vertices: Vec<BlendShapeVertex>,
+    pub shapes: Vec<MeshBlendShape>,
+    pub channels: Vec<MeshBlendShapeChannel>,
+    /// Weight at which every frame is fully applied
+    pub full_weights: Vec<f32>,
+}
+
+/// Offsets of all vertices of a mesh in a blend shape frame. Normals and
+/// tangents are empty if the frame does not change them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendShapeFrame {
+    pub weight: f32,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlendShapeChannel {
+    pub name: String,
+    pub name_hash: u32,
+    /// Frames by increasing weight, the last one is the full shape
+    pub frames: Vec<BlendShapeFrame>,
+}
+
+impl BlendShapeData {
+    pub(crate) fn from_map(map: &OrderedMap<String, ObjectValue>) -> Result<Self> {
+        let mut vertices = Vec::new();
+        for value in array(tryGet!(map, "vertices"))? {
+            let vertex = as_map(value)?;
+            vertices.push(BlendShapeVertex {
+                vertex: read_floats(tryGet!(vertex, "vertex"), &["x", "y", "z"])?,
+                normal: read_floats(tryGet!(vertex, "normal"), &["x", "y", "z"])?,
+                tangent: read_floats(tryGet!(vertex, "tangent"), &["x", "y", "z"])?,
+                index: tryGet!(vertex, "index").to_u32()?,
+            });
+        }
+        let mut shapes = Vec::new();
+        for value in array(tryGet!(map, "shapes"))? {
+            let shape = as_map(value)?;
+            shapes.push(MeshBlendShape {
+                first_vertex: tryGet!(shape, "firstVertex").to_u32()?,
+                vertex_count: tryGet!(shape, "vertexCount").to_u32()?,
+                has_normals: tryGet!(shape, "hasNormals").to_bool()?,
+                has_tangents: tryGet!(shape, "hasTangents").to_bool()?,
+            });
+        }
+        let mut channels = Vec::new();
+        for value in array(tryGet!(map, "channels"))? {
+            let channel = as_map(value)?;
+            channels.push(MeshBlendShapeChannel {
+                name: tryGet!(channel, "name").to_string()?,
+                name_hash: tryGet!(channel, "nameHash").to_u32()?,
+                frame_index: tryGet!(channel, "frameIndex").to_i32()?,
+                frame_count: tryGet!(channel, "frameCount").to_i32()?,
+            });
+        }
+        Ok(BlendShapeData {
+            vertices,
+            shapes,
+            channels,
+            full_weights: array(tryGet!(map, "fullWeights"))?
+                .iter()
+                .map(|weight| weight.to_f32())
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Expands the frames of every channel into offsets for all
+    /// `vertex_count` vertices of the mesh
+    pub fn decode(&self, vertex_count: usize) -> Result<Vec<BlendShapeChannel>> {
+        let mut channels = Vec::with_capacity(self.channels.len());
+        for channel in &self.channels {
+            let start = channel.frame_index.max(0) as usize;
+            let end = start + channel.frame_count.max(0) as usize;
+            if end > self.shapes.len() || end > self.full_weights.len() {
+                return Err(Error::EngineError(format!(
+                    "Blend shape {} refers to frames {}..{} of {}",
+                    channel.name,
+                    start,
+                    end,
+                    self.shapes.len().min(self.full_weights.len())
+                )));
+            }
+
+            let mut frames = Vec::with_capacity(end - start);
+            for (shape, &weight) in self.shapes[start..end]
+                .iter()
+                .zip(&self.full_weights[start..end])
+            {
+                let first = shape.first_vertex as usize;
+                let vertices = match self
+                    .vertices
+                    .get(first..first + shape.vertex_count as usize)
+                {
+                    Some(vertices) => vertices,
+                    None => {
+                        return Err(Error::EngineError(format!(
+                            "Blend shape {} has only {} of {} vertices",
+                            channel.name,
+                            self.vertices.len().saturating_sub(first),
+                            shape.vertex_count
+                        )));
+                    }
+                };
+
+                let mut frame = BlendShapeFrame {
+                    weight,
+                    positions: vec![[0.0; 3]; vertex_count],
+                    normals: Vec::new(),
+                    tangents: Vec::new(),
+                };
+                if shape.has_normals {
+                    frame.normals = vec![[0.0; 3]; vertex_count];
+                }
+                if shape.has_tangents {
+                    frame.tangents = vec![[0.0; 3]; vertex_count];
+                }
+                for vertex in vertices {
+                    let index = vertex.index as usize;
+                    if index >= vertex_count {
+                        return Err(Error::EngineError(format!(
+                            "Blend shape {} moves vertex {} of {}",
+                            channel.name, index, vertex_count
+                        )));
+                    }
+                    frame.positions[index] = vertex.vertex;
+                    if shape.has_normals {
+                        frame.normals[index] = vertex.normal;
+                    }
+                    if shape.has_tangents {
+                        frame.tangents[index] = vertex.tangent;
+                    }
+                }
+                frames.push(frame);
+            }
+            channels.push(BlendShapeChannel {
+                name: channel.name.clone(),
+                name_hash: channel.name_hash,
+                frames,
+            });
+        }
+        Ok(channels)
+    }
+}
+
+fn array(value: &ObjectValue) -> Result<&Vec<ObjectValue>> {
+    match *value {
+        ObjectValue::Array(ref array) => Ok(array),
+        _ => Err(Error::EngineError(format!(
+            "Value is not an array but {:?}",
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A "smile" channel with a half and a full frame that raises vertex
+    /// 2 of the quad, the full frame also turns its normal
+    pub fn blend_shapes() -> BlendShapeData {
+        let vertex = |index, y, normal| BlendShapeVertex {
+            vertex: [0.0, y, 0.0],
+            normal: [0.0, 0.0, normal],
+            tangent: [0.0; 3],
+            index,
+        };
+        BlendShapeData {
+            vertices: vec![vertex(2, 0.25, 0.0), vertex(2, 0.5, 1.0)],
+            shapes: vec![
+                MeshBlendShape {
+                    first_vertex: 0,
+                    vertex_count: 1,
+                    has_normals: false,
+                    has_tangents: false,
+                },
+                MeshBlendShape {
+                    first_vertex: 1,
+                    vertex_count: 1,
+                    has_normals: true,
+                    has_tangents: false,
+                },
+            ],
+            channels: vec![MeshBlendShapeChannel {
+                name: "smile".to_string(),
+                name_hash: 1234,
+                frame_index: 0,
+                frame_count: 2,
+            }],
+            full_weights: vec![50.0, 100.0],
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let channels = blend_shapes().decode(4).unwrap();
+        assert_eq!(channels.len(), 1);
+        assert_eq!(channels[0].name, "smile");
+        let frames = &channels[0].frames;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].weight, 50.0);
+        assert_eq!(frames[0].positions[2], [0.0, 0.25, 0.0]);
+        assert_eq!(frames[0].positions[1], [0.0; 3]);
+        assert!(frames[0].normals.is_empty());
+        assert_eq!(frames[1].normals[2], [0.0, 0.0, 1.0]);
+        assert!(frames[1].tangents.is_empty());
+
+        assert!(blend_shapes().decode(2).is_err());
+        let mut shapes = blend_shapes();
+        shapes.channels[0].frame_count = 3;
+        assert!(shapes.decode(4).is_err());
+    }
+}
@@ -0,0 +1,448 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+use super::Vertices;
+use error::{Error, Result};
+use extras::containers::OrderedMap;
+use object::{ObjectValue, ToByteVec};
+
+/// Values quantized to `bit_size` bits and packed least significant bit
+/// first. Float vectors map the quantized values linearly onto
+/// `start..start + range`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedBitVector {
+    pub num_items: u32,
+    pub range: f32,
+    pub start: f32,
+    pub data: Vec<u8>,
+    pub bit_size: u8,
+}
+
+impl PackedBitVector {
+    /// Reads both `PackedBitVector` float vectors and the integer vectors,
+    /// which have no range and start
+    pub(crate) fn from_map(map: &OrderedMap<String, ObjectValue>) -> Result<Self> {
+        Ok(PackedBitVector {
+            num_items: tryGet!(map, "m_NumItems").to_u32()?,
+            range: match map.get(&"m_Range".to_string()) {
+                Some(range) => range.to_f32()?,
+                None => 0.0,
+            },
+            start: match map.get(&"m_Start".to_string()) {
+                Some(start) => start.to_f32()?,
+                None => 0.0,
+            },
+            data: tryGet!(map, "m_Data").to_byte_vec()?,
+            bit_size: match map.get(&"m_BitSize".to_string()) {
+                Some(bit_size) => bit_size.to_u8()?,
+                None => 0,
+            },
+        })
+    }
+
+    /// Unpacks `count` integers starting at item `start`
+    pub fn unpack_ints(&self, start: usize, count: usize) -> Result<Vec<u32>> {
+        let bit_size = usize::from(self.bit_size);
+        if bit_size > 32 {
+            return Err(Error::EngineError(format!(
+                "Packed bit vector has an invalid bit size of {}",
+                bit_size
+            )));
+        }
+        if (start + count) * bit_size > self.data.len() * 8 {
+            return Err(Error::EngineError(format!(
+                "Packed bit vector of {} bytes is too short for {} items of {} bits",
+                self.data.len(),
+                start + count,
+                bit_size
+            )));
+        }
+
+        let mask = (1u64 << bit_size) - 1;
+        let mut values = Vec::with_capacity(count);
+        let mut position = start * bit_size;
+        for _ in 0..count {
+            let mut value = 0u64;
+            let mut bits = 0;
+            while bits < bit_size {
+                let (index, shift) = (position / 8, position % 8);
+                value |= u64::from(self.data[index] >> shift) << bits;
+                let read = (bit_size - bits).min(8 - shift);
+                bits += read;
+                position += read;
+            }
+            values.push((value & mask) as u32);
+        }
+        Ok(values)
+    }
+
+    /// Unpacks `count` floats starting at item `start`
+    pub fn unpack_floats(&self, start: usize, count: usize) -> Result<Vec<f32>> {
+        let values = self.unpack_ints(start, count)?;
+        let max = ((1u64 << self.bit_size) - 1) as f32;
+        Ok(values
+            .into_iter()
+            .map(|value| {
+                if self.bit_size == 0 {
+                    self.start
+                } else {
+                    value as f32 * self.range / max + self.start
+                }
+            })
+            .collect())
+    }
+
+    /// Unpacks all integers
+    pub fn ints(&self) -> Result<Vec<u32>> {
+        self.unpack_ints(0, self.num_items as usize)
+    }
+
+    /// Unpacks all floats
+    pub fn floats(&self) -> Result<Vec<f32>> {
+        self.unpack_floats(0, self.num_items as usize)
+    }
+}
+
+/// Geometry of meshes with `m_MeshCompression` set, stored in
+/// `m_CompressedMesh` instead of the vertex data and index buffer
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedMesh {
+    pub vertices: PackedBitVector,
+    pub uv: PackedBitVector,
+    pub normals: PackedBitVector,
+    pub tangents: PackedBitVector,
+    pub weights: PackedBitVector,
+    pub normal_signs: PackedBitVector,
+    pub tangent_signs: PackedBitVector,
+    /// RGBA float colors since Unity 5, earlier versions store packed bytes
+    /// in `colors`
+    pub float_colors: Option<PackedBitVector>,
+    pub colors: Option<PackedBitVector>,
+    pub bone_indices: PackedBitVector,
+    pub triangles: PackedBitVector,
+    /// Presence and dimension of the UV sets in `uv` since Unity 5
+    pub uv_info: u32,
+}
+
+/// Bits per UV set in `uv_info`: the dimension minus 1 in the lower 2 bits
+/// and whether the set exists in the third bit
+const UV_INFO_BITS: u32 = 4;
+const UV_DIMENSION_MASK: u32 = 3;
+const UV_CHANNEL_EXISTS: u32 = 4;
+
+/// Bone weights are quantized so that the weights of a vertex add up to 31
+const WEIGHT_SCALE: u32 = 31;
+
+impl CompressedMesh {
+    pub(crate) fn from_map(map: &OrderedMap<String, ObjectValue>) -> Result<Self> {
+        let vector = |key: &str| -> Result<PackedBitVector> {
+            match map.get(&key.to_string()) {
+                Some(ObjectValue::Map(ref vector)) => PackedBitVector::from_map(vector),
+                _ => Err(Error::EngineError(format!(
+                    "Packed bit vector not found for key {}",
+                    key
+                ))),
+            }
+        };
+        let optional_vector = |key: &str| -> Result<Option<PackedBitVector>> {
+            match map.get(&key.to_string()) {
+                Some(ObjectValue::Map(ref vector)) => Ok(Some(PackedBitVector::from_map(vector)?)),
+                _ => Ok(None),
+            }
+        };
+        Ok(CompressedMesh {
+            vertices: vector("m_Vertices")?,
+            uv: vector("m_UV")?,
+            normals: vector("m_Normals")?,
+            tangents: vector("m_Tangents")?,
+            weights: vector("m_Weights")?,
+            normal_signs: vector("m_NormalSigns")?,
+            tangent_signs: vector("m_TangentSigns")?,
+            float_colors: optional_vector("m_FloatColors")?,
+            colors: optional_vector("m_Colors")?,
+            bone_indices: vector("m_BoneIndices")?,
+            triangles: vector("m_Triangles")?,
+            uv_info: match map.get(&"m_UVInfo".to_string()) {
+                Some(uv_info) => uv_info.to_u32()?,
+                None => 0,
+            },
+        })
+    }
+
+    /// Unpacks the triangle list of all submeshes
+    pub fn indices(&self) -> Result<Vec<u32>> {
+        self.triangles.ints()
+    }
+
+    /// Reconstructs the vertex attributes
+    pub fn decode(&self) -> Result<Vertices> {
+        let vertex_count = self.vertices.num_items as usize / 3;
+        let mut vertices = Vertices::default();
+        vertices.positions = self
+            .vertices
+            .unpack_floats(0, vertex_count * 3)?
+            .chunks(3)
+            .map(|v| [v[0], v[1], v[2]])
+            .collect();
+
+        if self.uv.num_items > 0 {
+            self.decode_uvs(vertex_count, &mut vertices)?;
+        }
+
+        // only x and y are stored, z follows from the unit length and sign
+        if self.normals.num_items > 0 {
+            let data = unpack_xy(&self.normals, "normals", vertex_count)?;
+            let signs = self.normal_signs.ints()?;
+            vertices.normals = data
+                .chunks(2)
+                .zip(signs)
+                .map(|(xy, sign)| unit_vector(xy[0], xy[1], sign != 0))
+                .collect();
+        }
+
+        // tangents store the sign of z and of w
+        if self.tangents.num_items > 0 {
+            let data = unpack_xy(&self.tangents, "tangents", vertex_count)?;
+            let signs = self.tangent_signs.ints()?;
+            vertices.tangents = data
+                .chunks(2)
+                .zip(signs.chunks(2))
+                .map(|(xy, signs)| {
+                    let tangent = unit_vector(xy[0], xy[1], signs[0] != 0);
+                    let w = if signs.get(1).is_some_and(|&sign| sign > 0) {
+                        1.0
+                    } else {
+                        -1.0
+                    };
+                    [tangent[0], tangent[1], tangent[2], w]
+                })
+                .collect();
+        }
+
+        match (&self.float_colors, &self.colors) {
+            (Some(colors), _) if colors.num_items > 0 => {
+                vertices.colors = colors
+                    .floats()?
+                    .chunks(4)
+                    .filter(|c| c.len() == 4)
+                    .map(|c| [c[0], c[1], c[2], c[3]])
+                    .collect();
+            }
+            (_, Some(colors)) if colors.num_items > 0 => {
+                vertices.colors = colors
+                    .ints()?
+                    .into_iter()
+                    .map(|c| {
+                        let byte = |shift: u32| (c >> shift & 0xff) as f32 / 255.0;
+                        [byte(0), byte(8), byte(16), byte(24)]
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+
+        if self.weights.num_items > 0 {
+            self.decode_skin(&mut vertices)?;
+        }
+        Ok(vertices)
+    }
+
+    fn decode_uvs(&self, vertex_count: usize, vertices: &mut Vertices) -> Result<()> {
+        if self.uv_info == 0 {
+            // UV0 and optionally UV1, both with two components
+            vertices.uvs[0] = to_uvs(&self.uv.unpack_floats(0, vertex_count * 2)?, 2);
+            if self.uv.num_items as usize >= vertex_count * 4 {
+                vertices.uvs[1] = to_uvs(
+                    &self.uv.unpack_floats(vertex_count * 2, vertex_count * 2)?,
+                    2,
+                );
+            }
+            return Ok(());
+        }
+
+        let mut offset = 0;
+        for (set, uvs) in vertices.uvs.iter_mut().enumerate() {
+            let info = self.uv_info >> (set as u32 * UV_INFO_BITS);
+            if info & UV_CHANNEL_EXISTS == 0 {
+                continue;
+            }
+            let dimension = 1 + (info & UV_DIMENSION_MASK) as usize;
+            *uvs = to_uvs(
+                &self.uv.unpack_floats(offset, vertex_count * dimension)?,
+                dimension,
+            );
+            offset += vertex_count * dimension;
+        }
+        Ok(())
+    }
+
+    /// Bone weights are stored for each influence until the weights of a
+    /// vertex add up to one. The fourth weight of a vertex is implied by
+    /// the first three, only its bone index is stored.
+    fn decode_skin(&self, vertices: &mut Vertices) -> Result<()> {
+        let weights = self.weights.ints()?;
+        let bone_indices = self.bone_indices.ints()?;
+        let (mut influence_weights, mut influence_indices) = ([0f32; 4], [0u32; 4]);
+        let (mut influence, mut sum, mut index_position) = (0, 0, 0);
+        let next_index = |position: &mut usize| -> Result<u32> {
+            let index = bone_indices.get(*position).cloned().ok_or_else(|| {
+                Error::EngineError("Compressed bone indices are too short".to_string())
+            })?;
+            *position += 1;
+            Ok(index)
+        };
+        for weight in weights {
+            influence_weights[influence] = weight as f32 / WEIGHT_SCALE as f32;
+            influence_indices[influence] = next_index(&mut index_position)?;
+            influence += 1;
+            sum += weight;
+
+            if sum < WEIGHT_SCALE && influence == 3 {
+                influence_weights[3] = (WEIGHT_SCALE - sum) as f32 / WEIGHT_SCALE as f32;
+                influence_indices[3] = next_index(&mut index_position)?;
+            } else if sum < WEIGHT_SCALE {
+                continue;
+            }
+            vertices.bone_weights.push(influence_weights);
+            vertices.bone_indices.push(influence_indices);
+            influence_weights = [0.0; 4];
+            influence_indices = [0; 4];
+            influence = 0;
+            sum = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Unpacks the x and y components of a vector of every vertex
+fn unpack_xy(vector: &PackedBitVector, name: &str, vertex_count: usize) -> Result<Vec<f32>> {
+    if vector.num_items as usize != vertex_count * 2 {
+        return Err(Error::EngineError(format!(
+            "Compressed mesh has {} components of {} for {} vertices",
+            vector.num_items, name, vertex_count
+        )));
+    }
+    vector.floats()
+}
+
+/// Completes a unit vector from its x and y components
+fn unit_vector(x: f32, y: f32, positive_z: bool) -> [f32; 3] {
+    let z_squared = 1.0 - x * x - y * y;
+    let mut vector = if z_squared >= 0.0 {
+        [x, y, z_squared.sqrt()]
+    } else {
+        let length = (x * x + y * y).sqrt();
+        [x / length, y / length, 0.0]
+    };
+    if !positive_z {
+        vector[2] = -vector[2];
+    }
+    vector
+}
+
+/// Keeps the first two components of UVs with the given dimension
+fn to_uvs(data: &[f32], dimension: usize) -> Vec<[f32; 2]> {
+    data.chunks(dimension)
+        .map(|uv| [uv[0], uv.get(1).cloned().unwrap_or(0.0)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs values least significant bit first
+    fn pack(values: &[u32], bit_size: u8) -> PackedBitVector {
+        let mut data = vec![0u8; (values.len() * bit_size as usize).div_ceil(8)];
+        for (i, &value) in values.iter().enumerate() {
+            for bit in 0..bit_size as usize {
+                let position = i * bit_size as usize + bit;
+                data[position / 8] |= ((value >> bit & 1) as u8) << (position % 8);
+            }
+        }
+        PackedBitVector {
+            num_items: values.len() as u32,
+            range: 0.0,
+            start: 0.0,
+            data,
+            bit_size,
+        }
+    }
+
+    fn pack_floats(values: &[u32], bit_size: u8, start: f32, range: f32) -> PackedBitVector {
+        let mut vector = pack(values, bit_size);
+        vector.start = start;
+        vector.range = range;
+        vector
+    }
+
+    fn empty() -> PackedBitVector {
+        pack(&[], 0)
+    }
+
+    #[test]
+    fn test_unpack() {
+        let vector = pack(&[5, 0, 1023, 77, 512], 10);
+        assert_eq!(vector.ints().unwrap(), vec![5, 0, 1023, 77, 512]);
+        assert_eq!(vector.unpack_ints(2, 2).unwrap(), vec![1023, 77]);
+        assert!(vector.unpack_ints(4, 2).is_err());
+
+        let vector = pack(&[0xdead_beef, 1], 32);
+        assert_eq!(vector.ints().unwrap(), vec![0xdead_beef, 1]);
+
+        let vector = pack_floats(&[0, 1, 3], 2, -1.0, 3.0);
+        assert_eq!(vector.floats().unwrap(), vec![-1.0, 0.0, 2.0]);
+
+        let mut vector = pack_floats(&[0, 1, 3], 2, -1.0, 3.0);
+        vector.bit_size = 64;
+        assert!(vector.floats().is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let mesh = CompressedMesh {
+            vertices: pack_floats(&[0, 1, 2, 3, 2, 1], 2, 0.0, 3.0),
+            // UV0 with two and UV2 with one component
+            uv: pack_floats(&[0, 1, 1, 0, 1, 1], 1, 0.0, 1.0),
+            normals: pack_floats(&[0, 0, 1, 0], 1, 0.0, 1.0),
+            tangents: pack_floats(&[1, 0, 0, 0], 1, 0.0, 1.0),
+            weights: pack(&[31, 10, 10, 5], 5),
+            normal_signs: pack(&[0, 1], 1),
+            tangent_signs: pack(&[1, 0, 0, 1], 1),
+            float_colors: Some(empty()),
+            colors: None,
+            bone_indices: pack(&[2, 0, 1, 3, 7], 3),
+            triangles: pack(&[0, 1, 0], 1),
+            uv_info: 0b0101 | 0b0100 << 8,
+        };
+        let vertices = mesh.decode().unwrap();
+        assert_eq!(vertices.positions, vec![[0.0, 1.0, 2.0], [3.0, 2.0, 1.0]]);
+        assert_eq!(vertices.uvs[0], vec![[0.0, 1.0], [1.0, 0.0]]);
+        assert!(vertices.uvs[1].is_empty());
+        assert_eq!(vertices.uvs[2], vec![[1.0, 0.0], [1.0, 0.0]]);
+        assert_eq!(vertices.normals, vec![[0.0, 0.0, -1.0], [1.0, 0.0, 0.0]]);
+        assert_eq!(
+            vertices.tangents,
+            vec![[1.0, 0.0, 0.0, -1.0], [0.0, 0.0, -1.0, 1.0]]
+        );
+        assert_eq!(vertices.bone_weights[0], [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(vertices.bone_indices[0], [2, 0, 0, 0]);
+        assert_eq!(
+            vertices.bone_weights[1],
+            [10.0 / 31.0, 10.0 / 31.0, 5.0 / 31.0, 6.0 / 31.0]
+        );
+        assert_eq!(vertices.bone_indices[1], [0, 1, 3, 7]);
+        assert!(vertices.colors.is_empty());
+        assert_eq!(mesh.indices().unwrap(), vec![0, 1, 0]);
+
+        let mesh = CompressedMesh {
+            normals: pack_floats(&[0, 0, 1], 1, 0.0, 1.0),
+            ..mesh
+        };
+        assert!(mesh.decode().is_err());
+    }
+}
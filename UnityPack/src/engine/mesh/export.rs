@@ -0,0 +1,731 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Writers for a `Mesh` in Wavefront OBJ and glTF 2.0 files.
+//!
+//! Unity uses a left-handed coordinate system, both formats are
+//! right-handed. Exported meshes are mirrored along the x axis and the
+//! winding of their triangles is reversed.
+
+use super::{BlendShapeChannel, Mesh, Vertices};
+use engine::renderer::Skeleton;
+use engine::transform::{Matrix4x4, Transform};
+use error::{Error, Result};
+use serde_json::Value;
+use std::io::Write;
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a;
+const GLB_CHUNK_BIN: u32 = 0x004e_4942;
+
+const GLTF_ARRAY_BUFFER: u32 = 34962;
+const GLTF_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const GLTF_UNSIGNED_SHORT: u32 = 5123;
+const GLTF_UNSIGNED_INT: u32 = 5125;
+const GLTF_FLOAT: u32 = 5126;
+const GLTF_TRIANGLES: u32 = 4;
+const GLTF_POINTS: u32 = 0;
+
+impl Mesh {
+    /// Encodes the mesh as a Wavefront OBJ file
+    pub fn to_obj(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_obj(&mut result)?;
+        Ok(result)
+    }
+
+    /// Writes the mesh as a Wavefront OBJ file with a group for every
+    /// submesh. Vertex colors are appended to the positions, blend shapes
+    /// are left out.
+    pub fn write_obj<W: Write>(&self, mut writer: W) -> Result<()> {
+        let vertices = self.vertices()?;
+        let has_uvs = !vertices.uvs[0].is_empty();
+        let has_normals = !vertices.normals.is_empty();
+
+        writeln!(writer, "o {}", self.object.name)?;
+        for (i, position) in vertices.positions.iter().enumerate() {
+            write!(
+                writer,
+                "v {} {} {}",
+                mirror(position[0]),
+                position[1],
+                position[2]
+            )?;
+            if let Some(color) = vertices.colors.get(i) {
+                write!(writer, " {} {} {}", color[0], color[1], color[2])?;
+            }
+            writeln!(writer)?;
+        }
+        for uv in &vertices.uvs[0] {
+            writeln!(writer, "vt {} {}", uv[0], uv[1])?;
+        }
+        for normal in &vertices.normals {
+            writeln!(
+                writer,
+                "vn {} {} {}",
+                mirror(normal[0]),
+                normal[1],
+                normal[2]
+            )?;
+        }
+
+        for submesh in 0..self.submeshes.len() {
+            writeln!(writer, "g {}_{}", self.object.name, submesh)?;
+            for triangle in self.triangles(submesh)? {
+                write!(writer, "f")?;
+                for &index in &[triangle[0], triangle[2], triangle[1]] {
+                    let index = index + 1;
+                    match (has_uvs, has_normals) {
+                        (true, true) => write!(writer, " {}/{}/{}", index, index, index)?,
+                        (true, false) => write!(writer, " {}/{}", index, index)?,
+                        (false, true) => write!(writer, " {}//{}", index, index)?,
+                        (false, false) => write!(writer, " {}", index)?,
+                    }
+                }
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the mesh as a glTF file and its binary buffer, which the
+    /// glTF file references as `bin_uri`. See `write_gltf`.
+    pub fn to_gltf(&self, bin_uri: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+        let (mut json, mut bin) = (Vec::new(), Vec::new());
+        self.write_gltf(&mut json, &mut bin, bin_uri)?;
+        Ok((json, bin))
+    }
+
+    /// Writes the mesh as a glTF file with a primitive for every submesh
+    /// and its binary buffer. Blend shapes become morph targets, named in
+    /// the `targetNames` extra of the mesh. Skinned meshes get a skin with
+    /// the bind poses of the mesh, whose joints are placeholder nodes at
+    /// the origin. See `write_gltf_with_skeleton` for the actual bones.
+    pub fn write_gltf<W: Write, B: Write>(
+        &self,
+        mut writer: W,
+        mut bin_writer: B,
+        bin_uri: &str,
+    ) -> Result<()> {
+        let (json, bin) = self.gltf_document(Some(bin_uri), None)?;
+        writer.write_all(&json)?;
+        bin_writer.write_all(&bin)?;
+        Ok(())
+    }
+
+    /// Encodes the mesh and the bones of its renderer as a glTF file and
+    /// its binary buffer, see `write_gltf_with_skeleton`
+    pub fn to_gltf_with_skeleton(
+        &self,
+        skeleton: &Skeleton,
+        bin_uri: &str,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let (mut json, mut bin) = (Vec::new(), Vec::new());
+        self.write_gltf_with_skeleton(&mut json, &mut bin, skeleton, bin_uri)?;
+        Ok((json, bin))
+    }
+
+    /// Writes the mesh like `write_gltf` with a node for every transform
+    /// of the skeleton. The joints of the skin are the bones of the
+    /// skeleton, missing bones are replaced by placeholder nodes.
+    pub fn write_gltf_with_skeleton<W: Write, B: Write>(
+        &self,
+        mut writer: W,
+        mut bin_writer: B,
+        skeleton: &Skeleton,
+        bin_uri: &str,
+    ) -> Result<()> {
+        let (json, bin) = self.gltf_document(Some(bin_uri), Some(skeleton))?;
+        writer.write_all(&json)?;
+        bin_writer.write_all(&bin)?;
+        Ok(())
+    }
+
+    /// Encodes the mesh as a binary glTF file
+    pub fn to_glb(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_glb(&mut result)?;
+        Ok(result)
+    }
+
+    /// Writes the mesh as a binary glTF file, see `write_gltf`
+    pub fn write_glb<W: Write>(&self, writer: W) -> Result<()> {
+        let (json, bin) = self.gltf_document(None, None)?;
+        write_glb_chunks(writer, json, bin)
+    }
+
+    /// Encodes the mesh and the bones of its renderer as a binary glTF
+    /// file
+    pub fn to_glb_with_skeleton(&self, skeleton: &Skeleton) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_glb_with_skeleton(&mut result, skeleton)?;
+        Ok(result)
+    }
+
+    /// Writes the mesh and the bones of its renderer as a binary glTF
+    /// file, see `write_gltf_with_skeleton`
+    pub fn write_glb_with_skeleton<W: Write>(&self, writer: W, skeleton: &Skeleton) -> Result<()> {
+        let (json, bin) = self.gltf_document(None, Some(skeleton))?;
+        write_glb_chunks(writer, json, bin)
+    }
+
+    /// Builds the glTF JSON and binary buffer, with the nodes of the
+    /// skeleton if there is one. The buffer is embedded in
+    /// GLB files when `bin_uri` is `None`.
+    fn gltf_document(
+        &self,
+        bin_uri: Option<&str>,
+        skeleton: Option<&Skeleton>,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let vertices = self.vertices()?;
+        if vertices.positions.is_empty() {
+            return Err(Error::EngineError(format!(
+                "Mesh {} has no vertices",
+                self.object.name
+            )));
+        }
+
+        let mut buffer = GltfBuffer::default();
+        let attributes = gltf_attributes(&vertices, &mut buffer);
+        let channels = if self.blend_shapes.is_empty() {
+            Vec::new()
+        } else {
+            self.blend_shapes.decode(vertices.positions.len())?
+        };
+        let (targets, target_names) = gltf_targets(&channels, &mut buffer);
+
+        let mut primitives = Vec::new();
+        for submesh in 0..self.submeshes.len() {
+            let triangles = self.triangles(submesh)?;
+            if triangles.is_empty() {
+                continue;
+            }
+            let indices: Vec<u32> = triangles
+                .iter()
+                .flat_map(|t| vec![t[0], t[2], t[1]])
+                .collect();
+            primitives.push(json!({
+                "attributes": attributes,
+                "indices": buffer.push_indices(&indices),
+                "mode": GLTF_TRIANGLES,
+            }));
+        }
+        if primitives.is_empty() {
+            primitives.push(json!({
+                "attributes": attributes,
+                "mode": GLTF_POINTS,
+            }));
+        }
+        let mut gltf_mesh = json!({ "name": self.object.name });
+        if !targets.is_empty() {
+            for primitive in &mut primitives {
+                primitive["targets"] = json!(targets);
+            }
+            gltf_mesh["weights"] = json!(vec![0.0; targets.len()]);
+            gltf_mesh["extras"] = json!({ "targetNames": target_names });
+        }
+        gltf_mesh["primitives"] = Value::Array(primitives);
+
+        let mut nodes = vec![json!({
+            "name": self.object.name,
+            "mesh": 0,
+        })];
+        let mut scene_nodes = vec![0];
+        let mut skins = Vec::new();
+        if !vertices.bone_indices.is_empty() && !vertices.bone_weights.is_empty() {
+            let joint_count = vertices
+                .bone_indices
+                .iter()
+                .flat_map(|indices| indices.iter())
+                .max()
+                .map_or(0, |&max| max as usize + 1)
+                .max(self.bind_pose.len());
+
+            // the node of every bone follows the mesh node
+            let mut bone_joints = Vec::new();
+            if let Some(skeleton) = skeleton {
+                for (bone, node) in skeleton.bones.iter().enumerate() {
+                    nodes.push(gltf_node(&node.name, &node.transform));
+                    let children: Vec<usize> =
+                        skeleton.children(bone).iter().map(|&i| i + 1).collect();
+                    if !children.is_empty() {
+                        nodes[bone + 1]["children"] = json!(children);
+                    }
+                }
+                scene_nodes.extend(skeleton.roots().iter().map(|&i| i + 1));
+                bone_joints = skeleton.joints.clone();
+            }
+
+            // glTF joints are unique, bones used twice get a child node
+            let mut joints: Vec<usize> = Vec::with_capacity(joint_count);
+            for joint in 0..joint_count {
+                let name = format!("bone_{}", joint);
+                match bone_joints.get(joint).cloned().and_then(|bone| bone) {
+                    Some(bone) if !joints.contains(&(bone + 1)) => joints.push(bone + 1),
+                    Some(bone) => {
+                        nodes.push(json!({ "name": name }));
+                        let node = nodes.len() - 1;
+                        match nodes[bone + 1]["children"] {
+                            Value::Array(ref mut children) => children.push(json!(node)),
+                            _ => nodes[bone + 1]["children"] = json!([node]),
+                        }
+                        joints.push(node);
+                    }
+                    None => {
+                        nodes.push(json!({ "name": name }));
+                        scene_nodes.push(nodes.len() - 1);
+                        joints.push(nodes.len() - 1);
+                    }
+                }
+            }
+            nodes[0]["skin"] = json!(0);
+
+            let mut skin = json!({ "joints": joints });
+            if !self.bind_pose.is_empty() {
+                let matrices: Vec<f32> = (0..joint_count)
+                    .flat_map(|joint| {
+                        let matrix = self
+                            .bind_pose
+                            .get(joint)
+                            .cloned()
+                            .unwrap_or_else(Matrix4x4::identity);
+                        mirror_matrix(&matrix).to_column_major().to_vec()
+                    })
+                    .collect();
+                skin["inverseBindMatrices"] = json!(buffer.push_matrices(&matrices));
+            }
+            skins.push(skin);
+        }
+
+        let mut gltf_buffer = json!({ "byteLength": buffer.data.len() });
+        if let Some(uri) = bin_uri {
+            gltf_buffer["uri"] = json!(uri);
+        }
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "UnityPack" },
+            "scene": 0,
+            "scenes": [{ "nodes": scene_nodes }],
+            "nodes": nodes,
+            "meshes": [gltf_mesh],
+            "buffers": [gltf_buffer],
+            "bufferViews": buffer.views,
+            "accessors": buffer.accessors,
+        });
+        if !skins.is_empty() {
+            document["skins"] = Value::Array(skins);
+        }
+        Ok((document.to_string().into_bytes(), buffer.data))
+    }
+}
+
+/// Writes the vertex attributes to the buffer and returns the glTF
+/// attributes of the primitives, which all share the same vertices
+fn gltf_attributes(vertices: &Vertices, buffer: &mut GltfBuffer) -> Value {
+    let mut attributes = json!({});
+    let positions: Vec<f32> = vertices
+        .positions
+        .iter()
+        .flat_map(|p| vec![mirror(p[0]), p[1], p[2]])
+        .collect();
+    attributes["POSITION"] = json!(buffer.push_floats(&positions, 3, true));
+
+    if !vertices.normals.is_empty() {
+        let normals: Vec<f32> = vertices
+            .normals
+            .iter()
+            .flat_map(|n| vec![mirror(n[0]), n[1], n[2]])
+            .collect();
+        attributes["NORMAL"] = json!(buffer.push_floats(&normals, 3, false));
+    }
+    // mirroring flips the handedness of the bitangent
+    if !vertices.tangents.is_empty() {
+        let tangents: Vec<f32> = vertices
+            .tangents
+            .iter()
+            .flat_map(|t| vec![mirror(t[0]), t[1], t[2], mirror(t[3])])
+            .collect();
+        attributes["TANGENT"] = json!(buffer.push_floats(&tangents, 4, false));
+    }
+    // glTF places the UV origin at the top left
+    for (set, uvs) in vertices
+        .uvs
+        .iter()
+        .enumerate()
+        .filter(|(_, uvs)| !uvs.is_empty())
+    {
+        let uvs: Vec<f32> = uvs.iter().flat_map(|uv| vec![uv[0], 1.0 - uv[1]]).collect();
+        attributes[format!("TEXCOORD_{}", set)] = json!(buffer.push_floats(&uvs, 2, false));
+    }
+    if !vertices.colors.is_empty() {
+        let colors: Vec<f32> = vertices.colors.iter().flat_map(|c| c.to_vec()).collect();
+        attributes["COLOR_0"] = json!(buffer.push_floats(&colors, 4, false));
+    }
+    if !vertices.bone_indices.is_empty() && !vertices.bone_weights.is_empty() {
+        let joints: Vec<u16> = vertices
+            .bone_indices
+            .iter()
+            .flat_map(|j| j.iter().map(|&j| j as u16).collect::<Vec<_>>())
+            .collect();
+        attributes["JOINTS_0"] = json!(buffer.push_joints(&joints));
+        let weights: Vec<f32> = vertices
+            .bone_weights
+            .iter()
+            .flat_map(|w| w.to_vec())
+            .collect();
+        attributes["WEIGHTS_0"] = json!(buffer.push_floats(&weights, 4, false));
+    }
+    attributes
+}
+
+/// Writes the full frame of every blend shape channel to the buffer as a
+/// morph target and returns the targets with their names. glTF has no
+/// equivalent of in-between frames, they are left out.
+fn gltf_targets(
+    channels: &[BlendShapeChannel],
+    buffer: &mut GltfBuffer,
+) -> (Vec<Value>, Vec<String>) {
+    let mut targets = Vec::new();
+    let mut names = Vec::new();
+    for channel in channels {
+        let frame = match channel.frames.last() {
+            Some(frame) => frame,
+            None => continue,
+        };
+        let mirrored = |deltas: &[[f32; 3]]| -> Vec<f32> {
+            deltas
+                .iter()
+                .flat_map(|d| vec![mirror(d[0]), d[1], d[2]])
+                .collect()
+        };
+        let mut target = json!({
+            "POSITION": buffer.push_floats(&mirrored(&frame.positions), 3, true),
+        });
+        if !frame.normals.is_empty() {
+            target["NORMAL"] = json!(buffer.push_floats(&mirrored(&frame.normals), 3, false));
+        }
+        if !frame.tangents.is_empty() {
+            target["TANGENT"] = json!(buffer.push_floats(&mirrored(&frame.tangents), 3, false));
+        }
+        targets.push(target);
+        names.push(channel.name.clone());
+    }
+    (targets, names)
+}
+
+/// A glTF node with the local transformation of a Unity transform
+fn gltf_node(name: &str, transform: &Transform) -> Value {
+    let position = transform.local_position;
+    let rotation = transform.local_rotation;
+    json!({
+        "name": name,
+        "translation": [mirror(position[0]), position[1], position[2]],
+        "rotation": [rotation[0], mirror(rotation[1]), mirror(rotation[2]), rotation[3]],
+        "scale": transform.local_scale,
+    })
+}
+
+/// The binary buffer of a glTF file with its buffer views and accessors
+#[derive(Default)]
+struct GltfBuffer {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GltfBuffer {
+    fn push_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        pad(&mut self.data, 0);
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.views.push(view);
+        self.data.extend_from_slice(bytes);
+        self.views.len() - 1
+    }
+
+    fn push_accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    /// Adds an accessor of vectors, glTF requires the bounds of positions
+    fn push_floats(&mut self, values: &[f32], components: usize, bounds: bool) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+        let view = self.push_view(&bytes, Some(GLTF_ARRAY_BUFFER));
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": GLTF_FLOAT,
+            "count": values.len() / components,
+            "type": format!("VEC{}", components),
+        });
+        if bounds {
+            let mut min = vec![f32::MAX; components];
+            let mut max = vec![f32::MIN; components];
+            for vector in values.chunks(components) {
+                for (i, &value) in vector.iter().enumerate() {
+                    min[i] = min[i].min(value);
+                    max[i] = max[i].max(value);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.push_accessor(accessor)
+    }
+
+    /// Adds an accessor of 4x4 matrices given by columns
+    fn push_matrices(&mut self, values: &[f32]) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+        let view = self.push_view(&bytes, None);
+        self.push_accessor(json!({
+            "bufferView": view,
+            "componentType": GLTF_FLOAT,
+            "count": values.len() / 16,
+            "type": "MAT4",
+        }))
+    }
+
+    fn push_joints(&mut self, joints: &[u16]) -> usize {
+        let bytes: Vec<u8> = joints
+            .iter()
+            .flat_map(|j| j.to_le_bytes().to_vec())
+            .collect();
+        let view = self.push_view(&bytes, Some(GLTF_ARRAY_BUFFER));
+        self.push_accessor(json!({
+            "bufferView": view,
+            "componentType": GLTF_UNSIGNED_SHORT,
+            "count": joints.len() / 4,
+            "type": "VEC4",
+        }))
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes: Vec<u8> = indices
+            .iter()
+            .flat_map(|i| i.to_le_bytes().to_vec())
+            .collect();
+        let view = self.push_view(&bytes, Some(GLTF_ELEMENT_ARRAY_BUFFER));
+        self.push_accessor(json!({
+            "bufferView": view,
+            "componentType": GLTF_UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }))
+    }
+}
+
+/// Negates an x coordinate without producing negative zeros
+fn mirror(x: f32) -> f32 {
+    0.0 - x
+}
+
+/// Mirrors a transformation along the x axis, which negates the elements
+/// mixing x with the other coordinates
+fn mirror_matrix(matrix: &Matrix4x4) -> Matrix4x4 {
+    let mut res = *matrix;
+    for i in 1..4 {
+        res.e[0][i] = mirror(res.e[0][i]);
+        res.e[i][0] = mirror(res.e[i][0]);
+    }
+    res
+}
+
+/// Writes the header and the chunks of a binary glTF file
+fn write_glb_chunks<W: Write>(mut writer: W, mut json: Vec<u8>, mut bin: Vec<u8>) -> Result<()> {
+    pad(&mut json, b' ');
+    pad(&mut bin, 0);
+    let length = 12 + 8 + json.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+
+    writer.write_all(GLB_MAGIC)?;
+    writer.write_all(&GLB_VERSION.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(&GLB_CHUNK_JSON.to_le_bytes())?;
+    writer.write_all(&json)?;
+    if !bin.is_empty() {
+        writer.write_all(&(bin.len() as u32).to_le_bytes())?;
+        writer.write_all(&GLB_CHUNK_BIN.to_le_bytes())?;
+        writer.write_all(&bin)?;
+    }
+    Ok(())
+}
+
+/// Pads data to a multiple of 4 bytes as glTF requires
+fn pad(data: &mut Vec<u8>, byte: u8) {
+    while !data.len().is_multiple_of(4) {
+        data.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::blend_shape::tests::blend_shapes;
+    use super::super::tests::quad_mesh;
+    use engine::renderer::tests::{load, renderer};
+    use engine::transform::Matrix4x4;
+    use serde_json;
+    use serde_json::Value;
+
+    #[test]
+    fn test_obj() {
+        let obj = String::from_utf8(quad_mesh().to_obj().unwrap()).unwrap();
+        let lines: Vec<&str> = obj.lines().collect();
+        assert_eq!(lines[0], "o mesh");
+        assert_eq!(lines[3], "v -1 1 0");
+        assert_eq!(lines[7], "vt 1 1");
+        assert_eq!(lines[1], "v 0 0 0");
+        assert_eq!(lines[9], "vn 0 0 -1");
+        assert_eq!(lines[13], "g mesh_0");
+        assert_eq!(lines[14], "f 1/1/1 3/3/3 2/2/2");
+        assert_eq!(lines[15], "f 3/3/3 1/1/1 4/4/4");
+        assert_eq!(lines.len(), 16);
+    }
+
+    #[test]
+    fn test_gltf() {
+        let (json, bin) = quad_mesh().to_gltf("quad.bin").unwrap();
+        let gltf: Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(gltf["buffers"][0]["uri"], "quad.bin");
+        assert_eq!(gltf["buffers"][0]["byteLength"], bin.len());
+
+        let primitive = &gltf["meshes"][0]["primitives"][0];
+        let position =
+            &gltf["accessors"][primitive["attributes"]["POSITION"].as_u64().unwrap() as usize];
+        assert_eq!(position["count"], 4);
+        assert_eq!(position["min"], json!([-1.0, 0.0, 0.0]));
+        assert!(primitive["attributes"]["TEXCOORD_0"].is_number());
+        assert!(primitive["attributes"]["JOINTS_0"].is_null());
+        assert!(gltf["skins"].is_null());
+
+        // the first triangle is reversed
+        let indices = &gltf["accessors"][primitive["indices"].as_u64().unwrap() as usize];
+        assert_eq!(indices["count"], 6);
+        let view = &gltf["bufferViews"][indices["bufferView"].as_u64().unwrap() as usize];
+        let offset = view["byteOffset"].as_u64().unwrap() as usize;
+        assert_eq!(
+            &bin[offset..offset + 12],
+            &[0, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_glb() {
+        let mut mesh = quad_mesh();
+        for _ in 0..4 {
+            mesh.skin.push(super::super::BoneInfluence {
+                weights: [1.0, 0.0, 0.0, 0.0],
+                indices: [2, 0, 0, 0],
+            });
+        }
+        let glb = mesh.to_glb().unwrap();
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(
+            glb.len() as u32,
+            u32::from_le_bytes([glb[8], glb[9], glb[10], glb[11]])
+        );
+        let json_length = u32::from_le_bytes([glb[12], glb[13], glb[14], glb[15]]) as usize;
+        assert_eq!(json_length % 4, 0);
+        let gltf: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        assert!(gltf["buffers"][0]["uri"].is_null());
+        assert_eq!(gltf["skins"][0]["joints"], json!([1, 2, 3]));
+        assert_eq!(gltf["nodes"][0]["skin"], 0);
+        assert!(gltf["meshes"][0]["primitives"][0]["attributes"]["WEIGHTS_0"].is_number());
+        assert_eq!(&glb[20 + json_length + 4..20 + json_length + 8], b"BIN\0");
+    }
+
+    #[test]
+    fn test_gltf_skeleton() {
+        let mut mesh = quad_mesh();
+        for &bone in &[0, 1, 2, 1] {
+            mesh.skin.push(super::super::BoneInfluence {
+                weights: [1.0, 0.0, 0.0, 0.0],
+                indices: [bone, 0, 0, 0],
+            });
+        }
+        for bone in 0..3 {
+            let mut matrix = Matrix4x4::identity();
+            matrix.e[0][3] = bone as f32;
+            mesh.bind_pose.push(matrix);
+        }
+        let skeleton = renderer().build_skeleton(load).unwrap();
+        let (json, bin) = mesh.to_gltf_with_skeleton(&skeleton, "mesh.bin").unwrap();
+        let gltf: Value = serde_json::from_slice(&json).unwrap();
+
+        // the missing third bone is replaced by a placeholder
+        let nodes = &gltf["nodes"];
+        assert_eq!(nodes[1]["name"], "root");
+        assert_eq!(nodes[1]["children"], json!([2]));
+        assert_eq!(nodes[4]["name"], "bone_2");
+        assert_eq!(gltf["scenes"][0]["nodes"], json!([0, 1, 4]));
+        assert_eq!(gltf["skins"][0]["joints"], json!([3, 2, 4]));
+
+        // the hips are mirrored along with the mesh
+        let half = 0.5f32.sqrt();
+        assert_eq!(nodes[2]["translation"], json!([0.0, 1.0, 0.0]));
+        assert_eq!(nodes[2]["rotation"], json!([0.0, -half, 0.0, half]));
+
+        let accessor =
+            &gltf["accessors"][gltf["skins"][0]["inverseBindMatrices"].as_u64().unwrap() as usize];
+        assert_eq!(accessor["type"], "MAT4");
+        assert_eq!(accessor["count"], 3);
+        let view = &gltf["bufferViews"][accessor["bufferView"].as_u64().unwrap() as usize];
+        assert!(view["target"].is_null());
+        let offset = view["byteOffset"].as_u64().unwrap() as usize + 64 + 48;
+        assert_eq!(
+            f32::from_le_bytes([
+                bin[offset],
+                bin[offset + 1],
+                bin[offset + 2],
+                bin[offset + 3]
+            ]),
+            -1.0
+        );
+    }
+
+    #[test]
+    fn test_gltf_morph_targets() {
+        let mut mesh = quad_mesh();
+        mesh.blend_shapes = blend_shapes();
+        let (json, bin) = mesh.to_gltf("mesh.bin").unwrap();
+        let gltf: Value = serde_json::from_slice(&json).unwrap();
+
+        let gltf_mesh = &gltf["meshes"][0];
+        assert_eq!(gltf_mesh["weights"], json!([0.0]));
+        assert_eq!(gltf_mesh["extras"]["targetNames"], json!(["smile"]));
+
+        // only the full frame is exported
+        let target = &gltf_mesh["primitives"][0]["targets"][0];
+        assert!(target["TANGENT"].is_null());
+        let position = &gltf["accessors"][target["POSITION"].as_u64().unwrap() as usize];
+        assert_eq!(position["count"], 4);
+        assert_eq!(position["max"], json!([0.0, 0.5, 0.0]));
+        let normal = &gltf["accessors"][target["NORMAL"].as_u64().unwrap() as usize];
+        let view = &gltf["bufferViews"][normal["bufferView"].as_u64().unwrap() as usize];
+        let offset = view["byteOffset"].as_u64().unwrap() as usize + 2 * 12 + 8;
+        assert_eq!(
+            f32::from_le_bytes([
+                bin[offset],
+                bin[offset + 1],
+                bin[offset + 2],
+                bin[offset + 3]
+            ]),
+            1.0
+        );
+    }
+}
@@ -0,0 +1,571 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+mod blend_shape;
+mod compressed;
+mod export;
+mod vertex;
+
+pub use self::blend_shape::{
+    BlendShapeChannel, BlendShapeData, BlendShapeFrame, BlendShapeVertex, MeshBlendShape,
+    MeshBlendShapeChannel,
+};
+pub use self::compressed::{CompressedMesh, PackedBitVector};
+pub use self::vertex::{ChannelInfo, StreamInfo, VertexAttribute, VertexFormat, Vertices};
+
+use super::transform::Matrix4x4;
+use super::EngineObject;
+use engine::object::Object;
+use error::{Error, Result};
+use extras::containers::OrderedMap;
+use object::ObjectValue;
+use object::ToByteVec;
+use version::UnityVersion;
+
+pub trait IntoMesh {
+    fn to_mesh(self) -> Result<Mesh>;
+}
+
+#[derive(Debug)]
+pub struct Mesh {
+    pub object: Object,
+    pub root_bone_name_hash: u32,
+    pub index_buffer: Vec<u8>,
+    pub index_format: IndexFormat,
+    /// Inverse of the world matrix of every bone when the mesh was bound
+    pub bind_pose: Vec<Matrix4x4>,
+    /// Hashes of the paths of the bones, which identify the bones when
+    /// the renderer does not reference them
+    pub bone_name_hashes: Vec<u32>,
+    pub baked_convex_collision_mesh: Vec<u8>,
+    pub mesh_compression: u8,
+    pub submeshes: Vec<SubMesh>,
+    pub vertex_data: VertexData,
+    /// Geometry of meshes with `mesh_compression` set, which replaces the
+    /// vertex data and index buffer
+    pub compressed_mesh: Option<CompressedMesh>,
+    /// Blend shapes of the mesh, see `blend_shape_channels`
+    pub blend_shapes: BlendShapeData,
+    /// Bone weights of meshes before Unity 2018.1, later versions store
+    /// them in the vertex data
+    pub skin: Vec<BoneInfluence>,
+    /// Unity version the mesh was built with, see `set_version`
+    pub unity_version: Option<UnityVersion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexFormat {
+    UInt16,
+    UInt32,
+}
+
+/// Indices of a submesh in the width of the mesh's index buffer
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexList {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl IndexList {
+    pub fn len(&self) -> usize {
+        match self {
+            IndexList::U16(indices) => indices.len(),
+            IndexList::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            IndexList::U16(indices) => indices.iter().map(|&i| u32::from(i)).collect(),
+            IndexList::U32(indices) => indices.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoneInfluence {
+    pub weights: [f32; 4],
+    pub indices: [u32; 4],
+}
+
+impl BoneInfluence {
+    fn from_map(map: &OrderedMap<String, ObjectValue>) -> Result<Self> {
+        let mut influence = BoneInfluence {
+            weights: [0.0; 4],
+            indices: [0; 4],
+        };
+        for i in 0..4 {
+            influence.weights[i] = tryGet!(map, format!("weight[{}]", i)).to_f32()?;
+            influence.indices[i] = tryGet!(map, format!("boneIndex[{}]", i)).to_i32()? as u32;
+        }
+        Ok(influence)
+    }
+}
+
+#[derive(Debug)]
+pub struct SubMesh {
+    pub first_byte: u32,
+    pub first_vertex: u32,
+    pub index_count: u32,
+    pub local_aabb: OrderedMap<String, ObjectValue>,
+    pub topology: i32,
+    pub vertex_count: u32,
+    /// Offset added to every index of the submesh, 0 before Unity 2017.3
+    pub base_vertex: u32,
+}
+
+impl SubMesh {
+    fn from_map(map: &mut OrderedMap<String, ObjectValue>) -> Result<Self> {
+        Ok(Self {
+            topology: tryGet!(map, "topology").to_i32()?,
+            index_count: tryGet!(map, "indexCount").to_u32()?,
+            first_vertex: tryGet!(map, "firstVertex").to_u32()?,
+            vertex_count: tryGet!(map, "vertexCount").to_u32()?,
+            first_byte: tryGet!(map, "firstByte").to_u32()?,
+            local_aabb: tryConsume!(map, "localAABB").into_map()?,
+            base_vertex: match map.get(&"baseVertex".to_string()) {
+                Some(base_vertex) => base_vertex.to_u32()?,
+                None => 0,
+            },
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct VertexData {
+    pub object: Object,
+    pub channels: Vec<ChannelInfo>,
+    /// Stream layout stored before Unity 5, later versions derive it from
+    /// the channels, see `streams`
+    pub stored_streams: Vec<StreamInfo>,
+    pub current_channels: i32,
+    pub data: Vec<u8>,
+    pub vertex_count: u32,
+}
+
+impl VertexData {
+    pub(crate) fn from_map(map: &mut OrderedMap<String, ObjectValue>) -> Result<Self> {
+        let channels = {
+            let array = tryConsume!(map, "m_Channels").into_vec()?;
+            let mut res = Vec::with_capacity(array.len());
+            for obj in array {
+                let channelsmap = obj.into_map()?;
+                res.push(ChannelInfo::from_map(&channelsmap)?);
+            }
+            res
+        };
+        let stored_streams = match map.remove(&"m_Streams".to_string()) {
+            Some(array) => {
+                let mut res = Vec::new();
+                for obj in array.into_vec()? {
+                    res.push(StreamInfo::from_map(&obj.into_map()?)?);
+                }
+                res
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            object: Object::new(map)?,
+            // m_CurrentChannels was dropped in 2018.1, the mask of the used
+            // channels is rebuilt from the channels
+            current_channels: match map.get(&"m_CurrentChannels".to_string()) {
+                Some(current_channels) => current_channels.to_i32()?,
+                None => channels
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, channel)| channel.component_count() > 0)
+                    .fold(0, |mask, (i, _)| mask | 1 << i),
+            },
+            vertex_count: tryGet!(map, "m_VertexCount").to_u32()?,
+            data: tryGet!(map, "m_DataSize").to_byte_vec()?,
+            channels,
+            stored_streams,
+        })
+    }
+}
+
+impl Mesh {
+    /// Sets the Unity version the mesh was built with. The numbering of the
+    /// vertex formats changed in 2017.1 and 2019.1, meshes of unknown
+    /// versions are assumed to use the 2017.1 formats if they have the 8
+    /// channels of earlier versions and the 2019.1 formats otherwise.
+    pub fn set_version(&mut self, version: UnityVersion) {
+        self.unity_version = Some(version);
+    }
+
+    /// Decodes the vertex attributes of the mesh
+    pub fn vertices(&self) -> Result<Vertices> {
+        if let Some(ref compressed_mesh) = self.compressed_mesh {
+            return compressed_mesh.decode();
+        }
+        let mut vertices = self.vertex_data.decode(self.unity_version)?;
+        if vertices.bone_weights.is_empty() && !self.skin.is_empty() {
+            vertices.bone_weights = self.skin.iter().map(|b| b.weights).collect();
+            vertices.bone_indices = self.skin.iter().map(|b| b.indices).collect();
+        }
+        Ok(vertices)
+    }
+
+    /// Decodes the blend shapes into offsets for every vertex
+    pub fn blend_shape_channels(&self) -> Result<Vec<BlendShapeChannel>> {
+        if self.blend_shapes.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.blend_shapes.decode(self.vertices()?.positions.len())
+    }
+
+    /// Returns the indices of a submesh. The submesh's `base_vertex` is not
+    /// added to them.
+    pub fn submesh_indices(&self, submesh: usize) -> Result<IndexList> {
+        let submesh = self.submeshes.get(submesh).ok_or_else(|| {
+            Error::EngineError(format!(
+                "Submesh {} is out of range, the mesh has {}",
+                submesh,
+                self.submeshes.len()
+            ))
+        })?;
+        let index_size = match self.index_format {
+            IndexFormat::UInt16 => 2,
+            IndexFormat::UInt32 => 4,
+        };
+        if let Some(ref compressed_mesh) = self.compressed_mesh {
+            let indices = compressed_mesh.indices()?;
+            let start = submesh.first_byte as usize / index_size;
+            let end = start + submesh.index_count as usize;
+            let indices = match indices.get(start..end) {
+                Some(indices) => indices,
+                None => {
+                    return Err(Error::EngineError(format!(
+                        "Compressed mesh has {} indices, submesh indices are {}..{}",
+                        indices.len(),
+                        start,
+                        end
+                    )));
+                }
+            };
+            return Ok(match self.index_format {
+                IndexFormat::UInt16 => IndexList::U16(indices.iter().map(|&i| i as u16).collect()),
+                IndexFormat::UInt32 => IndexList::U32(indices.to_vec()),
+            });
+        }
+        let start = submesh.first_byte as usize;
+        let end = start + submesh.index_count as usize * index_size;
+        let data = match self.index_buffer.get(start..end) {
+            Some(data) => data,
+            None => {
+                return Err(Error::EngineError(format!(
+                    "Index buffer of {} bytes is too short for submesh indices {}..{}",
+                    self.index_buffer.len(),
+                    start,
+                    end
+                )));
+            }
+        };
+        Ok(match self.index_format {
+            IndexFormat::UInt16 => IndexList::U16(
+                data.chunks(2)
+                    .map(|b| u16::from(b[0]) | u16::from(b[1]) << 8)
+                    .collect(),
+            ),
+            IndexFormat::UInt32 => IndexList::U32(
+                data.chunks(4)
+                    .map(|b| {
+                        u32::from(b[0])
+                            | u32::from(b[1]) << 8
+                            | u32::from(b[2]) << 16
+                            | u32::from(b[3]) << 24
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Returns the triangles of a submesh with its `base_vertex` applied.
+    /// Triangle strips and quads are split into triangles, submeshes of
+    /// lines and points have none.
+    pub fn triangles(&self, submesh: usize) -> Result<Vec<[u32; 3]>> {
+        let indices = self.submesh_indices(submesh)?.to_u32();
+        let base_vertex = self.submeshes[submesh].base_vertex;
+        let indices: Vec<u32> = indices.iter().map(|&i| i + base_vertex).collect();
+        let triangles = match Topology::from_i32(self.submeshes[submesh].topology) {
+            Topology::Triangles => indices
+                .chunks(3)
+                .filter(|t| t.len() == 3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // every other triangle of a strip is wound the other way
+            Topology::TriangleStrip => indices
+                .windows(3)
+                .enumerate()
+                .filter(|(_, t)| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+                .map(|(i, t)| {
+                    if i % 2 == 0 {
+                        [t[0], t[1], t[2]]
+                    } else {
+                        [t[1], t[0], t[2]]
+                    }
+                })
+                .collect(),
+            Topology::Quads => indices
+                .chunks(4)
+                .filter(|q| q.len() == 4)
+                .flat_map(|q| vec![[q[0], q[1], q[2]], [q[0], q[2], q[3]]])
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(triangles)
+    }
+}
+
+/// Primitive type of a submesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    Triangles,
+    TriangleStrip,
+    Quads,
+    Lines,
+    LineStrip,
+    Points,
+    Unknown(i32),
+}
+
+impl Topology {
+    pub fn from_i32(n: i32) -> Self {
+        match n {
+            0 => Topology::Triangles,
+            1 => Topology::TriangleStrip,
+            2 => Topology::Quads,
+            3 => Topology::Lines,
+            4 => Topology::LineStrip,
+            5 => Topology::Points,
+            _ => Topology::Unknown(n),
+        }
+    }
+}
+
+impl IntoMesh for EngineObject {
+    fn to_mesh(mut self) -> Result<Mesh> {
+        let mesh_compression = tryGet!(self.map, "m_MeshCompression").to_u8()?;
+        let compressed_mesh = match self.map.get(&"m_CompressedMesh".to_string()) {
+            Some(ObjectValue::Map(ref map)) if mesh_compression != 0 => {
+                Some(CompressedMesh::from_map(map)?)
+            }
+            _ => None,
+        };
+        Ok(Mesh {
+            object: Object::new(&self.map)?,
+            root_bone_name_hash: tryGet!(self.map, "m_RootBoneNameHash").to_u32()?,
+            index_buffer: tryGet!(self.map, "m_IndexBuffer").to_byte_vec()?,
+            // 32 bit indices were added in 2017.3
+            index_format: match self.map.get(&"m_IndexFormat".to_string()) {
+                Some(format) if format.to_i32()? == 1 => IndexFormat::UInt32,
+                _ => IndexFormat::UInt16,
+            },
+            bind_pose: tryConsume!(self.map, "m_BindPose")
+                .into_vec()?
+                .iter()
+                .map(Matrix4x4::from_map)
+                .collect::<Result<_>>()?,
+            bone_name_hashes: match self.map.get(&"m_BoneNameHashes".to_string()) {
+                Some(ObjectValue::Array(ref array)) => array
+                    .iter()
+                    .map(|hash| hash.to_u32())
+                    .collect::<Result<_>>()?,
+                _ => Vec::new(),
+            },
+            baked_convex_collision_mesh: tryGet!(self.map, "m_BakedConvexCollisionMesh")
+                .to_byte_vec()?,
+            mesh_compression,
+            submeshes: {
+                let array = tryConsume!(self.map, "m_SubMeshes").into_vec()?;
+                let mut res = Vec::with_capacity(array.len());
+                for obj in array {
+                    let mut submeshmap = obj.into_map()?;
+                    res.push(SubMesh::from_map(&mut submeshmap)?);
+                }
+                res
+            },
+            vertex_data: {
+                let mut map = tryConsume!(self.map, "m_VertexData").into_map()?;
+                VertexData::from_map(&mut map)?
+            },
+            compressed_mesh,
+            // blend shapes were added in 4.3
+            blend_shapes: match self.map.get(&"m_Shapes".to_string()) {
+                Some(ObjectValue::Map(ref map)) => BlendShapeData::from_map(map)?,
+                _ => BlendShapeData::default(),
+            },
+            skin: match self.map.remove(&"m_Skin".to_string()) {
+                Some(array) => {
+                    let array = array.into_vec()?;
+                    let mut res = Vec::with_capacity(array.len());
+                    for obj in array {
+                        res.push(BoneInfluence::from_map(&obj.into_map()?)?);
+                    }
+                    res
+                }
+                None => Vec::new(),
+            },
+            unity_version: self.unity_version,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn submesh(first_byte: u32, index_count: u32, topology: i32) -> SubMesh {
+        SubMesh {
+            first_byte,
+            first_vertex: 0,
+            index_count,
+            local_aabb: OrderedMap::default(),
+            topology,
+            vertex_count: 4,
+            base_vertex: 0,
+        }
+    }
+
+    pub fn mesh(index_buffer: Vec<u8>, submeshes: Vec<SubMesh>) -> Mesh {
+        Mesh {
+            object: Object {
+                name: "mesh".to_string(),
+            },
+            root_bone_name_hash: 0,
+            index_buffer,
+            index_format: IndexFormat::UInt16,
+            bind_pose: Vec::new(),
+            bone_name_hashes: Vec::new(),
+            baked_convex_collision_mesh: Vec::new(),
+            mesh_compression: 0,
+            submeshes,
+            vertex_data: VertexData {
+                object: Object {
+                    name: String::new(),
+                },
+                channels: Vec::new(),
+                stored_streams: Vec::new(),
+                current_channels: 0,
+                data: Vec::new(),
+                vertex_count: 0,
+            },
+            compressed_mesh: None,
+            blend_shapes: BlendShapeData::default(),
+            skin: Vec::new(),
+            unity_version: None,
+        }
+    }
+
+    /// A unit quad facing -z with positions, normals and UV0 in a single
+    /// stream, split into two triangles
+    pub fn quad_mesh() -> Mesh {
+        let mut mesh = mesh(
+            vec![0, 0, 1, 0, 2, 0, 2, 0, 3, 0, 0, 0],
+            vec![submesh(0, 6, 0)],
+        );
+        let unused = ChannelInfo {
+            stream: 0,
+            offset: 0,
+            format: 0,
+            dimension: 0,
+        };
+        let channel = |offset, dimension| ChannelInfo {
+            offset,
+            dimension,
+            ..unused
+        };
+        mesh.vertex_data.channels = vec![
+            channel(0, 3),
+            channel(12, 3),
+            unused,
+            channel(24, 2),
+            unused,
+            unused,
+            unused,
+            unused,
+        ];
+        mesh.vertex_data.vertex_count = 4;
+        for &(x, y) in &[(0.0f32, 0.0f32), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)] {
+            for value in &[x, y, 0.0, 0.0, 0.0, -1.0, x, y] {
+                mesh.vertex_data
+                    .data
+                    .extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        mesh
+    }
+
+    #[test]
+    fn test_submesh_indices() {
+        let mut mesh = mesh(
+            vec![0, 0, 1, 0, 2, 0, 2, 0, 1, 0, 3, 0],
+            vec![submesh(0, 3, 0), submesh(6, 3, 0)],
+        );
+        assert_eq!(
+            mesh.submesh_indices(1).unwrap(),
+            IndexList::U16(vec![2, 1, 3])
+        );
+        assert!(mesh.submesh_indices(2).is_err());
+
+        mesh.index_format = IndexFormat::UInt32;
+        mesh.submeshes = vec![submesh(4, 2, 0)];
+        assert_eq!(
+            mesh.submesh_indices(0).unwrap(),
+            IndexList::U32(vec![0x0002_0002, 0x0003_0001])
+        );
+        mesh.submeshes = vec![submesh(4, 3, 0)];
+        assert!(mesh.submesh_indices(0).is_err());
+    }
+
+    #[test]
+    fn test_triangles() {
+        let mut mesh = mesh(
+            vec![0, 0, 1, 0, 2, 0, 3, 0],
+            vec![submesh(0, 4, 1), submesh(0, 4, 2), submesh(0, 4, 3)],
+        );
+        assert_eq!(mesh.triangles(0).unwrap(), vec![[0, 1, 2], [2, 1, 3]]);
+        assert_eq!(mesh.triangles(1).unwrap(), vec![[0, 1, 2], [0, 2, 3]]);
+        assert!(mesh.triangles(2).unwrap().is_empty());
+
+        mesh.submeshes[0] = submesh(0, 3, 0);
+        mesh.submeshes[0].base_vertex = 10;
+        assert_eq!(mesh.triangles(0).unwrap(), vec![[10, 11, 12]]);
+        assert_eq!(quad_mesh().vertices().unwrap().uvs[0][2], [1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_asset_info() {
+        let mut vertex_data = OrderedMap::default();
+        vertex_data.insert("m_Channels".to_string(), ObjectValue::Array(Vec::new()));
+        vertex_data.insert("m_VertexCount".to_string(), ObjectValue::U32(0));
+        vertex_data.insert("m_DataSize".to_string(), ObjectValue::U8Array(Vec::new()));
+        let mut map = OrderedMap::default();
+        map.insert("m_MeshCompression".to_string(), ObjectValue::U8(0));
+        map.insert("m_RootBoneNameHash".to_string(), ObjectValue::U32(0));
+        map.insert(
+            "m_IndexBuffer".to_string(),
+            ObjectValue::U8Array(Vec::new()),
+        );
+        map.insert("m_BindPose".to_string(), ObjectValue::Array(Vec::new()));
+        map.insert(
+            "m_BakedConvexCollisionMesh".to_string(),
+            ObjectValue::U8Array(Vec::new()),
+        );
+        map.insert("m_SubMeshes".to_string(), ObjectValue::Array(Vec::new()));
+        map.insert("m_VertexData".to_string(), ObjectValue::Map(vertex_data));
+
+        let mut object = EngineObject::new(map);
+        object.unity_version = Some(UnityVersion::new(2018, 4, 0));
+        let mesh = object.to_mesh().unwrap();
+        assert_eq!(mesh.unity_version, Some(UnityVersion::new(2018, 4, 0)));
+    }
+}
@@ -0,0 +1,481 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+use super::VertexData;
+use engine::texture::half_to_f32;
+use error::{Error, Result};
+use extras::containers::OrderedMap;
+use object::ObjectValue;
+use version::UnityVersion;
+
+/// Storage format of a vertex attribute component
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexFormat {
+    Float,
+    Float16,
+    UNorm8,
+    SNorm8,
+    UNorm16,
+    SNorm16,
+    UInt8,
+    SInt8,
+    UInt16,
+    SInt16,
+    UInt32,
+    SInt32,
+}
+
+/// Numbering of the vertex formats, which changed in 2017.1 and 2019.1
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FormatTable {
+    Unity5,
+    Unity2017,
+    Unity2019,
+}
+
+impl VertexFormat {
+    fn from_u8(n: u8, table: FormatTable) -> Result<Self> {
+        let format = match (table, n) {
+            (_, 0) => VertexFormat::Float,
+            (_, 1) => VertexFormat::Float16,
+            // colors of earlier versions are 4 normalized bytes
+            (FormatTable::Unity5, 2) | (FormatTable::Unity2017, 2) => VertexFormat::UNorm8,
+            (FormatTable::Unity5, 3) => VertexFormat::UInt8,
+            (FormatTable::Unity5, 4) => VertexFormat::UInt32,
+            (FormatTable::Unity2017, 3..=12) => {
+                VertexFormat::from_u8(n - 1, FormatTable::Unity2019)?
+            }
+            (FormatTable::Unity2019, 2) => VertexFormat::UNorm8,
+            (FormatTable::Unity2019, 3) => VertexFormat::SNorm8,
+            (FormatTable::Unity2019, 4) => VertexFormat::UNorm16,
+            (FormatTable::Unity2019, 5) => VertexFormat::SNorm16,
+            (FormatTable::Unity2019, 6) => VertexFormat::UInt8,
+            (FormatTable::Unity2019, 7) => VertexFormat::SInt8,
+            (FormatTable::Unity2019, 8) => VertexFormat::UInt16,
+            (FormatTable::Unity2019, 9) => VertexFormat::SInt16,
+            (FormatTable::Unity2019, 10) => VertexFormat::UInt32,
+            (FormatTable::Unity2019, 11) => VertexFormat::SInt32,
+            _ => {
+                return Err(Error::EngineError(format!("Unknown vertex format {}", n)));
+            }
+        };
+        Ok(format)
+    }
+
+    /// Size of one component in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            VertexFormat::UNorm8
+            | VertexFormat::SNorm8
+            | VertexFormat::UInt8
+            | VertexFormat::SInt8 => 1,
+            VertexFormat::Float16
+            | VertexFormat::UNorm16
+            | VertexFormat::SNorm16
+            | VertexFormat::UInt16
+            | VertexFormat::SInt16 => 2,
+            VertexFormat::Float | VertexFormat::UInt32 | VertexFormat::SInt32 => 4,
+        }
+    }
+
+    /// Reads a little endian component. Normalized formats are mapped to
+    /// [0, 1] or [-1, 1], integer formats keep their value.
+    fn read(&self, bytes: &[u8]) -> f32 {
+        let u16_value = || u16::from(bytes[0]) | u16::from(bytes[1]) << 8;
+        let u32_value = || {
+            u32::from(bytes[0])
+                | u32::from(bytes[1]) << 8
+                | u32::from(bytes[2]) << 16
+                | u32::from(bytes[3]) << 24
+        };
+        match self {
+            VertexFormat::Float => f32::from_bits(u32_value()),
+            VertexFormat::Float16 => half_to_f32(u16_value()),
+            VertexFormat::UNorm8 => f32::from(bytes[0]) / 255.0,
+            VertexFormat::SNorm8 => (f32::from(bytes[0] as i8) / 127.0).max(-1.0),
+            VertexFormat::UNorm16 => f32::from(u16_value()) / 65535.0,
+            VertexFormat::SNorm16 => (f32::from(u16_value() as i16) / 32767.0).max(-1.0),
+            VertexFormat::UInt8 => f32::from(bytes[0]),
+            VertexFormat::SInt8 => f32::from(bytes[0] as i8),
+            VertexFormat::UInt16 => f32::from(u16_value()),
+            VertexFormat::SInt16 => f32::from(u16_value() as i16),
+            VertexFormat::UInt32 => u32_value() as f32,
+            VertexFormat::SInt32 => u32_value() as i32 as f32,
+        }
+    }
+}
+
+/// Meaning of a vertex channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    Tangent,
+    Color,
+    /// UV set 0 to 7
+    TexCoord(usize),
+    BlendWeight,
+    BlendIndices,
+}
+
+/// Location of a vertex attribute, an entry of `m_Channels`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelInfo {
+    pub stream: u8,
+    /// Byte offset of the attribute within a vertex of its stream
+    pub offset: u8,
+    /// Version dependent vertex format, see `VertexData::channel_format`
+    pub format: u8,
+    /// Component count in the lower 4 bits, 0 for unused channels
+    pub dimension: u8,
+}
+
+impl ChannelInfo {
+    pub(crate) fn from_map(map: &OrderedMap<String, ObjectValue>) -> Result<Self> {
+        Ok(ChannelInfo {
+            stream: tryGet!(map, "stream").to_u8()?,
+            offset: tryGet!(map, "offset").to_u8()?,
+            format: tryGet!(map, "format").to_u8()?,
+            dimension: tryGet!(map, "dimension").to_u8()?,
+        })
+    }
+
+    /// Number of components of the attribute
+    pub fn component_count(&self) -> usize {
+        (self.dimension & 0xf) as usize
+    }
+}
+
+/// Layout of a vertex stream in the vertex data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamInfo {
+    pub channel_mask: u32,
+    pub offset: u32,
+    pub stride: u32,
+}
+
+impl StreamInfo {
+    pub(crate) fn from_map(map: &OrderedMap<String, ObjectValue>) -> Result<Self> {
+        Ok(StreamInfo {
+            channel_mask: tryGet!(map, "channelMask").to_u32()?,
+            offset: tryGet!(map, "offset").to_u32()?,
+            // the stride shrank to a byte in Unity 4
+            stride: match tryGet!(map, "stride") {
+                ObjectValue::U8(stride) => u32::from(*stride),
+                stride => stride.to_u32()?,
+            },
+        })
+    }
+}
+
+/// Decoded vertex attributes. Attributes the mesh does not store are empty,
+/// all others hold one entry per vertex.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Vertices {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 4]>,
+    /// RGBA colors in [0, 1]
+    pub colors: Vec<[f32; 4]>,
+    /// The first two components of the UV sets 0 to 7
+    pub uvs: [Vec<[f32; 2]>; 8],
+    pub bone_weights: Vec<[f32; 4]>,
+    pub bone_indices: Vec<[u32; 4]>,
+}
+
+impl VertexData {
+    /// Returns the attribute stored in a channel. Unity 2018.1 grew the
+    /// channel list from 8 to 14 entries and reordered it.
+    pub fn attribute(&self, channel: usize) -> Option<VertexAttribute> {
+        if self.channels.len() >= 14 {
+            match channel {
+                0 => Some(VertexAttribute::Position),
+                1 => Some(VertexAttribute::Normal),
+                2 => Some(VertexAttribute::Tangent),
+                3 => Some(VertexAttribute::Color),
+                4..=11 => Some(VertexAttribute::TexCoord(channel - 4)),
+                12 => Some(VertexAttribute::BlendWeight),
+                13 => Some(VertexAttribute::BlendIndices),
+                _ => None,
+            }
+        } else {
+            // the tangents are always the last channel
+            match channel {
+                0 => Some(VertexAttribute::Position),
+                1 => Some(VertexAttribute::Normal),
+                2 => Some(VertexAttribute::Color),
+                _ if channel + 1 == self.channels.len() => Some(VertexAttribute::Tangent),
+                3..=6 => Some(VertexAttribute::TexCoord(channel - 3)),
+                _ => None,
+            }
+        }
+    }
+
+    fn format_table(&self, version: Option<UnityVersion>) -> FormatTable {
+        match version {
+            Some(version) if !version.is_at_least(2017, 1) => FormatTable::Unity5,
+            Some(version) if !version.is_at_least(2019, 1) => FormatTable::Unity2017,
+            Some(_) => FormatTable::Unity2019,
+            None if self.channels.len() < 14 => FormatTable::Unity2017,
+            None => FormatTable::Unity2019,
+        }
+    }
+
+    /// Returns the format of a channel for meshes of the given Unity version
+    pub fn channel_format(
+        &self,
+        channel: usize,
+        version: Option<UnityVersion>,
+    ) -> Result<VertexFormat> {
+        match self.channels.get(channel) {
+            Some(info) => VertexFormat::from_u8(info.format, self.format_table(version)),
+            None => Err(Error::EngineError(format!(
+                "Vertex channel {} is out of range",
+                channel
+            ))),
+        }
+    }
+
+    /// Returns the layout of the vertex streams. Since Unity 5 the streams
+    /// follow each other in the order of their index, each stream holds the
+    /// attributes of its channels for every vertex and starts at a 16 byte
+    /// boundary.
+    pub fn streams(&self, version: Option<UnityVersion>) -> Result<Vec<StreamInfo>> {
+        if !self.stored_streams.is_empty() {
+            return Ok(self.stored_streams.clone());
+        }
+        let stream_count = self
+            .channels
+            .iter()
+            .filter(|channel| channel.component_count() > 0)
+            .map(|channel| channel.stream as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut streams = Vec::with_capacity(stream_count);
+        let mut offset = 0;
+        for stream in 0..stream_count {
+            let mut info = StreamInfo {
+                channel_mask: 0,
+                offset,
+                stride: 0,
+            };
+            for (i, channel) in self.channels.iter().enumerate() {
+                if channel.stream as usize == stream && channel.component_count() > 0 {
+                    let format = self.channel_format(i, version)?;
+                    info.channel_mask |= 1 << i;
+                    info.stride += (format.size() * channel.component_count()) as u32;
+                }
+            }
+            offset += self.vertex_count * info.stride;
+            offset = (offset + 15) & !15;
+            streams.push(info);
+        }
+        Ok(streams)
+    }
+
+    /// Reads the components of a channel for every vertex. Components the
+    /// channel does not store are taken from `default`.
+    fn read_channel(
+        &self,
+        channel: usize,
+        streams: &[StreamInfo],
+        version: Option<UnityVersion>,
+        default: [f32; 4],
+    ) -> Result<Vec<[f32; 4]>> {
+        let info = self.channels[channel];
+        let format = self.channel_format(channel, version)?;
+        let stream = streams.get(info.stream as usize).ok_or_else(|| {
+            Error::EngineError(format!("Vertex stream {} is missing", info.stream))
+        })?;
+        let count = info.component_count().min(4);
+        let vertex_count = self.vertex_count as usize;
+        let (offset, stride) = (
+            stream.offset as usize + info.offset as usize,
+            stream.stride as usize,
+        );
+        if vertex_count > 0 {
+            let end = offset + (vertex_count - 1) * stride + info.component_count() * format.size();
+            if end > self.data.len() {
+                return Err(Error::EngineError(format!(
+                    "Vertex data of {} bytes is too short for channel {} ending at {}",
+                    self.data.len(),
+                    channel,
+                    end
+                )));
+            }
+        }
+
+        let mut values = Vec::with_capacity(vertex_count);
+        for vertex in 0..vertex_count {
+            let start = offset + vertex * stride;
+            let mut value = default;
+            for (i, item) in value.iter_mut().enumerate().take(count) {
+                *item = format.read(&self.data[start + i * format.size()..]);
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    /// Decodes all vertex attributes, see `Mesh::set_version` for how the
+    /// version selects the vertex formats
+    pub fn decode(&self, version: Option<UnityVersion>) -> Result<Vertices> {
+        let streams = self.streams(version)?;
+        let mut vertices = Vertices::default();
+        for channel in 0..self.channels.len() {
+            if self.channels[channel].component_count() == 0 {
+                continue;
+            }
+            let attribute = match self.attribute(channel) {
+                Some(attribute) => attribute,
+                None => continue,
+            };
+            let default = match attribute {
+                VertexAttribute::Color | VertexAttribute::Tangent => [0.0, 0.0, 0.0, 1.0],
+                _ => [0.0; 4],
+            };
+            let values = self.read_channel(channel, &streams, version, default)?;
+            match attribute {
+                VertexAttribute::Position => {
+                    vertices.positions = values.iter().map(|v| [v[0], v[1], v[2]]).collect()
+                }
+                VertexAttribute::Normal => {
+                    vertices.normals = values.iter().map(|v| [v[0], v[1], v[2]]).collect()
+                }
+                VertexAttribute::Tangent => vertices.tangents = values,
+                VertexAttribute::Color => vertices.colors = values,
+                VertexAttribute::TexCoord(set) => {
+                    vertices.uvs[set] = values.iter().map(|v| [v[0], v[1]]).collect()
+                }
+                VertexAttribute::BlendWeight => vertices.bone_weights = values,
+                VertexAttribute::BlendIndices => {
+                    vertices.bone_indices = values
+                        .iter()
+                        .map(|v| [v[0] as u32, v[1] as u32, v[2] as u32, v[3] as u32])
+                        .collect()
+                }
+            }
+        }
+        Ok(vertices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::object::Object;
+
+    fn channel(stream: u8, offset: u8, format: u8, dimension: u8) -> ChannelInfo {
+        ChannelInfo {
+            stream,
+            offset,
+            format,
+            dimension,
+        }
+    }
+
+    fn vertex_data(channels: Vec<ChannelInfo>, data: Vec<u8>, vertex_count: u32) -> VertexData {
+        VertexData {
+            object: Object {
+                name: String::new(),
+            },
+            channels,
+            stored_streams: Vec::new(),
+            current_channels: 0,
+            data,
+            vertex_count,
+        }
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(
+            VertexFormat::from_u8(4, FormatTable::Unity5).unwrap(),
+            VertexFormat::UInt32
+        );
+        assert_eq!(
+            VertexFormat::from_u8(12, FormatTable::Unity2017).unwrap(),
+            VertexFormat::SInt32
+        );
+        assert_eq!(
+            VertexFormat::from_u8(2, FormatTable::Unity2019).unwrap(),
+            VertexFormat::UNorm8
+        );
+        assert!(VertexFormat::from_u8(12, FormatTable::Unity2019).is_err());
+        assert_eq!(VertexFormat::SNorm8.read(&[0x81]), -1.0);
+        assert_eq!(VertexFormat::UNorm16.read(&[0xff, 0xff]), 1.0);
+        assert_eq!(VertexFormat::Float16.read(&[0x00, 0x3c]), 1.0);
+    }
+
+    #[test]
+    fn test_unity5_layout() {
+        // positions and colors in stream 0, UV0 in stream 1
+        let mut channels = vec![channel(0, 0, 0, 0); 8];
+        channels[0] = channel(0, 0, 0, 3);
+        channels[2] = channel(0, 12, 2, 4);
+        channels[3] = channel(1, 0, 0, 2);
+        let mut data = floats(&[1.0, 2.0, 3.0]);
+        data.extend_from_slice(&[255, 0, 51, 255]);
+        data.extend(floats(&[4.0, 5.0, 6.0]));
+        data.extend_from_slice(&[0, 255, 0, 0]);
+        // stream 1 starts at a 16 byte boundary
+        data.extend(vec![0; 32 - data.len()]);
+        data.extend(floats(&[0.25, 0.5, 0.75, 1.0]));
+
+        let vertex_data = vertex_data(channels, data, 2);
+        let streams = vertex_data.streams(None).unwrap();
+        assert_eq!(streams[0].stride, 16);
+        assert_eq!(streams[1].offset, 32);
+        let vertices = vertex_data
+            .decode(Some(UnityVersion::new(5, 6, 0)))
+            .unwrap();
+        assert_eq!(vertices.positions, vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+        assert_eq!(vertices.colors[0], [1.0, 0.0, 51.0 / 255.0, 1.0]);
+        assert_eq!(vertices.uvs[0], vec![[0.25, 0.5], [0.75, 1.0]]);
+        assert!(vertices.normals.is_empty() && vertices.tangents.is_empty());
+    }
+
+    #[test]
+    fn test_unity2019_layout() {
+        // half normals, float UV7, blend weights and indices in stream 1
+        let mut channels = vec![channel(0, 0, 0, 0); 14];
+        channels[0] = channel(0, 0, 0, 3);
+        channels[1] = channel(0, 12, 1, 3);
+        channels[11] = channel(0, 18, 0, 2);
+        channels[12] = channel(1, 0, 0, 2);
+        channels[13] = channel(1, 8, 6, 4);
+        let mut data = floats(&[1.0, 2.0, 3.0]);
+        data.extend_from_slice(&[0x00, 0x3c, 0x00, 0x00, 0x00, 0xbc]);
+        data.extend(floats(&[0.5, 0.25]));
+        data.extend(vec![0; 6]);
+        data.extend(floats(&[0.75, 0.25]));
+        data.extend_from_slice(&[3, 1, 0, 0]);
+
+        let vertex_data = vertex_data(channels, data, 1);
+        assert_eq!(
+            vertex_data.attribute(11),
+            Some(VertexAttribute::TexCoord(7))
+        );
+        let vertices = vertex_data.decode(None).unwrap();
+        assert_eq!(vertices.normals, vec![[1.0, 0.0, -1.0]]);
+        assert_eq!(vertices.uvs[7], vec![[0.5, 0.25]]);
+        assert_eq!(vertices.bone_weights, vec![[0.75, 0.25, 0.0, 0.0]]);
+        assert_eq!(vertices.bone_indices, vec![[3, 1, 0, 0]]);
+
+        // the same channels are too short with the 2018 formats
+        assert!(vertex_data
+            .decode(Some(UnityVersion::new(2018, 4, 0)))
+            .is_err());
+    }
+}
@@ -36,15 +36,25 @@ macro_rules! tryConsume {
 pub mod font;
 pub mod mesh;
 pub mod object;
+pub mod renderer;
+pub mod sprite;
 pub mod text;
 pub mod texture;
+pub mod transform;
 
-use super::object::ObjectValue;
+use super::object::{ObjectPointer, ObjectValue};
+use enums::BuildTarget;
+use error::{Error, Result};
 use extras::containers::OrderedMap;
+use version::UnityVersion;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct EngineObject {
     pub map: OrderedMap<String, ObjectValue>,
+    /// Platform of the asset the object was read from, if the asset stores it
+    pub target_platform: Option<BuildTarget>,
+    /// Unity version of the asset the object was read from
+    pub unity_version: Option<UnityVersion>,
 }
 
 pub enum EngineObjectVariant {
@@ -53,17 +63,72 @@ pub enum EngineObjectVariant {
 }
 
 impl EngineObject {
+    pub fn new(map: OrderedMap<String, ObjectValue>) -> EngineObject {
+        EngineObject {
+            map,
+            ..Default::default()
+        }
+    }
+
     pub fn get_object(
         type_name: &str,
         ordered_map: OrderedMap<String, ObjectValue>,
     ) -> EngineObjectVariant {
         match type_name {
             // implemented engine object types
-            "Texture2D" | "TextAsset" | "FontDef" | "Font" | "MonoBehaviour" | "AssetBundle"
-            | "GameObject" | "Mesh" => {
-                EngineObjectVariant::EngineObject(EngineObject { map: ordered_map })
+            "Texture2D"
+            | "Cubemap"
+            | "Texture2DArray"
+            | "Texture3D"
+            | "TextAsset"
+            | "FontDef"
+            | "Font"
+            | "MonoBehaviour"
+            | "AssetBundle"
+            | "GameObject"
+            | "Mesh"
+            | "Sprite"
+            | "SpriteAtlas"
+            | "Transform"
+            | "SkinnedMeshRenderer" => {
+                EngineObjectVariant::EngineObject(EngineObject::new(ordered_map))
             }
             _ => EngineObjectVariant::NotImplemented(ordered_map),
         }
     }
 }
+
+/// Borrows the fields of a structure
+pub(crate) fn as_map(value: &ObjectValue) -> Result<&OrderedMap<String, ObjectValue>> {
+    match value {
+        ObjectValue::Map(ref map) => Ok(map),
+        _ => Err(Error::EngineError(format!(
+            "Value is not a structure but {:?}",
+            value
+        ))),
+    }
+}
+
+/// Reads the named float fields of a structure, like the `x`, `y`, `z` of a vector
+pub(crate) fn read_floats<A: Default + AsMut<[f32]>>(
+    value: &ObjectValue,
+    fields: &[&str],
+) -> Result<A> {
+    let map = as_map(value)?;
+    let mut res = A::default();
+    for (item, field) in res.as_mut().iter_mut().zip(fields) {
+        *item = tryGet!(map, *field).to_f32()?;
+    }
+    Ok(res)
+}
+
+/// Reads a PPtr, `None` for null pointers
+pub(crate) fn read_pointer(value: &ObjectValue) -> Result<Option<ObjectPointer>> {
+    match value {
+        ObjectValue::ObjectPointer(ref pointer) => Ok(Some(pointer.clone())),
+        ObjectValue::None => Ok(None),
+        _ => Err(Error::EngineError(
+            "Value is not of ObjectPointer type".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,293 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+use super::object::Object;
+use super::transform::{IntoTransform, Matrix4x4, Transform};
+use super::{read_pointer, EngineObject};
+use asset::Asset;
+use assetbundle::Signature;
+use error::{Error, Result};
+use object::{ObjectPointer, ObjectValue};
+use std::collections::HashMap;
+
+pub trait IntoSkinnedMeshRenderer {
+    fn to_skinned_mesh_renderer(self) -> Result<SkinnedMeshRenderer>;
+}
+
+#[derive(Debug)]
+pub struct SkinnedMeshRenderer {
+    pub game_object: Option<ObjectPointer>,
+    pub enabled: bool,
+    /// Material of every submesh, `None` for missing materials
+    pub materials: Vec<Option<ObjectPointer>>,
+    pub mesh: Option<ObjectPointer>,
+    /// Transform of every bone index of the mesh, the order matches the
+    /// bind poses of the mesh
+    pub bones: Vec<Option<ObjectPointer>>,
+    pub root_bone: Option<ObjectPointer>,
+    pub blend_shape_weights: Vec<f32>,
+}
+
+/// A transform of the hierarchy that holds the bones of a skinned mesh
+#[derive(Debug, Clone)]
+pub struct Bone {
+    /// Name of the bone's `GameObject`
+    pub name: String,
+    pub path_id: i64,
+    /// Index of the parent bone in the skeleton
+    pub parent: Option<usize>,
+    pub transform: Transform,
+}
+
+/// The transform hierarchy of a skinned mesh, from the topmost ancestor
+/// of its bones down to the bones
+#[derive(Debug, Default, Clone)]
+pub struct Skeleton {
+    /// All transforms of the hierarchy, parents precede their children
+    pub bones: Vec<Bone>,
+    /// Index into `bones` for every bone index of the mesh, `None` for
+    /// missing bones
+    pub joints: Vec<Option<usize>>,
+}
+
+impl Skeleton {
+    /// Bones without a parent
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.bones.len())
+            .filter(|&i| self.bones[i].parent.is_none())
+            .collect()
+    }
+
+    pub fn children(&self, bone: usize) -> Vec<usize> {
+        (0..self.bones.len())
+            .filter(|&i| self.bones[i].parent == Some(bone))
+            .collect()
+    }
+
+    /// The transformation from the space of the bone to the space of the
+    /// root of the hierarchy
+    pub fn world_matrix(&self, bone: usize) -> Matrix4x4 {
+        let mut matrix = self.bones[bone].transform.local_matrix();
+        let mut parent = self.bones[bone].parent;
+        while let Some(index) = parent {
+            matrix = self.bones[index].transform.local_matrix() * matrix;
+            parent = self.bones[index].parent;
+        }
+        matrix
+    }
+
+    /// The names of the bone and its ancestors joined by slashes, the
+    /// way animation clips address transforms
+    pub fn path(&self, bone: usize) -> String {
+        let mut names = vec![self.bones[bone].name.as_str()];
+        let mut parent = self.bones[bone].parent;
+        while let Some(index) = parent {
+            names.push(&self.bones[index].name);
+            parent = self.bones[index].parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+}
+
+impl SkinnedMeshRenderer {
+    /// Reads the bone hierarchy of the renderer from the asset it is
+    /// stored in. Bones in other assets are not supported.
+    pub fn skeleton(&self, asset: &Asset, signature: &mut Signature) -> Result<Skeleton> {
+        self.build_skeleton(|pointer| {
+            if pointer.file_id != 0 {
+                return Err(Error::EngineError(format!(
+                    "Object {} is stored in another asset",
+                    pointer.path_id
+                )));
+            }
+            let info = match asset.objects.get(&pointer.path_id) {
+                Some(info) => info,
+                None => {
+                    return Err(Error::EngineError(format!(
+                        "Object {} not found",
+                        pointer.path_id
+                    )));
+                }
+            };
+            match info.read_signature(asset, signature)? {
+                ObjectValue::EngineObject(engine_object) => Ok(engine_object),
+                _ => Err(Error::EngineError(format!(
+                    "Object {} is a {}, not an engine object",
+                    pointer.path_id, info.type_name
+                ))),
+            }
+        })
+    }
+
+    /// Builds the bone hierarchy of the renderer. `load` reads the
+    /// `Transform` and `GameObject` objects the pointers refer to.
+    pub fn build_skeleton<F>(&self, mut load: F) -> Result<Skeleton>
+    where
+        F: FnMut(&ObjectPointer) -> Result<EngineObject>,
+    {
+        let mut skeleton = Skeleton::default();
+        let mut indices: HashMap<i64, usize> = HashMap::new();
+        for bone in &self.bones {
+            let pointer = match *bone {
+                Some(ref pointer) => pointer,
+                None => {
+                    skeleton.joints.push(None);
+                    continue;
+                }
+            };
+
+            // collect the ancestors which are not part of the skeleton yet
+            let mut chain: Vec<(i64, Transform)> = Vec::new();
+            let mut next = Some(pointer.clone());
+            while let Some(pointer) = next {
+                if indices.contains_key(&pointer.path_id) {
+                    break;
+                }
+                if chain.iter().any(|&(path_id, _)| path_id == pointer.path_id) {
+                    return Err(Error::EngineError(format!(
+                        "Transform {} is its own ancestor",
+                        pointer.path_id
+                    )));
+                }
+                let transform = load(&pointer)?.to_transform()?;
+                next = transform.father.clone();
+                chain.push((pointer.path_id, transform));
+            }
+
+            while let Some((path_id, transform)) = chain.pop() {
+                let name = match transform.game_object {
+                    Some(ref game_object) => Object::new(&load(game_object)?.map)?.name,
+                    None => String::new(),
+                };
+                let parent = match transform.father {
+                    Some(ref father) => indices.get(&father.path_id).cloned(),
+                    None => None,
+                };
+                indices.insert(path_id, skeleton.bones.len());
+                skeleton.bones.push(Bone {
+                    name,
+                    path_id,
+                    parent,
+                    transform,
+                });
+            }
+            skeleton.joints.push(indices.get(&pointer.path_id).cloned());
+        }
+        Ok(skeleton)
+    }
+}
+
+fn read_pointers(value: &ObjectValue) -> Result<Vec<Option<ObjectPointer>>> {
+    match *value {
+        ObjectValue::Array(ref array) => array.iter().map(read_pointer).collect(),
+        _ => Err(Error::EngineError(format!(
+            "Value is not an array but {:?}",
+            value
+        ))),
+    }
+}
+
+impl IntoSkinnedMeshRenderer for EngineObject {
+    fn to_skinned_mesh_renderer(self) -> Result<SkinnedMeshRenderer> {
+        Ok(SkinnedMeshRenderer {
+            game_object: read_pointer(tryGet!(self.map, "m_GameObject"))?,
+            enabled: tryGet!(self.map, "m_Enabled").to_bool()?,
+            materials: read_pointers(tryGet!(self.map, "m_Materials"))?,
+            mesh: read_pointer(tryGet!(self.map, "m_Mesh"))?,
+            bones: read_pointers(tryGet!(self.map, "m_Bones"))?,
+            root_bone: match self.map.get(&"m_RootBone".to_string()) {
+                Some(value) => read_pointer(value)?,
+                None => None,
+            },
+            // blend shapes were added in 4.3
+            blend_shape_weights: match self.map.get(&"m_BlendShapeWeights".to_string()) {
+                Some(ObjectValue::Array(ref array)) => array
+                    .iter()
+                    .map(|weight| weight.to_f32())
+                    .collect::<Result<_>>()?,
+                _ => Vec::new(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::transform::tests::{pointer, transform};
+    use super::*;
+    use extras::containers::OrderedMap;
+    use std::ffi::OsString;
+
+    fn game_object(name: &str) -> EngineObject {
+        let mut map = OrderedMap::default();
+        map.insert(
+            "m_Name".to_string(),
+            ObjectValue::String(OsString::from(name)),
+        );
+        EngineObject::new(map)
+    }
+
+    /// A renderer whose bones are the transforms 3 and 2, which is null
+    pub fn renderer() -> SkinnedMeshRenderer {
+        let mut map = OrderedMap::default();
+        map.insert("m_GameObject".to_string(), pointer(10));
+        map.insert("m_Enabled".to_string(), ObjectValue::Bool(true));
+        map.insert(
+            "m_Materials".to_string(),
+            ObjectValue::Array(vec![ObjectValue::None]),
+        );
+        map.insert("m_Mesh".to_string(), pointer(20));
+        map.insert(
+            "m_Bones".to_string(),
+            ObjectValue::Array(vec![pointer(3), pointer(2), ObjectValue::None]),
+        );
+        map.insert("m_RootBone".to_string(), pointer(2));
+        EngineObject::new(map).to_skinned_mesh_renderer().unwrap()
+    }
+
+    /// The hierarchy root (1) -> hips (2) -> spine (3) of `renderer`
+    pub fn load(pointer: &ObjectPointer) -> Result<EngineObject> {
+        Ok(match pointer.path_id {
+            1 => transform(101, [0.0, 0.0, 0.0], None, &[2]),
+            2 => transform(102, [0.0, 1.0, 0.0], Some(1), &[3]),
+            3 => transform(103, [0.0, 0.5, 1.0], Some(2), &[]),
+            101 => game_object("root"),
+            102 => game_object("hips"),
+            103 => game_object("spine"),
+            _ => return Err(Error::EngineError("Unknown object".to_string())),
+        })
+    }
+
+    #[test]
+    fn test_renderer() {
+        let renderer = renderer();
+        assert!(renderer.enabled);
+        assert_eq!(renderer.materials.len(), 1);
+        assert!(renderer.materials[0].is_none());
+        assert_eq!(renderer.mesh.unwrap().path_id, 20);
+        assert_eq!(renderer.bones.len(), 3);
+        assert!(renderer.blend_shape_weights.is_empty());
+    }
+
+    #[test]
+    fn test_skeleton() {
+        let skeleton = renderer().build_skeleton(load).unwrap();
+        let names: Vec<&str> = skeleton.bones.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, vec!["root", "hips", "spine"]);
+        assert_eq!(skeleton.joints, vec![Some(2), Some(1), None]);
+        assert_eq!(skeleton.roots(), vec![0]);
+        assert_eq!(skeleton.children(1), vec![2]);
+        assert_eq!(skeleton.path(2), "root/hips/spine");
+
+        // the hips turn the offset of the spine to +x, the root turns it to -z
+        let position = skeleton.world_matrix(2).transform_point([0.0, 0.0, 0.0]);
+        for (a, b) in position.iter().zip(&[0.0, 1.5, -1.0]) {
+            assert!((a - b).abs() < 1e-5, "{:?}", position);
+        }
+    }
+}
@@ -0,0 +1,651 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+use super::mesh::VertexData;
+use super::texture::{Texture2D, TextureFormat};
+use super::{as_map, read_floats, read_pointer, EngineObject};
+use engine::object::Object;
+use error::{Error, Result};
+use extras::containers::OrderedMap;
+use object::{ObjectPointer, ObjectValue, ToByteVec};
+
+pub trait IntoSprite {
+    fn to_sprite(self) -> Result<Sprite>;
+}
+
+pub trait IntoSpriteAtlas {
+    fn to_sprite_atlas(self) -> Result<SpriteAtlas>;
+}
+
+/// Identifies the render data of a sprite inside its `SpriteAtlas`
+pub type RenderDataKey = ([u32; 4], i64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpritePackingMode {
+    Tight,
+    Rectangle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpritePackingRotation {
+    None,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate180,
+    Rotate90,
+    Unknown(u32),
+}
+
+/// Packing flags of a sprite, stored as a bit field in `settingsRaw`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteSettings {
+    pub packed: bool,
+    pub packing_mode: SpritePackingMode,
+    pub packing_rotation: SpritePackingRotation,
+}
+
+impl SpriteSettings {
+    pub fn from_u32(n: u32) -> Self {
+        SpriteSettings {
+            packed: n & 1 != 0,
+            packing_mode: if n >> 1 & 1 == 0 {
+                SpritePackingMode::Tight
+            } else {
+                SpritePackingMode::Rectangle
+            },
+            packing_rotation: match n >> 2 & 0xf {
+                0 => SpritePackingRotation::None,
+                1 => SpritePackingRotation::FlipHorizontal,
+                2 => SpritePackingRotation::FlipVertical,
+                3 => SpritePackingRotation::Rotate180,
+                4 => SpritePackingRotation::Rotate90,
+                n => SpritePackingRotation::Unknown(n),
+            },
+        }
+    }
+}
+
+/// Location of a sprite in its texture. Sprites store this in `m_RD`,
+/// sprite atlases store one for each packed sprite.
+#[derive(Debug)]
+pub struct SpriteRenderData {
+    pub texture: Option<ObjectPointer>,
+    pub alpha_texture: Option<ObjectPointer>,
+    pub texture_rect: Rect,
+    pub texture_rect_offset: [f32; 2],
+    pub atlas_rect_offset: [f32; 2],
+    pub settings: SpriteSettings,
+    pub uv_transform: [f32; 4],
+    pub downscale_multiplier: f32,
+    /// Positions of the sprite mesh in world units, empty for atlas data
+    pub vertices: Vec<[f32; 3]>,
+    /// Triangle list of the sprite mesh, empty for atlas data
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub struct Sprite {
+    pub object: Object,
+    pub rect: Rect,
+    pub offset: [f32; 2],
+    pub border: [f32; 4],
+    pub pixels_to_units: f32,
+    pub pivot: [f32; 2],
+    pub extrude: u32,
+    pub is_polygon: bool,
+    pub render_data_key: Option<RenderDataKey>,
+    pub atlas_tags: Vec<String>,
+    pub sprite_atlas: Option<ObjectPointer>,
+    pub render_data: SpriteRenderData,
+}
+
+#[derive(Debug)]
+pub struct SpriteAtlas {
+    pub object: Object,
+    pub packed_sprites: Vec<Option<ObjectPointer>>,
+    pub packed_sprite_names: Vec<String>,
+    pub render_data: Vec<(RenderDataKey, SpriteRenderData)>,
+    pub tag: String,
+    pub is_variant: bool,
+}
+
+impl SpriteAtlas {
+    pub fn get_render_data(&self, key: &RenderDataKey) -> Option<&SpriteRenderData> {
+        self.render_data
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, data)| data)
+    }
+}
+
+impl Sprite {
+    /// Returns the render data of the sprite. Sprites packed into the given
+    /// atlas are looked up in its render data, all other sprites use `m_RD`.
+    pub fn get_render_data<'a>(&'a self, atlas: Option<&'a SpriteAtlas>) -> &'a SpriteRenderData {
+        let atlas_data = match (atlas, self.render_data_key) {
+            (Some(atlas), Some(ref key)) => atlas.get_render_data(key),
+            _ => None,
+        };
+        atlas_data.unwrap_or(&self.render_data)
+    }
+
+    /// Crops the sprite out of its decoded texture, which is the texture of
+    /// `get_render_data`. The result is an RGBA32 texture with rows in
+    /// Unity's bottom-up order. Rotated sprites are turned upright and
+    /// pixels outside of the mesh of tightly packed sprites are cleared.
+    pub fn crop(&self, texture: &Texture2D, atlas: Option<&SpriteAtlas>) -> Result<Texture2D> {
+        let render_data = self.get_render_data(atlas);
+        let image = texture.to_image()?;
+        let (texture_width, texture_height) = (texture.width as usize, texture.height as usize);
+
+        let rect = render_data.texture_rect;
+        let clamp = |value: f32, max: usize| (value.max(0.0) as usize).min(max);
+        let x0 = clamp(rect.x.floor(), texture_width);
+        let y0 = clamp(rect.y.floor(), texture_height);
+        let x1 = clamp((rect.x + rect.width).ceil(), texture_width);
+        let y1 = clamp((rect.y + rect.height).ceil(), texture_height);
+        if x1 <= x0 || y1 <= y0 {
+            return Err(Error::EngineError(format!(
+                "Sprite {} is outside of its {}x{} texture",
+                self.object.name, texture_width, texture_height
+            )));
+        }
+
+        let (mut width, mut height) = (x1 - x0, y1 - y0);
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for y in y0..y1 {
+            pixels.extend_from_slice(
+                &image[(y * texture_width + x0) * 4..(y * texture_width + x1) * 4],
+            );
+        }
+
+        let settings = render_data.settings;
+        if settings.packed {
+            let (rotated, rotated_width, rotated_height) =
+                unrotate(&pixels, width, height, settings.packing_rotation)?;
+            pixels = rotated;
+            width = rotated_width;
+            height = rotated_height;
+        }
+        // the packing mode of sprites which were never packed is meaningless
+        if settings.packed
+            && settings.packing_mode == SpritePackingMode::Tight
+            && !self.render_data.indices.is_empty()
+        {
+            let offset = [
+                self.rect.width * self.pivot[0] - render_data.texture_rect_offset[0],
+                self.rect.height * self.pivot[1] - render_data.texture_rect_offset[1],
+            ];
+            let vertices: Vec<[f32; 2]> = self
+                .render_data
+                .vertices
+                .iter()
+                .map(|v| {
+                    [
+                        v[0] * self.pixels_to_units + offset[0],
+                        v[1] * self.pixels_to_units + offset[1],
+                    ]
+                })
+                .collect();
+            mask_triangles(
+                &mut pixels,
+                width,
+                height,
+                &vertices,
+                &self.render_data.indices,
+            )?;
+        }
+
+        Ok(Texture2D::new(
+            &self.object.name,
+            width as u32,
+            height as u32,
+            TextureFormat::RGBA32,
+            pixels,
+        ))
+    }
+}
+
+/// Undoes the packing rotation of a sprite. Rows are bottom-up, so a sprite
+/// packed with `Rotate90` is turned clockwise.
+fn unrotate(
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    rotation: SpritePackingRotation,
+) -> Result<(Vec<u8>, usize, usize)> {
+    let (out_width, out_height) = match rotation {
+        SpritePackingRotation::Rotate90 => (height, width),
+        SpritePackingRotation::Unknown(n) => {
+            return Err(Error::EngineError(format!(
+                "Unknown sprite packing rotation {}",
+                n
+            )));
+        }
+        _ => (width, height),
+    };
+    let mut out = Vec::with_capacity(pixels.len());
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let (source_x, source_y) = match rotation {
+                SpritePackingRotation::FlipHorizontal => (width - 1 - x, y),
+                SpritePackingRotation::FlipVertical => (x, height - 1 - y),
+                SpritePackingRotation::Rotate180 => (width - 1 - x, height - 1 - y),
+                SpritePackingRotation::Rotate90 => (width - 1 - y, x),
+                _ => (x, y),
+            };
+            let index = (source_y * width + source_x) * 4;
+            out.extend_from_slice(&pixels[index..index + 4]);
+        }
+    }
+    Ok((out, out_width, out_height))
+}
+
+/// Clears every pixel whose center is not covered by a triangle
+fn mask_triangles(
+    pixels: &mut [u8],
+    width: usize,
+    height: usize,
+    vertices: &[[f32; 2]],
+    indices: &[u32],
+) -> Result<()> {
+    let mut mask = vec![false; width * height];
+    for triangle in indices.chunks(3).filter(|t| t.len() == 3) {
+        let mut points = [[0f32; 2]; 3];
+        for (point, &index) in points.iter_mut().zip(triangle) {
+            *point = *vertices.get(index as usize).ok_or_else(|| {
+                Error::EngineError(format!("Sprite vertex index {} is out of range", index))
+            })?;
+        }
+        let [a, b, c] = points;
+        let edge = |p: [f32; 2], q: [f32; 2], x: f32, y: f32| {
+            (q[0] - p[0]) * (y - p[1]) - (q[1] - p[1]) * (x - p[0])
+        };
+        let area = edge(a, b, c[0], c[1]);
+        if area == 0.0 {
+            continue;
+        }
+
+        let min = |i: usize| a[i].min(b[i]).min(c[i]).floor().max(0.0) as usize;
+        let max =
+            |i: usize, size: usize| (a[i].max(b[i]).max(c[i]).ceil().max(0.0) as usize).min(size);
+        for y in min(1)..max(1, height) {
+            for x in min(0)..max(0, width) {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = edge(b, c, px, py) * area;
+                let w1 = edge(c, a, px, py) * area;
+                let w2 = edge(a, b, px, py) * area;
+                if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                    mask[y * width + x] = true;
+                }
+            }
+        }
+    }
+    for (pixel, &covered) in pixels.chunks_mut(4).zip(&mask) {
+        if !covered {
+            for byte in pixel.iter_mut() {
+                *byte = 0;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl IntoSprite for EngineObject {
+    fn to_sprite(mut self) -> Result<Sprite> {
+        Ok(Sprite {
+            object: Object::new(&self.map)?,
+            rect: read_rect(tryGet!(self.map, "m_Rect"))?,
+            offset: read_floats(tryGet!(self.map, "m_Offset"), &["x", "y"])?,
+            border: match self.map.get(&"m_Border".to_string()) {
+                Some(border) => read_floats(border, &["x", "y", "z", "w"])?,
+                None => [0.0; 4],
+            },
+            pixels_to_units: tryGet!(self.map, "m_PixelsToUnits").to_f32()?,
+            // sprites were always centered before Unity 5.4
+            pivot: match self.map.get(&"m_Pivot".to_string()) {
+                Some(pivot) => read_floats(pivot, &["x", "y"])?,
+                None => [0.5, 0.5],
+            },
+            extrude: tryGet!(self.map, "m_Extrude").to_u32()?,
+            is_polygon: match self.map.get(&"m_IsPolygon".to_string()) {
+                Some(value) => value.to_bool()?,
+                None => false,
+            },
+            render_data_key: match self.map.remove(&"m_RenderDataKey".to_string()) {
+                Some(key) => Some(read_render_data_key(key)?),
+                None => None,
+            },
+            atlas_tags: match self.map.remove(&"m_AtlasTags".to_string()) {
+                Some(tags) => {
+                    let mut res = Vec::new();
+                    for tag in tags.into_vec()? {
+                        res.push(tag.to_string()?);
+                    }
+                    res
+                }
+                None => Vec::new(),
+            },
+            sprite_atlas: match self.map.get(&"m_SpriteAtlas".to_string()) {
+                Some(pointer) => read_pointer(pointer)?,
+                None => None,
+            },
+            render_data: { read_render_data(tryConsume!(self.map, "m_RD").into_map()?)? },
+        })
+    }
+}
+
+impl IntoSpriteAtlas for EngineObject {
+    fn to_sprite_atlas(mut self) -> Result<SpriteAtlas> {
+        Ok(SpriteAtlas {
+            object: Object::new(&self.map)?,
+            packed_sprites: {
+                let array = tryConsume!(self.map, "m_PackedSprites").into_vec()?;
+                let mut res = Vec::with_capacity(array.len());
+                for pointer in &array {
+                    res.push(read_pointer(pointer)?);
+                }
+                res
+            },
+            packed_sprite_names: {
+                let array = tryConsume!(self.map, "m_PackedSpriteNamesToIndex").into_vec()?;
+                let mut res = Vec::with_capacity(array.len());
+                for name in &array {
+                    res.push(name.to_string()?);
+                }
+                res
+            },
+            render_data: {
+                let array = tryConsume!(self.map, "m_RenderDataMap").into_vec()?;
+                let mut res = Vec::with_capacity(array.len());
+                for pair in array {
+                    let (key, data) = pair.into_pair()?;
+                    res.push((
+                        read_render_data_key(*key)?,
+                        read_render_data(data.into_map()?)?,
+                    ));
+                }
+                res
+            },
+            tag: tryGet!(self.map, "m_Tag").to_string()?,
+            is_variant: tryGet!(self.map, "m_IsVariant").to_bool()?,
+        })
+    }
+}
+
+fn read_rect(value: &ObjectValue) -> Result<Rect> {
+    let [x, y, width, height]: [f32; 4] = read_floats(value, &["x", "y", "width", "height"])?;
+    Ok(Rect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+fn read_render_data_key(value: ObjectValue) -> Result<RenderDataKey> {
+    let (guid, id) = value.into_pair()?;
+    let guid = as_map(&guid)?;
+    let mut data = [0; 4];
+    for (i, item) in data.iter_mut().enumerate() {
+        *item = tryGet!(guid, format!("data[{}]", i)).to_u32()?;
+    }
+    Ok((data, id.to_i64()?))
+}
+
+fn read_render_data(mut map: OrderedMap<String, ObjectValue>) -> Result<SpriteRenderData> {
+    let (vertices, indices) = read_sprite_mesh(&mut map)?;
+    Ok(SpriteRenderData {
+        texture: read_pointer(tryGet!(map, "texture"))?,
+        alpha_texture: match map.get(&"alphaTexture".to_string()) {
+            Some(pointer) => read_pointer(pointer)?,
+            None => None,
+        },
+        texture_rect: read_rect(tryGet!(map, "textureRect"))?,
+        texture_rect_offset: read_floats(tryGet!(map, "textureRectOffset"), &["x", "y"])?,
+        atlas_rect_offset: match map.get(&"atlasRectOffset".to_string()) {
+            Some(offset) => read_floats(offset, &["x", "y"])?,
+            None => [0.0; 2],
+        },
+        settings: SpriteSettings::from_u32(tryGet!(map, "settingsRaw").to_u32()?),
+        uv_transform: match map.get(&"uvTransform".to_string()) {
+            Some(transform) => read_floats(transform, &["x", "y", "z", "w"])?,
+            None => [0.0; 4],
+        },
+        downscale_multiplier: match map.get(&"downscaleMultiplier".to_string()) {
+            Some(multiplier) => multiplier.to_f32()?,
+            None => 1.0,
+        },
+        vertices,
+        indices,
+    })
+}
+
+/// Reads the positions and triangles of a sprite mesh. Before Unity 5.6
+/// sprites stored plain vertex and index lists, later versions store vertex
+/// data like meshes and 16 bit indices.
+fn read_sprite_mesh(
+    map: &mut OrderedMap<String, ObjectValue>,
+) -> Result<(Vec<[f32; 3]>, Vec<u32>)> {
+    if let Some(ObjectValue::Array(ref array)) = map.get(&"vertices".to_string()) {
+        let mut vertices = Vec::with_capacity(array.len());
+        for vertex in array {
+            vertices.push(read_floats(
+                tryGet!(as_map(vertex)?, "pos"),
+                &["x", "y", "z"],
+            )?);
+        }
+        let mut indices = Vec::new();
+        if let Some(ObjectValue::Array(ref array)) = map.get(&"indices".to_string()) {
+            for index in array {
+                indices.push(u32::from(index.to_u16()?));
+            }
+        }
+        return Ok((vertices, indices));
+    }
+
+    let vertices = match map.remove(&"m_VertexData".to_string()) {
+        Some(vertex_data) => {
+            let mut vertex_data = vertex_data.into_map()?;
+            // positions are floats in every version
+            VertexData::from_map(&mut vertex_data)?
+                .decode(None)?
+                .positions
+        }
+        // atlas render data has no mesh
+        None => return Ok((Vec::new(), Vec::new())),
+    };
+
+    let index_buffer = tryGet!(map, "m_IndexBuffer").to_byte_vec()?;
+    let read_index = |i: usize| -> Result<u32> {
+        match index_buffer.get(i * 2..i * 2 + 2) {
+            Some(bytes) => Ok(u32::from(bytes[0]) | u32::from(bytes[1]) << 8),
+            None => Err(Error::EngineError(
+                "Sprite index buffer is too short".to_string(),
+            )),
+        }
+    };
+    let mut indices = Vec::new();
+    match map.get(&"m_SubMeshes".to_string()) {
+        Some(ObjectValue::Array(ref submeshes)) if !submeshes.is_empty() => {
+            for submesh in submeshes {
+                let submesh = as_map(submesh)?;
+                let first = tryGet!(submesh, "firstByte").to_u32()? as usize / 2;
+                let count = tryGet!(submesh, "indexCount").to_u32()? as usize;
+                let base_vertex = match submesh.get(&"baseVertex".to_string()) {
+                    Some(base_vertex) => base_vertex.to_u32()?,
+                    None => 0,
+                };
+                for i in first..first + count {
+                    indices.push(read_index(i)? + base_vertex);
+                }
+            }
+        }
+        _ => {
+            for i in 0..index_buffer.len() / 2 {
+                indices.push(read_index(i)?);
+            }
+        }
+    }
+    Ok((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(n: u8) -> [u8; 4] {
+        [n, n, n, 255]
+    }
+
+    fn texture(width: u32, height: u32) -> Texture2D {
+        let data = (0..width * height)
+            .flat_map(|i| pixel(i as u8).to_vec())
+            .collect();
+        Texture2D::new("atlas", width, height, TextureFormat::RGBA32, data)
+    }
+
+    fn sprite(rect: Rect, settings: u32) -> Sprite {
+        Sprite {
+            object: Object {
+                name: "sprite".to_string(),
+            },
+            rect: Rect {
+                x: 0.0,
+                y: 0.0,
+                width: rect.width,
+                height: rect.height,
+            },
+            offset: [0.0; 2],
+            border: [0.0; 4],
+            pixels_to_units: 1.0,
+            pivot: [0.0, 0.0],
+            extrude: 0,
+            is_polygon: false,
+            render_data_key: None,
+            atlas_tags: Vec::new(),
+            sprite_atlas: None,
+            render_data: SpriteRenderData {
+                texture: None,
+                alpha_texture: None,
+                texture_rect: rect,
+                texture_rect_offset: [0.0; 2],
+                atlas_rect_offset: [0.0; 2],
+                settings: SpriteSettings::from_u32(settings),
+                uv_transform: [0.0; 4],
+                downscale_multiplier: 1.0,
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = SpriteSettings::from_u32(0b1_0011);
+        assert!(settings.packed);
+        assert_eq!(settings.packing_mode, SpritePackingMode::Rectangle);
+        assert_eq!(settings.packing_rotation, SpritePackingRotation::Rotate90);
+    }
+
+    #[test]
+    fn test_crop() {
+        let rect = Rect {
+            x: 1.0,
+            y: 1.0,
+            width: 2.0,
+            height: 2.0,
+        };
+        let image = sprite(rect, 0b10).crop(&texture(4, 4), None).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        let expected: Vec<u8> = [5, 6, 9, 10]
+            .iter()
+            .flat_map(|&n| pixel(n).to_vec())
+            .collect();
+        assert_eq!(image.data, expected);
+    }
+
+    #[test]
+    fn test_rotate90() {
+        // a 3x2 sprite packed as a 2x3 region
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 2.0,
+            height: 3.0,
+        };
+        let image = sprite(rect, 0b1_0011).crop(&texture(2, 3), None).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        let expected: Vec<u8> = [1, 3, 5, 0, 2, 4]
+            .iter()
+            .flat_map(|&n| pixel(n).to_vec())
+            .collect();
+        assert_eq!(image.data, expected);
+    }
+
+    #[test]
+    fn test_tight_mask() {
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 2.0,
+            height: 2.0,
+        };
+        let mut sprite = sprite(rect, 0b1);
+        // the lower left half of the sprite
+        sprite.render_data.vertices = vec![[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]];
+        sprite.render_data.indices = vec![0, 1, 2];
+        let image = sprite.crop(&texture(2, 2), None).unwrap();
+        let expected: Vec<u8> = [pixel(0), pixel(1), pixel(2), [0; 4]]
+            .iter()
+            .flat_map(|p| p.to_vec())
+            .collect();
+        assert_eq!(image.data, expected);
+
+        // sprites which were never packed keep the whole rect
+        sprite.render_data.settings = SpriteSettings::from_u32(0);
+        let image = sprite.crop(&texture(2, 2), None).unwrap();
+        let expected: Vec<u8> = (0..4).flat_map(|n| pixel(n).to_vec()).collect();
+        assert_eq!(image.data, expected);
+    }
+
+    #[test]
+    fn test_atlas_render_data() {
+        let key = ([1, 2, 3, 4], 5);
+        let rect = Rect {
+            x: 2.0,
+            y: 0.0,
+            width: 2.0,
+            height: 1.0,
+        };
+        let atlas = SpriteAtlas {
+            object: Object {
+                name: "atlas".to_string(),
+            },
+            packed_sprites: Vec::new(),
+            packed_sprite_names: Vec::new(),
+            render_data: vec![(key, sprite(rect, 0b11).render_data)],
+            tag: String::new(),
+            is_variant: false,
+        };
+        let mut sprite = sprite(rect, 0b10);
+        sprite.render_data.texture_rect.x = 0.0;
+        assert_eq!(sprite.get_render_data(Some(&atlas)).texture_rect.x, 0.0);
+        sprite.render_data_key = Some(key);
+        let image = sprite.crop(&texture(4, 1), Some(&atlas)).unwrap();
+        let expected: Vec<u8> = [2, 3].iter().flat_map(|&n| pixel(n).to_vec()).collect();
+        assert_eq!(image.data, expected);
+    }
+}
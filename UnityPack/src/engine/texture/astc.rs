@@ -0,0 +1,1319 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! ASTC block decoder for LDR and HDR blocks, following the Khronos Data
+//! Format Specification. Invalid blocks decode to magenta.
+
+use super::convert::{decode_blocks, half_to_f32, unorm_to_u8};
+use error::Result;
+
+/// A decoded texel with UNORM16 values in LDR channels and half float bits
+/// in HDR channels
+#[derive(Clone, Copy, Default)]
+struct Texel {
+    values: [u16; 4],
+    hdr: [bool; 4],
+}
+
+const ERROR_TEXEL: Texel = Texel {
+    values: [0xffff, 0, 0xffff, 0xffff],
+    hdr: [false; 4],
+};
+
+impl Texel {
+    fn to_rgba8(self) -> [u8; 4] {
+        let mut rgba = [0u8; 4];
+        for (c, value) in rgba.iter_mut().enumerate() {
+            *value = if self.hdr[c] {
+                unorm_to_u8(half_to_f32(self.values[c]))
+            } else {
+                (self.values[c] >> 8) as u8
+            };
+        }
+        rgba
+    }
+
+    fn to_rgba_f32(self) -> [f32; 4] {
+        let mut rgba = [0f32; 4];
+        for (c, value) in rgba.iter_mut().enumerate() {
+            *value = if self.hdr[c] {
+                half_to_f32(self.values[c])
+            } else {
+                f32::from(self.values[c]) / 65535.0
+            };
+        }
+        rgba
+    }
+}
+
+/// Endpoint pair of a partition with UNORM16 values in LDR channels and
+/// 16 bit logarithmic values in HDR channels
+#[derive(Clone, Copy, Default)]
+struct Endpoints {
+    colors: [[i32; 4]; 2],
+    hdr: [bool; 4],
+}
+
+/// Integer sequence encoding of a value range: the number of trits (3) or
+/// quints (5) per value, 0 if none, and the number of plain bits
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Range {
+    kind: u32,
+    bits: u32,
+}
+
+const fn range(kind: u32, bits: u32) -> Range {
+    Range { kind, bits }
+}
+
+/// Weight ranges indexed by the range field of the block mode
+const WEIGHT_RANGES: [Option<Range>; 16] = [
+    None,
+    None,
+    Some(range(0, 1)),
+    Some(range(3, 0)),
+    Some(range(0, 2)),
+    Some(range(5, 0)),
+    Some(range(3, 1)),
+    Some(range(0, 3)),
+    None,
+    None,
+    Some(range(5, 1)),
+    Some(range(3, 2)),
+    Some(range(0, 4)),
+    Some(range(5, 2)),
+    Some(range(3, 3)),
+    Some(range(0, 5)),
+];
+
+/// Color endpoint ranges from the largest to the smallest
+const COLOR_RANGES: [Range; 19] = [
+    range(0, 8),
+    range(3, 6),
+    range(5, 5),
+    range(0, 7),
+    range(3, 5),
+    range(5, 4),
+    range(0, 6),
+    range(3, 4),
+    range(5, 3),
+    range(0, 5),
+    range(3, 3),
+    range(5, 2),
+    range(0, 4),
+    range(3, 2),
+    range(5, 1),
+    range(0, 3),
+    range(3, 1),
+    range(0, 2),
+    range(0, 1),
+];
+
+/// Decodes ASTC data with the given block footprint to RGBA8, HDR values
+/// are clamped
+pub fn decode_astc(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+) -> Result<Vec<u8>> {
+    decode(
+        data,
+        width,
+        height,
+        block_width,
+        block_height,
+        Texel::to_rgba8,
+    )
+}
+
+/// Decodes ASTC data with the given block footprint to RGBA32F
+pub fn decode_astc_f32(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+) -> Result<Vec<f32>> {
+    decode(
+        data,
+        width,
+        height,
+        block_width,
+        block_height,
+        Texel::to_rgba_f32,
+    )
+}
+
+fn decode<T: Copy + Default>(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+    convert: fn(Texel) -> [T; 4],
+) -> Result<Vec<T>> {
+    let mut texels = vec![Texel::default(); block_width * block_height];
+    decode_blocks(
+        data,
+        width,
+        height,
+        block_width,
+        block_height,
+        16,
+        |block, pixels| {
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&block[..16]);
+            let block = u128::from_le_bytes(bytes);
+            if decode_block(block, block_width, block_height, &mut texels).is_none() {
+                for texel in texels.iter_mut() {
+                    *texel = ERROR_TEXEL;
+                }
+            }
+            for (pixel, &texel) in pixels.iter_mut().zip(texels.iter()) {
+                *pixel = convert(texel);
+            }
+        },
+    )
+}
+
+fn bits(block: u128, low: u32, count: u32) -> u32 {
+    if low >= 128 || count == 0 {
+        return 0;
+    }
+    ((block >> low) & ((1 << count) - 1)) as u32
+}
+
+fn ise_size(count: usize, range: Range) -> usize {
+    let bits = count * range.bits as usize;
+    match range.kind {
+        3 => bits + (count * 8).div_ceil(5),
+        5 => bits + (count * 7).div_ceil(3),
+        _ => bits,
+    }
+}
+
+/// Decodes `count` values of an integer sequence starting at bit `offset`.
+/// Each value is returned as its plain bits and its trit or quint.
+fn decode_ise(block: u128, offset: u32, count: usize, range: Range) -> Vec<(u32, u32)> {
+    // bits after the sequence read as zero
+    let end = offset as usize + ise_size(count, range);
+    let block = if end < 128 {
+        block & ((1u128 << end) - 1)
+    } else {
+        block
+    };
+    let b = range.bits;
+    let mut values = Vec::with_capacity(count + 4);
+    let mut p = offset;
+    while values.len() < count {
+        match range.kind {
+            3 => {
+                let m = [
+                    bits(block, p, b),
+                    bits(block, p + b + 2, b),
+                    bits(block, p + 2 * b + 4, b),
+                    bits(block, p + 3 * b + 5, b),
+                    bits(block, p + 4 * b + 7, b),
+                ];
+                let t = bits(block, p + b, 2)
+                    | bits(block, p + 2 * b + 2, 2) << 2
+                    | bits(block, p + 3 * b + 4, 1) << 4
+                    | bits(block, p + 4 * b + 5, 2) << 5
+                    | bits(block, p + 5 * b + 7, 1) << 7;
+                for (&m, t) in m.iter().zip(decode_trits(t).iter()) {
+                    values.push((m, *t));
+                }
+                p += 8 + 5 * b;
+            }
+            5 => {
+                let m = [
+                    bits(block, p, b),
+                    bits(block, p + b + 3, b),
+                    bits(block, p + 2 * b + 5, b),
+                ];
+                let q = bits(block, p + b, 3)
+                    | bits(block, p + 2 * b + 3, 2) << 3
+                    | bits(block, p + 3 * b + 5, 2) << 5;
+                for (&m, q) in m.iter().zip(decode_quints(q).iter()) {
+                    values.push((m, *q));
+                }
+                p += 7 + 3 * b;
+            }
+            _ => {
+                values.push((bits(block, p, b), 0));
+                p += b;
+            }
+        }
+    }
+    values.truncate(count);
+    values
+}
+
+fn bit(value: u32, n: u32) -> u32 {
+    (value >> n) & 1
+}
+
+fn decode_trits(t: u32) -> [u32; 5] {
+    let (c, t3, t4);
+    if (t >> 2) & 7 == 7 {
+        c = (t >> 5) << 2 | (t & 3);
+        t4 = 2;
+        t3 = 2;
+    } else {
+        c = t & 0x1f;
+        if (t >> 5) & 3 == 3 {
+            t4 = 2;
+            t3 = bit(t, 7);
+        } else {
+            t4 = bit(t, 7);
+            t3 = (t >> 5) & 3;
+        }
+    }
+    let (t0, t1, t2);
+    if c & 3 == 3 {
+        t2 = 2;
+        t1 = bit(c, 4);
+        t0 = bit(c, 3) << 1 | (bit(c, 2) & !bit(c, 3) & 1);
+    } else if (c >> 2) & 3 == 3 {
+        t2 = 2;
+        t1 = 2;
+        t0 = c & 3;
+    } else {
+        t2 = bit(c, 4);
+        t1 = (c >> 2) & 3;
+        t0 = bit(c, 1) << 1 | (bit(c, 0) & !bit(c, 1) & 1);
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(q: u32) -> [u32; 3] {
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let not_q0 = !bit(q, 0) & 1;
+        let q2 = bit(q, 0) << 2 | (bit(q, 4) & not_q0) << 1 | (bit(q, 3) & not_q0);
+        return [4, 4, q2];
+    }
+    let (c, q2);
+    if (q >> 1) & 3 == 3 {
+        q2 = 4;
+        c = ((q >> 3) & 3) << 3 | (!(q >> 5) & 3) << 1 | bit(q, 0);
+    } else {
+        q2 = (q >> 5) & 3;
+        c = q & 0x1f;
+    }
+    if c & 7 == 5 {
+        [(c >> 3) & 3, 4, q2]
+    } else {
+        [c & 7, (c >> 3) & 3, q2]
+    }
+}
+
+/// Unquantizes a color endpoint value to 0..255
+fn unquantize_color((m, d): (u32, u32), range: Range) -> i32 {
+    let b = range.bits;
+    if range.kind == 0 {
+        // bit replication
+        let mut value = m << (8 - b);
+        let mut shift = b;
+        while shift < 8 {
+            value |= value >> shift;
+            shift *= 2;
+        }
+        return value as i32;
+    }
+    let a = (m & 1) * 0x1ff;
+    let x = m >> 1;
+    let (b, c) = match (range.kind, b) {
+        (3, 1) => (0, 204),
+        (3, 2) => (0x116 * x, 93),
+        (3, 3) => (x << 7 | x << 2 | x, 44),
+        (3, 4) => (x << 6 | x, 22),
+        (3, 5) => (x << 5 | x >> 2, 11),
+        (3, _) => (x << 4 | x >> 4, 5),
+        (5, 1) => (0, 113),
+        (5, 2) => (0x10c * x, 54),
+        (5, 3) => (x << 7 | x << 1 | x >> 1, 26),
+        (5, 4) => (x << 6 | x >> 1, 13),
+        (_, _) => (x << 5 | x >> 3, 6),
+    };
+    ((a & 0x80) | ((d * c + b) ^ a) >> 2) as i32
+}
+
+/// Unquantizes a weight to 0..64
+fn unquantize_weight((m, d): (u32, u32), range: Range) -> u32 {
+    let value = match (range.kind, range.bits) {
+        (0, 1) => m * 63,
+        (0, 2) => m * 21,
+        (0, 3) => m * 9,
+        (0, 4) => m << 2 | m >> 2,
+        (0, _) => m << 1 | m >> 4,
+        (3, 0) => [0, 32, 63][d as usize],
+        (5, 0) => [0, 16, 32, 47, 63][d as usize],
+        (kind, b) => {
+            let a = (m & 1) * 0x7f;
+            let x = m >> 1;
+            let (b, c) = match (kind, b) {
+                (3, 1) => (0, 50),
+                (3, 2) => (0x45 * x, 23),
+                (3, _) => (x << 5 | x, 11),
+                (5, 1) => (0, 28),
+                (_, _) => (0x42 * x, 13),
+            };
+            (a & 0x20) | ((d * c + b) ^ a) >> 2
+        }
+    };
+    if value > 32 {
+        value + 1
+    } else {
+        value
+    }
+}
+
+struct BlockMode {
+    grid_width: usize,
+    grid_height: usize,
+    dual_plane: bool,
+    weight_range: Range,
+}
+
+fn decode_block_mode(block: u128) -> Option<BlockMode> {
+    let mode = bits(block, 0, 11);
+    let a = bits(block, 5, 2) as usize;
+    let b = bits(block, 7, 2) as usize;
+    let mut dual_plane = bit(mode, 10) == 1;
+    let mut high_precision = bit(mode, 9);
+
+    let (grid_width, grid_height, range);
+    if mode & 3 != 0 {
+        range = (mode & 3) << 1 | bit(mode, 4);
+        let (w, h) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if bit(mode, 8) == 0 => (a + 2, (b & 1) + 6),
+            _ => ((b & 1) + 2, a + 2),
+        };
+        grid_width = w;
+        grid_height = h;
+    } else {
+        if mode & 0xf == 0 {
+            return None;
+        }
+        range = ((mode >> 2) & 3) << 1 | bit(mode, 4);
+        let (w, h) = match (mode >> 7) & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            // the dual plane and precision bits are part of the height
+            2 => (a + 6, bits(block, 9, 2) as usize + 6),
+            _ => {
+                if bit(mode, 6) == 1 {
+                    return None;
+                }
+                if bit(mode, 5) == 1 {
+                    (10, 6)
+                } else {
+                    (6, 10)
+                }
+            }
+        };
+        grid_width = w;
+        grid_height = h;
+        if (mode >> 7) & 3 == 2 {
+            dual_plane = false;
+            high_precision = 0;
+        }
+    }
+
+    WEIGHT_RANGES[(high_precision << 3 | range) as usize].map(|weight_range| BlockMode {
+        grid_width,
+        grid_height,
+        dual_plane,
+        weight_range,
+    })
+}
+
+fn decode_block(
+    block: u128,
+    block_width: usize,
+    block_height: usize,
+    texels: &mut [Texel],
+) -> Option<()> {
+    if bits(block, 0, 9) == 0x1fc {
+        // void-extent block of a constant color, HDR ones store half floats
+        let texel = Texel {
+            values: [
+                bits(block, 64, 16) as u16,
+                bits(block, 80, 16) as u16,
+                bits(block, 96, 16) as u16,
+                bits(block, 112, 16) as u16,
+            ],
+            hdr: [bits(block, 9, 1) == 1; 4],
+        };
+        for pixel in texels.iter_mut() {
+            *pixel = texel;
+        }
+        return Some(());
+    }
+
+    let mode = decode_block_mode(block)?;
+    let planes = if mode.dual_plane { 2 } else { 1 };
+    let weight_count = mode.grid_width * mode.grid_height * planes;
+    let weight_bits = ise_size(weight_count, mode.weight_range);
+    let partitions = bits(block, 11, 2) as usize + 1;
+    if weight_count > 64
+        || !(24..=96).contains(&weight_bits)
+        || mode.grid_width > block_width
+        || mode.grid_height > block_height
+        || (partitions == 4 && mode.dual_plane)
+    {
+        return None;
+    }
+    let weight_bits = weight_bits as u32;
+
+    // color endpoint modes
+    let mut modes = [0u32; 4];
+    let mut extra_bits = 0;
+    let mut partition_seed = 0;
+    let color_offset;
+    if partitions == 1 {
+        modes[0] = bits(block, 13, 4);
+        color_offset = 17;
+    } else {
+        partition_seed = bits(block, 13, 10);
+        color_offset = 29;
+        let selector = bits(block, 23, 2);
+        if selector == 0 {
+            let shared = bits(block, 25, 4);
+            for mode in modes.iter_mut().take(partitions) {
+                *mode = shared;
+            }
+        } else {
+            let n = partitions as u32;
+            extra_bits = 3 * n - 4;
+            let encoded =
+                bits(block, 23, 6) | bits(block, 128 - weight_bits - extra_bits, extra_bits) << 6;
+            for (i, mode) in modes.iter_mut().take(partitions).enumerate() {
+                let i = i as u32;
+                let class = selector - 1 + bit(encoded, 2 + i);
+                *mode = class << 2 | (encoded >> (2 + n + 2 * i)) & 3;
+            }
+        }
+    }
+    let plane_selector = if mode.dual_plane {
+        bits(block, 128 - weight_bits - extra_bits - 2, 2) as usize
+    } else {
+        0
+    };
+
+    let value_count: usize = modes[..partitions]
+        .iter()
+        .map(|mode| ((mode >> 2) as usize + 1) * 2)
+        .sum();
+    if value_count > 18 {
+        return None;
+    }
+    // blocks whose weights and mode bits leave no room for colors are invalid
+    let color_bits =
+        128u32.checked_sub(weight_bits + extra_bits + 2 * (planes as u32 - 1) + color_offset)?;
+    let color_range = *COLOR_RANGES
+        .iter()
+        .find(|&&range| ise_size(value_count, range) <= color_bits as usize)?;
+    let values: Vec<i32> = decode_ise(block, color_offset, value_count, color_range)
+        .into_iter()
+        .map(|value| unquantize_color(value, color_range))
+        .collect();
+
+    let mut endpoints = [Endpoints::default(); 4];
+    let mut offset = 0;
+    for (i, &mode) in modes[..partitions].iter().enumerate() {
+        let count = ((mode >> 2) as usize + 1) * 2;
+        endpoints[i] = decode_endpoints(mode, &values[offset..offset + count])?;
+        offset += count;
+    }
+
+    let weights: Vec<u32> = decode_ise(block.reverse_bits(), 0, weight_count, mode.weight_range)
+        .into_iter()
+        .map(|value| unquantize_weight(value, mode.weight_range))
+        .collect();
+
+    let ds = (1024 + block_width / 2) / (block_width - 1).max(1);
+    let dt = (1024 + block_height / 2) / (block_height - 1).max(1);
+    let small_block = block_width * block_height < 31;
+    for y in 0..block_height {
+        for x in 0..block_width {
+            // bilinear infill of the weight grid
+            let gs = (ds * x * (mode.grid_width - 1) + 32) >> 6;
+            let gt = (dt * y * (mode.grid_height - 1) + 32) >> 6;
+            let (js, fs) = (gs >> 4, (gs & 0xf) as u32);
+            let (jt, ft) = (gt >> 4, (gt & 0xf) as u32);
+            let w11 = (fs * ft + 8) >> 4;
+            let factors = [16 + w11 - fs - ft, fs - w11, ft - w11, w11];
+            let v0 = js + jt * mode.grid_width;
+            let grid = [v0, v0 + 1, v0 + mode.grid_width, v0 + mode.grid_width + 1];
+            let mut texel_weights = [0u32; 2];
+            for (plane, weight) in texel_weights.iter_mut().enumerate().take(planes) {
+                let sum: u32 = grid
+                    .iter()
+                    .zip(factors.iter())
+                    .filter(|&(_, &factor)| factor != 0)
+                    .map(|(&index, &factor)| {
+                        weights.get(index * planes + plane).unwrap_or(&0) * factor
+                    })
+                    .sum();
+                *weight = (sum + 8) >> 4;
+            }
+
+            let partition = if partitions > 1 {
+                select_partition(partition_seed, x, y, partitions, small_block)
+            } else {
+                0
+            };
+            let Endpoints {
+                colors: [e0, e1],
+                hdr,
+            } = endpoints[partition];
+            let texel = &mut texels[y * block_width + x];
+            texel.hdr = hdr;
+            for c in 0..4 {
+                let weight = if mode.dual_plane && c == plane_selector {
+                    texel_weights[1]
+                } else {
+                    texel_weights[0]
+                } as i32;
+                let value = (e0[c] * (64 - weight) + e1[c] * weight + 32) >> 6;
+                texel.values[c] = if hdr[c] {
+                    lns_to_half(value as u32)
+                } else {
+                    value as u16
+                };
+            }
+        }
+    }
+    Some(())
+}
+
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let mut a = (a >> 1) & 0x3f;
+    if a & 0x20 != 0 {
+        a -= 0x40;
+    }
+    (a, b)
+}
+
+fn blue_contract(color: [i32; 4]) -> [i32; 4] {
+    [
+        (color[0] + color[2]) >> 1,
+        (color[1] + color[2]) >> 1,
+        color[2],
+        color[3],
+    ]
+}
+
+fn clamp_color(color: [i32; 4]) -> [i32; 4] {
+    [
+        color[0].clamp(0, 255),
+        color[1].clamp(0, 255),
+        color[2].clamp(0, 255),
+        color[3].clamp(0, 255),
+    ]
+}
+
+/// Decodes the endpoint pair of a partition
+fn decode_endpoints(mode: u32, v: &[i32]) -> Option<Endpoints> {
+    let (colors, hdr_alpha) = match mode {
+        2 => (hdr_luminance_large_range(v), false),
+        3 => (hdr_luminance_small_range(v), false),
+        7 => (hdr_rgb_scale(v), false),
+        11 => (hdr_rgb(v), false),
+        14 => {
+            let mut colors = hdr_rgb(v);
+            colors[0][3] = v[6] << 8 | v[6];
+            colors[1][3] = v[7] << 8 | v[7];
+            (colors, false)
+        }
+        15 => {
+            let mut colors = hdr_rgb(v);
+            let (a0, a1) = hdr_alpha(v[6], v[7]);
+            colors[0][3] = a0;
+            colors[1][3] = a1;
+            (colors, true)
+        }
+        _ => {
+            let mut colors = decode_ldr_endpoints(mode, v)?;
+            for value in colors.iter_mut().flat_map(|color| color.iter_mut()) {
+                *value |= *value << 8;
+            }
+            return Some(Endpoints {
+                colors,
+                hdr: [false; 4],
+            });
+        }
+    };
+    Some(Endpoints {
+        colors,
+        hdr: [true, true, true, hdr_alpha],
+    })
+}
+
+/// Converts an interpolated logarithmic HDR value to half float bits
+fn lns_to_half(value: u32) -> u16 {
+    let exponent = value >> 11;
+    let mantissa = value & 0x7ff;
+    let mantissa = if mantissa < 512 {
+        3 * mantissa
+    } else if mantissa < 1536 {
+        4 * mantissa - 512
+    } else {
+        5 * mantissa - 2048
+    };
+    ((exponent << 10 | mantissa >> 3).min(0x7bff)) as u16
+}
+
+/// Gray HDR endpoint from a 12 bit value, alpha is 1
+fn hdr_gray(y: i32) -> [i32; 4] {
+    [y << 4, y << 4, y << 4, 0xffff]
+}
+
+fn hdr_luminance_large_range(v: &[i32]) -> [[i32; 4]; 2] {
+    let (y0, y1) = if v[1] >= v[0] {
+        (v[0] << 4, v[1] << 4)
+    } else {
+        ((v[1] << 4) + 8, (v[0] << 4) - 8)
+    };
+    [hdr_gray(y0), hdr_gray(y1)]
+}
+
+fn hdr_luminance_small_range(v: &[i32]) -> [[i32; 4]; 2] {
+    let (y0, d) = if v[0] & 0x80 != 0 {
+        ((v[1] & 0xe0) << 4 | (v[0] & 0x7f) << 2, (v[1] & 0x1f) << 2)
+    } else {
+        ((v[1] & 0xf0) << 4 | (v[0] & 0x7f) << 1, (v[1] & 0x0f) << 1)
+    };
+    [hdr_gray(y0), hdr_gray((y0 + d).min(0xfff))]
+}
+
+/// HDR RGB base + scale, the placement of the variable bits depends on the
+/// submode
+fn hdr_rgb_scale(v: &[i32]) -> [[i32; 4]; 2] {
+    let mode_value = (v[0] & 0xc0) >> 6 | (v[1] & 0x80) >> 5 | (v[2] & 0x80) >> 4;
+    let (major, mode) = if mode_value & 0xc != 0xc {
+        (mode_value >> 2, mode_value & 3)
+    } else if mode_value != 0xf {
+        (mode_value & 3, 4)
+    } else {
+        (0, 5)
+    };
+
+    let mut red = v[0] & 0x3f;
+    let mut green = v[1] & 0x1f;
+    let mut blue = v[2] & 0x1f;
+    let mut scale = v[3] & 0x1f;
+    let x = [
+        (v[1] >> 6) & 1,
+        (v[1] >> 5) & 1,
+        (v[2] >> 6) & 1,
+        (v[2] >> 5) & 1,
+        (v[3] >> 7) & 1,
+        (v[3] >> 6) & 1,
+        (v[3] >> 5) & 1,
+    ];
+    let one_hot = 1 << mode;
+    if one_hot & 0x30 != 0 {
+        green |= x[0] << 6;
+        blue |= x[2] << 6;
+    }
+    if one_hot & 0x3a != 0 {
+        green |= x[1] << 5;
+        blue |= x[3] << 5;
+    }
+    if one_hot & 0x3d != 0 {
+        scale |= x[6] << 5;
+    }
+    if one_hot & 0x2d != 0 {
+        scale |= x[5] << 6;
+    }
+    if one_hot & 0x04 != 0 {
+        scale |= x[4] << 7;
+        red |= x[3] << 6;
+    }
+    if one_hot & 0x3b != 0 {
+        red |= x[4] << 6;
+    }
+    if one_hot & 0x10 != 0 {
+        red |= x[5] << 7;
+    }
+    if one_hot & 0x0f != 0 {
+        red |= x[2] << 7;
+    }
+    if one_hot & 0x05 != 0 {
+        red |= x[1] << 8 | x[0] << 9;
+    }
+    if one_hot & 0x0a != 0 {
+        red |= x[0] << 8;
+    }
+    if one_hot & 0x02 != 0 {
+        red |= x[6] << 9 | x[5] << 10;
+    }
+    if one_hot & 0x01 != 0 {
+        red |= x[3] << 10;
+    }
+
+    let shift = [1, 1, 2, 3, 4, 5][mode as usize];
+    let (mut red, mut green, mut blue, scale) =
+        (red << shift, green << shift, blue << shift, scale << shift);
+    // all but the last submode store green and blue as differences
+    if mode != 5 {
+        green = red - green;
+        blue = red - blue;
+    }
+    match major {
+        1 => std::mem::swap(&mut red, &mut green),
+        2 => std::mem::swap(&mut red, &mut blue),
+        _ => (),
+    }
+
+    let e1 = [red, green, blue];
+    let mut colors = [[0, 0, 0, 0xffff]; 2];
+    for c in 0..3 {
+        colors[0][c] = (e1[c] - scale).clamp(0, 0xfff) << 4;
+        colors[1][c] = e1[c].clamp(0, 0xfff) << 4;
+    }
+    colors
+}
+
+/// HDR RGB direct, the placement of the variable bits depends on the submode
+fn hdr_rgb(v: &[i32]) -> [[i32; 4]; 2] {
+    let mode_value = (v[1] & 0x80) >> 7 | (v[2] & 0x80) >> 6 | (v[3] & 0x80) >> 5;
+    let major = (v[4] & 0x80) >> 7 | (v[5] & 0x80) >> 6;
+    if major == 3 {
+        return [
+            [v[0] << 8, v[2] << 8, (v[4] & 0x7f) << 9, 0xffff],
+            [v[1] << 8, v[3] << 8, (v[5] & 0x7f) << 9, 0xffff],
+        ];
+    }
+
+    let mut a = v[0] | (v[1] & 0x40) << 2;
+    let mut b0 = v[2] & 0x3f;
+    let mut b1 = v[3] & 0x3f;
+    let mut c = v[1] & 0x3f;
+    let mut d0 = v[4] & 0x1f;
+    let mut d1 = v[5] & 0x1f;
+    let x = [
+        (v[2] >> 6) & 1,
+        (v[3] >> 6) & 1,
+        (v[4] >> 6) & 1,
+        (v[5] >> 6) & 1,
+        (v[4] >> 5) & 1,
+        (v[5] >> 5) & 1,
+    ];
+    let one_hot = 1 << mode_value;
+    if one_hot & 0xa4 != 0 {
+        a |= x[0] << 9;
+    }
+    if one_hot & 0x08 != 0 {
+        a |= x[2] << 9;
+    }
+    if one_hot & 0x50 != 0 {
+        a |= x[4] << 9 | x[5] << 10;
+    }
+    if one_hot & 0xa0 != 0 {
+        a |= x[1] << 10;
+    }
+    if one_hot & 0xc0 != 0 {
+        a |= x[2] << 11;
+    }
+    if one_hot & 0x04 != 0 {
+        c |= x[1] << 6;
+    }
+    if one_hot & 0xe8 != 0 {
+        c |= x[3] << 6;
+    }
+    if one_hot & 0x20 != 0 {
+        c |= x[2] << 7;
+    }
+    if one_hot & 0x5b != 0 {
+        b0 |= x[0] << 6;
+        b1 |= x[1] << 6;
+    }
+    if one_hot & 0x12 != 0 {
+        b0 |= x[2] << 7;
+        b1 |= x[3] << 7;
+    }
+    if one_hot & 0xaf != 0 {
+        d0 |= x[4] << 5;
+        d1 |= x[5] << 5;
+    }
+    if one_hot & 0x05 != 0 {
+        d0 |= x[2] << 6;
+        d1 |= x[3] << 6;
+    }
+
+    // the differences are signed
+    let d_shift = 32 - [7, 6, 7, 6, 5, 6, 5, 6][mode_value as usize];
+    let (d0, d1) = ((d0 << d_shift) >> d_shift, (d1 << d_shift) >> d_shift);
+    let shift = (mode_value >> 1) ^ 3;
+    let (a, b0, b1, c, d0, d1) = (
+        a << shift,
+        b0 << shift,
+        b1 << shift,
+        c << shift,
+        d0 << shift,
+        d1 << shift,
+    );
+
+    let mut colors = [
+        [a - c, a - b0 - c - d0, a - b1 - c - d1, 0xffff],
+        [a, a - b0, a - b1, 0xffff],
+    ];
+    for color in colors.iter_mut() {
+        for value in color.iter_mut().take(3) {
+            *value = (*value).clamp(0, 0xfff) << 4;
+        }
+        match major {
+            1 => color.swap(0, 1),
+            2 => color.swap(0, 2),
+            _ => (),
+        }
+    }
+    colors
+}
+
+fn hdr_alpha(v6: i32, v7: i32) -> (i32, i32) {
+    let selector = (v6 >> 7) & 1 | (v7 >> 6) & 2;
+    let (mut v6, mut v7) = (v6 & 0x7f, v7 & 0x7f);
+    if selector == 3 {
+        return (v6 << 9, v7 << 9);
+    }
+    v6 |= (v7 << (selector + 1)) & 0x780;
+    v7 &= 0x3f >> selector;
+    v7 ^= 0x20 >> selector;
+    v7 -= 0x20 >> selector;
+    v6 <<= 4 - selector;
+    v7 <<= 4 - selector;
+    v7 = (v7 + v6).clamp(0, 0xfff);
+    (v6 << 4, v7 << 4)
+}
+
+/// Decodes an LDR endpoint pair of a partition to 8 bit values
+fn decode_ldr_endpoints(mode: u32, v: &[i32]) -> Option<[[i32; 4]; 2]> {
+    let endpoints = match mode {
+        // luminance direct
+        0 => [[v[0], v[0], v[0], 0xff], [v[1], v[1], v[1], 0xff]],
+        // luminance base + offset
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xc0);
+            let l1 = (l0 + (v[1] & 0x3f)).min(0xff);
+            [[l0, l0, l0, 0xff], [l1, l1, l1, 0xff]]
+        }
+        // luminance + alpha direct
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        // luminance + alpha base + offset
+        5 => {
+            let (d0, l0) = bit_transfer_signed(v[1], v[0]);
+            let (d1, a0) = bit_transfer_signed(v[3], v[2]);
+            let l1 = l0 + d0;
+            [[l0, l0, l0, a0], clamp_color([l1, l1, l1, a0 + d1])]
+        }
+        // RGB base + scale
+        6 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                0xff,
+            ],
+            [v[0], v[1], v[2], 0xff],
+        ],
+        // RGB(A) direct
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 {
+                (v[6], v[7])
+            } else {
+                (0xff, 0xff)
+            };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]]
+            } else {
+                [
+                    blue_contract([v[1], v[3], v[5], a1]),
+                    blue_contract([v[0], v[2], v[4], a0]),
+                ]
+            }
+        }
+        // RGB(A) base + offset
+        9 | 13 => {
+            let (d0, r) = bit_transfer_signed(v[1], v[0]);
+            let (d1, g) = bit_transfer_signed(v[3], v[2]);
+            let (d2, b) = bit_transfer_signed(v[5], v[4]);
+            let (d3, a) = if mode == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 0xff)
+            };
+            let base = [r, g, b, a];
+            let offset = [r + d0, g + d1, b + d2, a + d3];
+            if d0 + d1 + d2 >= 0 {
+                [base, clamp_color(offset)]
+            } else {
+                [clamp_color(blue_contract(offset)), blue_contract(base)]
+            }
+        }
+        // RGB base + scale plus two alphas
+        10 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ],
+        _ => return None,
+    };
+    Some(endpoints)
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+fn select_partition(seed: u32, x: usize, y: usize, partitions: usize, small_block: bool) -> usize {
+    let (x, y) = if small_block {
+        (x as u32 * 2, y as u32 * 2)
+    } else {
+        (x as u32, y as u32)
+    };
+    let seed = seed + (partitions as u32 - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [
+        rnum & 0xf,
+        (rnum >> 4) & 0xf,
+        (rnum >> 8) & 0xf,
+        (rnum >> 12) & 0xf,
+        (rnum >> 16) & 0xf,
+        (rnum >> 20) & 0xf,
+        (rnum >> 24) & 0xf,
+        (rnum >> 28) & 0xf,
+        (rnum >> 18) & 0xf,
+        (rnum >> 22) & 0xf,
+        (rnum >> 26) & 0xf,
+        rnum.rotate_left(2) & 0xf,
+    ];
+    for seed in seeds.iter_mut() {
+        *seed *= *seed;
+    }
+
+    let (sh1, sh2) = if seed & 1 != 0 {
+        (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        )
+    };
+    let sh3 = if seed & 0x10 != 0 { sh1 } else { sh2 };
+    for (i, seed) in seeds.iter_mut().enumerate() {
+        *seed >>= match i {
+            0..=7 if i % 2 == 0 => sh1,
+            0..=7 => sh2,
+            _ => sh3,
+        };
+    }
+
+    // z is always zero for 2D textures
+    let a = (seeds[0] * x + seeds[1] * y).wrapping_add(rnum >> 14) & 0x3f;
+    let b = (seeds[2] * x + seeds[3] * y).wrapping_add(rnum >> 10) & 0x3f;
+    let mut c = (seeds[4] * x + seeds[5] * y).wrapping_add(rnum >> 6) & 0x3f;
+    let mut d = (seeds[6] * x + seeds[7] * y).wrapping_add(rnum >> 2) & 0x3f;
+    if partitions < 4 {
+        d = 0;
+    }
+    if partitions < 3 {
+        c = 0;
+    }
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERROR_COLOR: [u8; 4] = [0xff, 0, 0xff, 0xff];
+
+    fn set_bits(block: &mut u128, low: u32, count: u32, value: u32) {
+        *block |= u128::from(value & ((1 << count) - 1)) << low;
+    }
+
+    /// Stores weights from the top of the block downwards
+    fn set_weights(block: &mut u128, bits: u32, weights: &[u32]) {
+        for (i, &weight) in weights.iter().enumerate() {
+            for k in 0..bits {
+                set_bits(block, 127 - (i as u32 * bits + k), 1, weight >> k);
+            }
+        }
+    }
+
+    fn pixel(image: &[u8], width: usize, x: usize, y: usize) -> &[u8] {
+        let offset = (y * width + x) * 4;
+        &image[offset..offset + 4]
+    }
+
+    #[test]
+    fn test_unquantize() {
+        let trits = range(3, 1);
+        let colors: Vec<i32> = (0..6)
+            .map(|v| unquantize_color((v & 1, v >> 1), trits))
+            .collect();
+        assert_eq!(colors, vec![0, 255, 51, 204, 102, 153]);
+        let weights: Vec<u32> = (0..6)
+            .map(|v| unquantize_weight((v & 1, v >> 1), trits))
+            .collect();
+        assert_eq!(weights, vec![0, 64, 12, 52, 25, 39]);
+
+        let weights: Vec<u32> = (0..5)
+            .map(|d| unquantize_weight((0, d), WEIGHT_RANGES[5].unwrap()))
+            .collect();
+        assert_eq!(weights, vec![0, 16, 32, 48, 64]);
+        assert_eq!(unquantize_color((5, 0), range(0, 3)), 0b1011_0110);
+    }
+
+    #[test]
+    fn test_integer_sequences() {
+        // every combination of trits and quints has an encoding
+        let mut trits: Vec<[u32; 5]> = (0..256).map(decode_trits).collect();
+        trits.sort();
+        trits.dedup();
+        assert_eq!(trits.len(), 243);
+        assert!(trits.iter().all(|t| t.iter().all(|&t| t < 3)));
+
+        let mut quints: Vec<[u32; 3]> = (0..128).map(decode_quints).collect();
+        quints.sort();
+        quints.dedup();
+        assert_eq!(quints.len(), 125);
+        assert!(quints.iter().all(|q| q.iter().all(|&q| q < 5)));
+
+        assert_eq!(decode_trits(0), [0; 5]);
+        assert_eq!(decode_quints(0), [0; 3]);
+
+        // bits 5 and 7 of 2 bit values with trits [2, 0, 0, 0, 0]
+        let mut block = 0;
+        set_bits(&mut block, 0, 2, 1);
+        set_bits(&mut block, 2, 2, 0b10);
+        set_bits(&mut block, 4, 2, 3);
+        let values = decode_ise(block, 0, 2, range(3, 2));
+        assert_eq!(values, vec![(1, 2), (3, 0)]);
+    }
+
+    #[test]
+    fn test_void_extent() {
+        let mut block = 0u128;
+        set_bits(&mut block, 0, 9, 0x1fc);
+        // reserved bits and the extent coordinates are all ones
+        set_bits(&mut block, 10, 27, !0);
+        set_bits(&mut block, 37, 27, !0);
+        set_bits(&mut block, 64, 16, 0x1234);
+        set_bits(&mut block, 80, 16, 0x5678);
+        set_bits(&mut block, 96, 16, 0x9abc);
+        set_bits(&mut block, 112, 16, 0xffff);
+        let data = block.to_le_bytes();
+        for &size in &[4, 5, 6, 8, 10, 12] {
+            let image = decode_astc(&data, size, size, size, size).unwrap();
+            assert_eq!(image.len(), size * size * 4);
+            assert!(image.chunks(4).all(|p| p == [0x12, 0x56, 0x9a, 0xff]));
+        }
+        // a 12x12 block cropped to a 3x2 texture
+        let image = decode_astc(&data, 3, 2, 12, 12).unwrap();
+        assert_eq!(image.len(), 3 * 2 * 4);
+    }
+
+    #[test]
+    fn test_rgb_direct() {
+        let mut block = 0u128;
+        // 4x4 weight grid of 2 bit weights, single partition RGB direct
+        set_bits(&mut block, 0, 11, 0x42);
+        set_bits(&mut block, 13, 4, 8);
+        for (i, &value) in [0, 255, 0, 128, 64, 64].iter().enumerate() {
+            set_bits(&mut block, 17 + 8 * i as u32, 8, value);
+        }
+        let mut weights = [0; 16];
+        weights[1] = 3;
+        weights[2] = 1;
+        weights[3] = 2;
+        set_weights(&mut block, 2, &weights);
+
+        let image = decode_astc(&block.to_le_bytes(), 4, 4, 4, 4).unwrap();
+        assert_eq!(pixel(&image, 4, 0, 0), &[0, 0, 64, 255]);
+        assert_eq!(pixel(&image, 4, 1, 0), &[255, 128, 64, 255]);
+        assert_eq!(pixel(&image, 4, 2, 0), &[84, 42, 64, 255]);
+        assert_eq!(pixel(&image, 4, 3, 0), &[171, 86, 64, 255]);
+        assert_eq!(pixel(&image, 4, 3, 3), &[0, 0, 64, 255]);
+    }
+
+    #[test]
+    fn test_dual_plane_infill() {
+        let mut block = 0u128;
+        // 2x2 dual plane weight grid of 3 bit weights, RGBA direct
+        set_bits(&mut block, 0, 11, 0x51f);
+        set_bits(&mut block, 13, 4, 12);
+        for (i, &value) in [0, 255, 0, 255, 0, 255, 255, 0].iter().enumerate() {
+            set_bits(&mut block, 17 + 8 * i as u32, 8, value);
+        }
+        // alpha uses the second plane
+        set_bits(&mut block, 128 - 24 - 2, 2, 3);
+        set_weights(&mut block, 3, &[0, 7, 7, 0, 0, 0, 7, 7]);
+
+        let image = decode_astc(&block.to_le_bytes(), 4, 4, 4, 4).unwrap();
+        assert_eq!(pixel(&image, 4, 0, 0), &[0, 0, 0, 0]);
+        assert_eq!(pixel(&image, 4, 1, 0), &[80, 80, 80, 80]);
+        assert_eq!(pixel(&image, 4, 3, 0), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&image, 4, 1, 1), &[80, 80, 80, 96]);
+        assert_eq!(pixel(&image, 4, 3, 3), &[255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn test_partitions() {
+        // every texel of a two partition block belongs to one of them and
+        // both partitions are used by at least some seeds
+        let mut used = [false; 2];
+        for seed in 0..1024 {
+            for y in 0..4 {
+                for x in 0..4 {
+                    used[select_partition(seed, x, y, 2, true)] = true;
+                }
+            }
+        }
+        assert_eq!(used, [true, true]);
+        assert!((0..1024).all(|seed| select_partition(seed, 0, 0, 4, false) < 4));
+    }
+
+    #[test]
+    fn test_error_blocks() {
+        // reserved block mode
+        let image = decode_astc(&[0; 16], 4, 4, 4, 4).unwrap();
+        assert!(image.chunks(4).all(|p| p == ERROR_COLOR));
+
+        // more than 18 color values
+        let mut block = 0u128;
+        set_bits(&mut block, 0, 11, 0x42);
+        set_bits(&mut block, 11, 2, 3);
+        set_bits(&mut block, 23, 6, 0b11_1111);
+        let image = decode_astc(&block.to_le_bytes(), 4, 4, 4, 4).unwrap();
+        assert!(image.chunks(4).all(|p| p == ERROR_COLOR));
+
+        // random blocks, which include dual plane blocks without room for colors
+        let footprints = [
+            (4, 4),
+            (5, 4),
+            (5, 5),
+            (6, 5),
+            (6, 6),
+            (8, 5),
+            (8, 6),
+            (8, 8),
+            (10, 5),
+            (10, 6),
+            (10, 8),
+            (10, 10),
+            (12, 10),
+            (12, 12),
+        ];
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data: Vec<u8> = (0..16 * 256)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        for &(block_width, block_height) in &footprints {
+            let width = block_width * 16;
+            let height = block_height * 16;
+            let image = decode_astc(&data, width, height, block_width, block_height).unwrap();
+            assert_eq!(image.len(), width * height * 4);
+        }
+    }
+
+    #[test]
+    fn test_hdr_void_extent() {
+        let mut block = 0u128;
+        set_bits(&mut block, 0, 10, 0x3fc);
+        set_bits(&mut block, 10, 27, !0);
+        set_bits(&mut block, 37, 27, !0);
+        set_bits(&mut block, 64, 16, 0x3c00);
+        set_bits(&mut block, 80, 16, 0x4000);
+        set_bits(&mut block, 96, 16, 0x3800);
+        set_bits(&mut block, 112, 16, 0x3c00);
+        let data = block.to_le_bytes();
+        let image = decode_astc_f32(&data, 4, 4, 4, 4).unwrap();
+        assert!(image.chunks(4).all(|p| p == [1.0, 2.0, 0.5, 1.0]));
+        let image = decode_astc(&data, 4, 4, 4, 4).unwrap();
+        assert!(image.chunks(4).all(|p| p == [255, 255, 128, 255]));
+    }
+
+    #[test]
+    fn test_hdr_luminance() {
+        let mut block = 0u128;
+        set_bits(&mut block, 0, 11, 0x42);
+        set_bits(&mut block, 13, 4, 2);
+        set_bits(&mut block, 17, 8, 0x80);
+        set_bits(&mut block, 25, 8, 0xc0);
+        let mut weights = [0; 16];
+        weights[1] = 3;
+        set_weights(&mut block, 2, &weights);
+
+        let image = decode_astc_f32(&block.to_le_bytes(), 4, 4, 4, 4).unwrap();
+        assert_eq!(&image[0..4], &[2.0, 2.0, 2.0, 1.0]);
+        assert_eq!(&image[4..8], &[512.0, 512.0, 512.0, 1.0]);
+        assert_eq!(lns_to_half(0x7800), 0x3c00);
+    }
+
+    #[test]
+    fn test_hdr_endpoints() {
+        assert_eq!(
+            hdr_rgb(&[0x40, 0x01, 0x02, 0x03, 0x04, 0x05]),
+            [[8064, 7296, 7040, 0xffff], [8192, 7936, 7808, 0xffff]]
+        );
+        // major component 3 stores the values directly
+        assert_eq!(
+            hdr_rgb(&[0x10, 0x20, 0x30, 0x40, 0x85, 0x86]),
+            [
+                [0x1000, 0x3000, 0xa00, 0xffff],
+                [0x2000, 0x4000, 0xc00, 0xffff]
+            ]
+        );
+        assert_eq!(
+            hdr_rgb_scale(&[0x05, 0x01, 0x02, 0x03]),
+            [[64, 32, 0, 0xffff], [160, 128, 96, 0xffff]]
+        );
+        assert_eq!(hdr_alpha(0x81, 0x82), (512, 1024));
+    }
+}
@@ -0,0 +1,130 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! ATC (AMD/Adreno texture compression) decoder.
+
+use super::convert::{decode_blocks, expand};
+use error::Result;
+
+/// Decodes ATC RGB data
+pub fn decode_atc_rgb(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    decode_blocks(data, width, height, 4, 4, 8, decode_color_block)
+}
+
+/// Decodes ATC RGBA data with interpolated alpha
+pub fn decode_atc_rgba(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    decode_blocks(data, width, height, 4, 4, 16, |block, pixels| {
+        decode_color_block(&block[8..], pixels);
+        decode_alpha_block(block, pixels);
+    })
+}
+
+fn decode_color_block(block: &[u8], pixels: &mut [[u8; 4]]) {
+    let c0 = u32::from(block[0]) | u32::from(block[1]) << 8;
+    let c1 = u32::from(block[2]) | u32::from(block[3]) << 8;
+    // the first color is RGB555 with a mode bit on top, the second RGB565
+    let color0 = [
+        u32::from(expand((c0 >> 10) & 0x1f, 5)),
+        u32::from(expand((c0 >> 5) & 0x1f, 5)),
+        u32::from(expand(c0 & 0x1f, 5)),
+    ];
+    let color1 = [
+        u32::from(expand(c1 >> 11, 5)),
+        u32::from(expand((c1 >> 5) & 0x3f, 6)),
+        u32::from(expand(c1 & 0x1f, 5)),
+    ];
+
+    let mut palette = [[0u32; 3]; 4];
+    for c in 0..3 {
+        if c0 & 0x8000 == 0 {
+            palette[0][c] = color0[c];
+            palette[1][c] = (5 * color0[c] + 3 * color1[c]) / 8;
+            palette[2][c] = (3 * color0[c] + 5 * color1[c]) / 8;
+            palette[3][c] = color1[c];
+        } else {
+            palette[1][c] = color0[c].saturating_sub(color1[c] / 4);
+            palette[2][c] = color0[c];
+            palette[3][c] = color1[c];
+        }
+    }
+
+    let indices = u32::from(block[4])
+        | u32::from(block[5]) << 8
+        | u32::from(block[6]) << 16
+        | u32::from(block[7]) << 24;
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let color = palette[((indices >> (2 * i)) & 3) as usize];
+        *pixel = [color[0] as u8, color[1] as u8, color[2] as u8, 0xff];
+    }
+}
+
+/// Decodes an alpha block in the BC3 (DXT5) layout
+fn decode_alpha_block(block: &[u8], pixels: &mut [[u8; 4]]) {
+    let a0 = u32::from(block[0]);
+    let a1 = u32::from(block[1]);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 0xff];
+    if a0 > a1 {
+        for (i, alpha) in palette.iter_mut().enumerate().skip(2) {
+            *alpha = ((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for (i, alpha) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *alpha = ((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5;
+        }
+    }
+
+    let mut indices = 0u64;
+    for (i, &byte) in block[2..8].iter().enumerate() {
+        indices |= u64::from(byte) << (8 * i);
+    }
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = palette[((indices >> (3 * i)) & 7) as usize] as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atc_rgb() {
+        // red and blue, palette indices 0 to 3 in the first row
+        let block = [0x00, 0x7c, 0x1f, 0x00, 0xe4, 0, 0, 0];
+        let image = decode_atc_rgb(&block, 4, 4).unwrap();
+        assert_eq!(&image[0..4], &[255, 0, 0, 255]);
+        assert_eq!(&image[4..8], &[159, 0, 95, 255]);
+        assert_eq!(&image[8..12], &[95, 0, 159, 255]);
+        assert_eq!(&image[12..16], &[0, 0, 255, 255]);
+        assert_eq!(&image[16..20], &[255, 0, 0, 255]);
+
+        // alternative mode with black and a darkened first color
+        let block = [0x00, 0xc0, 0x00, 0x40, 0xe4, 0, 0, 0];
+        let image = decode_atc_rgb(&block, 4, 4).unwrap();
+        assert_eq!(&image[0..4], &[0, 0, 0, 255]);
+        assert_eq!(&image[4..8], &[116, 0, 0, 255]);
+        assert_eq!(&image[8..12], &[132, 0, 0, 255]);
+        assert_eq!(&image[12..16], &[66, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_atc_rgba() {
+        let mut block = vec![255, 0, 0b0011_1010, 0, 0, 0, 0, 0];
+        block.extend_from_slice(&[0x00, 0x7c, 0x1f, 0x00, 0, 0, 0, 0]);
+        let image = decode_atc_rgba(&block, 4, 4).unwrap();
+        assert_eq!(&image[0..4], &[255, 0, 0, 218]);
+        assert_eq!(&image[4..8], &[255, 0, 0, 36]);
+        assert_eq!(&image[8..12], &[255, 0, 0, 255]);
+
+        // six interpolated values plus 0 and 255
+        let block = [0, 255, 0b1011_1010, 0b1, 0, 0, 0, 0];
+        let mut pixels = [[0u8; 4]; 16];
+        decode_alpha_block(&block, &mut pixels);
+        assert_eq!(pixels[0][3], 51);
+        assert_eq!(pixels[1][3], 0xff);
+        assert_eq!(pixels[2][3], 0);
+    }
+}
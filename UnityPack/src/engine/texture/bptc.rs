@@ -0,0 +1,935 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! BPTC decoders: BC7 for LDR and BC6H for HDR color data, following the
+//! Direct3D 11 block compression specification.
+
+use super::convert::{decode_blocks, expand, half_to_f32};
+use error::Result;
+
+/// Subset of every pixel for the two subset partitions, one bit per pixel
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subset of every pixel for the three subset partitions, two bits per pixel
+const PARTITIONS_3: [u32; 64] = [
+    0xaa68_5050,
+    0x6a5a_5040,
+    0x5a5a_4200,
+    0x5450_a0a8,
+    0xa5a5_0000,
+    0xa0a0_5050,
+    0x5555_a0a0,
+    0x5a5a_5050,
+    0xaa55_0000,
+    0xaa55_5500,
+    0xaaaa_5500,
+    0x9090_9090,
+    0x9494_9494,
+    0xa4a4_a4a4,
+    0xa9a5_9450,
+    0x2a0a_4250,
+    0xa594_5040,
+    0x0a42_5054,
+    0xa5a5_a500,
+    0x55a0_a0a0,
+    0xa8a8_5454,
+    0x6a6a_4040,
+    0xa4a4_5000,
+    0x1a1a_0500,
+    0x0050_a4a4,
+    0xaaa5_9090,
+    0x1469_6914,
+    0x6969_1400,
+    0xa085_85a0,
+    0xaa82_1414,
+    0x50a4_a450,
+    0x6a5a_0200,
+    0xa9a5_8000,
+    0x5090_a0a8,
+    0xa8a0_9050,
+    0x2424_2424,
+    0x00aa_5500,
+    0x2492_4924,
+    0x2449_9224,
+    0x50a5_0a50,
+    0x500a_a550,
+    0xaaaa_4444,
+    0x6666_0000,
+    0xa5a0_a5a0,
+    0x50a0_50a0,
+    0x6928_6928,
+    0x44aa_aa44,
+    0x6666_6600,
+    0xaa44_4444,
+    0x54a8_54a8,
+    0x9580_9580,
+    0x9696_9600,
+    0xa854_54a8,
+    0x8095_9580,
+    0xaa14_1414,
+    0x9696_0000,
+    0xaaaa_1414,
+    0xa050_50a0,
+    0xa0a5_a5a0,
+    0x9600_0000,
+    0x4080_4080,
+    0xa9a8_a9a8,
+    0xaaaa_aa44,
+    0x2a4a_5254,
+];
+
+/// Anchor index of the second subset of the two subset partitions
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor index of the second subset of the three subset partitions
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+/// Anchor index of the third subset of the three subset partitions
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weight(bits: u32, index: u32) -> u32 {
+    match bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+fn subset(subsets: usize, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        2 => (PARTITIONS_2[partition] >> pixel) as usize & 1,
+        3 => (PARTITIONS_3[partition] >> (2 * pixel)) as usize & 3,
+        _ => 0,
+    }
+}
+
+/// Anchor pixels store their index with one bit less
+fn is_anchor(subsets: usize, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match subsets {
+            2 => pixel == ANCHORS_2[partition] as usize,
+            3 => {
+                pixel == ANCHORS_3_SECOND[partition] as usize
+                    || pixel == ANCHORS_3_THIRD[partition] as usize
+            }
+            _ => false,
+        }
+}
+
+/// Reads a 128 bit block from its least significant bit upwards
+struct BitReader {
+    block: u128,
+    offset: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8], offset: u32) -> BitReader {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&block[..16]);
+        BitReader {
+            block: u128::from_le_bytes(bytes),
+            offset,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.block >> self.offset) as u32 & ((1u64 << count) - 1) as u32;
+        self.offset += count;
+        value
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PBits {
+    None,
+    PerEndpoint,
+    PerSubset,
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    p_bits: PBits,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const fn bc7_mode(
+    subsets: usize,
+    (partition_bits, rotation_bits, selection_bits): (u32, u32, u32),
+    (color_bits, alpha_bits, p_bits): (u32, u32, PBits),
+    (index_bits, secondary_index_bits): (u32, u32),
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        selection_bits,
+        color_bits,
+        alpha_bits,
+        p_bits,
+        index_bits,
+        secondary_index_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, (4, 0, 0), (4, 0, PBits::PerEndpoint), (3, 0)),
+    bc7_mode(2, (6, 0, 0), (6, 0, PBits::PerSubset), (3, 0)),
+    bc7_mode(3, (6, 0, 0), (5, 0, PBits::None), (2, 0)),
+    bc7_mode(2, (6, 0, 0), (7, 0, PBits::PerEndpoint), (2, 0)),
+    bc7_mode(1, (0, 2, 1), (5, 6, PBits::None), (2, 3)),
+    bc7_mode(1, (0, 2, 0), (7, 8, PBits::None), (2, 2)),
+    bc7_mode(1, (0, 0, 0), (7, 7, PBits::PerEndpoint), (4, 0)),
+    bc7_mode(2, (6, 0, 0), (5, 5, PBits::PerEndpoint), (2, 0)),
+];
+
+/// Decodes BC7 data
+pub fn decode_bc7(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
+    decode_blocks(data, width, height, 4, 4, 16, decode_bc7_block)
+}
+
+fn decode_bc7_block(block: &[u8], pixels: &mut [[u8; 4]]) {
+    // the mode is given by the position of the lowest set bit
+    let mode_number = block[0].trailing_zeros();
+    if mode_number >= 8 {
+        for pixel in pixels.iter_mut() {
+            *pixel = [0; 4];
+        }
+        return;
+    }
+    let mode = &BC7_MODES[mode_number as usize];
+    let mut reader = BitReader::new(block, mode_number + 1);
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let selection = reader.read(mode.selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0xffu32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    if mode.alpha_bits > 0 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[3] = reader.read(mode.alpha_bits);
+        }
+    }
+
+    let (color_bits, alpha_bits) = if mode.p_bits == PBits::None {
+        (mode.color_bits, mode.alpha_bits)
+    } else {
+        let mut p_bits = [0u32; 6];
+        for i in 0..endpoint_count {
+            p_bits[i] = if mode.p_bits == PBits::PerEndpoint || i % 2 == 0 {
+                reader.read(1)
+            } else {
+                p_bits[i - 1]
+            };
+        }
+        for (endpoint, &p_bit) in endpoints.iter_mut().zip(p_bits.iter()) {
+            for value in endpoint
+                .iter_mut()
+                .take(if mode.alpha_bits > 0 { 4 } else { 3 })
+            {
+                *value = *value << 1 | p_bit;
+            }
+        }
+        (mode.color_bits + 1, mode.alpha_bits + 1)
+    };
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut().take(3) {
+            *value = u32::from(expand(*value, color_bits));
+        }
+        if mode.alpha_bits > 0 {
+            endpoint[3] = u32::from(expand(endpoint[3], alpha_bits));
+        }
+    }
+
+    let mut indices = [0u32; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, i) as u32;
+        *index = reader.read(mode.index_bits - anchor);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (i, index) in secondary_indices.iter_mut().enumerate() {
+            *index = reader.read(mode.secondary_index_bits - (i == 0) as u32);
+        }
+    }
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let w = weight(mode.index_bits, indices[i]);
+            (w, w)
+        } else {
+            let primary = weight(mode.index_bits, indices[i]);
+            let secondary = weight(mode.secondary_index_bits, secondary_indices[i]);
+            if selection == 0 {
+                (primary, secondary)
+            } else {
+                (secondary, primary)
+            }
+        };
+        let s = subset(mode.subsets, partition, i) * 2;
+        let (e0, e1) = (endpoints[s], endpoints[s + 1]);
+        for c in 0..4 {
+            let w = if c == 3 { alpha_weight } else { color_weight };
+            pixel[c] = ((e0[c] * (64 - w) + e1[c] * w + 32) >> 6) as u8;
+        }
+        if rotation > 0 {
+            pixel.swap(rotation as usize - 1, 3);
+        }
+    }
+}
+
+// BC6H endpoint components: w and x are the endpoints of the first subset,
+// y and z the endpoints of the second one
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+
+struct Bc6Mode {
+    /// Mode bits and their count
+    header: (u32, u32),
+    subsets: usize,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Bit runs of the endpoint components in storage order: the component,
+    /// the bit stored first and the bit stored last
+    layout: &'static [(u8, u8, u8)],
+}
+
+const BC6_MODES: [Bc6Mode; 14] = [
+    Bc6Mode {
+        header: (0b00, 2),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (GY, 4, 4),
+            (BY, 4, 4),
+            (BZ, 4, 4),
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        header: (0b01, 2),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (GY, 5, 5),
+            (GZ, 4, 5),
+            (RW, 0, 6),
+            (BZ, 0, 1),
+            (BY, 4, 4),
+            (GW, 0, 6),
+            (BY, 5, 5),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 6),
+            (BZ, 3, 3),
+            (BZ, 5, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+        ],
+    },
+    Bc6Mode {
+        header: (0b00010, 5),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 4),
+            (RW, 10, 10),
+            (GY, 0, 3),
+            (GX, 0, 3),
+            (GW, 10, 10),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 3),
+            (BW, 10, 10),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        header: (0b00110, 5),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 10, 10),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (GW, 10, 10),
+            (GZ, 0, 3),
+            (BX, 0, 3),
+            (BW, 10, 10),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 3),
+            (BZ, 0, 0),
+            (BZ, 2, 2),
+            (RZ, 0, 3),
+            (GY, 4, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        header: (0b01010, 5),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 10, 10),
+            (BY, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 3),
+            (GW, 10, 10),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BW, 10, 10),
+            (BY, 0, 3),
+            (RY, 0, 3),
+            (BZ, 1, 2),
+            (RZ, 0, 3),
+            (BZ, 4, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        header: (0b01110, 5),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (RW, 0, 8),
+            (BY, 4, 4),
+            (GW, 0, 8),
+            (GY, 4, 4),
+            (BW, 0, 8),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        header: (0b10010, 5),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        layout: &[
+            (RW, 0, 7),
+            (GZ, 4, 4),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (BZ, 3, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+        ],
+    },
+    Bc6Mode {
+        header: (0b10110, 5),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        layout: &[
+            (RW, 0, 7),
+            (BZ, 0, 0),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (GY, 5, 4),
+            (BW, 0, 7),
+            (GZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        header: (0b11010, 5),
+        subsets: 2,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        layout: &[
+            (RW, 0, 7),
+            (BZ, 1, 1),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (BY, 5, 5),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (BZ, 5, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+        ],
+    },
+    Bc6Mode {
+        header: (0b11110, 5),
+        subsets: 2,
+        transformed: false,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (RW, 0, 5),
+            (GZ, 4, 4),
+            (BZ, 0, 1),
+            (BY, 4, 4),
+            (GW, 0, 5),
+            (GY, 5, 5),
+            (BY, 5, 5),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 5),
+            (GZ, 5, 5),
+            (BZ, 3, 3),
+            (BZ, 5, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+        ],
+    },
+    Bc6Mode {
+        header: (0b00011, 5),
+        subsets: 1,
+        transformed: false,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 9),
+            (GX, 0, 9),
+            (BX, 0, 9),
+        ],
+    },
+    Bc6Mode {
+        header: (0b00111, 5),
+        subsets: 1,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 8),
+            (RW, 10, 10),
+            (GX, 0, 8),
+            (GW, 10, 10),
+            (BX, 0, 8),
+            (BW, 10, 10),
+        ],
+    },
+    Bc6Mode {
+        header: (0b01011, 5),
+        subsets: 1,
+        transformed: true,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 7),
+            (RW, 11, 10),
+            (GX, 0, 7),
+            (GW, 11, 10),
+            (BX, 0, 7),
+            (BW, 11, 10),
+        ],
+    },
+    Bc6Mode {
+        header: (0b01111, 5),
+        subsets: 1,
+        transformed: true,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 15, 10),
+            (GX, 0, 3),
+            (GW, 15, 10),
+            (BX, 0, 3),
+            (BW, 15, 10),
+        ],
+    },
+];
+
+/// Decodes BC6H data to RGBA32F, `signed` selects the signed half float
+/// variant (BC6H_SF16) instead of the unsigned one
+pub fn decode_bc6h(data: &[u8], width: usize, height: usize, signed: bool) -> Result<Vec<f32>> {
+    decode_blocks(data, width, height, 4, 4, 16, |block, pixels| {
+        decode_bc6h_block(block, signed, pixels)
+    })
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else {
+        if bits >= 16 || value == 0 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    }
+}
+
+/// Scales an interpolated value to the final half float
+fn finish_unquantize(value: i32, signed: bool) -> f32 {
+    let half = if !signed {
+        (value * 31) >> 6
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5)
+    } else {
+        (value * 31) >> 5
+    };
+    half_to_f32(half as u16)
+}
+
+fn decode_bc6h_block(block: &[u8], signed: bool, pixels: &mut [[f32; 4]]) {
+    let header = if block[0] & 2 == 0 {
+        (u32::from(block[0] & 1), 2)
+    } else {
+        (u32::from(block[0] & 0x1f), 5)
+    };
+    let mode = match BC6_MODES.iter().find(|mode| mode.header == header) {
+        Some(mode) => mode,
+        None => {
+            // reserved modes decode to black
+            for pixel in pixels.iter_mut() {
+                *pixel = [0.0, 0.0, 0.0, 1.0];
+            }
+            return;
+        }
+    };
+
+    let mut reader = BitReader::new(block, header.1);
+    let mut endpoints = [0i32; 12];
+    for &(component, first, last) in mode.layout {
+        let mut position = first;
+        loop {
+            endpoints[component as usize] |= (reader.read(1) as i32) << position;
+            if position == last {
+                break;
+            }
+            if first < last {
+                position += 1;
+            } else {
+                position -= 1;
+            }
+        }
+    }
+    let partition = if mode.subsets == 2 {
+        reader.read(5) as usize
+    } else {
+        0
+    };
+
+    let components = mode.subsets * 6;
+    let bits = mode.endpoint_bits;
+    if signed {
+        for value in endpoints.iter_mut().take(3) {
+            *value = sign_extend(*value, bits);
+        }
+    }
+    for i in 3..components {
+        if mode.transformed {
+            // the other endpoints are stored as deltas to the first one
+            let delta = sign_extend(endpoints[i], mode.delta_bits[i % 3]);
+            endpoints[i] = (endpoints[i % 3] + delta) & ((1 << bits) - 1);
+        }
+        if signed {
+            endpoints[i] = sign_extend(endpoints[i], bits);
+        }
+    }
+    for value in endpoints.iter_mut().take(components) {
+        *value = unquantize(*value, bits, signed);
+    }
+
+    let index_bits = if mode.subsets == 2 { 3 } else { 4 };
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, i) as u32;
+        let w = weight(index_bits, reader.read(index_bits - anchor)) as i32;
+        let s = subset(mode.subsets, partition, i) * 6;
+        for c in 0..3 {
+            let (e0, e1) = (endpoints[s + c], endpoints[s + 3 + c]);
+            pixel[c] = finish_unquantize((e0 * (64 - w) + e1 * w + 32) >> 6, signed);
+        }
+        pixel[3] = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_bits(block: &mut [u8; 16], low: u32, count: u32, value: u32) {
+        let mut bits = u128::from_le_bytes(*block);
+        bits |= u128::from(value & ((1u64 << count) - 1) as u32) << low;
+        *block = bits.to_le_bytes();
+    }
+
+    #[test]
+    fn test_bc7_single_subset() {
+        // mode 6: white to transparent black, pixel i uses index i
+        let mut block = [0u8; 16];
+        set_bits(&mut block, 0, 7, 0x40);
+        for channel in 0..4 {
+            set_bits(&mut block, 7 + 14 * channel, 7, 0x7f);
+        }
+        set_bits(&mut block, 63, 1, 1);
+        for i in 1..16 {
+            set_bits(&mut block, 68 + 4 * (i - 1), 4, i);
+        }
+        let image = decode_bc7(&block, 4, 4).unwrap();
+        assert_eq!(&image[0..4], &[255, 255, 255, 255]);
+        assert_eq!(&image[4..8], &[239, 239, 239, 239]);
+        assert_eq!(&image[32..36], &[120, 120, 120, 120]);
+        assert_eq!(&image[60..64], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_bc7_rotation() {
+        // mode 5 with the alpha channel swapped into red
+        let mut block = [0u8; 16];
+        set_bits(&mut block, 0, 6, 0x20);
+        set_bits(&mut block, 6, 2, 1);
+        // red endpoints 0x7f and 0, alpha endpoints 0x40 and 0x40
+        set_bits(&mut block, 8, 7, 0x7f);
+        set_bits(&mut block, 50, 8, 0x40);
+        set_bits(&mut block, 58, 8, 0x40);
+        let image = decode_bc7(&block, 4, 4).unwrap();
+        assert_eq!(&image[0..4], &[0x40, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_bc7_partitions() {
+        // mode 1, partition 13 splits the block into the two lower rows and
+        // the two upper rows
+        let mut block = [0u8; 16];
+        set_bits(&mut block, 0, 2, 0b10);
+        set_bits(&mut block, 2, 6, 13);
+        // red of the second subset and its shared p-bit
+        set_bits(&mut block, 20, 6, 0x3f);
+        set_bits(&mut block, 26, 6, 0x3f);
+        set_bits(&mut block, 81, 1, 1);
+        let image = decode_bc7(&block, 4, 4).unwrap();
+        let red: Vec<u8> = image.chunks(4).map(|p| p[0]).collect();
+        assert_eq!(red[..8], [0; 8]);
+        assert_eq!(red[8..], [255; 8]);
+        assert_eq!(image[3], 255);
+
+        assert_eq!(decode_bc7(&[0; 16], 4, 4).unwrap(), vec![0; 64]);
+        assert!(decode_bc7(&[0x40; 15], 4, 4).is_err());
+    }
+
+    #[test]
+    fn test_bc6h_direct() {
+        // mode 11: black to the largest half float in red
+        let mut block = [0u8; 16];
+        set_bits(&mut block, 0, 5, 0b00011);
+        set_bits(&mut block, 35, 10, 0x3ff);
+        for i in 0..16 {
+            let (offset, bits) = if i == 0 { (65, 3) } else { (64 + 4 * i, 4) };
+            set_bits(&mut block, offset, bits, if i == 0 { 0 } else { i });
+        }
+        let image = decode_bc6h(&block, 4, 4, false).unwrap();
+        assert_eq!(&image[0..4], &[0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(image[8 * 4], 2.935_546_9);
+        assert_eq!(image[15 * 4], 65504.0);
+    }
+
+    #[test]
+    fn test_bc6h_transformed() {
+        // mode 12: the red base 1024 with a delta of -1
+        let mut block = [0u8; 16];
+        set_bits(&mut block, 0, 5, 0b00111);
+        set_bits(&mut block, 35, 9, 0x1ff);
+        set_bits(&mut block, 44, 1, 1);
+        let image = decode_bc6h(&block, 4, 4, false).unwrap();
+        assert_eq!(&image[0..4], &[1.506_835_9, 0.0, 0.0, 1.0]);
+
+        // reserved mode
+        let image = decode_bc6h(&[0b10011; 16], 4, 4, false).unwrap();
+        assert_eq!(&image[0..4], &[0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_bc6h_layouts() {
+        // every layout together with the mode, partition and index bits
+        // fills the whole block
+        for mode in BC6_MODES.iter() {
+            let endpoint_bits: u32 = mode
+                .layout
+                .iter()
+                .map(|&(_, first, last)| u32::from(first.max(last) - first.min(last)) + 1)
+                .sum();
+            let rest = if mode.subsets == 2 { 5 + 46 } else { 63 };
+            assert_eq!(mode.header.1 + endpoint_bits + rest, 128);
+        }
+    }
+}
@@ -0,0 +1,543 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Conversion of uncompressed and block compressed pixel data to RGBA8.
+
+use super::TextureFormat;
+use error::{Error, Result};
+
+/// Expands `bits` wide channel values to 8 bits
+pub fn expand(value: u32, bits: u32) -> u8 {
+    ((value << (8 - bits)) | (value >> (2 * bits - 8))) as u8
+}
+
+/// Converts a half precision float to single precision
+pub fn half_to_f32(half: u16) -> f32 {
+    let sign = u32::from(half >> 15) << 31;
+    let exponent = u32::from(half >> 10) & 0x1f;
+    let mantissa = u32::from(half) & 0x3ff;
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        // subnormals are normalized in single precision
+        (0, _) => {
+            let shift = mantissa.leading_zeros() - 21;
+            sign | (113 - shift) << 23 | (mantissa << shift & 0x3ff) << 13
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | mantissa << 13,
+        _ => sign | (exponent + 112) << 23 | mantissa << 13,
+    };
+    f32::from_bits(bits)
+}
+
+/// Converts a shared exponent RGB9E5 value to single precision floats
+pub fn rgb9e5_to_f32(value: u32) -> [f32; 3] {
+    let scale = 2f32.powi((value >> 27) as i32 - 24);
+    [
+        (value & 0x1ff) as f32 * scale,
+        ((value >> 9) & 0x1ff) as f32 * scale,
+        ((value >> 18) & 0x1ff) as f32 * scale,
+    ]
+}
+
+/// Converts a float in the [0, 1] range to 8 bits, values outside are clamped
+pub fn unorm_to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+fn read_u16(data: &[u8]) -> u32 {
+    u32::from(data[0]) | u32::from(data[1]) << 8
+}
+
+fn read_f32(data: &[u8]) -> f32 {
+    f32::from_bits(read_u16(data) | read_u16(&data[2..]) << 16)
+}
+
+/// Maps a signed normalized 8 bit value to 0-255 for display
+fn snorm8_to_u8(value: u8) -> u8 {
+    (i32::from(value as i8) + 0x80) as u8
+}
+
+/// Maps a signed normalized 16 bit value to 0-255 for display
+fn snorm16_to_u8(value: u32) -> u8 {
+    ((i32::from(value as u16 as i16) + 0x8000) >> 8) as u8
+}
+
+fn short_data_error(data: &[u8], pixels: usize, format: TextureFormat) -> Error {
+    Error::EngineError(format!(
+        "Image data of {} bytes is too short for {} pixels of {:?}",
+        data.len(),
+        pixels,
+        format
+    ))
+}
+
+/// Converts uncompressed 8 and 16 bit integer pixel data to RGBA8, the row
+/// order is kept as it is. Single and two channel formats fill red and green,
+/// signed formats are mapped from [-1, 1] to [0, 255].
+pub fn to_rgba8(format: TextureFormat, data: &[u8], pixels: usize) -> Result<Vec<u8>> {
+    let bytes_per_pixel = match format {
+        TextureFormat::Alpha8 | TextureFormat::R8 | TextureFormat::R8Signed => 1,
+        TextureFormat::ARGB4444
+        | TextureFormat::RGBA4444
+        | TextureFormat::RGB565
+        | TextureFormat::R16
+        | TextureFormat::RG16
+        | TextureFormat::R16Signed
+        | TextureFormat::RG16Signed
+        | TextureFormat::YUY2 => 2,
+        TextureFormat::RGB24 | TextureFormat::BGR24 | TextureFormat::RGB24Signed => 3,
+        TextureFormat::RGBA32
+        | TextureFormat::ARGB32
+        | TextureFormat::BGRA32
+        | TextureFormat::RG32
+        | TextureFormat::RGBA32Signed
+        | TextureFormat::RG32Signed => 4,
+        TextureFormat::RGB48 | TextureFormat::RGB48Signed => 6,
+        TextureFormat::RGBA64 | TextureFormat::RGBA64Signed => 8,
+        _ => {
+            return Err(Error::EngineError(format!(
+                "{:?} is not an uncompressed format",
+                format
+            )))
+        }
+    };
+    // YUY2 stores pixels in pairs, an odd last pixel takes a whole group
+    let size = match format {
+        TextureFormat::YUY2 => pixels.div_ceil(2) * 4,
+        _ => pixels * bytes_per_pixel,
+    };
+    if data.len() < size {
+        return Err(short_data_error(data, pixels, format));
+    }
+    if format == TextureFormat::YUY2 {
+        return Ok(yuy2_to_rgba8(data, pixels));
+    }
+
+    let mut result = Vec::with_capacity(pixels * 4);
+    for pixel in data.chunks(bytes_per_pixel).take(pixels) {
+        let rgba = match format {
+            TextureFormat::Alpha8 => [0xff, 0xff, 0xff, pixel[0]],
+            TextureFormat::ARGB4444 => {
+                let p = read_u16(pixel);
+                [
+                    expand((p >> 8) & 0xf, 4),
+                    expand((p >> 4) & 0xf, 4),
+                    expand(p & 0xf, 4),
+                    expand(p >> 12, 4),
+                ]
+            }
+            TextureFormat::RGBA4444 => {
+                let p = read_u16(pixel);
+                [
+                    expand(p >> 12, 4),
+                    expand((p >> 8) & 0xf, 4),
+                    expand((p >> 4) & 0xf, 4),
+                    expand(p & 0xf, 4),
+                ]
+            }
+            TextureFormat::RGB565 => {
+                let p = read_u16(pixel);
+                [
+                    expand(p >> 11, 5),
+                    expand((p >> 5) & 0x3f, 6),
+                    expand(p & 0x1f, 5),
+                    0xff,
+                ]
+            }
+            TextureFormat::R8 => [pixel[0], 0, 0, 0xff],
+            TextureFormat::RG16 => [pixel[0], pixel[1], 0, 0xff],
+            TextureFormat::RGB24 => [pixel[0], pixel[1], pixel[2], 0xff],
+            TextureFormat::BGR24 => [pixel[2], pixel[1], pixel[0], 0xff],
+            TextureFormat::ARGB32 => [pixel[1], pixel[2], pixel[3], pixel[0]],
+            TextureFormat::BGRA32 => [pixel[2], pixel[1], pixel[0], pixel[3]],
+            TextureFormat::R16 => [pixel[1], 0, 0, 0xff],
+            TextureFormat::RG32 => [pixel[1], pixel[3], 0, 0xff],
+            TextureFormat::RGB48 => [pixel[1], pixel[3], pixel[5], 0xff],
+            TextureFormat::RGBA64 => [pixel[1], pixel[3], pixel[5], pixel[7]],
+            TextureFormat::R8Signed => [snorm8_to_u8(pixel[0]), 0, 0, 0xff],
+            TextureFormat::RG16Signed => [snorm8_to_u8(pixel[0]), snorm8_to_u8(pixel[1]), 0, 0xff],
+            TextureFormat::RGB24Signed => [
+                snorm8_to_u8(pixel[0]),
+                snorm8_to_u8(pixel[1]),
+                snorm8_to_u8(pixel[2]),
+                0xff,
+            ],
+            TextureFormat::RGBA32Signed => [
+                snorm8_to_u8(pixel[0]),
+                snorm8_to_u8(pixel[1]),
+                snorm8_to_u8(pixel[2]),
+                snorm8_to_u8(pixel[3]),
+            ],
+            TextureFormat::R16Signed => [snorm16_to_u8(read_u16(pixel)), 0, 0, 0xff],
+            TextureFormat::RG32Signed => [
+                snorm16_to_u8(read_u16(pixel)),
+                snorm16_to_u8(read_u16(&pixel[2..])),
+                0,
+                0xff,
+            ],
+            TextureFormat::RGB48Signed => [
+                snorm16_to_u8(read_u16(pixel)),
+                snorm16_to_u8(read_u16(&pixel[2..])),
+                snorm16_to_u8(read_u16(&pixel[4..])),
+                0xff,
+            ],
+            TextureFormat::RGBA64Signed => [
+                snorm16_to_u8(read_u16(pixel)),
+                snorm16_to_u8(read_u16(&pixel[2..])),
+                snorm16_to_u8(read_u16(&pixel[4..])),
+                snorm16_to_u8(read_u16(&pixel[6..])),
+            ],
+            _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+        };
+        result.extend_from_slice(&rgba);
+    }
+    Ok(result)
+}
+
+/// Converts YUY2 (YUV 4:2:2) data using the BT.601 limited range
+/// coefficients, each 4 byte group holds two pixels sharing U and V
+fn yuy2_to_rgba8(data: &[u8], pixels: usize) -> Vec<u8> {
+    let mut result = Vec::with_capacity(pixels * 4 + 4);
+    for group in data.chunks(4).take(pixels.div_ceil(2)) {
+        let d = i32::from(group[1]) - 128;
+        let e = i32::from(group[3]) - 128;
+        for &y in &[group[0], group[2]] {
+            let c = 298 * (i32::from(y) - 16) + 128;
+            result.extend_from_slice(&[
+                ((c + 409 * e) >> 8).clamp(0, 255) as u8,
+                ((c - 100 * d - 208 * e) >> 8).clamp(0, 255) as u8,
+                ((c + 516 * d) >> 8).clamp(0, 255) as u8,
+                0xff,
+            ]);
+        }
+    }
+    result.truncate(pixels * 4);
+    result
+}
+
+/// Converts uncompressed floating point and 16 bit integer pixel data to
+/// RGBA32F, the row order is kept as it is. Signed formats keep their
+/// [-1, 1] range.
+pub fn to_rgba_f32(format: TextureFormat, data: &[u8], pixels: usize) -> Result<Vec<f32>> {
+    let bytes_per_pixel = match format {
+        TextureFormat::R8Signed => 1,
+        TextureFormat::RHalf
+        | TextureFormat::R16
+        | TextureFormat::R16Signed
+        | TextureFormat::RG16Signed => 2,
+        TextureFormat::RGB24Signed => 3,
+        TextureFormat::RGHalf
+        | TextureFormat::RFloat
+        | TextureFormat::RGB9e5Float
+        | TextureFormat::RG32
+        | TextureFormat::RG32Signed
+        | TextureFormat::RGBA32Signed => 4,
+        TextureFormat::RGB48 | TextureFormat::RGB48Signed => 6,
+        TextureFormat::RGBAHalf
+        | TextureFormat::RGFloat
+        | TextureFormat::RGBA64
+        | TextureFormat::RGBA64Signed => 8,
+        TextureFormat::RGBFloat => 12,
+        TextureFormat::RGBAFloat | TextureFormat::ARGBFloat => 16,
+        _ => {
+            return Err(Error::EngineError(format!(
+                "{:?} is not an uncompressed floating point format",
+                format
+            )))
+        }
+    };
+    if data.len() < pixels * bytes_per_pixel {
+        return Err(short_data_error(data, pixels, format));
+    }
+
+    let half = |offset: usize, pixel: &[u8]| half_to_f32(read_u16(&pixel[offset..]) as u16);
+    let float = |offset: usize, pixel: &[u8]| read_f32(&pixel[offset..]);
+    let unorm16 = |offset: usize, pixel: &[u8]| read_u16(&pixel[offset..]) as f32 / 65535.0;
+    let snorm8 = |offset: usize, pixel: &[u8]| (f32::from(pixel[offset] as i8) / 127.0).max(-1.0);
+    let snorm16 = |offset: usize, pixel: &[u8]| {
+        (f32::from(read_u16(&pixel[offset..]) as u16 as i16) / 32767.0).max(-1.0)
+    };
+
+    let mut result = Vec::with_capacity(pixels * 4);
+    for pixel in data.chunks(bytes_per_pixel).take(pixels) {
+        let rgba = match format {
+            TextureFormat::RHalf => [half(0, pixel), 0.0, 0.0, 1.0],
+            TextureFormat::RGHalf => [half(0, pixel), half(2, pixel), 0.0, 1.0],
+            TextureFormat::RGBAHalf => [
+                half(0, pixel),
+                half(2, pixel),
+                half(4, pixel),
+                half(6, pixel),
+            ],
+            TextureFormat::RFloat => [float(0, pixel), 0.0, 0.0, 1.0],
+            TextureFormat::RGFloat => [float(0, pixel), float(4, pixel), 0.0, 1.0],
+            TextureFormat::RGBFloat => [float(0, pixel), float(4, pixel), float(8, pixel), 1.0],
+            TextureFormat::RGBAFloat => [
+                float(0, pixel),
+                float(4, pixel),
+                float(8, pixel),
+                float(12, pixel),
+            ],
+            TextureFormat::ARGBFloat => [
+                float(4, pixel),
+                float(8, pixel),
+                float(12, pixel),
+                float(0, pixel),
+            ],
+            TextureFormat::RGB9e5Float => {
+                let [r, g, b] = rgb9e5_to_f32(read_u16(pixel) | read_u16(&pixel[2..]) << 16);
+                [r, g, b, 1.0]
+            }
+            TextureFormat::R16 => [unorm16(0, pixel), 0.0, 0.0, 1.0],
+            TextureFormat::RG32 => [unorm16(0, pixel), unorm16(2, pixel), 0.0, 1.0],
+            TextureFormat::RGB48 => [unorm16(0, pixel), unorm16(2, pixel), unorm16(4, pixel), 1.0],
+            TextureFormat::RGBA64 => [
+                unorm16(0, pixel),
+                unorm16(2, pixel),
+                unorm16(4, pixel),
+                unorm16(6, pixel),
+            ],
+            TextureFormat::R8Signed => [snorm8(0, pixel), 0.0, 0.0, 1.0],
+            TextureFormat::RG16Signed => [snorm8(0, pixel), snorm8(1, pixel), 0.0, 1.0],
+            TextureFormat::RGB24Signed => {
+                [snorm8(0, pixel), snorm8(1, pixel), snorm8(2, pixel), 1.0]
+            }
+            TextureFormat::RGBA32Signed => [
+                snorm8(0, pixel),
+                snorm8(1, pixel),
+                snorm8(2, pixel),
+                snorm8(3, pixel),
+            ],
+            TextureFormat::R16Signed => [snorm16(0, pixel), 0.0, 0.0, 1.0],
+            TextureFormat::RG32Signed => [snorm16(0, pixel), snorm16(2, pixel), 0.0, 1.0],
+            TextureFormat::RGB48Signed => {
+                [snorm16(0, pixel), snorm16(2, pixel), snorm16(4, pixel), 1.0]
+            }
+            _ => [
+                snorm16(0, pixel),
+                snorm16(2, pixel),
+                snorm16(4, pixel),
+                snorm16(6, pixel),
+            ],
+        };
+        result.extend_from_slice(&rgba);
+    }
+    Ok(result)
+}
+
+/// Converts RGBA32F pixels to RGBA8, clamping every channel to [0, 1]
+pub fn f32_to_rgba8(data: &[f32]) -> Vec<u8> {
+    data.iter().map(|&value| unorm_to_u8(value)).collect()
+}
+
+/// Converts RGBA8 pixels to RGBA32F
+pub fn rgba8_to_f32(data: &[u8]) -> Vec<f32> {
+    data.iter().map(|&value| f32::from(value) / 255.0).collect()
+}
+
+/// Decodes block compressed data to four channel pixels. `decode_block`
+/// receives the bytes of one block and fills its pixels in row-major order,
+/// blocks sticking out of the image are cropped.
+pub fn decode_blocks<T, F>(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+    block_size: usize,
+    mut decode_block: F,
+) -> Result<Vec<T>>
+where
+    T: Copy + Default,
+    F: FnMut(&[u8], &mut [[T; 4]]),
+{
+    let blocks_x = width.div_ceil(block_width);
+    let blocks_y = height.div_ceil(block_height);
+    if data.len() < blocks_x * blocks_y * block_size {
+        return Err(Error::EngineError(format!(
+            "Image data of {} bytes is too short for {}x{} blocks",
+            data.len(),
+            blocks_x,
+            blocks_y
+        )));
+    }
+
+    let mut result = vec![T::default(); width * height * 4];
+    let mut pixels = vec![[T::default(); 4]; block_width * block_height];
+    for (i, block) in data
+        .chunks(block_size)
+        .take(blocks_x * blocks_y)
+        .enumerate()
+    {
+        decode_block(block, &mut pixels);
+        let block_x = (i % blocks_x) * block_width;
+        let block_y = (i / blocks_x) * block_height;
+        for y in 0..block_height.min(height - block_y) {
+            for x in 0..block_width.min(width - block_x) {
+                let offset = ((block_y + y) * width + block_x + x) * 4;
+                result[offset..offset + 4].copy_from_slice(&pixels[y * block_width + x]);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Expands single channel data (e.g. decoded BC4) to grayscale RGBA8
+pub fn luminance_to_rgba8(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len() * 4);
+    for &l in data {
+        result.extend_from_slice(&[l, l, l, 0xff]);
+    }
+    result
+}
+
+/// Reverses the row order of an image. Unity stores images bottom-up, image
+/// files are usually stored top-down.
+pub fn flip_vertical<T: Copy>(
+    data: &[T],
+    width: usize,
+    height: usize,
+    values_per_pixel: usize,
+) -> Vec<T> {
+    let stride = width * values_per_pixel;
+    let mut result = Vec::with_capacity(data.len());
+    for row in data.chunks(stride).take(height).rev() {
+        result.extend_from_slice(row);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rgba8() {
+        // 0xA123: A=a, R=1, G=2, B=3
+        assert_eq!(
+            to_rgba8(TextureFormat::ARGB4444, &[0x23, 0xa1], 1).unwrap(),
+            vec![0x11, 0x22, 0x33, 0xaa]
+        );
+        assert_eq!(
+            to_rgba8(TextureFormat::RGBA4444, &[0x3a, 0x12], 1).unwrap(),
+            vec![0x11, 0x22, 0x33, 0xaa]
+        );
+        // pure red and pure green
+        assert_eq!(
+            to_rgba8(TextureFormat::RGB565, &[0x00, 0xf8, 0xe0, 0x07], 2).unwrap(),
+            vec![0xff, 0, 0, 0xff, 0, 0xff, 0, 0xff]
+        );
+        assert_eq!(
+            to_rgba8(TextureFormat::ARGB32, &[4, 1, 2, 3], 1).unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            to_rgba8(TextureFormat::BGRA32, &[3, 2, 1, 4], 1).unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            to_rgba8(TextureFormat::RGB24, &[1, 2, 3], 1).unwrap(),
+            vec![1, 2, 3, 0xff]
+        );
+        assert!(to_rgba8(TextureFormat::RGB24, &[1, 2], 1).is_err());
+        assert!(to_rgba8(TextureFormat::DXT1, &[0; 8], 16).is_err());
+
+        assert_eq!(
+            to_rgba8(TextureFormat::BGR24, &[3, 2, 1], 1).unwrap(),
+            vec![1, 2, 3, 0xff]
+        );
+        assert_eq!(
+            to_rgba8(TextureFormat::RG16, &[1, 2], 1).unwrap(),
+            vec![1, 2, 0, 0xff]
+        );
+        // the high bytes of 16 bit channels
+        assert_eq!(
+            to_rgba8(TextureFormat::RGBA64, &[0, 1, 0, 2, 0, 3, 0, 4], 1).unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        // -1, 0 and 1
+        assert_eq!(
+            to_rgba8(TextureFormat::RGB24Signed, &[0x81, 0, 0x7f], 1).unwrap(),
+            vec![1, 0x80, 0xff, 0xff]
+        );
+        assert_eq!(
+            to_rgba8(TextureFormat::R16Signed, &[0x01, 0x80], 1).unwrap(),
+            vec![0, 0, 0, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_yuy2() {
+        // black and white sharing neutral chroma
+        assert_eq!(
+            to_rgba8(TextureFormat::YUY2, &[16, 128, 235, 128], 2).unwrap(),
+            vec![0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        // pure red is Y=81, U=90, V=240
+        let red = to_rgba8(TextureFormat::YUY2, &[81, 90, 81, 240], 1).unwrap();
+        assert_eq!(red, vec![255, 0, 0, 0xff]);
+        assert!(to_rgba8(TextureFormat::YUY2, &[81, 90, 81], 1).is_err());
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x0200), 2f32.powi(-15));
+        assert_eq!(half_to_f32(0x7c00), f32::INFINITY);
+        assert!(half_to_f32(0x7e00).is_nan());
+
+        // mantissas 1, 2 and 256 with the exponent bias of 15 + 9
+        let value = 1 | 2 << 9 | 256 << 18 | 15 << 27;
+        assert_eq!(rgb9e5_to_f32(value), [1.0 / 512.0, 2.0 / 512.0, 0.5]);
+
+        let data = [0x00, 0x3c, 0x00, 0xb8];
+        assert_eq!(
+            to_rgba_f32(TextureFormat::RGHalf, &data, 1).unwrap(),
+            vec![1.0, -0.5, 0.0, 1.0]
+        );
+        let mut data = Vec::new();
+        for value in &[4.0f32, 1.0, 2.0, 3.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(
+            to_rgba_f32(TextureFormat::ARGBFloat, &data, 1).unwrap(),
+            vec![1.0, 2.0, 3.0, 4.0]
+        );
+        assert_eq!(
+            to_rgba_f32(TextureFormat::RG32Signed, &[0x00, 0x80, 0xff, 0x7f], 1).unwrap(),
+            vec![-1.0, 1.0, 0.0, 1.0]
+        );
+        assert!(to_rgba_f32(TextureFormat::RFloat, &[0; 3], 1).is_err());
+        assert!(to_rgba_f32(TextureFormat::RGBA32, &[0; 4], 1).is_err());
+
+        assert_eq!(f32_to_rgba8(&[-1.0, 0.5, 2.0, 1.0]), vec![0, 128, 255, 255]);
+    }
+
+    #[test]
+    fn test_decode_blocks() {
+        // 2x2 blocks of a 3x3 image, every pixel holds its block index
+        let data = [0, 1, 2, 3];
+        let image = decode_blocks(&data, 3, 3, 2, 2, 1, |block, pixels| {
+            for pixel in pixels.iter_mut() {
+                *pixel = [block[0]; 4];
+            }
+        })
+        .unwrap();
+        let red: Vec<u8> = image.chunks(4).map(|p| p[0]).collect();
+        assert_eq!(red, vec![0, 0, 1, 0, 0, 1, 2, 2, 3]);
+        assert!(decode_blocks::<u8, _>(&data[..3], 3, 3, 2, 2, 1, |_, _| ()).is_err());
+    }
+
+    #[test]
+    fn test_flip_vertical() {
+        let data = [1, 2, 3, 4, 5, 6];
+        assert_eq!(flip_vertical(&data, 2, 3, 1), vec![5, 6, 3, 4, 1, 2]);
+    }
+}
@@ -0,0 +1,1154 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Decoder for the crunch variant Unity ships since 2017.3. It shares the
+//! file header, the Huffman coding and most of the codebooks with the
+//! original crunch format, but codes the endpoints of a block as references
+//! to its neighbours instead of in 2x2 block chunks and adds ETC formats.
+//! Textures crunched by older versions are unpacked by the crunch library
+//! `decrunch` links, cubemaps through its C interface as `decrunch` only
+//! unpacks the first face.
+
+use super::{Texture2D, TextureFormat};
+use decrunch::CrunchedData;
+use error::{Error, Result};
+use libc::c_void;
+use version::UnityVersion;
+
+const SIGNATURE: u32 = 0x4878;
+const HEADER_SIZE: usize = 70;
+
+const FORMAT_DXT1: u8 = 0;
+const FORMAT_DXT5: u8 = 2;
+const FORMAT_DXT5_AGBR: u8 = 6;
+const FORMAT_ETC1: u8 = 10;
+const FORMAT_ETC2: u8 = 11;
+const FORMAT_ETC2A: u8 = 12;
+const FORMAT_ETC1S: u8 = 13;
+const FORMAT_ETC2AS: u8 = 14;
+/// Only used by the original crunch format
+const FORMAT_DXT5A: u8 = 9;
+
+const MAX_SYMBOLS: u32 = 8192;
+const MAX_CODE_SIZE: usize = 16;
+
+/// Order in which the code sizes of code length codes are sent
+const CODE_LENGTH_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+/// Selectors are coded in ascending order of the colors they pick
+const DXT1_FROM_LINEAR: [u32; 4] = [0, 2, 3, 1];
+const DXT5_FROM_LINEAR: [u64; 8] = [0, 2, 3, 4, 5, 6, 7, 1];
+const ETC1_FROM_LINEAR: [u32; 4] = [3, 2, 0, 1];
+const ETC2A_FROM_LINEAR: [u64; 8] = [3, 2, 1, 0, 4, 5, 6, 7];
+
+fn crunch_error(message: &str) -> Error {
+    Error::EngineError(format!("Invalid crunched data: {}", message))
+}
+
+/// Reads big-endian numbers of `size` bytes from the header
+fn read_packed(data: &[u8], offset: usize, size: usize) -> u32 {
+    data[offset..offset + size]
+        .iter()
+        .fold(0, |value, byte| value << 8 | u32::from(*byte))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Palette {
+    offset: usize,
+    size: usize,
+    count: usize,
+}
+
+impl Palette {
+    fn read(data: &[u8], offset: usize) -> Palette {
+        Palette {
+            offset: read_packed(data, offset, 3) as usize,
+            size: read_packed(data, offset + 3, 3) as usize,
+            count: read_packed(data, offset + 6, 2) as usize,
+        }
+    }
+
+    fn data<'a>(&self, data: &'a [u8]) -> Result<&'a [u8]> {
+        match data.get(self.offset..self.offset + self.size) {
+            Some(data) if !data.is_empty() => Ok(data),
+            _ => Err(crunch_error("palette out of bounds")),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Header {
+    width: u32,
+    height: u32,
+    levels: u32,
+    faces: u32,
+    format: u8,
+    color_endpoints: Palette,
+    color_selectors: Palette,
+    alpha_endpoints: Palette,
+    alpha_selectors: Palette,
+    tables_size: usize,
+    tables_offset: usize,
+    level_offsets: Vec<usize>,
+}
+
+impl Header {
+    fn read(data: &[u8]) -> Result<Header> {
+        if data.len() < HEADER_SIZE + 4 || read_packed(data, 0, 2) != SIGNATURE {
+            return Err(crunch_error("missing header"));
+        }
+        let levels = u32::from(data[16]);
+        if levels == 0 || data.len() < HEADER_SIZE + 4 * levels as usize {
+            return Err(crunch_error("missing mip levels"));
+        }
+        Ok(Header {
+            width: read_packed(data, 12, 2),
+            height: read_packed(data, 14, 2),
+            levels,
+            faces: u32::from(data[17]),
+            format: data[18],
+            color_endpoints: Palette::read(data, 33),
+            color_selectors: Palette::read(data, 41),
+            alpha_endpoints: Palette::read(data, 49),
+            alpha_selectors: Palette::read(data, 57),
+            tables_size: read_packed(data, 65, 2) as usize,
+            tables_offset: read_packed(data, 67, 3) as usize,
+            level_offsets: (0..levels as usize)
+                .map(|level| read_packed(data, HEADER_SIZE + 4 * level, 4) as usize)
+                .collect(),
+        })
+    }
+
+    fn has_etc_blocks(&self) -> bool {
+        self.format >= FORMAT_ETC1 && self.format <= FORMAT_ETC2AS
+    }
+
+    /// Whether ETC blocks have two endpoints, one for each half
+    fn has_subblocks(&self) -> bool {
+        matches!(self.format, FORMAT_ETC1 | FORMAT_ETC2 | FORMAT_ETC2A)
+    }
+
+    fn has_alpha(&self) -> bool {
+        self.alpha_endpoints.count > 0
+    }
+}
+
+/// Canonical Huffman code, decoded most significant bit first
+struct Model {
+    /// First code and number of codes of each length
+    first_codes: [u32; MAX_CODE_SIZE + 1],
+    counts: [u32; MAX_CODE_SIZE + 1],
+    /// Index of the first symbol of each length in `symbols`
+    offsets: [u32; MAX_CODE_SIZE + 1],
+    /// Symbols sorted by code length
+    symbols: Vec<u16>,
+}
+
+impl Model {
+    fn new(code_sizes: &[u8]) -> Result<Model> {
+        let mut counts = [0; MAX_CODE_SIZE + 1];
+        for size in code_sizes {
+            if *size as usize > MAX_CODE_SIZE {
+                return Err(crunch_error("code size out of range"));
+            }
+            counts[*size as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut first_codes = [0; MAX_CODE_SIZE + 1];
+        let mut offsets = [0; MAX_CODE_SIZE + 1];
+        let (mut code, mut offset) = (0, 0);
+        for size in 1..=MAX_CODE_SIZE {
+            first_codes[size] = code;
+            offsets[size] = offset;
+            code = (code + counts[size]) << 1;
+            offset += counts[size];
+        }
+
+        let mut symbols = vec![0; offset as usize];
+        let mut positions = offsets;
+        for (symbol, size) in code_sizes.iter().enumerate() {
+            if *size > 0 {
+                symbols[positions[*size as usize] as usize] = symbol as u16;
+                positions[*size as usize] += 1;
+            }
+        }
+        Ok(Model {
+            first_codes,
+            counts,
+            offsets,
+            symbols,
+        })
+    }
+}
+
+/// Reads the bit streams of the header tables, the codebooks and the levels
+struct Codec<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit_buffer: u64,
+    bit_count: u32,
+}
+
+impl<'a> Codec<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Codec {
+            data,
+            position: 0,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Makes at least `count` bits available, reading zeros past the end
+    fn fill(&mut self, count: u32) {
+        while self.bit_count < count {
+            let byte = self.data.get(self.position).cloned().unwrap_or(0);
+            self.position += 1;
+            self.bit_buffer = self.bit_buffer << 8 | u64::from(byte);
+            self.bit_count += 8;
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        self.fill(count);
+        self.bit_count -= count;
+        ((self.bit_buffer >> self.bit_count) & ((1 << count) - 1)) as u32
+    }
+
+    fn decode(&mut self, model: &Model) -> Result<u32> {
+        self.fill(MAX_CODE_SIZE as u32);
+        let peek = self.bit_buffer >> (self.bit_count - MAX_CODE_SIZE as u32);
+        for size in 1..=MAX_CODE_SIZE {
+            let code = ((peek >> (MAX_CODE_SIZE - size)) & ((1 << size) - 1)) as u32;
+            let first = model.first_codes[size];
+            if code >= first && code - first < model.counts[size] {
+                self.bit_count -= size as u32;
+                let index = model.offsets[size] + code - first;
+                return Ok(u32::from(model.symbols[index as usize]));
+            }
+        }
+        Err(crunch_error("invalid Huffman code"))
+    }
+
+    /// Reads the code sizes of a Huffman code, themselves Huffman coded
+    /// with runs of zeros and repeats
+    fn receive_model(&mut self) -> Result<Model> {
+        let total = self.bits(32 - MAX_SYMBOLS.leading_zeros()) as usize;
+        if total == 0 {
+            return Model::new(&[]);
+        }
+        let code_length_codes = self.bits(5) as usize;
+        if code_length_codes < 1 || code_length_codes > CODE_LENGTH_ORDER.len() {
+            return Err(crunch_error("invalid code length codes"));
+        }
+        let mut code_length_sizes = [0; 21];
+        for index in CODE_LENGTH_ORDER.iter().take(code_length_codes) {
+            code_length_sizes[*index] = self.bits(3) as u8;
+        }
+        let code_lengths = Model::new(&code_length_sizes)?;
+
+        let mut sizes = vec![0u8; total];
+        let mut offset = 0;
+        while offset < total {
+            let remaining = total - offset;
+            let code = self.decode(&code_lengths)?;
+            let (length, value) = match code {
+                0..=16 => (1, code as u8),
+                17 => (self.bits(3) as usize + 3, 0),
+                18 => (self.bits(7) as usize + 11, 0),
+                19 | 20 => {
+                    let length = if code == 19 {
+                        self.bits(2) as usize + 3
+                    } else {
+                        self.bits(6) as usize + 7
+                    };
+                    match offset.checked_sub(1).map(|last| sizes[last]) {
+                        Some(previous) if previous > 0 => (length, previous),
+                        _ => return Err(crunch_error("repeat without a code size")),
+                    }
+                }
+                _ => return Err(crunch_error("invalid code length code")),
+            };
+            if length > remaining {
+                return Err(crunch_error("code sizes overflow"));
+            }
+            for size in &mut sizes[offset..offset + length] {
+                *size = value;
+            }
+            offset += length;
+        }
+        Model::new(&sizes)
+    }
+}
+
+/// Codebooks and Huffman codes shared by every level of a crunched texture
+struct Unpacker<'a> {
+    data: &'a [u8],
+    header: Header,
+    reference_model: Model,
+    endpoint_models: Vec<Model>,
+    selector_models: Vec<Model>,
+    /// Color endpoints as the first four bytes of a DXT1 or ETC1 block
+    color_endpoints: Vec<[u8; 4]>,
+    /// Color selectors as the last four bytes of a DXT1 or ETC1 block. ETC
+    /// blocks with halves store a transposed and a regular copy.
+    color_selectors: Vec<[u8; 4]>,
+    alpha_endpoints: Vec<[u8; 2]>,
+    /// Alpha selectors as the last six bytes of a DXT5 alpha or EAC block,
+    /// stored like the color selectors
+    alpha_selectors: Vec<[u8; 6]>,
+}
+
+impl<'a> Unpacker<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let header = Header::read(data)?;
+        if header.color_endpoints.count == 0 && !header.has_alpha() {
+            return Err(crunch_error("no endpoints"));
+        }
+        let tables = match data.get(header.tables_offset..header.tables_offset + header.tables_size)
+        {
+            Some(tables) => tables,
+            None => return Err(crunch_error("tables out of bounds")),
+        };
+        let mut codec = Codec::new(tables);
+        let reference_model = codec.receive_model()?;
+        let mut endpoint_models = Vec::new();
+        let mut selector_models = Vec::new();
+        if header.color_endpoints.count > 0 {
+            endpoint_models.push(codec.receive_model()?);
+            selector_models.push(codec.receive_model()?);
+        } else {
+            endpoint_models.push(Model::new(&[])?);
+            selector_models.push(Model::new(&[])?);
+        }
+        if header.has_alpha() {
+            endpoint_models.push(codec.receive_model()?);
+            selector_models.push(codec.receive_model()?);
+        }
+
+        let mut unpacker = Unpacker {
+            data,
+            header,
+            reference_model,
+            endpoint_models,
+            selector_models,
+            color_endpoints: Vec::new(),
+            color_selectors: Vec::new(),
+            alpha_endpoints: Vec::new(),
+            alpha_selectors: Vec::new(),
+        };
+        if unpacker.header.color_endpoints.count > 0 {
+            unpacker.decode_color_endpoints()?;
+            unpacker.decode_color_selectors()?;
+        }
+        if unpacker.header.has_alpha() {
+            unpacker.decode_alpha_endpoints()?;
+            unpacker.decode_alpha_selectors()?;
+        }
+        Ok(unpacker)
+    }
+
+    fn decode_color_endpoints(&mut self) -> Result<()> {
+        let palette = self.header.color_endpoints;
+        let mut codec = Codec::new(palette.data(self.data)?);
+        let etc = self.header.has_etc_blocks();
+        let models = if etc {
+            vec![codec.receive_model()?]
+        } else {
+            vec![codec.receive_model()?, codec.receive_model()?]
+        };
+
+        let mut values = [0u32; 6];
+        for _ in 0..palette.count {
+            if etc {
+                // red, green, blue and the intensity table, each delta coded
+                let mut delta = 0u32;
+                for byte in 0..4 {
+                    delta |= codec.decode(&models[0])? << (8 * byte);
+                }
+                values[0] = values[0].wrapping_add(delta) & 0x1f1f_1f1f;
+                let endpoint = values[0];
+                let endpoint = if self.header.has_subblocks() {
+                    endpoint
+                } else {
+                    // a differential block without deltas
+                    (endpoint & 0x0700_0000) << 5
+                        | (endpoint & 0x0700_0000) << 2
+                        | 0x0200_0000
+                        | (endpoint & 0x001f_1f1f) << 3
+                };
+                self.color_endpoints.push(endpoint.to_le_bytes());
+            } else {
+                // two RGB565 colors, green is coded with its own model
+                for (i, value) in values.iter_mut().enumerate() {
+                    let (model, mask) = if i % 3 == 1 { (1, 63) } else { (0, 31) };
+                    *value = (*value + codec.decode(&models[model])?) & mask;
+                }
+                let color0 = values[2] | values[1] << 5 | values[0] << 11;
+                let color1 = values[5] | values[4] << 5 | values[3] << 11;
+                self.color_endpoints
+                    .push((color0 | color1 << 16).to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_color_selectors(&mut self) -> Result<()> {
+        let palette = self.header.color_selectors;
+        let mut codec = Codec::new(palette.data(self.data)?);
+        let model = codec.receive_model()?;
+        let etc = self.header.has_etc_blocks();
+        let subblocks = self.header.has_subblocks();
+
+        // each symbol holds two 2 bit selectors, xored with the previous
+        // selectors of the codebook
+        let mut linear = 0u32;
+        for _ in 0..palette.count {
+            for shift in (0..32).step_by(4) {
+                linear ^= codec.decode(&model)? << shift;
+            }
+            let selector = |x: usize, y: usize| (linear >> (2 * (4 * y + x))) & 3;
+            if etc {
+                // ETC pixels are stored column by column, most significant
+                // bits first
+                let etc_selectors = |transposed: bool| {
+                    let mut value = 0u32;
+                    for x in 0..4 {
+                        for y in 0..4 {
+                            let index = if transposed {
+                                selector(y, x)
+                            } else {
+                                selector(x, y)
+                            };
+                            let etc = ETC1_FROM_LINEAR[index as usize];
+                            let pixel = 4 * x + y;
+                            value |= (etc >> 1) << (16 + pixel) | (etc & 1) << pixel;
+                        }
+                    }
+                    value.to_be_bytes()
+                };
+                if subblocks {
+                    self.color_selectors.push(etc_selectors(true));
+                }
+                self.color_selectors.push(etc_selectors(false));
+            } else {
+                let mut value = 0u32;
+                for pixel in 0..16 {
+                    value |=
+                        DXT1_FROM_LINEAR[selector(pixel % 4, pixel / 4) as usize] << (2 * pixel);
+                }
+                self.color_selectors.push(value.to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_alpha_endpoints(&mut self) -> Result<()> {
+        let palette = self.header.alpha_endpoints;
+        let mut codec = Codec::new(palette.data(self.data)?);
+        let model = codec.receive_model()?;
+        let (mut a, mut b) = (0, 0);
+        for _ in 0..palette.count {
+            a = (a + codec.decode(&model)?) & 0xff;
+            b = (b + codec.decode(&model)?) & 0xff;
+            self.alpha_endpoints.push([a as u8, b as u8]);
+        }
+        Ok(())
+    }
+
+    fn decode_alpha_selectors(&mut self) -> Result<()> {
+        let palette = self.header.alpha_selectors;
+        let mut codec = Codec::new(palette.data(self.data)?);
+        let model = codec.receive_model()?;
+        let etc = self.header.has_etc_blocks();
+        let subblocks = self.header.has_subblocks();
+
+        // each symbol holds two 3 bit selectors
+        let mut linear = 0u64;
+        for _ in 0..palette.count {
+            for shift in (0..48).step_by(6) {
+                linear ^= u64::from(codec.decode(&model)?) << shift;
+            }
+            let selector = |x: usize, y: usize| ((linear >> (3 * (4 * y + x))) & 7) as usize;
+            if etc {
+                // EAC pixels are stored column by column from the most
+                // significant bits of a 48 bit number
+                let eac_selectors = |transposed: bool| {
+                    let mut value = 0u64;
+                    for x in 0..4 {
+                        for y in 0..4 {
+                            let index = if transposed {
+                                selector(y, x)
+                            } else {
+                                selector(x, y)
+                            };
+                            value |= ETC2A_FROM_LINEAR[index] << (45 - 3 * (4 * x + y));
+                        }
+                    }
+                    let mut bytes = [0; 6];
+                    bytes.copy_from_slice(&value.to_be_bytes()[2..]);
+                    bytes
+                };
+                if subblocks {
+                    self.alpha_selectors.push(eac_selectors(true));
+                }
+                self.alpha_selectors.push(eac_selectors(false));
+            } else {
+                let mut value = 0u64;
+                for pixel in 0..16 {
+                    value |= DXT5_FROM_LINEAR[selector(pixel % 4, pixel / 4)] << (3 * pixel);
+                }
+                let mut bytes = [0; 6];
+                bytes.copy_from_slice(&value.to_le_bytes()[..6]);
+                self.alpha_selectors.push(bytes);
+            }
+        }
+        Ok(())
+    }
+
+    fn block_size(&self) -> usize {
+        match self.header.format {
+            FORMAT_DXT1 | FORMAT_ETC1 | FORMAT_ETC2 | FORMAT_ETC1S => 8,
+            _ => 16,
+        }
+    }
+
+    /// Unpacks every face of a mip level to DXT or ETC blocks
+    fn unpack_level(&self, level: u32) -> Result<Vec<Vec<u8>>> {
+        let header = &self.header;
+        if level >= header.levels {
+            return Err(crunch_error("mip level out of range"));
+        }
+        let start = header.level_offsets[level as usize];
+        let end = match header.level_offsets.get(level as usize + 1) {
+            Some(end) => *end,
+            None => self.data.len(),
+        };
+        let data = match self.data.get(start..end) {
+            Some(data) if !data.is_empty() => data,
+            _ => return Err(crunch_error("mip level out of bounds")),
+        };
+
+        let blocks_x = (header.width >> level).max(1).div_ceil(4) as usize;
+        let blocks_y = (header.height >> level).max(1).div_ceil(4) as usize;
+        let mut faces =
+            vec![vec![0; blocks_x * blocks_y * self.block_size()]; header.faces as usize];
+        let mut codec = Codec::new(data);
+        match header.format {
+            FORMAT_DXT1 | FORMAT_DXT5..=FORMAT_DXT5_AGBR => {
+                self.unpack_dxt(&mut codec, &mut faces, blocks_x, blocks_y)?
+            }
+            FORMAT_ETC1..=FORMAT_ETC2AS => {
+                self.unpack_etc(&mut codec, &mut faces, blocks_x, blocks_y)?
+            }
+            format => {
+                return Err(Error::EngineError(format!(
+                    "Unsupported crunch format: {}",
+                    format
+                )));
+            }
+        }
+        Ok(faces)
+    }
+
+    /// Adds a coded delta to an endpoint index, wrapping around the codebook
+    fn next_endpoint(
+        &self,
+        codec: &mut Codec,
+        model: usize,
+        index: usize,
+        count: usize,
+    ) -> Result<usize> {
+        let index = index + codec.decode(&self.endpoint_models[model])? as usize;
+        match index.checked_sub(count) {
+            Some(index) if index < count => Ok(index),
+            Some(_) => Err(crunch_error("endpoint index out of range")),
+            None => Ok(index),
+        }
+    }
+
+    fn selector(&self, codec: &mut Codec, model: usize, count: usize) -> Result<usize> {
+        let index = codec.decode(&self.selector_models[model])? as usize;
+        if index >= count {
+            return Err(crunch_error("selector index out of range"));
+        }
+        Ok(index)
+    }
+
+    /// DXT blocks reuse the endpoints of the block to the left (1) or above
+    /// (2), or code new ones (0). The references of a 2x2 group of blocks
+    /// are coded together.
+    fn unpack_dxt(
+        &self,
+        codec: &mut Codec,
+        faces: &mut [Vec<u8>],
+        blocks_x: usize,
+        blocks_y: usize,
+    ) -> Result<()> {
+        let block_size = self.block_size();
+        let has_alpha = self.header.format != FORMAT_DXT1;
+        let (color_count, alpha_count) = (self.color_endpoints.len(), self.alpha_endpoints.len());
+        let width = (blocks_x + 1) & !1;
+        let height = (blocks_y + 1) & !1;
+        // references and endpoint indices of the row above
+        let mut above = vec![(0u32, 0usize, 0usize); width];
+        let (mut color_index, mut alpha_index) = (0, 0);
+        let mut group = 0;
+
+        for face in faces.iter_mut() {
+            for y in 0..height {
+                for x in 0..width {
+                    if y & 1 == 0 && x & 1 == 0 {
+                        group = codec.decode(&self.reference_model)?;
+                    }
+                    let reference = if y & 1 == 1 {
+                        above[x].0
+                    } else {
+                        let reference = group & 3;
+                        above[x].0 = (group >> 2) & 3;
+                        group >>= 4;
+                        reference
+                    };
+                    match reference {
+                        0 => {
+                            color_index = self.next_endpoint(codec, 0, color_index, color_count)?;
+                            if has_alpha {
+                                alpha_index =
+                                    self.next_endpoint(codec, 1, alpha_index, alpha_count)?;
+                            }
+                            above[x].1 = color_index;
+                            above[x].2 = alpha_index;
+                        }
+                        1 => {
+                            above[x].1 = color_index;
+                            above[x].2 = alpha_index;
+                        }
+                        _ => {
+                            color_index = above[x].1;
+                            alpha_index = above[x].2;
+                        }
+                    }
+                    let color_selector = self.selector(codec, 0, self.color_selectors.len())?;
+                    let alpha_selector = if has_alpha {
+                        self.selector(codec, 1, self.alpha_selectors.len())?
+                    } else {
+                        0
+                    };
+                    if x >= blocks_x || y >= blocks_y {
+                        continue;
+                    }
+
+                    let block = &mut face[(y * blocks_x + x) * block_size..][..block_size];
+                    let (alpha, color) = block.split_at_mut(block_size - 8);
+                    if has_alpha {
+                        alpha[..2].copy_from_slice(&self.alpha_endpoints[alpha_index]);
+                        alpha[2..].copy_from_slice(&self.alpha_selectors[alpha_selector]);
+                    }
+                    color[..4].copy_from_slice(&self.color_endpoints[color_index]);
+                    color[4..].copy_from_slice(&self.color_selectors[color_selector]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// ETC blocks code the references of their halves separately: the left
+    /// half may also reuse the right half of the block diagonally above (3),
+    /// the right half either reuses the left half (0) or codes new
+    /// endpoints. The alpha endpoints follow the left half.
+    fn unpack_etc(
+        &self,
+        codec: &mut Codec,
+        faces: &mut [Vec<u8>],
+        blocks_x: usize,
+        blocks_y: usize,
+    ) -> Result<()> {
+        let block_size = self.block_size();
+        let has_alpha = self.header.has_alpha();
+        let subblocks = self.header.has_subblocks();
+        let (color_count, alpha_count) = (self.color_endpoints.len(), self.alpha_endpoints.len());
+        let width = (blocks_x + 1) & !1;
+        let height = (blocks_y + 1) & !1;
+        // references and endpoint indices of both halves of the row above
+        let mut above = vec![(0u32, 0usize, 0usize); 2 * width];
+        let (mut color_index, mut alpha_index) = (0, 0);
+        let (mut diagonal_color, mut diagonal_alpha) = (0, 0);
+
+        for face in faces.iter_mut() {
+            for y in 0..height {
+                for x in 0..width {
+                    let mut reference = if y & 1 == 1 {
+                        above[2 * x].0
+                    } else {
+                        let group = codec.decode(&self.reference_model)?;
+                        above[2 * x].0 = (group >> 2 & 3) | (group >> 4 & 12);
+                        (group & 3) | (group >> 2 & 12)
+                    };
+                    match reference & 3 {
+                        0 => {
+                            color_index = self.next_endpoint(codec, 0, color_index, color_count)?;
+                            if has_alpha {
+                                alpha_index =
+                                    self.next_endpoint(codec, 1, alpha_index, alpha_count)?;
+                            }
+                            above[2 * x].1 = color_index;
+                            above[2 * x].2 = alpha_index;
+                        }
+                        1 => {
+                            above[2 * x].1 = color_index;
+                            above[2 * x].2 = alpha_index;
+                        }
+                        3 => {
+                            color_index = diagonal_color;
+                            alpha_index = diagonal_alpha;
+                            above[2 * x].1 = color_index;
+                            above[2 * x].2 = alpha_index;
+                        }
+                        _ => {
+                            color_index = above[2 * x].1;
+                            alpha_index = above[2 * x].2;
+                        }
+                    }
+                    reference >>= 2;
+                    let left = self.color_endpoints[color_index];
+                    let color_selector =
+                        self.selector(codec, 0, self.color_selectors.len() >> subblocks as u32)?;
+                    let alpha_selector = if has_alpha {
+                        self.selector(codec, 1, self.alpha_selectors.len() >> subblocks as u32)?
+                    } else {
+                        0
+                    };
+                    if reference != 0 {
+                        color_index = self.next_endpoint(codec, 0, color_index, color_count)?;
+                    }
+                    diagonal_color = above[2 * x + 1].1;
+                    diagonal_alpha = above[2 * x + 1].2;
+                    above[2 * x + 1].1 = color_index;
+                    above[2 * x + 1].2 = alpha_index;
+                    if x >= blocks_x || y >= blocks_y {
+                        continue;
+                    }
+
+                    let block = &mut face[(y * blocks_x + x) * block_size..][..block_size];
+                    let (alpha, color) = block.split_at_mut(block_size - 8);
+                    // halves are stored side by side in the codebook and
+                    // transposed back for blocks split horizontally
+                    let flip = (reference >> 1 ^ 1) as usize;
+                    if has_alpha {
+                        let selector = if subblocks {
+                            alpha_selector << 1 | flip
+                        } else {
+                            alpha_selector
+                        };
+                        alpha[..2].copy_from_slice(&self.alpha_endpoints[alpha_index]);
+                        alpha[2..].copy_from_slice(&self.alpha_selectors[selector]);
+                    }
+                    if subblocks {
+                        let right = self.color_endpoints[color_index];
+                        color[..4].copy_from_slice(&etc_endpoints(left, right, flip as u8));
+                        color[4..]
+                            .copy_from_slice(&self.color_selectors[color_selector << 1 | flip]);
+                    } else {
+                        color[..4].copy_from_slice(&left);
+                        color[4..].copy_from_slice(&self.color_selectors[color_selector]);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Combines the endpoints of both halves of an ETC1 block, in differential
+/// mode when the colors are close enough
+fn etc_endpoints(left: [u8; 4], right: [u8; 4], flip: u8) -> [u8; 4] {
+    let differential = (0..3).all(|c| left[c] + 3 >= right[c] && right[c] + 4 >= left[c]);
+    let mut block = [0; 4];
+    for c in 0..3 {
+        block[c] = if differential {
+            left[c] << 3 | (right[c].wrapping_sub(left[c]) & 7)
+        } else {
+            (left[c] << 3 & 0xf0) | right[c] >> 1
+        };
+    }
+    block[3] = left[3] << 5 | right[3] << 2 | (differential as u8) << 1 | flip;
+    block
+}
+
+impl TextureFormat {
+    /// Format of the blocks of a crunched format after decrunching
+    pub fn decrunched_format(&self) -> Option<TextureFormat> {
+        match self {
+            TextureFormat::DXT1Crunched => Some(TextureFormat::DXT1),
+            TextureFormat::DXT5Crunched => Some(TextureFormat::DXT5),
+            TextureFormat::EtcRgb4Crunched => Some(TextureFormat::EtcRgb4),
+            TextureFormat::Etc2Rgba8Crunched => Some(TextureFormat::Etc2Rgba8),
+            _ => None,
+        }
+    }
+}
+
+impl Texture2D {
+    /// Sets the Unity version the texture was built with. Unity 2017.3
+    /// changed the crunch bitstream, crunched textures of unknown versions
+    /// are assumed to use the original crunch format unless they are ETC
+    /// textures, which only the newer format supports.
+    pub fn set_version(&mut self, version: UnityVersion) {
+        self.unity_version = Some(version);
+    }
+
+    /// Whether the image data uses Unity's crunch variant
+    pub fn uses_unity_crunch(&self) -> bool {
+        match self.texture_format {
+            TextureFormat::EtcRgb4Crunched | TextureFormat::Etc2Rgba8Crunched => true,
+            _ => match self.unity_version {
+                Some(version) => version.is_at_least(2017, 3),
+                None => false,
+            },
+        }
+    }
+
+    /// Unpacks a face of a mip level of a crunched texture to the blocks of
+    /// its `decrunched_format`
+    pub(super) fn decrunch(&self, face: u32, level: u32) -> Result<Vec<u8>> {
+        if self.uses_unity_crunch() {
+            let mut faces = Unpacker::new(&self.data)?.unpack_level(level)?;
+            if face as usize >= faces.len() {
+                return Err(crunch_error("face out of range"));
+            }
+            return Ok(faces.swap_remove(face as usize));
+        }
+
+        // validate the header before handing the data over to crunch
+        let header = Header::read(&self.data)?;
+        if level >= header.levels {
+            return Err(crunch_error("mip level out of range"));
+        }
+        if face >= header.faces {
+            return Err(crunch_error("face out of range"));
+        }
+        if header.faces > 1 {
+            let mut faces = unpack_legacy_level(&self.data, &header, level)?;
+            return Ok(faces.swap_remove(face as usize));
+        }
+        match CrunchedData::new(&self.data).decode_level(level) {
+            Some(data) => Ok(data),
+            None => Err(Error::EngineError("DXT decrunch failed".to_owned())),
+        }
+    }
+}
+
+// C wrappers of the crunch decoder linked by `decrunch`, which is pinned to
+// a version that exports them
+extern "C" {
+    fn crnd_unpack_begin(data: *const c_void, data_size: u32) -> *mut c_void;
+
+    fn crnd_unpack_end(context: *mut c_void) -> bool;
+
+    fn crnd_unpack_level(
+        context: *mut c_void,
+        dst: *mut *mut c_void,
+        dst_size: u32,
+        row_pitch: u32,
+        level: u32,
+    ) -> bool;
+}
+
+/// Unpacks every face of a mip level of the original crunch format with the
+/// crunch library
+fn unpack_legacy_level(data: &[u8], header: &Header, level: u32) -> Result<Vec<Vec<u8>>> {
+    let block_size = match header.format {
+        FORMAT_DXT1 | FORMAT_DXT5A => 8,
+        _ => 16,
+    };
+    let blocks_x = (header.width >> level).max(1).div_ceil(4) as usize;
+    let blocks_y = (header.height >> level).max(1).div_ceil(4) as usize;
+    let row_pitch = blocks_x * block_size;
+    let mut faces = vec![vec![0u8; row_pitch * blocks_y]; header.faces as usize];
+    let mut pointers: Vec<*mut c_void> = faces
+        .iter_mut()
+        .map(|face| face.as_mut_ptr() as *mut c_void)
+        .collect();
+
+    let unpacked = unsafe {
+        let context = crnd_unpack_begin(data.as_ptr() as *const c_void, data.len() as u32);
+        if context.is_null() {
+            return Err(crunch_error("invalid texture"));
+        }
+        let unpacked = crnd_unpack_level(
+            context,
+            pointers.as_mut_ptr(),
+            (row_pitch * blocks_y) as u32,
+            row_pitch as u32,
+            level,
+        );
+        crnd_unpack_end(context);
+        unpacked
+    };
+    if !unpacked {
+        return Err(Error::EngineError("DXT decrunch failed".to_owned()));
+    }
+    Ok(faces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes bits most significant bit first
+    struct BitWriter {
+        data: Vec<u8>,
+        bits: u32,
+    }
+
+    impl BitWriter {
+        fn new() -> Self {
+            BitWriter {
+                data: Vec::new(),
+                bits: 0,
+            }
+        }
+
+        fn put(&mut self, value: u32, count: u32) {
+            for bit in (0..count).rev() {
+                if self.bits.is_multiple_of(8) {
+                    self.data.push(0);
+                }
+                let last = self.data.len() - 1;
+                self.data[last] |= (((value >> bit) & 1) as u8) << (7 - self.bits % 8);
+                self.bits += 1;
+            }
+        }
+
+        /// Sends a model in which `symbols` all have 4 bit codes
+        fn put_model(&mut self, symbols: usize) {
+            assert!(symbols <= 16);
+            self.put(symbols as u32, 14);
+            // sizes of the first 13 code length codes in the order they are
+            // sent, only code length 4 is used
+            self.put(13, 5);
+            for i in 0..13 {
+                self.put(if i == 12 { 1 } else { 0 }, 3);
+            }
+            // code length 4 has the one bit code 0
+            for _ in 0..symbols {
+                self.put(0, 1);
+            }
+        }
+    }
+
+    /// Builds a single level crunch file of the Unity variant from its
+    /// tables, color codebooks and level streams
+    fn crunch_file(format: u8, width: u16, height: u16, streams: &[Vec<u8>; 4]) -> Vec<u8> {
+        let mut data = vec![0; HEADER_SIZE + 4];
+        data[0..2].copy_from_slice(b"Hx");
+        data[12..14].copy_from_slice(&width.to_be_bytes());
+        data[14..16].copy_from_slice(&height.to_be_bytes());
+        data[16] = 1;
+        data[17] = 1;
+        data[18] = format;
+        let mut offset = data.len();
+        let mut offsets = Vec::new();
+        for stream in streams {
+            offsets.push(offset as u32);
+            offset += stream.len();
+        }
+        // tables
+        data[65..67].copy_from_slice(&(streams[0].len() as u16).to_be_bytes());
+        data[67..70].copy_from_slice(&offsets[0].to_be_bytes()[1..]);
+        // color endpoints and selectors, two of each
+        for (palette, stream) in [(33, 1), (41, 2)] {
+            data[palette..palette + 3].copy_from_slice(&offsets[stream].to_be_bytes()[1..]);
+            let size = streams[stream].len() as u32;
+            data[palette + 3..palette + 6].copy_from_slice(&size.to_be_bytes()[1..]);
+            data[palette + 7] = 2;
+        }
+        data[HEADER_SIZE..].copy_from_slice(&offsets[3].to_be_bytes());
+        for stream in streams {
+            data.extend_from_slice(stream);
+        }
+        data
+    }
+
+    #[test]
+    fn test_unpack_dxt1() {
+        // reference, endpoint delta and selector delta codes
+        let mut tables = BitWriter::new();
+        for _ in 0..3 {
+            tables.put_model(16);
+        }
+
+        // deltas of the red, green and blue of both colors
+        let mut endpoints = BitWriter::new();
+        endpoints.put_model(16);
+        endpoints.put_model(16);
+        for delta in [1, 2, 3, 4, 5, 6, 1, 1, 1, 1, 1, 1] {
+            endpoints.put(delta, 4);
+        }
+
+        // the first pixel picks the color a third of the way, then all
+        // pixels pick the second color
+        let mut selectors = BitWriter::new();
+        selectors.put_model(16);
+        selectors.put(1, 4);
+        selectors.put(0, 28);
+        selectors.put(0b1110, 4);
+        for _ in 0..7 {
+            selectors.put(0b1111, 4);
+        }
+
+        // an 8x4 texture is coded as a 2x2 group of blocks. The first row
+        // codes new endpoints, the block below the first one reuses them.
+        let mut level = BitWriter::new();
+        let symbols = [8, 0, 0, 1, 1, 0, 1, 0];
+        for symbol in &symbols {
+            level.put(*symbol, 4);
+        }
+
+        let data = crunch_file(
+            FORMAT_DXT1,
+            8,
+            4,
+            &[tables.data, endpoints.data, selectors.data, level.data],
+        );
+        let mut tex = Texture2D::new("test", 8, 4, TextureFormat::DXT1Crunched, data);
+        tex.set_version(UnityVersion::new(2018, 4, 0));
+        let surface = tex.surface(0, 0).unwrap();
+        assert_eq!(surface.texture_format, TextureFormat::DXT1);
+
+        let color0 = (3 | 2 << 5 | 1 << 11) as u16;
+        let color1 = (6 | 5 << 5 | 4 << 11) as u16;
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&color0.to_le_bytes());
+        expected.extend_from_slice(&color1.to_le_bytes());
+        expected.extend_from_slice(&[2, 0, 0, 0]);
+        let color0 = (4 | 3 << 5 | 2 << 11) as u16;
+        let color1 = (7 | 6 << 5 | 5 << 11) as u16;
+        expected.extend_from_slice(&color0.to_le_bytes());
+        expected.extend_from_slice(&color1.to_le_bytes());
+        expected.extend_from_slice(&[0x55, 0x55, 0x55, 0x55]);
+        assert_eq!(surface.data, expected);
+        assert_eq!(tex.to_image().unwrap().len(), 8 * 4 * 4);
+        assert!(tex.surface(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_codec() {
+        let mut codec = Codec::new(&[0b1011_0011, 0xff]);
+        assert_eq!(codec.bits(3), 0b101);
+        assert_eq!(codec.bits(7), 0b1001111);
+        assert_eq!(codec.bits(10), 0b1111110000);
+        assert_eq!(codec.bits(0), 0);
+
+        // a: 0, b: 10, c: 110, d: 111
+        let model = Model::new(&[1, 2, 3, 3]).unwrap();
+        let mut codec = Codec::new(&[0b0110_1111, 0]);
+        let symbols: Vec<u32> = (0..5).map(|_| codec.decode(&model).unwrap()).collect();
+        assert_eq!(symbols, vec![0, 2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn test_receive_model() {
+        let mut writer = BitWriter::new();
+        writer.put_model(16);
+        for symbol in [3, 15, 0] {
+            writer.put(symbol, 4);
+        }
+        let mut codec = Codec::new(&writer.data);
+        let model = codec.receive_model().unwrap();
+        assert_eq!(codec.decode(&model).unwrap(), 3);
+        assert_eq!(codec.decode(&model).unwrap(), 15);
+        assert_eq!(codec.decode(&model).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_etc_endpoints() {
+        // close colors use differential mode
+        let block = etc_endpoints([10, 20, 30, 2], [12, 17, 30, 5], 1);
+        assert_eq!(
+            block,
+            [10 << 3 | 2, 20 << 3 | 5, 30 << 3, 2 << 5 | 5 << 2 | 3]
+        );
+        // distant colors fall back to individual 4 bit colors
+        let block = etc_endpoints([0, 31, 8, 0], [31, 0, 8, 1], 0);
+        assert_eq!(block, [0x0f, 0xf0, 0x44, 1 << 2]);
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut tex = Texture2D::new("test", 4, 4, TextureFormat::DXT1Crunched, Vec::new());
+        assert!(!tex.uses_unity_crunch());
+        tex.set_version(UnityVersion::new(2017, 3, 0));
+        assert!(tex.uses_unity_crunch());
+        tex.set_version(UnityVersion::new(5, 6, 0));
+        assert!(!tex.uses_unity_crunch());
+        tex.texture_format = TextureFormat::EtcRgb4Crunched;
+        assert!(tex.uses_unity_crunch());
+        assert_eq!(
+            tex.texture_format.decrunched_format(),
+            Some(TextureFormat::EtcRgb4)
+        );
+        assert!(tex.to_image().is_err());
+    }
+
+    /// The unpacking of every face matches `decrunch` on the texture of
+    /// its tests, which has a single face
+    #[test]
+    fn test_legacy_faces() {
+        let data = include_bytes!("../../../test_data/crunch/copyright_2048_dxt1.crn");
+        let header = Header::read(data).unwrap();
+        assert_eq!(header.faces, 1);
+        for &level in &[0, 5, header.levels - 1] {
+            let faces = unpack_legacy_level(data, &header, level).unwrap();
+            assert_eq!(faces.len(), 1);
+            assert_eq!(
+                Some(faces[0].clone()),
+                CrunchedData::new(data).decode_level(level)
+            );
+        }
+
+        let tex = Texture2D::new(
+            "copyright",
+            2048,
+            2048,
+            TextureFormat::DXT1Crunched,
+            data.to_vec(),
+        );
+        assert_eq!(tex.decrunch(0, 0).unwrap().len(), 512 * 512 * 8);
+        assert!(tex.decrunch(1, 0).is_err());
+    }
+
+    /// An 8x8 cubemap with two mip levels, every face is a single color
+    /// whose components grow with the face index
+    #[test]
+    fn test_legacy_cubemap() {
+        let data = include_bytes!("../../../test_data/crunch/cubemap_dxt1.crn");
+        let header = Header::read(data).unwrap();
+        assert_eq!((header.faces, header.levels), (6, 2));
+
+        let tex = Texture2D::new("cubemap", 8, 8, TextureFormat::DXT1Crunched, data.to_vec());
+        for face in 0..6 {
+            let n = face + 1;
+            let color0 = ((3 * n) | (2 * n) << 5 | n << 11) as u16;
+            let color1 = (n | n << 5 | n << 11) as u16;
+            let mut block = Vec::new();
+            block.extend_from_slice(&color0.to_le_bytes());
+            block.extend_from_slice(&color1.to_le_bytes());
+            block.extend_from_slice(&[0xcc; 4]);
+            assert_eq!(tex.decrunch(face, 0).unwrap(), block.repeat(4));
+            assert_eq!(tex.decrunch(face, 1).unwrap(), block);
+        }
+        assert!(tex.decrunch(6, 0).is_err());
+        assert!(tex.decrunch(0, 2).is_err());
+    }
+}
@@ -0,0 +1,427 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Encoders writing a `Texture2D` to common image file formats.
+
+use super::{Texture2D, TextureFormat};
+use error::{Error, Result};
+use png;
+use std::io::Write;
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_LINEARSIZE: u32 = 0x8_0000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DXGI_FORMAT_BC6H_UF16: u32 = 95;
+const DXGI_FORMAT_BC7_UNORM: u32 = 98;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+const EXR_MAGIC: &[u8] = &[0x76, 0x2f, 0x31, 0x01];
+const EXR_PIXEL_TYPE_FLOAT: u32 = 2;
+
+impl Texture2D {
+    /// Encodes the texture as a PNG image
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_png(&mut result)?;
+        Ok(result)
+    }
+
+    /// Writes the texture as a PNG image
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<()> {
+        let image = self.to_image_flipped()?;
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut png_writer = encoder.write_header().map_err(png_error)?;
+        png_writer.write_image_data(&image).map_err(png_error)?;
+        png_writer.finish().map_err(png_error)
+    }
+
+    /// Encodes the texture as an uncompressed 32 bit TGA image
+    pub fn to_tga(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_tga(&mut result)?;
+        Ok(result)
+    }
+
+    /// Writes the texture as an uncompressed 32 bit TGA image
+    pub fn write_tga<W: Write>(&self, mut writer: W) -> Result<()> {
+        if self.width > 0xffff || self.height > 0xffff {
+            return Err(Error::EngineError(format!(
+                "Texture of {}x{} is too large for TGA",
+                self.width, self.height
+            )));
+        }
+        let image = self.to_image_flipped()?;
+
+        let mut header = [0u8; 18];
+        // uncompressed true-color image
+        header[2] = 2;
+        header[12..14].copy_from_slice(&(self.width as u16).to_le_bytes());
+        header[14..16].copy_from_slice(&(self.height as u16).to_le_bytes());
+        header[16] = 32;
+        // top-left origin, 8 alpha bits
+        header[17] = 0x28;
+        writer.write_all(&header)?;
+
+        let mut pixels = Vec::with_capacity(image.len());
+        for pixel in image.chunks(4) {
+            pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+        writer.write_all(&pixels)?;
+        Ok(())
+    }
+
+    /// Wraps the block compressed data of the texture in a DDS container
+    /// without decoding it. Crunched textures are decrunched to their DXT
+    /// counterpart first. Only the first mip level is written and the rows
+    /// stay in Unity's bottom-up order.
+    pub fn to_dds(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_dds(&mut result)?;
+        Ok(result)
+    }
+
+    /// Writes the block compressed data of the texture in a DDS container,
+    /// see `to_dds`
+    pub fn write_dds<W: Write>(&self, mut writer: W) -> Result<()> {
+        if self.is_swizzled() {
+            return self.deswizzle_top()?.write_dds(writer);
+        }
+        if self.texture_format.is_crunched() {
+            return self.surface(0, 0)?.write_dds(writer);
+        }
+        let (four_cc, block_size, dxgi_format) = match self.texture_format {
+            TextureFormat::DXT1 => (*b"DXT1", 8, 0),
+            TextureFormat::DXT3 => (*b"DXT3", 16, 0),
+            TextureFormat::DXT5 => (*b"DXT5", 16, 0),
+            TextureFormat::BC4 => (*b"ATI1", 8, 0),
+            TextureFormat::BC5 => (*b"ATI2", 16, 0),
+            TextureFormat::BC6H => (*b"DX10", 16, DXGI_FORMAT_BC6H_UF16),
+            TextureFormat::BC7 => (*b"DX10", 16, DXGI_FORMAT_BC7_UNORM),
+            _ => {
+                return Err(Error::EngineError(format!(
+                    "DDS export is not supported for {:?}",
+                    self.texture_format
+                )));
+            }
+        };
+
+        let data = &self.data;
+        let blocks_wide = (self.width as usize).div_ceil(4).max(1);
+        let blocks_high = (self.height as usize).div_ceil(4).max(1);
+        let linear_size = blocks_wide * blocks_high * block_size;
+        if data.len() < linear_size {
+            return Err(Error::EngineError(format!(
+                "Image data of {} bytes is too short for a {}x{} {:?} texture",
+                data.len(),
+                self.width,
+                self.height,
+                self.texture_format
+            )));
+        }
+
+        let mut header = Vec::with_capacity(148);
+        header.extend_from_slice(DDS_MAGIC);
+        let mut put = |value: u32| header.extend_from_slice(&value.to_le_bytes());
+        put(124);
+        put(DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_LINEARSIZE);
+        put(self.height);
+        put(self.width);
+        put(linear_size as u32);
+        // depth, mip map count
+        put(0);
+        put(1);
+        for _ in 0..11 {
+            put(0);
+        }
+        // pixel format
+        put(32);
+        put(DDPF_FOURCC);
+        put(u32::from_le_bytes(four_cc));
+        for _ in 0..5 {
+            put(0);
+        }
+        put(DDSCAPS_TEXTURE);
+        // caps 2-4, reserved
+        for _ in 0..4 {
+            put(0);
+        }
+        if &four_cc == b"DX10" {
+            put(dxgi_format);
+            put(D3D10_RESOURCE_DIMENSION_TEXTURE2D);
+            put(0);
+            // array size
+            put(1);
+            put(0);
+        }
+
+        writer.write_all(&header)?;
+        writer.write_all(&data[..linear_size])?;
+        Ok(())
+    }
+
+    /// Encodes the texture as an uncompressed OpenEXR image with 32 bit
+    /// float RGBA channels
+    pub fn to_exr(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_exr(&mut result)?;
+        Ok(result)
+    }
+
+    /// Writes the texture as an uncompressed OpenEXR image, see `to_exr`
+    pub fn write_exr<W: Write>(&self, mut writer: W) -> Result<()> {
+        let image = self.to_image_f32_flipped()?;
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        let mut header = Vec::with_capacity(331 + height * 8);
+        header.extend_from_slice(EXR_MAGIC);
+        // version 2, single part scan lines
+        header.extend_from_slice(&2u32.to_le_bytes());
+
+        // channels are sorted by name
+        let mut channels = Vec::with_capacity(73);
+        for name in &[b"A", b"B", b"G", b"R"] {
+            channels.extend_from_slice(&name[..]);
+            channels.push(0);
+            channels.extend_from_slice(&EXR_PIXEL_TYPE_FLOAT.to_le_bytes());
+            // linear flag, reserved bytes, x and y sampling
+            channels.extend_from_slice(&[0; 4]);
+            channels.extend_from_slice(&1u32.to_le_bytes());
+            channels.extend_from_slice(&1u32.to_le_bytes());
+        }
+        channels.push(0);
+        let mut window = Vec::with_capacity(16);
+        for &value in &[0, 0, self.width as i32 - 1, self.height as i32 - 1] {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+        let attributes: [(&str, &str, &[u8]); 8] = [
+            ("channels", "chlist", &channels),
+            ("compression", "compression", &[0]),
+            ("dataWindow", "box2i", &window),
+            ("displayWindow", "box2i", &window),
+            ("lineOrder", "lineOrder", &[0]),
+            ("pixelAspectRatio", "float", &1f32.to_le_bytes()),
+            ("screenWindowCenter", "v2f", &[0; 8]),
+            ("screenWindowWidth", "float", &1f32.to_le_bytes()),
+        ];
+        for &(name, kind, value) in &attributes {
+            header.extend_from_slice(name.as_bytes());
+            header.push(0);
+            header.extend_from_slice(kind.as_bytes());
+            header.push(0);
+            header.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            header.extend_from_slice(value);
+        }
+        header.push(0);
+
+        // one scan line per chunk, the offset table points at each of them
+        let chunk_size = 8 + width * 16;
+        let first_chunk = header.len() + height * 8;
+        for y in 0..height {
+            header.extend_from_slice(&((first_chunk + y * chunk_size) as u64).to_le_bytes());
+        }
+        writer.write_all(&header)?;
+
+        let mut chunk = Vec::with_capacity(chunk_size);
+        for (y, row) in image.chunks(width * 4).take(height).enumerate() {
+            chunk.clear();
+            chunk.extend_from_slice(&(y as u32).to_le_bytes());
+            chunk.extend_from_slice(&((width * 16) as u32).to_le_bytes());
+            for &channel in &[3, 2, 1, 0] {
+                for pixel in row.chunks(4) {
+                    chunk.extend_from_slice(&pixel[channel].to_le_bytes());
+                }
+            }
+            writer.write_all(&chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes the texture as a Radiance RGBE image, the alpha channel is
+    /// dropped
+    pub fn to_hdr(&self) -> Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.write_hdr(&mut result)?;
+        Ok(result)
+    }
+
+    /// Writes the texture as a Radiance RGBE image, see `to_hdr`
+    pub fn write_hdr<W: Write>(&self, mut writer: W) -> Result<()> {
+        let image = self.to_image_f32_flipped()?;
+        let width = self.width as usize;
+        write!(
+            writer,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        )?;
+
+        let mut scanline = Vec::with_capacity(width * 4 + 4 + width.div_ceil(128) * 4);
+        let mut pixels = Vec::with_capacity(width);
+        for row in image.chunks(width * 4).take(self.height as usize) {
+            pixels.clear();
+            pixels.extend(row.chunks(4).map(to_rgbe));
+            scanline.clear();
+            if (8..0x8000).contains(&width) {
+                // run length encoded scan lines store each component
+                // separately, only literal runs are written
+                scanline.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+                for component in 0..4 {
+                    for run in pixels.chunks(128) {
+                        scanline.push(run.len() as u8);
+                        scanline.extend(run.iter().map(|pixel| pixel[component]));
+                    }
+                }
+            } else {
+                for pixel in &pixels {
+                    scanline.extend_from_slice(pixel);
+                }
+            }
+            writer.write_all(&scanline)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a color with a shared exponent, negative values are clamped
+fn to_rgbe(pixel: &[f32]) -> [u8; 4] {
+    let clamp = |value: f32| {
+        if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, 1e38)
+        }
+    };
+    let (r, g, b) = (clamp(pixel[0]), clamp(pixel[1]), clamp(pixel[2]));
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with the mantissa in [0.5, 1)
+    let exponent = ((max.to_bits() >> 23) & 0xff) as i32 - 126;
+    let scale = 256.0 / 2f32.powi(exponent);
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+fn png_error(err: png::EncodingError) -> Error {
+    Error::EngineError(format!("PNG encoding failed: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(format: TextureFormat, width: u32, height: u32, data: Vec<u8>) -> Texture2D {
+        Texture2D::new("test", width, height, format, data)
+    }
+
+    #[test]
+    fn test_tga() {
+        // bottom row red, top row green
+        let data = vec![0xff, 0, 0, 0xff, 0, 0xff, 0, 0x80];
+        let tga = texture(TextureFormat::RGBA32, 1, 2, data).to_tga().unwrap();
+        assert_eq!(tga.len(), 18 + 8);
+        assert_eq!(tga[2], 2);
+        assert_eq!(&tga[12..16], &[1, 0, 2, 0]);
+        assert_eq!(tga[16], 32);
+        assert_eq!(tga[17], 0x28);
+        assert_eq!(&tga[18..], &[0, 0xff, 0, 0x80, 0, 0, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_dds() {
+        let data: Vec<u8> = (0..40).collect();
+        let dds = texture(TextureFormat::DXT1, 8, 8, data).to_dds().unwrap();
+        assert_eq!(dds.len(), 128 + 32);
+        assert_eq!(&dds[0..4], b"DDS ");
+        assert_eq!(&dds[84..88], b"DXT1");
+        // linear size
+        assert_eq!(&dds[20..24], &[32, 0, 0, 0]);
+        assert_eq!(dds[128..], (0..32).collect::<Vec<u8>>()[..]);
+
+        let dds = texture(TextureFormat::BC6H, 4, 4, vec![0; 16])
+            .to_dds()
+            .unwrap();
+        assert_eq!(dds.len(), 148 + 16);
+        assert_eq!(&dds[128..132], &[95, 0, 0, 0]);
+
+        assert!(texture(TextureFormat::DXT5, 4, 4, vec![0; 8])
+            .to_dds()
+            .is_err());
+        assert!(texture(TextureFormat::RGBA32, 1, 1, vec![0; 4])
+            .to_dds()
+            .is_err());
+
+        let dds = texture(TextureFormat::BC7, 4, 4, vec![0; 16])
+            .to_dds()
+            .unwrap();
+        assert_eq!(&dds[128..132], &[98, 0, 0, 0]);
+    }
+
+    fn float_texture(width: u32, height: u32, values: &[f32]) -> Texture2D {
+        let mut data = Vec::new();
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        texture(TextureFormat::RGBAFloat, width, height, data)
+    }
+
+    #[test]
+    fn test_exr() {
+        // bottom row first in the texture, top row first in the image
+        let exr = float_texture(1, 2, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0])
+            .to_exr()
+            .unwrap();
+        assert_eq!(&exr[0..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        assert_eq!(&exr[8..24], b"channels\0chlist\0");
+        assert_eq!(exr.len(), 331 + 16 + 2 * (8 + 16));
+
+        let offset = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&exr[331 + 8 * i..339 + 8 * i]);
+            u64::from_le_bytes(bytes) as usize
+        };
+        assert_eq!(offset(0), 347);
+        assert_eq!(offset(1), 347 + 24);
+        let values: Vec<f32> = exr[offset(0) + 8..offset(1)]
+            .chunks(4)
+            .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+            .collect();
+        // A, B, G, R
+        assert_eq!(values, vec![8.0, 7.0, 6.0, 5.0]);
+        assert_eq!(&exr[offset(1)..offset(1) + 8], &[1, 0, 0, 0, 16, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_hdr() {
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 1\n";
+        let hdr = float_texture(1, 1, &[1.0, 0.5, 0.25, 1.0])
+            .to_hdr()
+            .unwrap();
+        assert_eq!(&hdr[..header.len()], &header[..]);
+        assert_eq!(&hdr[header.len()..], &[128, 64, 32, 129]);
+
+        // scan lines of 8 pixels and more are run length encoded
+        let hdr = float_texture(8, 1, &[0.0; 32]).to_hdr().unwrap();
+        let data = &hdr[header.len()..];
+        assert_eq!(data.len(), 4 + 4 * 9);
+        assert_eq!(&data[..5], &[2, 2, 0, 8, 8]);
+
+        assert_eq!(to_rgbe(&[-1.0, 0.0, 0.0]), [0; 4]);
+        assert_eq!(to_rgbe(&[3.0, 0.0, 0.0]), [192, 0, 0, 130]);
+    }
+}
@@ -0,0 +1,381 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Byte layout of the mip levels, array layers, cubemap faces and volume
+//! slices stored in the image data of a texture.
+
+use super::{Texture2D, TextureDimension, TextureFormat};
+use error::{Error, Result};
+
+impl TextureFormat {
+    /// Number of bytes a single `width`x`height` image of the format takes,
+    /// or `None` for formats without a fixed size such as crunched data
+    pub fn image_size(&self, width: u32, height: u32) -> Option<usize> {
+        let (width, height) = (width as usize, height as usize);
+        let blocks = |block_width: usize, block_height: usize, block_size: usize| {
+            width.div_ceil(block_width) * height.div_ceil(block_height) * block_size
+        };
+        let pvrtc =
+            |block_width: usize| width.div_ceil(block_width).max(2) * height.div_ceil(4).max(2) * 8;
+        if let Some(size) = self.astc_block_size() {
+            return Some(blocks(size, size, 16));
+        }
+        let size = match self {
+            TextureFormat::Alpha8 | TextureFormat::R8 | TextureFormat::R8Signed => width * height,
+            TextureFormat::ARGB4444
+            | TextureFormat::RGBA4444
+            | TextureFormat::RGB565
+            | TextureFormat::R16
+            | TextureFormat::RG16
+            | TextureFormat::RHalf
+            | TextureFormat::R16Signed
+            | TextureFormat::RG16Signed => width * height * 2,
+            TextureFormat::RGB24 | TextureFormat::BGR24 | TextureFormat::RGB24Signed => {
+                width * height * 3
+            }
+            TextureFormat::RGBA32
+            | TextureFormat::ARGB32
+            | TextureFormat::BGRA32
+            | TextureFormat::RGHalf
+            | TextureFormat::RFloat
+            | TextureFormat::RGB9e5Float
+            | TextureFormat::RG32
+            | TextureFormat::RGBA32Signed
+            | TextureFormat::RG32Signed => width * height * 4,
+            TextureFormat::RGB48 | TextureFormat::RGB48Signed => width * height * 6,
+            TextureFormat::RGBAHalf
+            | TextureFormat::RGFloat
+            | TextureFormat::RGBA64
+            | TextureFormat::RGBA64Signed => width * height * 8,
+            TextureFormat::RGBFloat => width * height * 12,
+            TextureFormat::RGBAFloat | TextureFormat::ARGBFloat => width * height * 16,
+            // two pixels share their chroma in four bytes
+            TextureFormat::YUY2 => width.div_ceil(2) * height * 4,
+            TextureFormat::DXT1
+            | TextureFormat::BC4
+            | TextureFormat::EtcRgb4
+            | TextureFormat::Etc3dsRgb4
+            | TextureFormat::AtcRgb4
+            | TextureFormat::EacR
+            | TextureFormat::EacRSigned
+            | TextureFormat::Etc2Rgb
+            | TextureFormat::Etc2Rgba1 => blocks(4, 4, 8),
+            TextureFormat::DXT3
+            | TextureFormat::DXT5
+            | TextureFormat::BC5
+            | TextureFormat::BC6H
+            | TextureFormat::BC7
+            | TextureFormat::Etc3dsRgba8
+            | TextureFormat::AtcRgba8
+            | TextureFormat::EacRg
+            | TextureFormat::EacRgSigned
+            | TextureFormat::Etc2Rgba8 => blocks(4, 4, 16),
+            TextureFormat::PvrtcRgb2
+            | TextureFormat::Pvrtc2bppRgb
+            | TextureFormat::PvrtcRgba2
+            | TextureFormat::Pvrtc2bppRgba => pvrtc(8),
+            TextureFormat::PvrtcRgb4
+            | TextureFormat::Pvrtc4bppRgb
+            | TextureFormat::PvrtcRgba4
+            | TextureFormat::Pvrtc4bppRgba => pvrtc(4),
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    /// Width and height in pixels and size in bytes of the smallest unit of
+    /// the image data: a compressed block, or a single pixel for
+    /// uncompressed formats. `None` for PVRTC and crunched formats, whose
+    /// blocks can't be addressed on their own.
+    pub fn block_footprint(&self) -> Option<(u32, u32, usize)> {
+        if let Some(size) = self.astc_block_size() {
+            return Some((size as u32, size as u32, 16));
+        }
+        match self {
+            TextureFormat::YUY2 => Some((2, 1, 4)),
+            TextureFormat::DXT1
+            | TextureFormat::BC4
+            | TextureFormat::EtcRgb4
+            | TextureFormat::Etc3dsRgb4
+            | TextureFormat::AtcRgb4
+            | TextureFormat::EacR
+            | TextureFormat::EacRSigned
+            | TextureFormat::Etc2Rgb
+            | TextureFormat::Etc2Rgba1 => Some((4, 4, 8)),
+            TextureFormat::DXT3
+            | TextureFormat::DXT5
+            | TextureFormat::BC5
+            | TextureFormat::BC6H
+            | TextureFormat::BC7
+            | TextureFormat::Etc3dsRgba8
+            | TextureFormat::AtcRgba8
+            | TextureFormat::EacRg
+            | TextureFormat::EacRgSigned
+            | TextureFormat::Etc2Rgba8 => Some((4, 4, 16)),
+            TextureFormat::PvrtcRgb2
+            | TextureFormat::Pvrtc2bppRgb
+            | TextureFormat::PvrtcRgba2
+            | TextureFormat::Pvrtc2bppRgba
+            | TextureFormat::PvrtcRgb4
+            | TextureFormat::Pvrtc4bppRgb
+            | TextureFormat::PvrtcRgba4
+            | TextureFormat::Pvrtc4bppRgba => None,
+            _ => self.image_size(1, 1).map(|size| (1, 1, size)),
+        }
+    }
+
+    /// Whether the image data is a crunch file holding every mip level
+    pub fn is_crunched(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::DXT1Crunched
+                | TextureFormat::DXT5Crunched
+                | TextureFormat::EtcRgb4Crunched
+                | TextureFormat::Etc2Rgba8Crunched
+        )
+    }
+}
+
+/// A single image of a texture, see `Texture2D::surfaces`
+pub struct Surface {
+    /// Array layer, cubemap face or, for 3D textures, depth slice of the mip
+    pub slice: u32,
+    pub mip: u32,
+    /// The image as a single level texture
+    pub texture: Texture2D,
+}
+
+/// Iterator over the images of a texture in the order they are stored
+pub struct Surfaces<'a> {
+    texture: &'a Texture2D,
+    slice: u32,
+    mip: u32,
+}
+
+impl<'a> Iterator for Surfaces<'a> {
+    type Item = Result<Surface>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let texture = self.texture;
+        if self.mip >= texture.mip_count || self.slice >= texture.slice_count(self.mip) {
+            return None;
+        }
+        let (slice, mip) = (self.slice, self.mip);
+        // volumes store the slices of a mip together, arrays and cubemaps
+        // store the mip chain of a layer together
+        if texture.dimension == TextureDimension::Tex3D {
+            self.slice += 1;
+            if self.slice >= texture.slice_count(mip) {
+                self.slice = 0;
+                self.mip += 1;
+            }
+        } else {
+            self.mip += 1;
+            if self.mip >= texture.mip_count {
+                self.mip = 0;
+                self.slice += 1;
+            }
+        }
+        Some(texture.surface(slice, mip).map(|texture| Surface {
+            slice,
+            mip,
+            texture,
+        }))
+    }
+}
+
+impl Texture2D {
+    /// Width, height and depth of a mip level
+    pub fn mip_size(&self, mip: u32) -> (u32, u32, u32) {
+        let shrink = |size: u32| size.checked_shr(mip).unwrap_or(0).max(1);
+        let depth = match self.dimension {
+            TextureDimension::Tex3D => shrink(self.depth),
+            _ => self.depth,
+        };
+        (shrink(self.width), shrink(self.height), depth)
+    }
+
+    /// Number of images at a mip level: the array layers or cubemap faces,
+    /// or the depth of the mip level for 3D textures
+    pub fn slice_count(&self, mip: u32) -> u32 {
+        match self.dimension {
+            TextureDimension::Tex3D => self.mip_size(mip).2,
+            _ => self.image_count,
+        }
+    }
+
+    /// Extracts a single image of the texture. `slice` is the array layer or
+    /// cubemap face, or the depth slice of the mip level for 3D textures.
+    pub fn surface(&self, slice: u32, mip: u32) -> Result<Texture2D> {
+        if mip >= self.mip_count || slice >= self.slice_count(mip) {
+            return Err(Error::EngineError(format!(
+                "Texture {} has no slice {} at mip level {}",
+                self.name, slice, mip
+            )));
+        }
+        if self.is_swizzled() {
+            return self.deswizzle()?.surface(slice, mip);
+        }
+        let (width, height, _) = self.mip_size(mip);
+        if let Some(texture_format) = self.texture_format.decrunched_format() {
+            // the crunch file holds every face and mip level
+            let mut texture = self.single_image(width, height, self.decrunch(slice, mip)?);
+            texture.texture_format = texture_format;
+            return Ok(texture);
+        }
+
+        let image_size = |mip: u32| -> Result<usize> {
+            let (width, height, _) = self.mip_size(mip);
+            match self.texture_format.image_size(width, height) {
+                Some(size) => Ok(size),
+                None => Err(Error::EngineError(format!(
+                    "Image size of {:?} textures is unknown",
+                    self.texture_format
+                ))),
+            }
+        };
+        let mut offset = 0;
+        if self.dimension == TextureDimension::Tex3D {
+            for level in 0..mip {
+                offset += image_size(level)? * self.slice_count(level) as usize;
+            }
+            offset += image_size(mip)? * slice as usize;
+        } else {
+            let mut slice_size = 0;
+            for level in 0..self.mip_count {
+                if level == mip {
+                    offset = slice_size;
+                }
+                slice_size += image_size(level)?;
+            }
+            offset += slice_size * slice as usize;
+        }
+
+        let size = image_size(mip)?;
+        if self.data.len() < offset + size {
+            return Err(Error::EngineError(format!(
+                "Image data of {} bytes is too short for slice {} at mip level {} of {}",
+                self.data.len(),
+                slice,
+                mip,
+                self.name
+            )));
+        }
+        Ok(self.single_image(width, height, self.data[offset..offset + size].to_vec()))
+    }
+
+    /// Iterates over every image of the texture in storage order
+    pub fn surfaces(&self) -> Surfaces<'_> {
+        Surfaces {
+            texture: self,
+            slice: 0,
+            mip: 0,
+        }
+    }
+
+    fn single_image(&self, width: u32, height: u32, data: Vec<u8>) -> Texture2D {
+        Texture2D {
+            height,
+            width,
+            depth: 1,
+            mip_count: 1,
+            image_count: 1,
+            dimension: TextureDimension::Tex2D,
+            stream_data: None,
+            name: self.name.clone(),
+            data,
+            platform_blob: self.platform_blob.clone(),
+            ..*self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(format: TextureFormat, width: u32, height: u32, data: Vec<u8>) -> Texture2D {
+        Texture2D::new("test", width, height, format, data)
+    }
+
+    #[test]
+    fn test_image_size() {
+        assert_eq!(TextureFormat::RGBA32.image_size(3, 2), Some(24));
+        assert_eq!(TextureFormat::RGB24.image_size(3, 2), Some(18));
+        assert_eq!(TextureFormat::YUY2.image_size(3, 2), Some(16));
+        assert_eq!(TextureFormat::RGBAFloat.image_size(1, 1), Some(16));
+        assert_eq!(TextureFormat::DXT1.image_size(5, 4), Some(16));
+        assert_eq!(TextureFormat::DXT5.image_size(1, 1), Some(16));
+        assert_eq!(TextureFormat::AstcRgba6x6.image_size(13, 6), Some(48));
+        assert_eq!(TextureFormat::PvrtcRgba2.image_size(1, 1), Some(32));
+        assert_eq!(TextureFormat::PvrtcRgba4.image_size(16, 8), Some(64));
+        assert_eq!(TextureFormat::DXT1Crunched.image_size(4, 4), None);
+    }
+
+    #[test]
+    fn test_mips() {
+        // 4x2, 2x1 and 1x1 levels of one byte pixels
+        let mut tex = texture(TextureFormat::Alpha8, 4, 2, (0..11).collect());
+        tex.mip_count = 3;
+        assert_eq!(tex.mip_size(1), (2, 1, 1));
+        assert_eq!(tex.mip_size(5), (1, 1, 1));
+        assert!(tex.surface(1, 0).is_err());
+
+        let surfaces: Vec<Surface> = tex.surfaces().map(|s| s.unwrap()).collect();
+        assert_eq!(surfaces.len(), 3);
+        assert_eq!(surfaces[1].mip, 1);
+        assert_eq!(surfaces[1].texture.data, vec![8, 9]);
+        assert_eq!(surfaces[2].texture.data, vec![10]);
+        assert_eq!(surfaces[2].texture.mip_count, 1);
+
+        tex.data.truncate(10);
+        assert!(tex.surface(0, 2).is_err());
+    }
+
+    #[test]
+    fn test_cubemap() {
+        // six faces of 8x8 DXT1 with a 4x4 mip: 32 + 8 bytes each
+        let data: Vec<u8> = (0..240).map(|i| (i / 40) as u8).collect();
+        let mut tex = texture(TextureFormat::DXT1, 8, 8, data);
+        tex.mip_count = 2;
+        tex.image_count = 6;
+        tex.dimension = TextureDimension::Cube;
+        let order: Vec<(u32, u32)> = tex
+            .surfaces()
+            .map(|s| s.unwrap())
+            .map(|s| (s.slice, s.mip))
+            .collect();
+        assert_eq!(order.len(), 12);
+        assert_eq!(&order[..3], &[(0, 0), (0, 1), (1, 0)]);
+
+        let face = tex.surface(4, 1).unwrap();
+        assert_eq!((face.width, face.height), (4, 4));
+        assert_eq!(face.data, vec![4; 8]);
+        assert_eq!(tex.surface(5, 0).unwrap().data, vec![5; 32]);
+    }
+
+    #[test]
+    fn test_volume() {
+        // 2x2x2 with a 1x1x1 mip, stored mip by mip
+        let mut tex = texture(TextureFormat::R8, 2, 2, (0..9).collect());
+        tex.mip_count = 2;
+        tex.depth = 2;
+        tex.dimension = TextureDimension::Tex3D;
+        assert_eq!(tex.slice_count(0), 2);
+        assert_eq!(tex.slice_count(1), 1);
+        assert_eq!(tex.surface(1, 0).unwrap().data, vec![4, 5, 6, 7]);
+        assert_eq!(tex.surface(0, 1).unwrap().data, vec![8]);
+        assert!(tex.surface(1, 1).is_err());
+
+        let order: Vec<(u32, u32)> = tex
+            .surfaces()
+            .map(|s| s.unwrap())
+            .map(|s| (s.slice, s.mip))
+            .collect();
+        assert_eq!(order, vec![(0, 0), (1, 0), (0, 1)]);
+    }
+}
@@ -0,0 +1,894 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+mod astc;
+mod atc;
+mod bptc;
+mod convert;
+mod crunch;
+mod etc;
+mod export;
+mod layout;
+mod pvrtc;
+mod swizzle;
+
+pub(crate) use self::convert::half_to_f32;
+pub use self::layout::{Surface, Surfaces};
+
+use super::EngineObject;
+use bcndecode::{decode, BcnDecoderFormat, BcnEncoding};
+use enums::TextureSwizzle;
+use error::{Error, Result};
+use extras::containers::OrderedMap;
+use object::{ObjectValue, ToByteVec};
+use version::UnityVersion;
+
+pub trait IntoTexture2D {
+    fn to_texture2d(self) -> Result<Texture2D>;
+}
+
+pub trait IntoTexture2DArray {
+    fn to_texture2d_array(self) -> Result<Texture2D>;
+}
+
+pub trait IntoTexture3D {
+    fn to_texture3d(self) -> Result<Texture2D>;
+}
+
+/// A texture with all of its mip levels and images. Besides `Texture2D`
+/// objects this also describes `Cubemap`, `Texture2DArray` and `Texture3D`
+/// objects, see `surfaces` to access their individual images.
+pub struct Texture2D {
+    pub height: u32,
+    pub width: u32,
+    /// Depth of 3D textures, 1 otherwise
+    pub depth: u32,
+    pub name: String,
+    pub data: Vec<u8>,
+    pub texture_format: TextureFormat,
+    pub mip_count: u32,
+    /// Number of cubemap faces or array layers
+    pub image_count: u32,
+    pub dimension: TextureDimension,
+    pub is_readable: bool,
+    pub color_space: ColorSpace,
+    /// Location of the image data when it is stored in a resource file
+    pub stream_data: Option<StreamingInfo>,
+    /// Platform specific data, such as the tiling of Switch textures
+    pub platform_blob: Vec<u8>,
+    /// Tiling of the image data, see `set_platform`
+    pub swizzle: TextureSwizzle,
+    /// Unity version the texture was built with, see `set_version`
+    pub unity_version: Option<UnityVersion>,
+}
+
+/// Reference to data stored outside of the serialized file
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamingInfo {
+    pub offset: u64,
+    pub size: u32,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureDimension {
+    Tex2D,
+    Tex3D,
+    Cube,
+    Tex2DArray,
+    CubeArray,
+    Unknown(i32),
+}
+
+impl TextureDimension {
+    pub fn from_i32(n: i32) -> Self {
+        match n {
+            2 => TextureDimension::Tex2D,
+            3 => TextureDimension::Tex3D,
+            4 => TextureDimension::Cube,
+            5 => TextureDimension::Tex2DArray,
+            6 => TextureDimension::CubeArray,
+            _ => TextureDimension::Unknown(n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    Gamma,
+    Linear,
+    Unknown(i32),
+}
+
+impl ColorSpace {
+    pub fn from_i32(n: i32) -> Self {
+        match n {
+            0 => ColorSpace::Gamma,
+            1 => ColorSpace::Linear,
+            _ => ColorSpace::Unknown(n),
+        }
+    }
+}
+
+impl Texture2D {
+    /// Creates a single image texture without mip levels
+    pub fn new(
+        name: &str,
+        width: u32,
+        height: u32,
+        texture_format: TextureFormat,
+        data: Vec<u8>,
+    ) -> Self {
+        Texture2D {
+            height,
+            width,
+            depth: 1,
+            name: name.to_owned(),
+            data,
+            texture_format,
+            mip_count: 1,
+            image_count: 1,
+            dimension: TextureDimension::Tex2D,
+            is_readable: false,
+            color_space: ColorSpace::Gamma,
+            stream_data: None,
+            platform_blob: Vec::new(),
+            swizzle: TextureSwizzle::None,
+            unity_version: None,
+        }
+    }
+
+    /// Decodes the texture to RGBA8 pixels. Rows are kept in Unity's
+    /// bottom-up order, see `to_image_flipped` for top-down rows.
+    ///
+    /// The result always holds 4 bytes per pixel, whatever the format:
+    ///
+    /// - uncompressed formats are converted to RGBA8 as well, earlier versions
+    ///   returned their data as it is stored; `data` still holds those bytes.
+    ///   `Alpha8` becomes white with the alpha, single and two channel
+    ///   formats fill red and green.
+    /// - BC4 is expanded to opaque grayscale, earlier versions returned one
+    ///   luminance byte per pixel.
+    /// - HDR formats are clamped to [0, 1], see `to_image_f32` for their full
+    ///   range.
+    pub fn to_image(&self) -> Result<Vec<u8>> {
+        if self.is_swizzled() {
+            return self.deswizzle_top()?.to_image();
+        }
+        if self.texture_format.is_crunched() {
+            return self.surface(0, 0)?.to_image();
+        }
+        let (width, height) = (self.width as usize, self.height as usize);
+        if let Some(size) = self.texture_format.astc_block_size() {
+            return astc::decode_astc(&self.data, width, height, size, size);
+        }
+        let encoding = match self.texture_format {
+            TextureFormat::DXT1 => BcnEncoding::Bc1,
+            TextureFormat::DXT3 => BcnEncoding::Bc2,
+            TextureFormat::DXT5 => BcnEncoding::Bc3,
+            TextureFormat::BC4 => BcnEncoding::Bc4,
+            TextureFormat::BC5 => BcnEncoding::Bc5,
+            TextureFormat::BC7 => return bptc::decode_bc7(&self.data, width, height),
+            // RAW formats
+            TextureFormat::Alpha8
+            | TextureFormat::ARGB4444
+            | TextureFormat::RGBA4444
+            | TextureFormat::RGB565
+            | TextureFormat::RGB24
+            | TextureFormat::BGR24
+            | TextureFormat::RGBA32
+            | TextureFormat::ARGB32
+            | TextureFormat::BGRA32
+            | TextureFormat::R8
+            | TextureFormat::RG16
+            | TextureFormat::R16
+            | TextureFormat::RG32
+            | TextureFormat::RGB48
+            | TextureFormat::RGBA64
+            | TextureFormat::YUY2
+            | TextureFormat::R8Signed
+            | TextureFormat::RG16Signed
+            | TextureFormat::RGB24Signed
+            | TextureFormat::RGBA32Signed
+            | TextureFormat::R16Signed
+            | TextureFormat::RG32Signed
+            | TextureFormat::RGB48Signed
+            | TextureFormat::RGBA64Signed => {
+                return convert::to_rgba8(self.texture_format, &self.data, width * height);
+            }
+            // HDR formats are clamped to [0, 1]
+            TextureFormat::RHalf
+            | TextureFormat::RGHalf
+            | TextureFormat::RGBAHalf
+            | TextureFormat::RFloat
+            | TextureFormat::RGFloat
+            | TextureFormat::RGBFloat
+            | TextureFormat::RGBAFloat
+            | TextureFormat::ARGBFloat
+            | TextureFormat::RGB9e5Float
+            | TextureFormat::BC6H => {
+                return Ok(convert::f32_to_rgba8(&self.to_image_f32()?));
+            }
+            // Ericsson
+            TextureFormat::EtcRgb4 | TextureFormat::Etc3dsRgb4 => {
+                return etc::decode_etc1(&self.data, width, height);
+            }
+            TextureFormat::Etc2Rgb => return etc::decode_etc2_rgb(&self.data, width, height),
+            TextureFormat::Etc2Rgba1 => return etc::decode_etc2_rgba1(&self.data, width, height),
+            TextureFormat::Etc2Rgba8 | TextureFormat::Etc3dsRgba8 => {
+                return etc::decode_etc2_rgba8(&self.data, width, height);
+            }
+            TextureFormat::EacR | TextureFormat::EacRSigned => {
+                let signed = self.texture_format == TextureFormat::EacRSigned;
+                return etc::decode_eac_r(&self.data, width, height, signed);
+            }
+            TextureFormat::EacRg | TextureFormat::EacRgSigned => {
+                let signed = self.texture_format == TextureFormat::EacRgSigned;
+                return etc::decode_eac_rg(&self.data, width, height, signed);
+            }
+            // PowerVR
+            TextureFormat::PvrtcRgb2
+            | TextureFormat::Pvrtc2bppRgb
+            | TextureFormat::PvrtcRgba2
+            | TextureFormat::Pvrtc2bppRgba => {
+                return pvrtc::decode_pvrtc(&self.data, width, height, true);
+            }
+            TextureFormat::PvrtcRgb4
+            | TextureFormat::Pvrtc4bppRgb
+            | TextureFormat::PvrtcRgba4
+            | TextureFormat::Pvrtc4bppRgba => {
+                return pvrtc::decode_pvrtc(&self.data, width, height, false);
+            }
+            // ATC
+            TextureFormat::AtcRgb4 => return atc::decode_atc_rgb(&self.data, width, height),
+            TextureFormat::AtcRgba8 => return atc::decode_atc_rgba(&self.data, width, height),
+            _ => {
+                return Err(Error::EngineError(format!(
+                    "Image encoding is not supported: {:?}",
+                    self.texture_format
+                )));
+            }
+        };
+
+        let format = match self.texture_format.pixel_format() {
+            PixelFormat::RGB | PixelFormat::RGB16 => BcnDecoderFormat::RGBA,
+            _ => match self.texture_format {
+                TextureFormat::BC4 => BcnDecoderFormat::LUM,
+                _ => BcnDecoderFormat::RGBA,
+            },
+        };
+
+        let result = decode(&self.data, width, height, encoding, format)?;
+        match format {
+            BcnDecoderFormat::LUM => Ok(convert::luminance_to_rgba8(&result)),
+            _ => Ok(result),
+        }
+    }
+
+    /// Decodes the texture to RGBA32F pixels in Unity's bottom-up row order.
+    /// HDR formats keep their full range, signed formats are in [-1, 1] and
+    /// all other formats in [0, 1].
+    pub fn to_image_f32(&self) -> Result<Vec<f32>> {
+        if self.is_swizzled() {
+            return self.deswizzle_top()?.to_image_f32();
+        }
+        let (width, height) = (self.width as usize, self.height as usize);
+        if let Some(size) = self.texture_format.astc_block_size() {
+            return astc::decode_astc_f32(&self.data, width, height, size, size);
+        }
+        match self.texture_format {
+            TextureFormat::BC6H => bptc::decode_bc6h(&self.data, width, height, false),
+            TextureFormat::RHalf
+            | TextureFormat::RGHalf
+            | TextureFormat::RGBAHalf
+            | TextureFormat::RFloat
+            | TextureFormat::RGFloat
+            | TextureFormat::RGBFloat
+            | TextureFormat::RGBAFloat
+            | TextureFormat::ARGBFloat
+            | TextureFormat::RGB9e5Float
+            | TextureFormat::R16
+            | TextureFormat::RG32
+            | TextureFormat::RGB48
+            | TextureFormat::RGBA64
+            | TextureFormat::R8Signed
+            | TextureFormat::RG16Signed
+            | TextureFormat::RGB24Signed
+            | TextureFormat::RGBA32Signed
+            | TextureFormat::R16Signed
+            | TextureFormat::RG32Signed
+            | TextureFormat::RGB48Signed
+            | TextureFormat::RGBA64Signed => {
+                convert::to_rgba_f32(self.texture_format, &self.data, width * height)
+            }
+            _ => Ok(convert::rgba8_to_f32(&self.to_image()?)),
+        }
+    }
+
+    /// Decodes the texture to RGBA32F pixels with the first row at the top
+    pub fn to_image_f32_flipped(&self) -> Result<Vec<f32>> {
+        Ok(convert::flip_vertical(
+            &self.to_image_f32()?,
+            self.width as usize,
+            self.height as usize,
+            4,
+        ))
+    }
+
+    /// Decodes the texture to RGBA8 pixels with the first row at the top
+    pub fn to_image_flipped(&self) -> Result<Vec<u8>> {
+        Ok(convert::flip_vertical(
+            &self.to_image()?,
+            self.width as usize,
+            self.height as usize,
+            4,
+        ))
+    }
+}
+
+impl IntoTexture2D for EngineObject {
+    /// Reads `Texture2D` and `Cubemap` objects
+    fn to_texture2d(self) -> Result<Texture2D> {
+        let width = tryGet!(self.map, "m_Width").to_i32()? as u32;
+        let height = tryGet!(self.map, "m_Height").to_i32()? as u32;
+        let mip_count = match get_i32(&self.map, "m_MipCount")? {
+            Some(count) => count as u32,
+            // before Unity 5.2 textures only flagged a full mip chain
+            None => match self.map.get(&"m_MipMap".to_string()) {
+                Some(mip_map) if mip_map.to_bool()? => {
+                    32 - width.max(height).max(1).leading_zeros()
+                }
+                _ => 1,
+            },
+        };
+        let mut texture = Texture2D {
+            height,
+            width,
+            depth: 1,
+            name: tryGet!(self.map, "m_Name").to_string()?,
+            data: tryGet!(self.map, "image data").to_byte_vec()?,
+            texture_format: TextureFormat::from_u32(
+                tryGet!(self.map, "m_TextureFormat").to_i32()? as u32,
+            )?,
+            mip_count: mip_count.max(1),
+            image_count: get_i32(&self.map, "m_ImageCount")?.unwrap_or(1).max(1) as u32,
+            dimension: TextureDimension::from_i32(
+                get_i32(&self.map, "m_TextureDimension")?.unwrap_or(2),
+            ),
+            is_readable: get_bool(&self.map, "m_IsReadable")?,
+            color_space: ColorSpace::from_i32(get_i32(&self.map, "m_ColorSpace")?.unwrap_or(0)),
+            stream_data: get_stream_data(&self.map)?,
+            platform_blob: get_byte_vec(&self.map, "m_PlatformBlob")?,
+            swizzle: TextureSwizzle::None,
+            unity_version: None,
+        };
+        set_asset_info(&self, &mut texture);
+        Ok(texture)
+    }
+}
+
+impl IntoTexture2DArray for EngineObject {
+    fn to_texture2d_array(self) -> Result<Texture2D> {
+        let mut texture = read_layered_texture(&self.map)?;
+        texture.image_count = tryGet!(self.map, "m_Depth").to_i32()?.max(1) as u32;
+        texture.dimension = TextureDimension::Tex2DArray;
+        set_asset_info(&self, &mut texture);
+        Ok(texture)
+    }
+}
+
+impl IntoTexture3D for EngineObject {
+    fn to_texture3d(self) -> Result<Texture2D> {
+        let mut texture = read_layered_texture(&self.map)?;
+        texture.depth = tryGet!(self.map, "m_Depth").to_i32()?.max(1) as u32;
+        texture.dimension = TextureDimension::Tex3D;
+        set_asset_info(&self, &mut texture);
+        Ok(texture)
+    }
+}
+
+/// Applies what the object knows about its asset to the texture
+fn set_asset_info(object: &EngineObject, texture: &mut Texture2D) {
+    if let Some(platform) = object.target_platform {
+        texture.set_platform(platform);
+    }
+    if let Some(version) = object.unity_version {
+        texture.set_version(version);
+    }
+}
+
+/// Reads the fields `Texture2DArray` and `Texture3D` objects share
+fn read_layered_texture(map: &OrderedMap<String, ObjectValue>) -> Result<Texture2D> {
+    let format = tryGet!(map, "m_Format").to_i32()? as u32;
+    // m_Format became a GraphicsFormat when m_UsageMode was added in 2019.1
+    let texture_format = if map.get(&"m_UsageMode".to_string()).is_some() {
+        TextureFormat::from_graphics_format(format)?
+    } else {
+        TextureFormat::from_u32(format)?
+    };
+    Ok(Texture2D {
+        height: tryGet!(map, "m_Height").to_i32()? as u32,
+        width: tryGet!(map, "m_Width").to_i32()? as u32,
+        depth: 1,
+        name: tryGet!(map, "m_Name").to_string()?,
+        data: tryGet!(map, "image data").to_byte_vec()?,
+        texture_format,
+        mip_count: get_i32(map, "m_MipCount")?.unwrap_or(1).max(1) as u32,
+        image_count: 1,
+        dimension: TextureDimension::Tex2D,
+        is_readable: get_bool(map, "m_IsReadable")?,
+        color_space: ColorSpace::from_i32(get_i32(map, "m_ColorSpace")?.unwrap_or(0)),
+        stream_data: get_stream_data(map)?,
+        platform_blob: get_byte_vec(map, "m_PlatformBlob")?,
+        swizzle: TextureSwizzle::None,
+        unity_version: None,
+    })
+}
+
+fn get_i32(map: &OrderedMap<String, ObjectValue>, key: &str) -> Result<Option<i32>> {
+    match map.get(&key.to_string()) {
+        Some(value) => Ok(Some(value.to_i32()?)),
+        None => Ok(None),
+    }
+}
+
+fn get_bool(map: &OrderedMap<String, ObjectValue>, key: &str) -> Result<bool> {
+    match map.get(&key.to_string()) {
+        Some(value) => value.to_bool(),
+        None => Ok(false),
+    }
+}
+
+fn get_byte_vec(map: &OrderedMap<String, ObjectValue>, key: &str) -> Result<Vec<u8>> {
+    match map.get(&key.to_string()) {
+        Some(value) => value.to_byte_vec(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn get_stream_data(map: &OrderedMap<String, ObjectValue>) -> Result<Option<StreamingInfo>> {
+    let stream_data = match map.get(&"m_StreamData".to_string()) {
+        Some(ObjectValue::Map(stream_data)) => stream_data,
+        _ => return Ok(None),
+    };
+    let path = tryGet!(stream_data, "path").to_string()?;
+    if path.is_empty() {
+        return Ok(None);
+    }
+    // the offset was widened to 64 bits in 2020.1
+    let offset = match tryGet!(stream_data, "offset") {
+        ObjectValue::U32(offset) => u64::from(*offset),
+        offset => offset.to_u64()?,
+    };
+    Ok(Some(StreamingInfo {
+        offset,
+        size: tryGet!(stream_data, "size").to_u32()?,
+        path,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFormat {
+    Alpha8,
+    ARGB4444,
+    RGB24,
+    RGBA32,
+    ARGB32,
+    ARGBFloat,
+    RGB565,
+    BGR24,
+    R16,
+
+    // Direct3D
+    DXT1,
+    DXT3,
+    DXT5,
+
+    RGBA4444,
+    BGRA32,
+
+    // HDR
+    RHalf,
+    RGHalf,
+    RGBAHalf,
+    RFloat,
+    RGFloat,
+    RGBAFloat,
+    YUY2,
+    RGB9e5Float,
+    RGBFloat,
+
+    // Direct3D 10
+    BC4,
+    BC5,
+    DXT1Crunched,
+    DXT5Crunched,
+
+    // Direct3D 11
+    BC6H,
+    BC7,
+
+    // PowerVR
+    PvrtcRgb2,
+    Pvrtc2bppRgb,
+    PvrtcRgba2,
+    Pvrtc2bppRgba,
+    PvrtcRgb4,
+    Pvrtc4bppRgb,
+    PvrtcRgba4,
+    Pvrtc4bppRgba,
+
+    // Ericsson (Android)
+    EtcRgb4,
+    AtcRgb4,
+    AtcRgba8,
+
+    // Adobe ATF
+    AtfRgbDxt1,
+    AtfRgbaJpg,
+    AtfRgbJpg,
+
+    // Ericsson
+    EacR,
+    EacRSigned,
+    EacRg,
+    EacRgSigned,
+    Etc2Rgb,
+    Etc2Rgba1,
+    Etc2Rgba8,
+
+    // OpenGL / GLES
+    AstcRgb4x4,
+    AstcRgb5x5,
+    AstcRgb6x6,
+    AstcRgb8x8,
+    AstcRgb10x10,
+    AstcRgb12x12,
+    AstcRgba4x4,
+    AstcRgba5x5,
+    AstcRgba6x6,
+    AstcRgba8x8,
+    AstcRgba10x10,
+    AstcRgba12x12,
+
+    // Nintendo 3DS
+    Etc3dsRgb4,
+    Etc3dsRgba8,
+
+    RG16,
+    R8,
+    EtcRgb4Crunched,
+    Etc2Rgba8Crunched,
+
+    // ASTC HDR
+    AstcHdr4x4,
+    AstcHdr5x5,
+    AstcHdr6x6,
+    AstcHdr8x8,
+    AstcHdr10x10,
+    AstcHdr12x12,
+
+    // 16 bit per channel
+    RG32,
+    RGB48,
+    RGBA64,
+
+    // Signed normalized
+    R8Signed,
+    RG16Signed,
+    RGB24Signed,
+    RGBA32Signed,
+    R16Signed,
+    RG32Signed,
+    RGB48Signed,
+    RGBA64Signed,
+}
+
+impl TextureFormat {
+    pub fn from_u32(n: u32) -> Result<Self> {
+        match n {
+            1 => Ok(TextureFormat::Alpha8),
+            2 => Ok(TextureFormat::ARGB4444),
+            3 => Ok(TextureFormat::RGB24),
+            4 => Ok(TextureFormat::RGBA32),
+            5 => Ok(TextureFormat::ARGB32),
+            6 => Ok(TextureFormat::ARGBFloat),
+            7 => Ok(TextureFormat::RGB565),
+            8 => Ok(TextureFormat::BGR24),
+            9 => Ok(TextureFormat::R16),
+
+            // Direct3D
+            10 => Ok(TextureFormat::DXT1),
+            11 => Ok(TextureFormat::DXT3),
+            12 => Ok(TextureFormat::DXT5),
+
+            13 => Ok(TextureFormat::RGBA4444),
+            14 => Ok(TextureFormat::BGRA32),
+
+            // HDR
+            15 => Ok(TextureFormat::RHalf),
+            16 => Ok(TextureFormat::RGHalf),
+            17 => Ok(TextureFormat::RGBAHalf),
+            18 => Ok(TextureFormat::RFloat),
+            19 => Ok(TextureFormat::RGFloat),
+            20 => Ok(TextureFormat::RGBAFloat),
+            21 => Ok(TextureFormat::YUY2),
+            22 => Ok(TextureFormat::RGB9e5Float),
+            23 => Ok(TextureFormat::RGBFloat),
+
+            // Direct3D 10
+            26 => Ok(TextureFormat::BC4),
+            27 => Ok(TextureFormat::BC5),
+            28 => Ok(TextureFormat::DXT1Crunched),
+            29 => Ok(TextureFormat::DXT5Crunched),
+
+            // Direct3D 11
+            24 => Ok(TextureFormat::BC6H),
+            25 => Ok(TextureFormat::BC7),
+
+            // PowerVR
+            30 => Ok(TextureFormat::PvrtcRgb2),  // Pvrtc2bppRgb
+            31 => Ok(TextureFormat::PvrtcRgba2), // Pvrtc2bppRgba
+            32 => Ok(TextureFormat::PvrtcRgb4),  // Pvrtc4bppRgb
+            33 => Ok(TextureFormat::PvrtcRgba4), // Pvrtc4bppRgba
+
+            // Ericsson (Android)
+            34 => Ok(TextureFormat::EtcRgb4),
+            35 => Ok(TextureFormat::AtcRgb4),
+            36 => Ok(TextureFormat::AtcRgba8),
+
+            // Adobe ATF
+            38 => Ok(TextureFormat::AtfRgbDxt1),
+            39 => Ok(TextureFormat::AtfRgbaJpg),
+            40 => Ok(TextureFormat::AtfRgbJpg),
+
+            // Ericsson
+            41 => Ok(TextureFormat::EacR),
+            42 => Ok(TextureFormat::EacRSigned),
+            43 => Ok(TextureFormat::EacRg),
+            44 => Ok(TextureFormat::EacRgSigned),
+            45 => Ok(TextureFormat::Etc2Rgb),
+            46 => Ok(TextureFormat::Etc2Rgba1),
+            47 => Ok(TextureFormat::Etc2Rgba8),
+
+            // OpenGL / GLES
+            48 => Ok(TextureFormat::AstcRgb4x4),
+            49 => Ok(TextureFormat::AstcRgb5x5),
+            50 => Ok(TextureFormat::AstcRgb6x6),
+            51 => Ok(TextureFormat::AstcRgb8x8),
+            52 => Ok(TextureFormat::AstcRgb10x10),
+            53 => Ok(TextureFormat::AstcRgb12x12),
+            54 => Ok(TextureFormat::AstcRgba4x4),
+            55 => Ok(TextureFormat::AstcRgba5x5),
+            56 => Ok(TextureFormat::AstcRgba6x6),
+            57 => Ok(TextureFormat::AstcRgba8x8),
+            58 => Ok(TextureFormat::AstcRgba10x10),
+            59 => Ok(TextureFormat::AstcRgba12x12),
+
+            // Nintendo 3DS
+            60 => Ok(TextureFormat::Etc3dsRgb4),
+            61 => Ok(TextureFormat::Etc3dsRgba8),
+
+            62 => Ok(TextureFormat::RG16),
+            63 => Ok(TextureFormat::R8),
+            64 => Ok(TextureFormat::EtcRgb4Crunched),
+            65 => Ok(TextureFormat::Etc2Rgba8Crunched),
+
+            // ASTC HDR
+            66 => Ok(TextureFormat::AstcHdr4x4),
+            67 => Ok(TextureFormat::AstcHdr5x5),
+            68 => Ok(TextureFormat::AstcHdr6x6),
+            69 => Ok(TextureFormat::AstcHdr8x8),
+            70 => Ok(TextureFormat::AstcHdr10x10),
+            71 => Ok(TextureFormat::AstcHdr12x12),
+
+            // 16 bit per channel
+            72 => Ok(TextureFormat::RG32),
+            73 => Ok(TextureFormat::RGB48),
+            74 => Ok(TextureFormat::RGBA64),
+
+            // Signed normalized
+            75 => Ok(TextureFormat::R8Signed),
+            76 => Ok(TextureFormat::RG16Signed),
+            77 => Ok(TextureFormat::RGB24Signed),
+            78 => Ok(TextureFormat::RGBA32Signed),
+            79 => Ok(TextureFormat::R16Signed),
+            80 => Ok(TextureFormat::RG32Signed),
+            81 => Ok(TextureFormat::RGB48Signed),
+            82 => Ok(TextureFormat::RGBA64Signed),
+            _ => Err(Error::EngineError(format!(
+                "Unidentified texture format: {}",
+                n
+            ))),
+        }
+    }
+
+    /// Maps the `GraphicsFormat` of newer texture objects to the texture
+    /// format with the same data layout. sRGB and linear variants map to the
+    /// same format, integer formats are read as normalized.
+    pub fn from_graphics_format(n: u32) -> Result<Self> {
+        match n {
+            1 | 5 | 13 => Ok(TextureFormat::R8),
+            2 | 6 | 14 => Ok(TextureFormat::RG16),
+            3 | 7 | 15 => Ok(TextureFormat::RGB24),
+            4 | 8 | 16 => Ok(TextureFormat::RGBA32),
+            9 | 17 => Ok(TextureFormat::R8Signed),
+            10 | 18 => Ok(TextureFormat::RG16Signed),
+            11 | 19 => Ok(TextureFormat::RGB24Signed),
+            12 | 20 => Ok(TextureFormat::RGBA32Signed),
+            21 | 29 => Ok(TextureFormat::R16),
+            22 | 30 => Ok(TextureFormat::RG32),
+            23 | 31 => Ok(TextureFormat::RGB48),
+            24 | 32 => Ok(TextureFormat::RGBA64),
+            25 | 33 => Ok(TextureFormat::R16Signed),
+            26 | 34 => Ok(TextureFormat::RG32Signed),
+            27 | 35 => Ok(TextureFormat::RGB48Signed),
+            28 | 36 => Ok(TextureFormat::RGBA64Signed),
+            45 => Ok(TextureFormat::RHalf),
+            46 => Ok(TextureFormat::RGHalf),
+            48 => Ok(TextureFormat::RGBAHalf),
+            49 => Ok(TextureFormat::RFloat),
+            50 => Ok(TextureFormat::RGFloat),
+            51 => Ok(TextureFormat::RGBFloat),
+            52 => Ok(TextureFormat::RGBAFloat),
+            56 | 58 | 62 => Ok(TextureFormat::BGR24),
+            57 | 59 | 63 => Ok(TextureFormat::BGRA32),
+            66 => Ok(TextureFormat::RGBA4444),
+            67 => Ok(TextureFormat::ARGB4444),
+            69 => Ok(TextureFormat::RGB565),
+            73 => Ok(TextureFormat::RGB9e5Float),
+            96 | 97 => Ok(TextureFormat::DXT1),
+            98 | 99 => Ok(TextureFormat::DXT3),
+            100 | 101 => Ok(TextureFormat::DXT5),
+            102 => Ok(TextureFormat::BC4),
+            104 => Ok(TextureFormat::BC5),
+            106 => Ok(TextureFormat::BC6H),
+            108 | 109 => Ok(TextureFormat::BC7),
+            110 | 111 => Ok(TextureFormat::PvrtcRgb2),
+            112 | 113 => Ok(TextureFormat::PvrtcRgb4),
+            114 | 115 => Ok(TextureFormat::PvrtcRgba2),
+            116 | 117 => Ok(TextureFormat::PvrtcRgba4),
+            118 => Ok(TextureFormat::EtcRgb4),
+            119 | 120 => Ok(TextureFormat::Etc2Rgb),
+            121 | 122 => Ok(TextureFormat::Etc2Rgba1),
+            123 | 124 => Ok(TextureFormat::Etc2Rgba8),
+            125 => Ok(TextureFormat::EacR),
+            126 => Ok(TextureFormat::EacRSigned),
+            127 => Ok(TextureFormat::EacRg),
+            128 => Ok(TextureFormat::EacRgSigned),
+            129 | 130 => Ok(TextureFormat::AstcRgba4x4),
+            131 | 132 => Ok(TextureFormat::AstcRgba5x5),
+            133 | 134 => Ok(TextureFormat::AstcRgba6x6),
+            135 | 136 => Ok(TextureFormat::AstcRgba8x8),
+            137 | 138 => Ok(TextureFormat::AstcRgba10x10),
+            139 | 140 => Ok(TextureFormat::AstcRgba12x12),
+            145 => Ok(TextureFormat::AstcHdr4x4),
+            146 => Ok(TextureFormat::AstcHdr5x5),
+            147 => Ok(TextureFormat::AstcHdr6x6),
+            148 => Ok(TextureFormat::AstcHdr8x8),
+            149 => Ok(TextureFormat::AstcHdr10x10),
+            150 => Ok(TextureFormat::AstcHdr12x12),
+            _ => Err(Error::EngineError(format!(
+                "Unsupported graphics format: {}",
+                n
+            ))),
+        }
+    }
+
+    /// Whether the format can hold values outside of the [0, 1] range
+    pub fn is_hdr(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::RHalf
+                | TextureFormat::RGHalf
+                | TextureFormat::RGBAHalf
+                | TextureFormat::RFloat
+                | TextureFormat::RGFloat
+                | TextureFormat::RGBFloat
+                | TextureFormat::RGBAFloat
+                | TextureFormat::ARGBFloat
+                | TextureFormat::RGB9e5Float
+                | TextureFormat::BC6H
+                | TextureFormat::AstcHdr4x4
+                | TextureFormat::AstcHdr5x5
+                | TextureFormat::AstcHdr6x6
+                | TextureFormat::AstcHdr8x8
+                | TextureFormat::AstcHdr10x10
+                | TextureFormat::AstcHdr12x12
+        )
+    }
+
+    /// The square block footprint of ASTC formats
+    fn astc_block_size(&self) -> Option<usize> {
+        match self {
+            TextureFormat::AstcRgb4x4 | TextureFormat::AstcRgba4x4 | TextureFormat::AstcHdr4x4 => {
+                Some(4)
+            }
+            TextureFormat::AstcRgb5x5 | TextureFormat::AstcRgba5x5 | TextureFormat::AstcHdr5x5 => {
+                Some(5)
+            }
+            TextureFormat::AstcRgb6x6 | TextureFormat::AstcRgba6x6 | TextureFormat::AstcHdr6x6 => {
+                Some(6)
+            }
+            TextureFormat::AstcRgb8x8 | TextureFormat::AstcRgba8x8 | TextureFormat::AstcHdr8x8 => {
+                Some(8)
+            }
+            TextureFormat::AstcRgb10x10
+            | TextureFormat::AstcRgba10x10
+            | TextureFormat::AstcHdr10x10 => Some(10),
+            TextureFormat::AstcRgb12x12
+            | TextureFormat::AstcRgba12x12
+            | TextureFormat::AstcHdr12x12 => Some(12),
+            _ => None,
+        }
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        match self {
+            TextureFormat::RGB24 => PixelFormat::RGB,
+            TextureFormat::ARGB32 => PixelFormat::ARGB,
+            TextureFormat::RGB565 => PixelFormat::RGB16,
+            TextureFormat::Alpha8 => PixelFormat::A,
+            TextureFormat::RGBA4444 => PixelFormat::RGBA4B,
+            TextureFormat::ARGB4444 => PixelFormat::ARGB4B,
+            _ => PixelFormat::RGBA,
+        }
+    }
+}
+
+pub enum PixelFormat {
+    RGB,
+    RGBA,
+    ARGB,
+    RGB16,
+    A,
+    RGBA4B,
+    ARGB4B,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use enums::BuildTarget;
+    use std::ffi::OsString;
+
+    /// A 4x4 RGBA32 `Texture2D` object
+    fn texture_object() -> EngineObject {
+        let mut map = OrderedMap::default();
+        map.insert(
+            "m_Name".to_string(),
+            ObjectValue::String(OsString::from("test")),
+        );
+        map.insert("m_Width".to_string(), ObjectValue::I32(4));
+        map.insert("m_Height".to_string(), ObjectValue::I32(4));
+        map.insert("m_TextureFormat".to_string(), ObjectValue::I32(4));
+        map.insert("image data".to_string(), ObjectValue::U8Array(vec![0; 64]));
+        EngineObject::new(map)
+    }
+
+    #[test]
+    fn test_asset_info() {
+        let texture = texture_object().to_texture2d().unwrap();
+        assert_eq!(texture.texture_format, TextureFormat::RGBA32);
+        assert_eq!(texture.swizzle, TextureSwizzle::None);
+
+        let mut object = texture_object();
+        object.target_platform = Some(BuildTarget::Switch);
+        let texture = object.to_texture2d().unwrap();
+        assert_eq!(texture.swizzle, TextureSwizzle::Switch);
+        assert_eq!(texture.unity_version, None);
+
+        let mut object = texture_object();
+        object.unity_version = Some(UnityVersion::new(2018, 4, 0));
+        object
+            .map
+            .insert("m_TextureFormat".to_string(), ObjectValue::I32(28));
+        let texture = object.to_texture2d().unwrap();
+        assert_eq!(texture.unity_version, Some(UnityVersion::new(2018, 4, 0)));
+        assert!(texture.uses_unity_crunch());
+    }
+}
@@ -0,0 +1,380 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! PVRTC1 2bpp and 4bpp decoder.
+//!
+//! Each 64 bit word holds two low resolution colors and the modulation
+//! data of a 8x4 (2bpp) or 4x4 (4bpp) pixel block. The colors are upscaled
+//! bilinearly from the block centers, wrapping around the texture edges, and
+//! blended per pixel by the modulation value. Words are stored in Morton
+//! order.
+
+use error::{Error, Result};
+
+/// Modulation weights (out of 8) of the 2 bit modulation values
+const MODULATION_WEIGHTS: [u32; 4] = [0, 3, 5, 8];
+
+struct Word {
+    modulation: u32,
+    color: u32,
+}
+
+/// Color A of a word as 5 bit RGB and 4 bit alpha
+fn color_a(color: u32) -> [u32; 4] {
+    if color & 0x8000 != 0 {
+        // opaque RGB554
+        let b = (color >> 1) & 0xf;
+        [
+            (color >> 10) & 0x1f,
+            (color >> 5) & 0x1f,
+            b << 1 | b >> 3,
+            0xf,
+        ]
+    } else {
+        // translucent ARGB3443
+        let (r, g, b) = ((color >> 8) & 0xf, (color >> 4) & 0xf, (color >> 1) & 0x7);
+        [
+            r << 1 | r >> 3,
+            g << 1 | g >> 3,
+            b << 2 | b >> 1,
+            ((color >> 12) & 0x7) << 1,
+        ]
+    }
+}
+
+/// Color B of a word as 5 bit RGB and 4 bit alpha
+fn color_b(color: u32) -> [u32; 4] {
+    if color & 0x8000_0000 != 0 {
+        // opaque RGB555
+        [
+            (color >> 26) & 0x1f,
+            (color >> 21) & 0x1f,
+            (color >> 16) & 0x1f,
+            0xf,
+        ]
+    } else {
+        // translucent ARGB3444
+        let (r, g, b) = (
+            (color >> 24) & 0xf,
+            (color >> 20) & 0xf,
+            (color >> 16) & 0xf,
+        );
+        [
+            r << 1 | r >> 3,
+            g << 1 | g >> 3,
+            b << 1 | b >> 3,
+            ((color >> 28) & 0x7) << 1,
+        ]
+    }
+}
+
+/// Morton index of a word, the bits of the smaller dimension are
+/// interleaved with y in the lower bit
+fn twiddle(x: usize, y: usize, blocks_x: usize, blocks_y: usize) -> usize {
+    let (min_dimension, mut remainder) = if blocks_y < blocks_x {
+        (blocks_y, x)
+    } else {
+        (blocks_x, y)
+    };
+    let mut result = 0;
+    let mut bit = 1;
+    let mut shift = 0;
+    while bit < min_dimension {
+        if y & bit != 0 {
+            result |= 1 << (2 * shift);
+        }
+        if x & bit != 0 {
+            result |= 1 << (2 * shift + 1);
+        }
+        bit <<= 1;
+        shift += 1;
+    }
+    remainder >>= shift;
+    result | remainder << (2 * shift)
+}
+
+/// Decodes PVRTC1 data, `two_bpp` selects the 2 bits per pixel variant
+pub fn decode_pvrtc(data: &[u8], width: usize, height: usize, two_bpp: bool) -> Result<Vec<u8>> {
+    let block_width = if two_bpp { 8 } else { 4 };
+    let block_height = 4;
+    let blocks_x = width.div_ceil(block_width).max(2);
+    let blocks_y = height.div_ceil(block_height).max(2);
+    if !blocks_x.is_power_of_two() || !blocks_y.is_power_of_two() {
+        return Err(Error::EngineError(format!(
+            "PVRTC textures must have power of two dimensions, got {}x{}",
+            width, height
+        )));
+    }
+    if data.len() < blocks_x * blocks_y * 8 {
+        return Err(Error::EngineError(format!(
+            "Image data of {} bytes is too short for {}x{} PVRTC words",
+            data.len(),
+            blocks_x,
+            blocks_y
+        )));
+    }
+
+    let read_u32 = |offset: usize| {
+        u32::from(data[offset])
+            | u32::from(data[offset + 1]) << 8
+            | u32::from(data[offset + 2]) << 16
+            | u32::from(data[offset + 3]) << 24
+    };
+    let mut words = Vec::with_capacity(blocks_x * blocks_y);
+    for y in 0..blocks_y {
+        for x in 0..blocks_x {
+            let offset = twiddle(x, y, blocks_x, blocks_y) * 8;
+            words.push(Word {
+                modulation: read_u32(offset),
+                color: read_u32(offset + 4),
+            });
+        }
+    }
+
+    // per pixel 2 bit modulation values and the modulation mode of the word
+    let full_width = blocks_x * block_width;
+    let full_height = blocks_y * block_height;
+    let mut values = vec![0u32; full_width * full_height];
+    let mut modes = vec![0u32; full_width * full_height];
+    for (i, word) in words.iter().enumerate() {
+        let (block_x, block_y) = (i % blocks_x * block_width, i / blocks_x * block_height);
+        let (mode, mut bits) = unpack_mode(word, two_bpp);
+        for y in 0..block_height {
+            for x in 0..block_width {
+                let index = (block_y + y) * full_width + block_x + x;
+                modes[index] = mode;
+                if !two_bpp || mode != 0 {
+                    // 2bpp interpolated modes only store every other pixel
+                    if !two_bpp || (x ^ y) & 1 == 0 {
+                        values[index] = bits & 3;
+                        bits >>= 2;
+                    }
+                } else {
+                    values[index] = (bits & 1) * 3;
+                    bits >>= 1;
+                }
+            }
+        }
+    }
+    let value = |x: isize, y: isize| {
+        let x = x.rem_euclid(full_width as isize) as usize;
+        let y = y.rem_euclid(full_height as isize) as usize;
+        MODULATION_WEIGHTS[values[y * full_width + x] as usize]
+    };
+
+    let mut result = Vec::with_capacity(width * height * 4);
+    let total = (block_width * block_height) as u32;
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * full_width + x;
+            let (modulation, punch_through) = match (two_bpp, modes[index]) {
+                (_, 0) => (MODULATION_WEIGHTS[values[index] as usize], false),
+                // 4bpp punch-through mode
+                (false, _) => match values[index] {
+                    0 => (0, false),
+                    1 => (4, false),
+                    2 => (4, true),
+                    _ => (8, false),
+                },
+                (true, mode) => {
+                    let (x, y) = (x as isize, y as isize);
+                    let modulation = if (x ^ y) & 1 == 0 {
+                        value(x, y)
+                    } else if mode == 1 {
+                        (value(x, y - 1) + value(x, y + 1) + value(x - 1, y) + value(x + 1, y) + 2)
+                            / 4
+                    } else if mode == 2 {
+                        (value(x - 1, y) + value(x + 1, y)).div_ceil(2)
+                    } else {
+                        (value(x, y - 1) + value(x, y + 1)).div_ceil(2)
+                    };
+                    (modulation, false)
+                }
+            };
+
+            // the four words whose centers surround the pixel
+            let ax = x + block_width - block_width / 2;
+            let ay = y + block_height - block_height / 2;
+            let (fx, fy) = ((ax % block_width) as u32, (ay % block_height) as u32);
+            let x0 = (ax / block_width + blocks_x - 1) % blocks_x;
+            let y0 = (ay / block_height + blocks_y - 1) % blocks_y;
+            let x1 = (x0 + 1) % blocks_x;
+            let y1 = (y0 + 1) % blocks_y;
+            let corners = [
+                (
+                    &words[y0 * blocks_x + x0],
+                    (block_width as u32 - fx) * (4 - fy),
+                ),
+                (&words[y0 * blocks_x + x1], fx * (4 - fy)),
+                (&words[y1 * blocks_x + x0], (block_width as u32 - fx) * fy),
+                (&words[y1 * blocks_x + x1], fx * fy),
+            ];
+            let mut a = [0u32; 4];
+            let mut b = [0u32; 4];
+            for &(word, weight) in &corners {
+                let (ca, cb) = (color_a(word.color), color_b(word.color));
+                for c in 0..4 {
+                    a[c] += ca[c] * weight;
+                    b[c] += cb[c] * weight;
+                }
+            }
+
+            let mut pixel = [0u8; 4];
+            for c in 0..4 {
+                // scale from 5 bit color and 4 bit alpha to 8 bits
+                let (a, b) = if c < 3 {
+                    (
+                        a[c] * 8 / total + a[c] / (4 * total),
+                        b[c] * 8 / total + b[c] / (4 * total),
+                    )
+                } else {
+                    (
+                        a[c] / total + a[c] * 16 / total,
+                        b[c] / total + b[c] * 16 / total,
+                    )
+                };
+                pixel[c] = ((a * (8 - modulation) + b * modulation) / 8) as u8;
+            }
+            if punch_through {
+                pixel[3] = 0;
+            }
+            result.extend_from_slice(&pixel);
+        }
+    }
+    Ok(result)
+}
+
+/// Modulation mode and modulation bits of a word. The 2bpp interpolated
+/// modes are 1 (horizontal and vertical), 2 (horizontal) and 3 (vertical).
+fn unpack_mode(word: &Word, two_bpp: bool) -> (u32, u32) {
+    let mut mode = word.color & 1;
+    let mut bits = word.modulation;
+    if two_bpp && mode == 1 {
+        if bits & 1 != 0 {
+            // the low bit of the center pixel selects the direction, its
+            // high bit is duplicated
+            mode = if bits & (1 << 20) != 0 { 3 } else { 2 };
+            if bits & (1 << 21) != 0 {
+                bits |= 1 << 20;
+            } else {
+                bits &= !(1 << 20);
+            }
+        }
+        if bits & 2 != 0 {
+            bits |= 1;
+        } else {
+            bits &= !1;
+        }
+    }
+    (mode, bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for &(modulation, color) in words {
+            data.extend_from_slice(&modulation.to_le_bytes());
+            data.extend_from_slice(&color.to_le_bytes());
+        }
+        data
+    }
+
+    fn pixel(image: &[u8], width: usize, x: usize, y: usize) -> &[u8] {
+        let offset = (y * width + x) * 4;
+        &image[offset..offset + 4]
+    }
+
+    #[test]
+    fn test_twiddle() {
+        assert_eq!(twiddle(0, 1, 2, 2), 1);
+        assert_eq!(twiddle(1, 0, 2, 2), 2);
+        assert_eq!(twiddle(3, 3, 4, 4), 15);
+        // the larger dimension's remaining bits go on top
+        assert_eq!(twiddle(2, 1, 4, 2), 5);
+        assert_eq!(twiddle(1, 3, 2, 4), 7);
+    }
+
+    #[test]
+    fn test_colors() {
+        // opaque color A: RGB554 (31, 16, 10)
+        assert_eq!(
+            color_a(0x8000 | 31 << 10 | 16 << 5 | 10 << 1),
+            [31, 16, 21, 15]
+        );
+        // translucent color A: ARGB3443 (5, 15, 1, 7)
+        assert_eq!(
+            color_a(5 << 12 | 15 << 8 | 1 << 4 | 7 << 1),
+            [31, 2, 31, 10]
+        );
+        assert_eq!(color_b(0x8000_0000 | 31 << 26 | 1 << 16), [31, 0, 1, 15]);
+        assert_eq!(color_b(7 << 28 | 8 << 24), [17, 0, 0, 14]);
+    }
+
+    #[test]
+    fn test_4bpp() {
+        // color A red 20, color B green 31, both opaque
+        let color = 0x8000_0000 | 31 << 21 | 0x8000 | 20 << 10;
+        // modulation values 0 to 3 in the first row
+        let data = words(&[(0b1110_0100, color); 4]);
+        let image = decode_pvrtc(&data, 8, 8, false).unwrap();
+        assert_eq!(pixel(&image, 8, 0, 0), &[165, 0, 0, 255]);
+        assert_eq!(pixel(&image, 8, 1, 0), &[103, 95, 0, 255]);
+        assert_eq!(pixel(&image, 8, 2, 0), &[61, 159, 0, 255]);
+        assert_eq!(pixel(&image, 8, 3, 0), &[0, 255, 0, 255]);
+
+        // punch-through mode
+        let data = words(&[(0b1110_0100, color | 1); 4]);
+        let image = decode_pvrtc(&data, 8, 8, false).unwrap();
+        assert_eq!(pixel(&image, 8, 1, 0), &[82, 127, 0, 255]);
+        assert_eq!(pixel(&image, 8, 2, 0), &[82, 127, 0, 0]);
+    }
+
+    #[test]
+    fn test_bilinear_upscale() {
+        // only the first word has a red color A
+        let mut data = words(&[(0, 0x8000); 4]);
+        data[4..8].copy_from_slice(&(0x8000u32 | 31 << 10).to_le_bytes());
+        let image = decode_pvrtc(&data, 8, 8, false).unwrap();
+        assert_eq!(pixel(&image, 8, 2, 2), &[255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 8, 3, 2), &[191, 0, 0, 255]);
+        // wraps around to the opposite corner
+        assert_eq!(pixel(&image, 8, 0, 0), &[63, 0, 0, 255]);
+        assert_eq!(pixel(&image, 8, 7, 7), &[15, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_2bpp() {
+        // black color A, white color B
+        let color = 0xffff_8001;
+        // interpolated in both directions, stored pixel (2,0) is zero
+        let data = words(&[(0xffff_fff2, color); 4]);
+        let image = decode_pvrtc(&data, 16, 8, true).unwrap();
+        assert_eq!(pixel(&image, 16, 0, 0), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&image, 16, 1, 0), &[191, 191, 191, 255]);
+        assert_eq!(pixel(&image, 16, 2, 0), &[0, 0, 0, 255]);
+
+        // horizontal only
+        let data = words(&[(0xffef_fff3, color); 4]);
+        let image = decode_pvrtc(&data, 16, 8, true).unwrap();
+        assert_eq!(pixel(&image, 16, 1, 0), &[127, 127, 127, 255]);
+
+        // one bit per pixel
+        let data = words(&[(0b10, color & !1); 4]);
+        let image = decode_pvrtc(&data, 16, 8, true).unwrap();
+        assert_eq!(pixel(&image, 16, 0, 0), &[0, 0, 0, 255]);
+        assert_eq!(pixel(&image, 16, 1, 0), &[255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        assert!(decode_pvrtc(&[0; 8 * 6], 12, 8, false).is_err());
+        assert!(decode_pvrtc(&[0; 8], 8, 8, false).is_err());
+    }
+}
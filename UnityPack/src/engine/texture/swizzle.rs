@@ -0,0 +1,401 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Conversion of the tiled texture layouts of console GPUs to linear rows
+//! of pixels or compressed blocks.
+
+use super::{Texture2D, TextureDimension, TextureFormat};
+use enums::{BuildTarget, TextureSwizzle};
+use error::{Error, Result};
+
+/// A Tegra GOB (group of bytes) is 64 bytes wide and 8 rows high
+const GOB_WIDTH: usize = 64;
+const GOB_HEIGHT: usize = 8;
+const GOB_SIZE: usize = GOB_WIDTH * GOB_HEIGHT;
+
+/// GNM micro tiles are 8x8 elements
+const TILE_SIZE: usize = 8;
+
+/// Size in bytes of a Tegra block linear surface of `width`x`height`
+/// elements with blocks that are `block_height` GOBs high
+fn block_linear_size(
+    width: usize,
+    height: usize,
+    element_size: usize,
+    block_height: usize,
+) -> usize {
+    let gobs_wide = (width * element_size).div_ceil(GOB_WIDTH);
+    let blocks_high = height.div_ceil(GOB_HEIGHT * block_height);
+    gobs_wide * blocks_high * GOB_SIZE * block_height
+}
+
+/// Block height of a mip level. The hardware halves the block height of
+/// levels that would otherwise be mostly padding.
+fn mip_block_height(height: usize, mut block_height: usize) -> usize {
+    while block_height > 1 && height.div_ceil(GOB_HEIGHT) <= block_height / 2 {
+        block_height /= 2;
+    }
+    block_height
+}
+
+/// Converts Tegra X1 block linear data to linear rows of elements.
+/// Blocks of `block_height` GOBs are stored row by row, the GOBs of a block
+/// from top to bottom and the 16 byte sectors of a GOB in a fixed pattern.
+fn deswizzle_block_linear(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    element_size: usize,
+    block_height: usize,
+) -> Vec<u8> {
+    let row_size = width * element_size;
+    let gobs_wide = row_size.div_ceil(GOB_WIDTH);
+    let block_rows = GOB_HEIGHT * block_height;
+    let mut result = vec![0; row_size * height];
+    for y in 0..height {
+        let gob_offset = (y / block_rows) * gobs_wide * GOB_SIZE * block_height
+            + (y % block_rows) / GOB_HEIGHT * GOB_SIZE;
+        let gob_row = y % GOB_HEIGHT;
+        let row_offset = (gob_row / 2) * 64 + (gob_row % 2) * 16;
+        for x in (0..row_size).step_by(16) {
+            let offset = gob_offset
+                + (x / GOB_WIDTH) * GOB_SIZE * block_height
+                + ((x % GOB_WIDTH) / 32) * 256
+                + ((x % 32) / 16) * 32
+                + row_offset;
+            let len = (row_size - x).min(16);
+            result[y * row_size + x..][..len].copy_from_slice(&data[offset..offset + len]);
+        }
+    }
+    result
+}
+
+/// Splits a Morton (Z-order) index into its coordinates. Bits alternate
+/// between x and y, starting with x, until one of the axes runs out.
+fn morton(mut index: usize, x_bits: u32, y_bits: u32) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let (mut x_bit, mut y_bit) = (0, 0);
+    while x_bit < x_bits || y_bit < y_bits {
+        if x_bit < x_bits {
+            x |= (index & 1) << x_bit;
+            index >>= 1;
+            x_bit += 1;
+        }
+        if y_bit < y_bits {
+            y |= (index & 1) << y_bit;
+            index >>= 1;
+            y_bit += 1;
+        }
+    }
+    (x, y)
+}
+
+/// Copies the elements of a surface stored in `tiles_x` by `tiles_y` tiles
+/// of `tile_width`x`tile_height` elements, each in Morton order
+fn deswizzle_morton_tiles(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    element_size: usize,
+    (tile_width, tile_height): (usize, usize),
+) -> Vec<u8> {
+    let tiles_x = width.div_ceil(tile_width);
+    let tiles_y = height.div_ceil(tile_height);
+    let x_bits = tile_width.trailing_zeros();
+    let y_bits = tile_height.trailing_zeros();
+    let mut result = vec![0; width * height * element_size];
+    let mut source = data.chunks(element_size);
+    for tile_y in 0..tiles_y {
+        for tile_x in 0..tiles_x {
+            for index in 0..tile_width * tile_height {
+                let element = source.next().unwrap_or(&[]);
+                let (x, y) = morton(index, x_bits, y_bits);
+                let (x, y) = (tile_x * tile_width + x, tile_y * tile_height + y);
+                if x < width && y < height && element.len() == element_size {
+                    let offset = (y * width + x) * element_size;
+                    result[offset..offset + element_size].copy_from_slice(element);
+                }
+            }
+        }
+    }
+    result
+}
+
+impl Texture2D {
+    /// Sets the tiling of the image data from the platform the asset was
+    /// built for, see `TypeMetadata::target_platform`. Textures converted
+    /// from objects read from an asset already have it set.
+    pub fn set_platform(&mut self, platform: BuildTarget) {
+        self.swizzle = platform.texture_swizzle();
+    }
+
+    /// Whether the image data has to be deswizzled before it can be decoded
+    pub fn is_swizzled(&self) -> bool {
+        match self.swizzle {
+            TextureSwizzle::None => false,
+            // only textures with a platform blob describing the block height
+            // are tiled
+            TextureSwizzle::Switch => self.platform_blob.len() >= 12,
+            _ => true,
+        }
+    }
+
+    /// Converts every mip level and slice of a texture stored in the tiled
+    /// layout of a console to the linear layout of other platforms. Supports
+    /// Switch block linear, PS4 tiled and Vita Morton order data.
+    pub fn deswizzle(&self) -> Result<Texture2D> {
+        self.deswizzle_surfaces(false)
+    }
+
+    /// Deswizzles only the top mip level of the first slice
+    pub(super) fn deswizzle_top(&self) -> Result<Texture2D> {
+        self.deswizzle_surfaces(true)
+    }
+
+    fn deswizzle_surfaces(&self, top_only: bool) -> Result<Texture2D> {
+        // Switch keeps 24 bit formats in 32 bit elements
+        let texture_format = match (self.swizzle, self.texture_format) {
+            (TextureSwizzle::Switch, TextureFormat::RGB24) => TextureFormat::RGBA32,
+            (TextureSwizzle::Switch, TextureFormat::BGR24) => TextureFormat::BGRA32,
+            (_, format) => format,
+        };
+        let (block_width, block_height, element_size) = match texture_format.block_footprint() {
+            Some(footprint) if !texture_format.is_crunched() => footprint,
+            _ => {
+                return Err(Error::EngineError(format!(
+                    "Can't deswizzle {:?} textures",
+                    texture_format
+                )));
+            }
+        };
+        let gobs_per_block = if self.swizzle == TextureSwizzle::Switch {
+            if self.platform_blob.len() < 12 {
+                return Err(Error::EngineError(format!(
+                    "Platform blob of {} bytes does not describe a Switch texture layout",
+                    self.platform_blob.len()
+                )));
+            }
+            let mut shift = [0; 4];
+            shift.copy_from_slice(&self.platform_blob[8..12]);
+            1 << u32::from_le_bytes(shift).min(5)
+        } else {
+            1
+        };
+
+        // surfaces in storage order, see `Surfaces`
+        let mut order = Vec::new();
+        if self.dimension == TextureDimension::Tex3D {
+            for mip in 0..self.mip_count {
+                for slice in 0..self.slice_count(mip) {
+                    order.push((slice, mip));
+                }
+            }
+        } else {
+            for slice in 0..self.image_count {
+                for mip in 0..self.mip_count {
+                    order.push((slice, mip));
+                }
+            }
+        }
+        if top_only {
+            order.truncate(1);
+        }
+
+        let mut data = Vec::new();
+        let mut offset = 0;
+        for &(slice, mip) in &order {
+            let (width, height, _) = self.mip_size(mip);
+            let width = (width as usize).div_ceil(block_width as usize);
+            let height = (height as usize).div_ceil(block_height as usize);
+            let (size, gob_block_height) = match self.swizzle {
+                TextureSwizzle::Switch => {
+                    let gob_block_height = mip_block_height(height, gobs_per_block);
+                    let size = block_linear_size(width, height, element_size, gob_block_height);
+                    (size, gob_block_height)
+                }
+                TextureSwizzle::PS4 => {
+                    let tiles = width.div_ceil(TILE_SIZE) * height.div_ceil(TILE_SIZE);
+                    (tiles * TILE_SIZE * TILE_SIZE * element_size, 1)
+                }
+                TextureSwizzle::Vita => {
+                    let size = width.next_power_of_two() * height.next_power_of_two();
+                    (size * element_size, 1)
+                }
+                swizzle => {
+                    return Err(Error::EngineError(format!(
+                        "Deswizzling {:?} textures is not supported",
+                        swizzle
+                    )));
+                }
+            };
+            if self.data.len() < offset + size {
+                return Err(Error::EngineError(format!(
+                    "Swizzled image data of {} bytes is too short for slice {} at mip level {} of {}",
+                    self.data.len(),
+                    slice,
+                    mip,
+                    self.name
+                )));
+            }
+
+            let source = &self.data[offset..offset + size];
+            data.extend(match self.swizzle {
+                TextureSwizzle::Switch => {
+                    deswizzle_block_linear(source, width, height, element_size, gob_block_height)
+                }
+                TextureSwizzle::PS4 => deswizzle_morton_tiles(
+                    source,
+                    width,
+                    height,
+                    element_size,
+                    (TILE_SIZE, TILE_SIZE),
+                ),
+                _ => {
+                    let tile = (width.next_power_of_two(), height.next_power_of_two());
+                    deswizzle_morton_tiles(source, width, height, element_size, tile)
+                }
+            });
+            offset += size;
+            // array layers of block linear textures start at a block boundary
+            if self.swizzle == TextureSwizzle::Switch && mip + 1 == self.mip_count {
+                let alignment = GOB_SIZE * gobs_per_block;
+                offset = offset.div_ceil(alignment) * alignment;
+            }
+        }
+
+        let mut texture = Texture2D {
+            name: self.name.clone(),
+            data,
+            texture_format,
+            stream_data: self.stream_data.clone(),
+            platform_blob: self.platform_blob.clone(),
+            swizzle: TextureSwizzle::None,
+            ..*self
+        };
+        if top_only {
+            texture.depth = 1;
+            texture.mip_count = 1;
+            texture.image_count = 1;
+            texture.dimension = TextureDimension::Tex2D;
+        }
+        Ok(texture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Swizzles linear elements with the inverse of `deswizzle_block_linear`
+    fn swizzle_block_linear(
+        data: &[u8],
+        width: usize,
+        height: usize,
+        element_size: usize,
+        block_height: usize,
+    ) -> Vec<u8> {
+        let size = block_linear_size(width, height, element_size, block_height);
+        let mut result = vec![0; size];
+        // every sector of the swizzled surface holds its own index
+        let sectors: Vec<u8> = (0..size / 16).flat_map(|i| vec![i as u8; 16]).collect();
+        let positions = deswizzle_block_linear(&sectors, width, height, element_size, block_height);
+        let mut filled = vec![0; size / 16];
+        for (i, value) in data.iter().enumerate() {
+            let sector = positions[i] as usize;
+            result[sector * 16 + filled[sector]] = *value;
+            filled[sector] += 1;
+        }
+        result
+    }
+
+    #[test]
+    fn test_morton() {
+        assert_eq!(morton(0, 3, 3), (0, 0));
+        assert_eq!(morton(1, 3, 3), (1, 0));
+        assert_eq!(morton(2, 3, 3), (0, 1));
+        assert_eq!(morton(63, 3, 3), (7, 7));
+        // the remaining bits of a longer axis follow the interleaved ones
+        assert_eq!(morton(0b1011, 3, 1), (0b101, 1));
+    }
+
+    #[test]
+    fn test_block_linear() {
+        assert_eq!(block_linear_size(16, 8, 4, 1), GOB_SIZE);
+        assert_eq!(block_linear_size(17, 9, 4, 2), 4 * GOB_SIZE);
+        assert_eq!(mip_block_height(64, 16), 8);
+        assert_eq!(mip_block_height(8, 16), 1);
+
+        // the first sector of a GOB holds the start of the first two rows
+        let data: Vec<u8> = (0..512).map(|i| (i % 251) as u8).collect();
+        let linear = deswizzle_block_linear(&data, 16, 8, 4, 1);
+        assert_eq!(&linear[0..16], &data[0..16]);
+        assert_eq!(&linear[64..80], &data[16..32]);
+        assert_eq!(&linear[16..32], &data[32..48]);
+        assert_eq!(&linear[32..48], &data[256..272]);
+    }
+
+    #[test]
+    fn test_switch_texture() {
+        // 32x24 RGB24 pixels are stored as RGBA32 in blocks of two GOBs
+        let pixels: Vec<u8> = (0..32 * 24 * 4).map(|i| (i * 7 % 251) as u8).collect();
+        let mut tex = Texture2D::new(
+            "test",
+            32,
+            24,
+            TextureFormat::RGB24,
+            swizzle_block_linear(&pixels, 32, 24, 4, 2),
+        );
+        assert!(!tex.is_swizzled());
+        tex.set_platform(BuildTarget::Switch);
+        assert!(!tex.is_swizzled());
+        assert!(tex.deswizzle().is_err());
+        tex.platform_blob = vec![0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0];
+        assert!(tex.is_swizzled());
+
+        let linear = tex.deswizzle().unwrap();
+        assert_eq!(linear.texture_format, TextureFormat::RGBA32);
+        assert_eq!(linear.swizzle, TextureSwizzle::None);
+        assert_eq!(linear.data, pixels);
+        assert_eq!(tex.to_image().unwrap(), pixels);
+    }
+
+    #[test]
+    fn test_ps4_texture() {
+        // 16x8 DXT1 is a 4x2 block surface padded to a single 8x8 tile
+        let mut data = vec![0; 64 * 8];
+        // block (1, 1) is the fourth element of the tile
+        data[3 * 8..4 * 8].copy_from_slice(&[1; 8]);
+        let mut tex = Texture2D::new("test", 16, 8, TextureFormat::DXT1, data);
+        tex.set_platform(BuildTarget::PS4);
+        let linear = tex.deswizzle().unwrap();
+        assert_eq!(linear.data.len(), 8 * 8);
+        assert_eq!(&linear.data[5 * 8..6 * 8], &[1; 8]);
+        assert_eq!(linear.data.iter().filter(|b| **b == 1).count(), 8);
+
+        tex.data.truncate(100);
+        assert!(tex.to_image().is_err());
+        tex.set_platform(BuildTarget::XboxOne);
+        assert!(tex.deswizzle().is_err());
+    }
+
+    #[test]
+    fn test_vita_mips() {
+        // 4x2 and 2x1 one byte pixels, each padded to a power of two
+        let mut tex = Texture2D::new(
+            "test",
+            4,
+            2,
+            TextureFormat::Alpha8,
+            vec![0, 1, 4, 5, 2, 3, 6, 7, 8, 9],
+        );
+        tex.mip_count = 2;
+        tex.set_platform(BuildTarget::PSP2);
+        let linear = tex.deswizzle().unwrap();
+        assert_eq!(linear.data, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(tex.surface(0, 1).unwrap().data, vec![8, 9]);
+    }
+}
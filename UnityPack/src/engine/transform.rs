@@ -0,0 +1,281 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+use super::{as_map, read_floats, read_pointer, EngineObject};
+use error::{Error, Result};
+use object::{ObjectPointer, ObjectValue};
+use std::ops::Mul;
+
+pub trait IntoTransform {
+    fn to_transform(self) -> Result<Transform>;
+}
+
+/// A 4x4 matrix stored by rows, `e[row][column]` is Unity's `eRC` field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4x4 {
+    pub e: [[f32; 4]; 4],
+}
+
+impl Matrix4x4 {
+    pub fn identity() -> Self {
+        let mut e = [[0.0; 4]; 4];
+        for (i, row) in e.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Matrix4x4 { e }
+    }
+
+    /// Composes a translation, a rotation quaternion (x, y, z, w) and a
+    /// scale like Unity's `Matrix4x4.TRS`
+    pub fn from_trs(translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3]) -> Self {
+        let [x, y, z, w] = rotation;
+        let rotation = [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ];
+        let mut e = [[0.0; 4]; 4];
+        for row in 0..3 {
+            for column in 0..3 {
+                e[row][column] = rotation[row][column] * scale[column];
+            }
+            e[row][3] = translation[row];
+        }
+        e[3][3] = 1.0;
+        Matrix4x4 { e }
+    }
+
+    pub(crate) fn from_map(value: &ObjectValue) -> Result<Self> {
+        let map = as_map(value)?;
+        let mut e = [[0.0; 4]; 4];
+        for (row, values) in e.iter_mut().enumerate() {
+            for (column, item) in values.iter_mut().enumerate() {
+                *item = tryGet!(map, format!("e{}{}", row, column)).to_f32()?;
+            }
+        }
+        Ok(Matrix4x4 { e })
+    }
+
+    /// The elements ordered by columns, as glTF and OpenGL expect them
+    pub fn to_column_major(&self) -> [f32; 16] {
+        let mut res = [0.0; 16];
+        for (i, item) in res.iter_mut().enumerate() {
+            *item = self.e[i % 4][i / 4];
+        }
+        res
+    }
+
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        let mut res = [0.0; 3];
+        for (row, item) in res.iter_mut().enumerate() {
+            *item = self.e[row][3]
+                + (0..3)
+                    .map(|column| self.e[row][column] * point[column])
+                    .sum::<f32>();
+        }
+        res
+    }
+
+    /// Inverts the matrix by Gauss-Jordan elimination, `None` if it is
+    /// singular
+    pub fn inverse(&self) -> Option<Matrix4x4> {
+        let mut a = self.e;
+        let mut res = Matrix4x4::identity().e;
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|&i, &j| {
+                a[i][column]
+                    .abs()
+                    .partial_cmp(&a[j][column].abs())
+                    .unwrap_or(::std::cmp::Ordering::Equal)
+            })?;
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            res.swap(column, pivot);
+            let scale = 1.0 / a[column][column];
+            for i in 0..4 {
+                a[column][i] *= scale;
+                res[column][i] *= scale;
+            }
+            for row in (0..4).filter(|&row| row != column) {
+                let factor = a[row][column];
+                for i in 0..4 {
+                    a[row][i] -= factor * a[column][i];
+                    res[row][i] -= factor * res[column][i];
+                }
+            }
+        }
+        Some(Matrix4x4 { e: res })
+    }
+}
+
+impl Mul for Matrix4x4 {
+    type Output = Matrix4x4;
+
+    fn mul(self, other: Matrix4x4) -> Matrix4x4 {
+        let mut e = [[0.0; 4]; 4];
+        for (row, values) in e.iter_mut().enumerate() {
+            for (column, item) in values.iter_mut().enumerate() {
+                *item = (0..4).map(|i| self.e[row][i] * other.e[i][column]).sum();
+            }
+        }
+        Matrix4x4 { e }
+    }
+}
+
+/// Position, rotation and scale of a `GameObject` relative to its parent
+#[derive(Debug, Clone)]
+pub struct Transform {
+    pub game_object: Option<ObjectPointer>,
+    pub local_position: [f32; 3],
+    /// Quaternion in x, y, z, w order
+    pub local_rotation: [f32; 4],
+    pub local_scale: [f32; 3],
+    pub children: Vec<ObjectPointer>,
+    pub father: Option<ObjectPointer>,
+}
+
+impl Transform {
+    /// The transformation from the local space to the parent's space
+    pub fn local_matrix(&self) -> Matrix4x4 {
+        Matrix4x4::from_trs(self.local_position, self.local_rotation, self.local_scale)
+    }
+}
+
+impl IntoTransform for EngineObject {
+    fn to_transform(self) -> Result<Transform> {
+        let mut children = Vec::new();
+        if let ObjectValue::Array(ref array) = *tryGet!(self.map, "m_Children") {
+            for child in array {
+                if let Some(pointer) = read_pointer(child)? {
+                    children.push(pointer);
+                }
+            }
+        }
+        Ok(Transform {
+            game_object: read_pointer(tryGet!(self.map, "m_GameObject"))?,
+            local_position: read_floats(tryGet!(self.map, "m_LocalPosition"), &["x", "y", "z"])?,
+            local_rotation: read_floats(
+                tryGet!(self.map, "m_LocalRotation"),
+                &["x", "y", "z", "w"],
+            )?,
+            local_scale: read_floats(tryGet!(self.map, "m_LocalScale"), &["x", "y", "z"])?,
+            children,
+            father: read_pointer(tryGet!(self.map, "m_Father"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use extras::containers::OrderedMap;
+
+    fn vector(fields: &[(&str, f32)]) -> ObjectValue {
+        let mut map = OrderedMap::default();
+        for &(field, value) in fields {
+            map.insert(field.to_string(), ObjectValue::Float(value));
+        }
+        ObjectValue::Map(map)
+    }
+
+    pub fn pointer(path_id: i64) -> ObjectValue {
+        ObjectValue::ObjectPointer(ObjectPointer {
+            type_name: String::new(),
+            file_id: 0,
+            path_id,
+        })
+    }
+
+    /// A transform moved by `position` and turned 90 degrees around the y axis
+    pub fn transform(
+        game_object: i64,
+        position: [f32; 3],
+        father: Option<i64>,
+        children: &[i64],
+    ) -> EngineObject {
+        let half = 0.5f32.sqrt();
+        let mut map = OrderedMap::default();
+        map.insert("m_GameObject".to_string(), pointer(game_object));
+        map.insert(
+            "m_LocalRotation".to_string(),
+            vector(&[("x", 0.0), ("y", half), ("z", 0.0), ("w", half)]),
+        );
+        map.insert(
+            "m_LocalPosition".to_string(),
+            vector(&[("x", position[0]), ("y", position[1]), ("z", position[2])]),
+        );
+        map.insert(
+            "m_LocalScale".to_string(),
+            vector(&[("x", 1.0), ("y", 1.0), ("z", 1.0)]),
+        );
+        map.insert(
+            "m_Children".to_string(),
+            ObjectValue::Array(children.iter().map(|&id| pointer(id)).collect()),
+        );
+        map.insert(
+            "m_Father".to_string(),
+            father.map_or(ObjectValue::None, pointer),
+        );
+        EngineObject::new(map)
+    }
+
+    fn assert_near(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        let transform = transform(7, [1.0, 2.0, 3.0], Some(4), &[5, 6])
+            .to_transform()
+            .unwrap();
+        assert_eq!(transform.game_object.as_ref().unwrap().path_id, 7);
+        assert_eq!(transform.father.as_ref().unwrap().path_id, 4);
+        assert_eq!(transform.children.len(), 2);
+
+        // rotating by 90 degrees around y takes x to -z
+        let matrix = transform.local_matrix();
+        assert_near(matrix.transform_point([1.0, 0.0, 0.0]), [1.0, 2.0, 2.0]);
+        assert_near(matrix.transform_point([0.0, 0.0, 0.0]), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_matrix() {
+        let matrix = Matrix4x4::from_trs([1.0, 2.0, 3.0], [0.0, 0.0, 0.0, 1.0], [2.0, 2.0, 2.0]);
+        assert_eq!(matrix.to_column_major()[12..15], [1.0, 2.0, 3.0]);
+        let inverse = matrix.inverse().unwrap();
+        assert_near(inverse.transform_point([3.0, 4.0, 5.0]), [1.0, 1.0, 1.0]);
+        assert_eq!(matrix * Matrix4x4::identity(), matrix);
+        assert!(Matrix4x4 { e: [[0.0; 4]; 4] }.inverse().is_none());
+
+        let mut map = OrderedMap::default();
+        for row in 0..4 {
+            for column in 0..4 {
+                map.insert(
+                    format!("e{}{}", row, column),
+                    ObjectValue::Float(matrix.e[row][column]),
+                );
+            }
+        }
+        assert_eq!(Matrix4x4::from_map(&ObjectValue::Map(map)).unwrap(), matrix);
+    }
+}
@@ -5,7 +5,10 @@
  * All rights reserved 2017
  */
 
-#[derive(Debug)]
+use engine::texture::TextureFormat;
+
+/// Platform a player is running on (`UnityEngine.RuntimePlatform`)
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RuntimePlatform {
     OSXEditor,
     OSXPlayer,
@@ -21,6 +24,7 @@ pub enum RuntimePlatform {
     NaCl,
     LinuxPlayer,
     FlashPlayer,
+    LinuxEditor,
     WebGLPlayer,
     MetroPlayerX86,
     MetroPlayerX64,
@@ -33,6 +37,28 @@ pub enum RuntimePlatform {
     PSM,
     XboxOne,
     SamsungTVPlayer,
+    WiiU,
+    TvOS,
+    Switch,
+    Lumin,
+    Stadia,
+    CloudRendering,
+    GameCoreXboxSeries,
+    GameCoreXboxOne,
+    PS5,
+    EmbeddedLinuxArm64,
+    EmbeddedLinuxArm32,
+    EmbeddedLinuxX64,
+    EmbeddedLinuxX86,
+    LinuxServer,
+    WindowsServer,
+    OSXServer,
+    QNXArm32,
+    QNXArm64,
+    QNXX64,
+    QNXX86,
+    VisionOS,
+    Unknown(u32),
 }
 
 pub fn get_runtime_platform(value: u32) -> RuntimePlatform {
@@ -51,6 +77,7 @@ pub fn get_runtime_platform(value: u32) -> RuntimePlatform {
         12 => RuntimePlatform::NaCl,
         13 => RuntimePlatform::LinuxPlayer,
         15 => RuntimePlatform::FlashPlayer,
+        16 => RuntimePlatform::LinuxEditor,
         17 => RuntimePlatform::WebGLPlayer,
         18 => RuntimePlatform::MetroPlayerX86,
         19 => RuntimePlatform::MetroPlayerX64,
@@ -63,6 +90,325 @@ pub fn get_runtime_platform(value: u32) -> RuntimePlatform {
         26 => RuntimePlatform::PSM,
         27 => RuntimePlatform::XboxOne,
         28 => RuntimePlatform::SamsungTVPlayer,
-        _ => RuntimePlatform::OSXEditor,
+        30 => RuntimePlatform::WiiU,
+        31 => RuntimePlatform::TvOS,
+        32 => RuntimePlatform::Switch,
+        33 => RuntimePlatform::Lumin,
+        34 => RuntimePlatform::Stadia,
+        35 => RuntimePlatform::CloudRendering,
+        36 => RuntimePlatform::GameCoreXboxSeries,
+        37 => RuntimePlatform::GameCoreXboxOne,
+        38 => RuntimePlatform::PS5,
+        39 => RuntimePlatform::EmbeddedLinuxArm64,
+        40 => RuntimePlatform::EmbeddedLinuxArm32,
+        41 => RuntimePlatform::EmbeddedLinuxX64,
+        42 => RuntimePlatform::EmbeddedLinuxX86,
+        43 => RuntimePlatform::LinuxServer,
+        44 => RuntimePlatform::WindowsServer,
+        45 => RuntimePlatform::OSXServer,
+        46 => RuntimePlatform::QNXArm32,
+        47 => RuntimePlatform::QNXArm64,
+        48 => RuntimePlatform::QNXX64,
+        49 => RuntimePlatform::QNXX86,
+        50 => RuntimePlatform::VisionOS,
+        n => RuntimePlatform::Unknown(n),
+    }
+}
+
+/// Platform an asset was built for (`UnityEditor.BuildTarget`), this is the
+/// value serialized in the header of asset files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildTarget {
+    NoTarget,
+    AnyPlayer,
+    ValidPlayer,
+    StandaloneOSX,
+    StandaloneOSXPPC,
+    StandaloneOSXIntel,
+    StandaloneWindows,
+    WebPlayer,
+    WebPlayerStreamed,
+    Wii,
+    IOS,
+    PS3,
+    XBOX360,
+    Broadcom,
+    Android,
+    StandaloneGLESEmu,
+    StandaloneGLES20Emu,
+    NaCl,
+    StandaloneLinux,
+    FlashPlayer,
+    StandaloneWindows64,
+    WebGL,
+    WSAPlayer,
+    StandaloneLinux64,
+    StandaloneLinuxUniversal,
+    WP8Player,
+    StandaloneOSXIntel64,
+    BlackBerry,
+    Tizen,
+    PSP2,
+    PS4,
+    PSM,
+    XboxOne,
+    SamsungTV,
+    N3DS,
+    WiiU,
+    TvOS,
+    Switch,
+    Lumin,
+    Stadia,
+    CloudRendering,
+    GameCoreXboxSeries,
+    GameCoreXboxOne,
+    PS5,
+    EmbeddedLinux,
+    QNX,
+    VisionOS,
+    Unknown(u32),
+}
+
+pub fn get_build_target(value: u32) -> BuildTarget {
+    match value as i32 {
+        -2 => BuildTarget::NoTarget,
+        -1 => BuildTarget::AnyPlayer,
+        1 => BuildTarget::ValidPlayer,
+        2 => BuildTarget::StandaloneOSX,
+        3 => BuildTarget::StandaloneOSXPPC,
+        4 => BuildTarget::StandaloneOSXIntel,
+        5 => BuildTarget::StandaloneWindows,
+        6 => BuildTarget::WebPlayer,
+        7 => BuildTarget::WebPlayerStreamed,
+        8 => BuildTarget::Wii,
+        9 => BuildTarget::IOS,
+        10 => BuildTarget::PS3,
+        11 => BuildTarget::XBOX360,
+        12 => BuildTarget::Broadcom,
+        13 => BuildTarget::Android,
+        14 => BuildTarget::StandaloneGLESEmu,
+        15 => BuildTarget::StandaloneGLES20Emu,
+        16 => BuildTarget::NaCl,
+        17 => BuildTarget::StandaloneLinux,
+        18 => BuildTarget::FlashPlayer,
+        19 => BuildTarget::StandaloneWindows64,
+        20 => BuildTarget::WebGL,
+        21 => BuildTarget::WSAPlayer,
+        24 => BuildTarget::StandaloneLinux64,
+        25 => BuildTarget::StandaloneLinuxUniversal,
+        26 => BuildTarget::WP8Player,
+        27 => BuildTarget::StandaloneOSXIntel64,
+        28 => BuildTarget::BlackBerry,
+        29 => BuildTarget::Tizen,
+        30 => BuildTarget::PSP2,
+        31 => BuildTarget::PS4,
+        32 => BuildTarget::PSM,
+        33 => BuildTarget::XboxOne,
+        34 => BuildTarget::SamsungTV,
+        35 => BuildTarget::N3DS,
+        36 => BuildTarget::WiiU,
+        37 => BuildTarget::TvOS,
+        38 => BuildTarget::Switch,
+        39 => BuildTarget::Lumin,
+        40 => BuildTarget::Stadia,
+        41 => BuildTarget::CloudRendering,
+        42 => BuildTarget::GameCoreXboxSeries,
+        43 => BuildTarget::GameCoreXboxOne,
+        44 => BuildTarget::PS5,
+        45 => BuildTarget::EmbeddedLinux,
+        46 => BuildTarget::QNX,
+        47 => BuildTarget::VisionOS,
+        _ => BuildTarget::Unknown(value),
+    }
+}
+
+/// Memory layout of texture data on a platform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSwizzle {
+    /// Rows of pixels or blocks are stored linearly
+    None,
+    /// Tegra X1 block linear layout
+    Switch,
+    /// GNM tiled layout
+    PS4,
+    /// AGC tiled layout
+    PS5,
+    /// Morton order
+    Vita,
+    /// Durango tiled layout
+    XboxOne,
+    /// GX2 tiled layout
+    WiiU,
+}
+
+/// Decoding hints derived from the build target of an asset
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlatformHints {
+    /// Layout of texture data when the texture is not stored linearly
+    pub texture_swizzle: TextureSwizzle,
+    /// Texture formats the GPUs of the platform support, the most likely first
+    pub texture_formats: Vec<TextureFormat>,
+    /// Whether the data of big-endian consoles has to be byte swapped
+    pub big_endian: bool,
+}
+
+const DESKTOP_FORMATS: &[TextureFormat] = &[
+    TextureFormat::DXT1,
+    TextureFormat::DXT5,
+    TextureFormat::BC4,
+    TextureFormat::BC5,
+    TextureFormat::BC6H,
+    TextureFormat::DXT1Crunched,
+    TextureFormat::DXT5Crunched,
+];
+
+const MOBILE_FORMATS: &[TextureFormat] = &[
+    TextureFormat::Etc2Rgb,
+    TextureFormat::Etc2Rgba8,
+    TextureFormat::EtcRgb4,
+    TextureFormat::AstcRgb6x6,
+    TextureFormat::AstcRgba6x6,
+    TextureFormat::AstcRgba4x4,
+    TextureFormat::AstcRgba8x8,
+    TextureFormat::EacR,
+    TextureFormat::EacRg,
+];
+
+const APPLE_FORMATS: &[TextureFormat] = &[
+    TextureFormat::AstcRgb6x6,
+    TextureFormat::AstcRgba6x6,
+    TextureFormat::AstcRgba4x4,
+    TextureFormat::AstcRgba8x8,
+    TextureFormat::PvrtcRgb4,
+    TextureFormat::PvrtcRgba4,
+    TextureFormat::PvrtcRgb2,
+    TextureFormat::PvrtcRgba2,
+    TextureFormat::Etc2Rgba8,
+];
+
+const LEGACY_ANDROID_FORMATS: &[TextureFormat] = &[
+    TextureFormat::EtcRgb4,
+    TextureFormat::AtcRgb4,
+    TextureFormat::AtcRgba8,
+    TextureFormat::PvrtcRgb4,
+    TextureFormat::PvrtcRgba4,
+    TextureFormat::DXT1,
+    TextureFormat::DXT5,
+];
+
+impl BuildTarget {
+    pub fn is_console(&self) -> bool {
+        matches!(
+            *self,
+            BuildTarget::Wii
+                | BuildTarget::PS3
+                | BuildTarget::XBOX360
+                | BuildTarget::PSP2
+                | BuildTarget::PS4
+                | BuildTarget::XboxOne
+                | BuildTarget::N3DS
+                | BuildTarget::WiiU
+                | BuildTarget::Switch
+                | BuildTarget::GameCoreXboxSeries
+                | BuildTarget::GameCoreXboxOne
+                | BuildTarget::PS5
+        )
+    }
+
+    pub fn is_mobile(&self) -> bool {
+        matches!(
+            *self,
+            BuildTarget::IOS
+                | BuildTarget::Android
+                | BuildTarget::TvOS
+                | BuildTarget::VisionOS
+                | BuildTarget::WP8Player
+                | BuildTarget::BlackBerry
+                | BuildTarget::Tizen
+                | BuildTarget::Lumin
+        )
+    }
+
+    /// Layout of the texture data of this platform
+    pub fn texture_swizzle(&self) -> TextureSwizzle {
+        match *self {
+            BuildTarget::Switch => TextureSwizzle::Switch,
+            BuildTarget::PS4 => TextureSwizzle::PS4,
+            BuildTarget::PS5 => TextureSwizzle::PS5,
+            BuildTarget::PSP2 => TextureSwizzle::Vita,
+            BuildTarget::XboxOne
+            | BuildTarget::GameCoreXboxOne
+            | BuildTarget::GameCoreXboxSeries => TextureSwizzle::XboxOne,
+            BuildTarget::WiiU => TextureSwizzle::WiiU,
+            _ => TextureSwizzle::None,
+        }
+    }
+
+    /// Texture formats textures built for this platform most likely use
+    pub fn texture_formats(&self) -> Vec<TextureFormat> {
+        let formats: &[TextureFormat] = match *self {
+            BuildTarget::Android | BuildTarget::Lumin => MOBILE_FORMATS,
+            BuildTarget::IOS | BuildTarget::TvOS | BuildTarget::VisionOS => APPLE_FORMATS,
+            BuildTarget::BlackBerry | BuildTarget::Tizen | BuildTarget::PSP2 => {
+                LEGACY_ANDROID_FORMATS
+            }
+            BuildTarget::Switch => &[
+                TextureFormat::AstcRgba4x4,
+                TextureFormat::AstcRgba6x6,
+                TextureFormat::AstcRgba8x8,
+                TextureFormat::DXT1,
+                TextureFormat::DXT5,
+                TextureFormat::BC4,
+                TextureFormat::BC5,
+                TextureFormat::BC6H,
+            ],
+            BuildTarget::WebGL => &[
+                TextureFormat::DXT1,
+                TextureFormat::DXT5,
+                TextureFormat::DXT1Crunched,
+                TextureFormat::DXT5Crunched,
+                TextureFormat::Etc2Rgb,
+                TextureFormat::Etc2Rgba8,
+                TextureFormat::AstcRgba6x6,
+            ],
+            _ => DESKTOP_FORMATS,
+        };
+        formats.to_vec()
+    }
+
+    pub fn hints(&self) -> PlatformHints {
+        PlatformHints {
+            texture_swizzle: self.texture_swizzle(),
+            texture_formats: self.texture_formats(),
+            big_endian: matches!(
+                *self,
+                BuildTarget::Wii | BuildTarget::PS3 | BuildTarget::XBOX360 | BuildTarget::WiiU
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platforms() {
+        assert_eq!(get_runtime_platform(32), RuntimePlatform::Switch);
+        assert_eq!(get_runtime_platform(38), RuntimePlatform::PS5);
+        assert_eq!(get_runtime_platform(200), RuntimePlatform::Unknown(200));
+
+        assert_eq!(get_build_target(19), BuildTarget::StandaloneWindows64);
+        assert_eq!(get_build_target(44), BuildTarget::PS5);
+        assert_eq!(get_build_target(0xffff_fffe), BuildTarget::NoTarget);
+
+        let hints = BuildTarget::Switch.hints();
+        assert_eq!(hints.texture_swizzle, TextureSwizzle::Switch);
+        assert_eq!(hints.texture_formats[0], TextureFormat::AstcRgba4x4);
+        assert!(!hints.big_endian);
+        assert_eq!(BuildTarget::Android.texture_swizzle(), TextureSwizzle::None);
+        assert!(BuildTarget::PS3.hints().big_endian);
+        assert!(BuildTarget::GameCoreXboxSeries.is_console());
+        assert!(BuildTarget::IOS.is_mobile());
     }
 }
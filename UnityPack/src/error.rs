@@ -29,6 +29,8 @@ pub enum Error {
     TypeError(String),
     ResourceError(String),
     EngineError(String),
+    QueryError(String),
+    VersionError(String),
 }
 
 impl error::Error for Error {
@@ -52,6 +54,8 @@ impl error::Error for Error {
             Error::ResourceError(ref s) => s,
             Error::CustomError(ref s) => s,
             Error::EngineError(ref s) => s,
+            Error::QueryError(ref s) => s,
+            Error::VersionError(ref s) => s,
         }
     }
 }
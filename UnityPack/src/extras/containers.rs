@@ -5,25 +5,25 @@
  * All rights reserved 2017
  */
 
-use std::collections::hash_map::Keys;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
+use std::slice;
 
 /// A HashMap which remembers its insertion order.
 pub struct OrderedMap<K: Hash + Eq, V> {
     items: HashMap<K, V>,
-    indices: HashMap<usize, K>,
+    order: Vec<K>,
 }
 
 impl<K: Hash + Eq + fmt::Debug, V: fmt::Debug> fmt::Debug for OrderedMap<K, V> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut content = String::new();
-        for i in 0..self.items.len() {
-            let key = self.indices.get(&i).unwrap();
+        for (i, key) in self.order.iter().enumerate() {
             content.push_str(&format!("{:?}: ", key));
-            let v = self.items.get(&key).unwrap();
-            if i == self.items.len() - 1 {
+            let v = self.items.get(key).unwrap();
+            if i == self.order.len() - 1 {
                 content.push_str(&format!("{:?}", v));
             } else {
                 content.push_str(&format!("{:?}, ", v));
@@ -40,7 +40,7 @@ where
     fn default() -> Self {
         OrderedMap {
             items: HashMap::new(),
-            indices: HashMap::new(),
+            order: Vec::new(),
         }
     }
 }
@@ -59,15 +59,14 @@ where
     ///
     /// [`None`]: ../../std/option/enum.Option.html#variant.None
     pub fn insert(&mut self, k: K, v: V) -> Option<V> {
-        if self.items.contains_key(&k) {
-            return self.items.insert(k, v);
+        match self.items.entry(k) {
+            Entry::Occupied(mut entry) => Some(entry.insert(v)),
+            Entry::Vacant(entry) => {
+                self.order.push(entry.key().clone());
+                entry.insert(v);
+                None
+            }
         }
-
-        let k_clone = k.clone();
-        self.items.insert(k, v);
-        self.indices.insert(self.items.len() - 1, k_clone);
-
-        None
     }
 
     /// Returns the number of key-value pairs in this map
@@ -79,24 +78,89 @@ where
         self.items.get(k)
     }
 
-    pub fn keys(&self) -> Keys<K, V> {
-        self.items.keys()
+    /// Returns the keys of the map in insertion order
+    pub fn keys(&self) -> slice::Iter<'_, K> {
+        self.order.iter()
+    }
+
+    /// Returns the key-value pairs of the map in insertion order
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            keys: self.order.iter(),
+            items: &self.items,
+        }
+    }
+
+    /// Returns the values of the map in insertion order
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
     }
 
     pub fn remove(&mut self, k: &K) -> Option<V> {
-        let mut remove_idx: usize = 0;
-        let mut idx_found = false;
-        for (i, ik) in &self.indices {
-            if ik == k {
-                remove_idx = *i;
-                idx_found = true;
-                break;
+        let item = self.items.remove(k);
+        if item.is_some() {
+            if let Some(index) = self.order.iter().position(|key| key == k) {
+                self.order.remove(index);
             }
         }
-        if idx_found {
-            self.indices.remove(&remove_idx);
+        item
+    }
+}
+
+/// Iterator over the key-value pairs of an `OrderedMap` in insertion order
+pub struct Iter<'a, K: 'a, V: 'a> {
+    keys: slice::Iter<'a, K>,
+    items: &'a HashMap<K, V>,
+}
+
+impl<'a, K: Hash + Eq, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.keys.next()?;
+        self.items.get(key).map(|v| (key, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+/// Iterator over the values of an `OrderedMap` in insertion order
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K: Hash + Eq, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = OrderedMap::default();
+        for (i, key) in ["c", "a", "b"].iter().enumerate() {
+            map.insert(key.to_string(), i);
         }
+        assert_eq!(map.remove(&"a".to_string()), Some(1));
+        map.insert("d".to_string(), 3);
+        map.insert("c".to_string(), 4);
 
-        self.items.remove(k)
+        let keys: Vec<&String> = map.keys().collect();
+        assert_eq!(keys, vec!["c", "b", "d"]);
+        let values: Vec<&usize> = map.values().collect();
+        assert_eq!(values, vec![&4, &2, &3]);
+        assert_eq!(map.iter().count(), map.len());
     }
 }
@@ -0,0 +1,105 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! MD4 message digest (RFC 1320), used by Unity to hash type trees.
+
+pub struct Md4 {
+    state: [u32; 4],
+    /// bytes which do not fill a complete block yet
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+impl Default for Md4 {
+    fn default() -> Md4 {
+        Md4 {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+}
+
+impl Md4 {
+    pub fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        self.buffer.extend_from_slice(data);
+        let blocks = self.buffer.len() / 64;
+        for i in 0..blocks {
+            let mut block = [0u8; 64];
+            block.copy_from_slice(&self.buffer[i * 64..(i + 1) * 64]);
+            self.process_block(&block);
+        }
+        self.buffer.drain(..blocks * 64);
+    }
+
+    pub fn finish(mut self) -> [u8; 16] {
+        let bit_length = self.length.wrapping_mul(8);
+        let mut padding = vec![0x80u8];
+        while (self.buffer.len() + padding.len()) % 64 != 56 {
+            padding.push(0);
+        }
+        padding.extend_from_slice(&bit_length.to_le_bytes());
+        let length = self.length;
+        self.update(&padding);
+        self.length = length;
+
+        let mut result = [0u8; 16];
+        for (i, word) in self.state.iter().enumerate() {
+            result[i * 4..(i + 1) * 4].copy_from_slice(&word.to_le_bytes());
+        }
+        result
+    }
+
+    fn process_block(&mut self, block: &[u8; 64]) {
+        let mut x = [0u32; 16];
+        for (i, word) in x.iter_mut().enumerate() {
+            *word = u32::from_le_bytes([
+                block[i * 4],
+                block[i * 4 + 1],
+                block[i * 4 + 2],
+                block[i * 4 + 3],
+            ]);
+        }
+
+        let [mut a, mut b, mut c, mut d] = self.state;
+
+        let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+        let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+        let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+        let step =
+            |func: &dyn Fn(u32, u32, u32) -> u32, a: u32, b: u32, c: u32, d: u32, x: u32, s| {
+                a.wrapping_add(func(b, c, d)).wrapping_add(x).rotate_left(s)
+            };
+
+        for &i in &[0, 4, 8, 12] {
+            a = step(&f, a, b, c, d, x[i], 3);
+            d = step(&f, d, a, b, c, x[i + 1], 7);
+            c = step(&f, c, d, a, b, x[i + 2], 11);
+            b = step(&f, b, c, d, a, x[i + 3], 19);
+        }
+        let k = 0x5a82_7999u32;
+        for &i in &[0, 1, 2, 3] {
+            a = step(&g, a, b, c, d, x[i].wrapping_add(k), 3);
+            d = step(&g, d, a, b, c, x[i + 4].wrapping_add(k), 5);
+            c = step(&g, c, d, a, b, x[i + 8].wrapping_add(k), 9);
+            b = step(&g, b, c, d, a, x[i + 12].wrapping_add(k), 13);
+        }
+        let k = 0x6ed9_eba1u32;
+        for &i in &[0, 2, 1, 3] {
+            a = step(&h, a, b, c, d, x[i].wrapping_add(k), 3);
+            d = step(&h, d, a, b, c, x[i + 8].wrapping_add(k), 9);
+            c = step(&h, c, d, a, b, x[i + 4].wrapping_add(k), 11);
+            b = step(&h, b, c, d, a, x[i + 12].wrapping_add(k), 15);
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+    }
+}
@@ -6,11 +6,13 @@
  */
 pub mod containers;
 pub mod lzma;
+pub mod md4;
 
 #[cfg(test)]
 mod tests {
 
     use extras::containers::OrderedMap;
+    use extras::md4::Md4;
 
     #[test]
     fn test_ordered_map() {
@@ -24,4 +26,20 @@ mod tests {
         assert_eq!(*(map.get(&"K").unwrap()), 1);
         assert_eq!(*(map.get(&"D").unwrap()), 4);
     }
+
+    #[test]
+    fn test_md4() {
+        let hex = |data: &[u8]| -> String {
+            let mut hasher = Md4::default();
+            hasher.update(data);
+            hasher.finish().iter().map(|b| format!("{:02x}", b)).collect()
+        };
+        // test vectors from RFC 1320
+        assert_eq!(hex(b""), "31d6cfe0d16ae931b73c59d7e0c089c0");
+        assert_eq!(hex(b"abc"), "a448017aaf21d8525fc10ae87aa6729d");
+        assert_eq!(
+            hex(b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"),
+            "e33b4ddc9c38f2199c3e7b164fcc0536"
+        );
+    }
 }
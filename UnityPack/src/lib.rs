@@ -12,34 +12,51 @@ extern crate libc;
 extern crate lz4_compress;
 extern crate lzma;
 extern crate lzma_sys;
-extern crate serde_json;
+extern crate png;
 extern crate uuid;
 
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate serde_json;
 
 pub mod asset;
 pub mod assetbundle;
-mod binaryreader;
+pub mod binaryreader;
+pub mod codegen;
+pub mod diff;
 pub mod engine;
-mod enums;
+pub mod enums;
 pub mod error;
 mod extras;
 pub mod object;
+pub mod query;
 mod resources;
-mod typetree;
+pub mod tpk;
+pub mod typedb;
+pub mod typeexport;
+pub mod typetree;
 pub mod unitypack_c;
+pub mod version;
+pub mod visitor;
 
 #[cfg(test)]
 mod tests {
 
     use assetbundle::*;
+    use binaryreader::BinaryReader;
+    use codegen::ReadTypeTree;
+    use enums::{BuildTarget, TextureSwizzle};
     use engine::font::IntoFont;
     use engine::font::IntoFontDef;
     use engine::mesh::IntoMesh;
     use engine::text::IntoTextAsset;
     use engine::texture::IntoTexture2D;
+    use error::Result;
     use object::*;
+    use std::io::{Read, Seek};
+    use typetree::TypeNode;
+    use visitor::Visitor;
 
     #[test]
     fn test_load_texture2d() {
@@ -87,11 +104,194 @@ mod tests {
                     texture.texture_format
                 );
 
-                let _ = texture.to_image().unwrap();
+                let image = texture.to_image().unwrap();
+                assert_eq!(
+                    image.len(),
+                    texture.width as usize * texture.height as usize * 4
+                );
+
+                let png = texture.to_png().unwrap();
+                assert_eq!(&png[1..4], b"PNG");
+                let decoder = png::Decoder::new(&png[..]);
+                let info = decoder.read_info().unwrap().info().clone();
+                assert_eq!((info.width, info.height), (texture.width, texture.height));
+
+                let dds = texture.to_dds().unwrap();
+                assert_eq!(&dds[84..88], b"DXT1");
+
+                // the mip chain fills the image data exactly
+                let mut size = 0;
+                for surface in texture.surfaces() {
+                    let surface = surface.unwrap();
+                    assert_eq!(surface.slice, 0);
+                    size += surface.texture.data.len();
+                }
+                assert_eq!(texture.mip_count, 12);
+                assert_eq!(size, texture.data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_visit_texture2d() {
+        #[derive(Default)]
+        struct ImageDataSize {
+            field_name: String,
+            size: usize,
+        }
+
+        impl Visitor for ImageDataSize {
+            fn field(&mut self, node: &TypeNode) -> Result<()> {
+                self.field_name = node.field_name.clone();
+                Ok(())
+            }
+
+            fn byte_array(&mut self, _node: &TypeNode, data: &[u8]) -> Result<()> {
+                if self.field_name == "image data" {
+                    self.size = data.len();
+                }
+                Ok(())
+            }
+        }
+
+        let input_file = "test_data/main_dxt1_bc1.unity3d";
+        let mut asset_bundle = AssetBundle::load_from_file(input_file).unwrap();
+        asset_bundle.resolve_asset(0).unwrap();
+        let asset = &asset_bundle.assets[0];
+
+        for obj in asset.objects.values() {
+            if obj.type_name == "Texture2D" {
+                let mut visitor = ImageDataSize::default();
+                obj.visit_signature(asset, &mut asset_bundle.signature, &mut visitor)
+                    .unwrap();
+
+                let engine_object = obj
+                    .read_signature(asset, &mut asset_bundle.signature)
+                    .unwrap();
+                let texture = match engine_object {
+                    ObjectValue::EngineObject(engine_object) => {
+                        engine_object.to_texture2d().unwrap()
+                    }
+                    _ => {
+                        panic!("Invalid engine object");
+                    }
+                };
+                assert_eq!(visitor.size, texture.data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_fields() {
+        let input_file = "test_data/main_dxt1_bc1.unity3d";
+        let mut asset_bundle = AssetBundle::load_from_file(input_file).unwrap();
+        asset_bundle.resolve_asset(0).unwrap();
+        let asset = &asset_bundle.assets[0];
+
+        for obj in asset.objects.values() {
+            if obj.type_name == "Texture2D" {
+                let fields = obj
+                    .read_fields(asset, &mut asset_bundle.signature, &["m_Name", "m_Width"])
+                    .unwrap();
+                assert_eq!(fields.len(), 2);
+                assert!(fields.get(&"image data".to_string()).is_none());
+
+                let value = obj
+                    .read_signature(asset, &mut asset_bundle.signature)
+                    .unwrap();
+                assert_eq!(
+                    fields.get(&"m_Width".to_string()).unwrap().to_i32().unwrap(),
+                    value.get::<i32>("m_Width").unwrap()
+                );
+                assert_eq!(
+                    obj.read_name(asset, &mut asset_bundle.signature).unwrap(),
+                    Some(value.get::<String>("m_Name").unwrap())
+                );
+            }
+        }
+    }
+
+    /// Reads only the leading `m_Name` of an object, like a generated struct would
+    struct NamedObject {
+        name: String,
+    }
+
+    impl ReadTypeTree for NamedObject {
+        fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<NamedObject> {
+            Ok(NamedObject {
+                name: String::read_type_tree(reader)?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_read_as() {
+        let input_file = "test_data/main_dxt1_bc1.unity3d";
+        let mut asset_bundle = AssetBundle::load_from_file(input_file).unwrap();
+        asset_bundle.resolve_asset(0).unwrap();
+        let asset = &asset_bundle.assets[0];
+
+        for obj in asset.objects.values() {
+            if obj.type_name == "Texture2D" {
+                let named: NamedObject = obj.read_as(asset, &mut asset_bundle.signature).unwrap();
+                assert_eq!(
+                    Some(named.name),
+                    obj.read_name(asset, &mut asset_bundle.signature).unwrap()
+                );
             }
         }
     }
 
+    #[test]
+    fn test_type_tree_hashes() {
+        let input_file = "test_data/main_dxt1_bc1.unity3d";
+        let mut asset_bundle = AssetBundle::load_from_file(input_file).unwrap();
+        asset_bundle.resolve_asset(0).unwrap();
+        let asset = &mut asset_bundle.assets[0];
+        asset.load_objects(&mut asset_bundle.signature).unwrap();
+        assert!(asset.warnings.is_empty());
+
+        let metadata = asset.tree.as_ref().unwrap();
+        assert!(!metadata.hashes().is_empty());
+        assert!(metadata.verify_hashes().is_empty());
+
+        let texture_tree = metadata.type_trees[&28].clone();
+        assert_eq!(asset.verify_type_tree(28, &texture_tree), Some(true));
+        let other_tree = TypeNode::from_parts(
+            "Texture2D".to_string(),
+            "Base".to_string(),
+            -1,
+            2,
+            false,
+            0,
+            Vec::new(),
+        );
+        assert_eq!(asset.verify_type_tree(28, &other_tree), Some(false));
+    }
+
+    #[test]
+    fn test_unity_version() {
+        let input_file = "test_data/main_dxt1_bc1.unity3d";
+        let mut asset_bundle = AssetBundle::load_from_file(input_file).unwrap();
+        let bundle_version = asset_bundle.version().unwrap();
+        assert_eq!(bundle_version.to_string(), asset_bundle.unity_version());
+        assert_eq!(asset_bundle.target_version, "5.x.x");
+
+        asset_bundle.resolve_asset(0).unwrap();
+        let asset = &mut asset_bundle.assets[0];
+        asset.load_objects(&mut asset_bundle.signature).unwrap();
+        let version = asset.version().unwrap();
+        assert_eq!(version.to_string(), asset.unity_version());
+        assert!(version.is_at_least(5, 0));
+
+        let metadata = asset.tree.as_ref().unwrap();
+        assert_eq!(metadata.target_platform, BuildTarget::StandaloneWindows);
+        assert_eq!(
+            metadata.platform_hints().texture_swizzle,
+            TextureSwizzle::None
+        );
+    }
+
     #[test]
     fn test_load_gameobjects() {
         let input_file = "/Applications/Hearthstone/Data/OSX/gameobjects0.unity3d";
@@ -7,18 +7,21 @@
 use asset::{Asset, AssetOrRef};
 use assetbundle::Signature;
 use binaryreader::{BinaryReader, ReadExtras, Teller};
+use codegen::ReadTypeTree;
 use engine::{EngineObject, EngineObjectVariant};
 use error::{Error, Result};
 use extras::containers::OrderedMap;
-use resources::{default_type_metadata, get_unity_class};
+use resources::{default_type_tree, get_unity_class};
 use std::clone::Clone;
+use std::cmp;
 use std::ffi::OsString;
 use std::fmt;
 use std::io;
 use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::os::unix::ffi::OsStringExt;
 use std::sync::Arc;
-use typetree::{TypeNode, DEFAULT_TYPENODE};
+use typetree::TypeNode;
+use visitor::{Primitive, TypeTreeWalker, Visitor};
 
 #[derive(Debug)]
 pub struct ObjectInfo {
@@ -91,19 +94,14 @@ impl ObjectInfo {
                 Err(_) => format!("<Unknown {}>", self.type_id),
             };
         } else if !&asset.typenames.contains_key(&self.type_id) {
-            let rawdata = match self.read(asset, buffer) {
-                Ok(object_value) => object_value,
+            // only m_Script is needed, the rest of the object is skipped
+            let map = match self.read_fields_from(asset, buffer, &["m_Script"]) {
+                Ok(map) => map,
                 Err(_) => {
                     return format!("<Unknown {}>", self.type_id);
                 }
             };
-            let typename = match rawdata {
-                ObjectValue::EngineObject(engine_object) => {
-                    self.get_script(asset, buffer, &engine_object.map)
-                }
-                ObjectValue::Map(map) => self.get_script(asset, buffer, &map),
-                _ => format!("<Unknown {}>", self.type_id),
-            };
+            let typename = self.get_script(asset, buffer, &map);
             asset.typenames.insert(self.type_id, typename);
         }
         asset.typenames.get(&self.type_id).unwrap().clone()
@@ -168,10 +166,8 @@ impl ObjectInfo {
                 if tree.type_trees.contains_key(&(self.class_id.into())) {
                     return tree.type_trees[&(self.class_id.into())].clone();
                 }
-                if let Ok(ref data) = default_type_metadata() {
-                    if data.type_trees.contains_key(&(self.class_id.into())) {
-                        return data.type_trees[&(self.class_id.into())].clone();
-                    }
+                if let Some(tree) = default_type_tree(asset.unity_version(), self.class_id.into()) {
+                    return tree;
                 };
             };
         }
@@ -179,154 +175,405 @@ impl ObjectInfo {
     }
 
     fn read<R: Read + Seek + Teller>(&self, asset: &Asset, buffer: &mut R) -> Result<ObjectValue> {
-        let _ = buffer.seek(SeekFrom::Start(
-            asset.bundle_offset as u64 + u64::from(self.data_offset),
-        ));
+        let mut builder = ObjectValueBuilder::default();
+        self.visit(asset, buffer, &mut builder)?;
+        Ok(with_asset_info(asset, builder.finish()?))
+    }
 
-        let mut object_buf = vec![0; self.size as usize];
-        try!(buffer.read_exact(object_buf.as_mut_slice()));
+    fn visit<R: Read + Seek + Teller, V: Visitor>(
+        &self,
+        asset: &Asset,
+        buffer: &mut R,
+        visitor: &mut V,
+    ) -> Result<()> {
+        let typetree = self.get_type_tree(asset);
+        let mut binreader = self.data_reader(asset, buffer);
+        TypeTreeWalker::new(asset).walk(&typetree, &mut binreader, visitor)
+    }
 
+    /// Reads the data of the object directly from the bundle, so that the
+    /// object is never loaded into memory as a whole
+    fn data_reader<'a, R: Read + Seek>(
+        &self,
+        asset: &Asset,
+        buffer: &'a mut R,
+    ) -> BinaryReader<ObjectDataReader<'a, R>> {
+        let reader = BufReader::new(ObjectDataReader {
+            inner: buffer,
+            start: asset.bundle_offset + u64::from(self.data_offset),
+            size: u64::from(self.size),
+            pos: 0,
+            inner_pos: None,
+        });
+        BinaryReader::new(reader, asset.endianness)
+    }
+
+    fn read_fields_from<R: Read + Seek + Teller>(
+        &self,
+        asset: &Asset,
+        buffer: &mut R,
+        fields: &[&str],
+    ) -> Result<OrderedMap<String, ObjectValue>> {
         let typetree = self.get_type_tree(asset);
+        let mut binreader = self.data_reader(asset, buffer);
 
-        let reader = BufReader::new(Cursor::new(object_buf));
-        let mut binreader = BinaryReader::new(reader, asset.endianness);
-        self.read_value_from_buffer(asset, &typetree, &mut binreader)
+        let mut selector = FieldSelector {
+            fields,
+            depth: 0,
+            builder: ObjectValueBuilder::default(),
+        };
+        TypeTreeWalker::new(asset).walk(&typetree, &mut binreader, &mut selector)?;
+
+        match selector.builder.finish()? {
+            ObjectValue::EngineObject(engine_object) => Ok(engine_object.map),
+            ObjectValue::Map(map) => Ok(map),
+            value => Err(Error::ObjectError(format!(
+                "Object is not a structure but {:?}",
+                value
+            ))),
+        }
     }
 
-    pub fn read_signature(&self, asset: &Asset, signature: &mut Signature) -> Result<ObjectValue> {
+    /// Decodes only the given top-level fields of the object. The data of all
+    /// other fields is skipped without being decoded.
+    pub fn read_fields(
+        &self,
+        asset: &Asset,
+        signature: &mut Signature,
+        fields: &[&str],
+    ) -> Result<OrderedMap<String, ObjectValue>> {
         match signature {
-            Signature::UnityFS(ref mut buf) => self.read(asset, buf),
-            Signature::UnityRaw(ref mut buf) => self.read(asset, buf),
+            Signature::UnityFS(ref mut buf) => self.read_fields_from(asset, buf, fields),
+            Signature::UnityRaw(ref mut buf) => self.read_fields_from(asset, buf, fields),
+            Signature::UnityRawCompressed(ref mut buf) => self.read_fields_from(
+                asset,
+                &mut BufReader::new(Cursor::new(buf.as_slice())),
+                fields,
+            ),
+            _ => Err(Error::InvalidSignatureError),
+        }
+    }
+
+    fn read_as_from<R: Read + Seek + Teller, T: ReadTypeTree>(
+        &self,
+        asset: &Asset,
+        buffer: &mut R,
+    ) -> Result<T> {
+        T::read_type_tree(&mut self.data_reader(asset, buffer))
+    }
+
+    /// Decodes the object into a struct generated by the `codegen` module
+    pub fn read_as<T: ReadTypeTree>(&self, asset: &Asset, signature: &mut Signature) -> Result<T> {
+        match signature {
+            Signature::UnityFS(ref mut buf) => self.read_as_from(asset, buf),
+            Signature::UnityRaw(ref mut buf) => self.read_as_from(asset, buf),
             Signature::UnityRawCompressed(ref mut buf) => {
-                self.read(asset, &mut BufReader::new(Cursor::new(buf.as_slice())))
+                self.read_as_from(asset, &mut BufReader::new(Cursor::new(buf.as_slice())))
             }
             _ => Err(Error::InvalidSignatureError),
         }
     }
 
-    fn read_value_from_buffer<R: Read + Seek>(
+    /// Reads the `m_Name` of the object, `None` if the object has no name
+    pub fn read_name(&self, asset: &Asset, signature: &mut Signature) -> Result<Option<String>> {
+        match self.read_fields(asset, signature, &["m_Name"])?.get(&"m_Name".to_string()) {
+            Some(name) => Ok(Some(name.to_string()?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn read_signature(&self, asset: &Asset, signature: &mut Signature) -> Result<ObjectValue> {
+        let mut builder = ObjectValueBuilder::default();
+        self.visit_signature(asset, signature, &mut builder)?;
+        Ok(with_asset_info(asset, builder.finish()?))
+    }
+
+    /// Decodes the object and reports its values to `visitor` without building an `ObjectValue`
+    pub fn visit_signature<V: Visitor>(
         &self,
         asset: &Asset,
-        typetree: &TypeNode,
-        buffer: &mut BinaryReader<R>,
-    ) -> Result<ObjectValue> {
-        let mut align = false;
-        let expected_size = typetree.size;
-        let pos_before = buffer.tell();
-        let t = &typetree.type_name;
-
-        let result;
-        if t == "bool" {
-            result = ObjectValue::Bool(try!(buffer.read_bool()));
-        } else if t == "UInt8" {
-            result = ObjectValue::U8(try!(buffer.read_u8()));
-        } else if t == "SInt8" {
-            result = ObjectValue::I8(buffer.read_i8()?);
-        } else if t == "UInt16" {
-            result = ObjectValue::U16(try!(buffer.read_u16()));
-        } else if t == "SInt16" {
-            result = ObjectValue::I16(try!(buffer.read_i16()));
-        } else if t == "UInt32" || t == "unsigned int" {
-            result = ObjectValue::U32(try!(buffer.read_u32()));
-        } else if t == "SInt32" || t == "int" {
-            result = ObjectValue::I32(try!(buffer.read_i32()));
-        } else if t == "UInt64" {
-            result = ObjectValue::U64(try!(buffer.read_u64()));
-        } else if t == "SInt64" {
-            result = ObjectValue::I64(try!(buffer.read_i64()));
-        } else if t == "float" {
-            result = ObjectValue::Float(try!(buffer.read_f32()));
-        } else if t == "string" {
-            let size = try!(buffer.read_u32());
-            result =
-                ObjectValue::String(OsString::from_vec(try!(buffer.read_bytes(size as usize))));
-            align = typetree.children[0].post_align();
-        } else {
-            let first_child: &TypeNode = if typetree.is_array {
-                typetree
-            } else {
-                match typetree.children.len() {
-                    x if x > 0 => &typetree.children[0],
-                    _ => &DEFAULT_TYPENODE,
-                }
-            };
+        signature: &mut Signature,
+        visitor: &mut V,
+    ) -> Result<()> {
+        match signature {
+            Signature::UnityFS(ref mut buf) => self.visit(asset, buf, visitor),
+            Signature::UnityRaw(ref mut buf) => self.visit(asset, buf, visitor),
+            Signature::UnityRawCompressed(ref mut buf) => self.visit(
+                asset,
+                &mut BufReader::new(Cursor::new(buf.as_slice())),
+                visitor,
+            ),
+            _ => Err(Error::InvalidSignatureError),
+        }
+    }
+}
 
-            if t.contains("PPtr<") {
-                let mut object_pointer = ObjectPointer::new(typetree.type_name.to_owned());
-                result = match object_pointer.load(asset, buffer) {
-                    Ok(_) => {
-                        if object_pointer.is_valid() {
-                            ObjectValue::ObjectPointer(object_pointer)
-                        } else {
-                            ObjectValue::None
-                        }
-                    }
-                    _ => ObjectValue::None,
-                };
-            } else if first_child.is_array {
-                align = first_child.post_align();
-                let size = try!(buffer.read_u32());
-                let array_type = &first_child.children[1].type_name;
-                if array_type == "char" || array_type == "UInt8" {
-                    let mut data: Vec<u8> = vec![0; size as usize];
-                    try!(buffer.read_exact(data.as_mut_slice()));
-                    result = ObjectValue::U8Array(data);
-                } else {
-                    // we dont know the type
-                    let mut array: Vec<ObjectValue> = Vec::with_capacity(size as usize);
-                    for _ in 0..size {
-                        let object_value = try!(self.read_value_from_buffer(
-                            asset,
-                            &first_child.children[1],
-                            buffer,
-                        ));
-                        array.push(object_value);
-                    }
-                    result = ObjectValue::Array(array);
-                }
-            } else if t == "pair" {
-                if typetree.children.len() != 2 {
-                    return Err(Error::ObjectError(format!(
-                        "Type pair needs exactly 2 elements not {}",
-                        typetree.children.len()
-                    )));
-                }
+/// Window over the data of a single object in the bundle buffer. The
+/// `BinaryReader` on top of it is the only buffering layer, the inner
+/// reader is only repositioned after skipping past the buffered data.
+struct ObjectDataReader<'a, R: 'a + Read + Seek> {
+    inner: &'a mut R,
+    start: u64,
+    size: u64,
+    pos: u64,
+    /// Position of `inner` relative to `start` if it is known
+    inner_pos: Option<u64>,
+}
 
-                let first = self.read_value_from_buffer(asset, &typetree.children[0], buffer)?;
-                let second = self.read_value_from_buffer(asset, &typetree.children[1], buffer)?;
-                result = ObjectValue::Pair((Box::new(first), Box::new(second)));
-            } else {
-                let mut ordered_map: OrderedMap<String, ObjectValue> = OrderedMap::default();
+impl<'a, R: Read + Seek> Read for ObjectDataReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size {
+            return Ok(0);
+        }
+        let len = cmp::min(buf.len() as u64, self.size - self.pos) as usize;
+        if self.inner_pos != Some(self.pos) {
+            self.inner.seek(SeekFrom::Start(self.start + self.pos))?;
+        }
+        let read = self.inner.read(&mut buf[..len])?;
+        self.pos += read as u64;
+        self.inner_pos = Some(self.pos);
+        Ok(read)
+    }
+}
 
-                for type_child in &typetree.children {
-                    let child = try!(self.read_value_from_buffer(asset, type_child, buffer));
-                    ordered_map.insert(type_child.field_name.clone(), child);
-                }
+impl<'a, R: Read + Seek> Seek for ObjectDataReader<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.size as i64 + p,
+            SeekFrom::Current(p) => self.pos as i64 + p,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Seek before the start of the object",
+            ));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
+/// Builds only the selected top-level fields of an object and skips the others
+struct FieldSelector<'a> {
+    fields: &'a [&'a str],
+    depth: usize,
+    builder: ObjectValueBuilder,
+}
+
+impl<'a> Visitor for FieldSelector<'a> {
+    fn enter_struct(&mut self, node: &TypeNode) -> Result<()> {
+        self.depth += 1;
+        self.builder.enter_struct(node)
+    }
+
+    fn exit_struct(&mut self, node: &TypeNode) -> Result<()> {
+        self.depth -= 1;
+        self.builder.exit_struct(node)
+    }
+
+    fn field(&mut self, node: &TypeNode) -> Result<()> {
+        self.builder.field(node)
+    }
+
+    fn skip_field(&mut self, node: &TypeNode) -> bool {
+        self.depth == 1 && !self.fields.contains(&node.field_name.as_str())
+    }
+
+    fn array_begin(&mut self, node: &TypeNode, len: usize) -> Result<()> {
+        self.builder.array_begin(node, len)
+    }
+
+    fn array_end(&mut self, node: &TypeNode) -> Result<()> {
+        self.builder.array_end(node)
+    }
+
+    fn byte_array(&mut self, node: &TypeNode, data: &[u8]) -> Result<()> {
+        self.builder.byte_array(node, data)
+    }
+
+    fn enter_pair(&mut self, node: &TypeNode) -> Result<()> {
+        self.builder.enter_pair(node)
+    }
+
+    fn exit_pair(&mut self, node: &TypeNode) -> Result<()> {
+        self.builder.exit_pair(node)
+    }
+
+    fn primitive(&mut self, node: &TypeNode, value: Primitive) -> Result<()> {
+        self.builder.primitive(node, value)
+    }
+
+    fn string(&mut self, node: &TypeNode, data: &[u8]) -> Result<()> {
+        self.builder.string(node, data)
+    }
 
-                result = load_object(t, ordered_map);
-                if t == "StreamedResource" {
+    fn pptr(&mut self, node: &TypeNode, file_id: i32, path_id: i64) -> Result<()> {
+        self.builder.pptr(node, file_id, path_id)
+    }
+}
+
+enum BuilderFrame {
+    Struct {
+        type_name: String,
+        map: OrderedMap<String, ObjectValue>,
+        field_name: String,
+    },
+    Array(Vec<ObjectValue>),
+    Pair(Vec<ObjectValue>),
+}
+
+/// Visitor which builds the `ObjectValue` tree of an object
+#[derive(Default)]
+pub struct ObjectValueBuilder {
+    stack: Vec<BuilderFrame>,
+    result: Option<ObjectValue>,
+}
+
+impl ObjectValueBuilder {
+    /// Returns the decoded value, fails if the object has not been fully visited
+    pub fn finish(self) -> Result<ObjectValue> {
+        match self.result {
+            Some(value) => Ok(value),
+            None => Err(Error::ObjectError(
+                "Object was not completely decoded".to_string(),
+            )),
+        }
+    }
+
+    fn push_value(&mut self, value: ObjectValue) -> Result<()> {
+        match self.stack.last_mut() {
+            Some(BuilderFrame::Struct {
+                ref mut map,
+                ref field_name,
+                ..
+            }) => {
+                map.insert(field_name.clone(), value);
+            }
+            Some(BuilderFrame::Array(ref mut array)) => array.push(value),
+            Some(BuilderFrame::Pair(ref mut pair)) => pair.push(value),
+            None => self.result = Some(value),
+        };
+        Ok(())
+    }
+}
+
+impl Visitor for ObjectValueBuilder {
+    fn enter_struct(&mut self, node: &TypeNode) -> Result<()> {
+        self.stack.push(BuilderFrame::Struct {
+            type_name: node.type_name.clone(),
+            map: OrderedMap::default(),
+            field_name: String::new(),
+        });
+        Ok(())
+    }
+
+    fn exit_struct(&mut self, _node: &TypeNode) -> Result<()> {
+        match self.stack.pop() {
+            Some(BuilderFrame::Struct { type_name, map, .. }) => {
+                let result = load_object(&type_name, map);
+                if type_name == "StreamedResource" {
                     //TODO: result.asset = self.resolve_streaming_asset(result.source);
                 } /* else if t == "StreamingInfo" {
                       //TODO: result.asset = self.resolve_streaming_asset(result.path);
                   }*/
+                self.push_value(result)
             }
+            _ => Err(Error::ObjectError("Unbalanced structure".to_string())),
         }
+    }
 
-        // Check to make sure we read at least as many bytes the tree says.
-        // We allow reading more for the case of alignment.
-        let pos_after = buffer.tell();
-        let actual_size = pos_after - pos_before;
-        if (expected_size > 0) && (actual_size < expected_size as u64) {
-            return Err(Error::ObjectError(format!(
-                "Expected read_value({}) to read {} bytes, but only read {} bytes",
-                typetree, expected_size, actual_size
-            )));
+    fn field(&mut self, node: &TypeNode) -> Result<()> {
+        if let Some(BuilderFrame::Struct {
+            ref mut field_name, ..
+        }) = self.stack.last_mut()
+        {
+            *field_name = node.field_name.clone();
         }
-        if align || typetree.post_align() {
-            buffer.align();
+        Ok(())
+    }
+
+    fn array_begin(&mut self, _node: &TypeNode, len: usize) -> Result<()> {
+        self.stack.push(BuilderFrame::Array(Vec::with_capacity(len)));
+        Ok(())
+    }
+
+    fn array_end(&mut self, _node: &TypeNode) -> Result<()> {
+        match self.stack.pop() {
+            Some(BuilderFrame::Array(array)) => self.push_value(ObjectValue::Array(array)),
+            _ => Err(Error::ObjectError("Unbalanced array".to_string())),
+        }
+    }
+
+    fn byte_array(&mut self, _node: &TypeNode, data: &[u8]) -> Result<()> {
+        self.push_value(ObjectValue::U8Array(data.to_vec()))
+    }
+
+    fn enter_pair(&mut self, _node: &TypeNode) -> Result<()> {
+        self.stack.push(BuilderFrame::Pair(Vec::with_capacity(2)));
+        Ok(())
+    }
+
+    fn exit_pair(&mut self, _node: &TypeNode) -> Result<()> {
+        match self.stack.pop() {
+            Some(BuilderFrame::Pair(mut pair)) => {
+                if pair.len() != 2 {
+                    return Err(Error::ObjectError(format!(
+                        "Type pair needs exactly 2 elements not {}",
+                        pair.len()
+                    )));
+                }
+                let second = pair.pop().unwrap();
+                let first = pair.pop().unwrap();
+                self.push_value(ObjectValue::Pair((Box::new(first), Box::new(second))))
+            }
+            _ => Err(Error::ObjectError("Unbalanced pair".to_string())),
+        }
+    }
+
+    fn primitive(&mut self, _node: &TypeNode, value: Primitive) -> Result<()> {
+        self.push_value(match value {
+            Primitive::Bool(v) => ObjectValue::Bool(v),
+            Primitive::U8(v) => ObjectValue::U8(v),
+            Primitive::I8(v) => ObjectValue::I8(v),
+            Primitive::U16(v) => ObjectValue::U16(v),
+            Primitive::I16(v) => ObjectValue::I16(v),
+            Primitive::U32(v) => ObjectValue::U32(v),
+            Primitive::I32(v) => ObjectValue::I32(v),
+            Primitive::U64(v) => ObjectValue::U64(v),
+            Primitive::I64(v) => ObjectValue::I64(v),
+            Primitive::Float(v) => ObjectValue::Float(v),
+        })
+    }
+
+    fn string(&mut self, _node: &TypeNode, data: &[u8]) -> Result<()> {
+        self.push_value(ObjectValue::String(OsString::from_vec(data.to_vec())))
+    }
+
+    fn pptr(&mut self, node: &TypeNode, file_id: i32, path_id: i64) -> Result<()> {
+        let object_pointer = ObjectPointer {
+            type_name: node.type_name.to_owned(),
+            file_id,
+            path_id,
+        };
+        if object_pointer.is_valid() {
+            self.push_value(ObjectValue::ObjectPointer(object_pointer))
+        } else {
+            self.push_value(ObjectValue::None)
         }
+    }
+}
 
-        Ok(result)
+/// Tells engine objects about the asset they were read from, the conversions
+/// need its platform and version for data whose layout depends on them
+fn with_asset_info(asset: &Asset, value: ObjectValue) -> ObjectValue {
+    match value {
+        ObjectValue::EngineObject(mut engine_object) => {
+            engine_object.target_platform = asset.tree.as_ref().map(|tree| tree.target_platform);
+            engine_object.unity_version = asset.version();
+            ObjectValue::EngineObject(engine_object)
+        }
+        value => value,
     }
 }
 
@@ -393,6 +640,16 @@ impl ObjectValue {
         }
     }
 
+    pub fn to_i8(&self) -> Result<i8> {
+        match self {
+            ObjectValue::I8(b) => Ok(*b),
+            _ => Err(Error::ObjectError(format!(
+                "ObjectValue is not i8 variant but {:?}",
+                self
+            ))),
+        }
+    }
+
     pub fn to_u16(&self) -> Result<u16> {
         match self {
             ObjectValue::U16(b) => Ok(*b),
@@ -403,6 +660,16 @@ impl ObjectValue {
         }
     }
 
+    pub fn to_i16(&self) -> Result<i16> {
+        match self {
+            ObjectValue::I16(b) => Ok(*b),
+            _ => Err(Error::ObjectError(format!(
+                "ObjectValue is not i16 variant but {:?}",
+                self
+            ))),
+        }
+    }
+
     pub fn to_i32(&self) -> Result<i32> {
         match self {
             ObjectValue::I32(b) => Ok(*b),
@@ -423,6 +690,26 @@ impl ObjectValue {
         }
     }
 
+    pub fn to_u64(&self) -> Result<u64> {
+        match self {
+            ObjectValue::U64(b) => Ok(*b),
+            _ => Err(Error::ObjectError(format!(
+                "ObjectValue is not u64 variant but {:?}",
+                self
+            ))),
+        }
+    }
+
+    pub fn to_i64(&self) -> Result<i64> {
+        match self {
+            ObjectValue::I64(b) => Ok(*b),
+            _ => Err(Error::ObjectError(format!(
+                "ObjectValue is not i64 variant but {:?}",
+                self
+            ))),
+        }
+    }
+
     pub fn to_f32(&self) -> Result<f32> {
         match self {
             ObjectValue::Float(b) => Ok(*b),
@@ -505,21 +792,6 @@ pub struct ObjectPointer {
 }
 
 impl ObjectPointer {
-    fn new(name: String) -> ObjectPointer {
-        ObjectPointer {
-            type_name: name,
-            file_id: 0,
-            path_id: 0,
-        }
-    }
-
-    fn load<R: Read + Seek>(&mut self, asset: &Asset, buffer: &mut BinaryReader<R>) -> Result<()> {
-        self.file_id = try!(buffer.read_i32());
-        self.path_id = try!(asset.read_id(buffer));
-
-        Ok(())
-    }
-
     fn is_valid(&self) -> bool {
         self.file_id != 0 || self.path_id != 0
     }
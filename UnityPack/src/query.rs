@@ -0,0 +1,633 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Path based access to `ObjectValue` trees.
+//!
+//! A path is a list of field names separated by dots. Array elements are
+//! addressed with `[n]`, and `[*]` selects every element of an array (or every
+//! value of a map). A field name of `*` selects every field of a map. Field
+//! names containing characters of the path syntax are quoted in brackets,
+//! with `"` and `\` escaped by a backslash.
+//!
+//! ```text
+//! m_Name
+//! m_VertexData.m_Channels[2].dimension
+//! m_Component[*].component
+//! m_Container[*].second.asset
+//! m_Skin[0]["weight[0]"]
+//! ```
+
+use asset::Asset;
+use assetbundle::Signature;
+use engine::EngineObject;
+use error::{Error, Result};
+use extras::containers::OrderedMap;
+use object::ObjectValue;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    /// Named field of a map, engine object or pair (`first` / `second`)
+    Field(String),
+    /// Element of an array
+    Index(usize),
+    /// Every field or element of the current value
+    Wildcard,
+}
+
+/// A parsed query path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub segments: Vec<PathSegment>,
+}
+
+impl Path {
+    pub fn parse(path: &str) -> Result<Path> {
+        let mut segments = Vec::new();
+        let mut chars = path.chars().peekable();
+        let mut name = String::new();
+        // true if the last token was a closing bracket, then a field name may not follow directly
+        let mut after_bracket = false;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '.' => {
+                    if !name.is_empty() {
+                        segments.push(Path::field_segment(&name));
+                        name.clear();
+                    } else if !after_bracket {
+                        return Err(Error::QueryError(format!(
+                            "Empty field name in path {}",
+                            path
+                        )));
+                    }
+                    after_bracket = false;
+                }
+                '[' => {
+                    if !name.is_empty() {
+                        segments.push(Path::field_segment(&name));
+                        name.clear();
+                    }
+                    let mut index = String::new();
+                    let mut quoted = false;
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                        quoted = true;
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some('\\') if chars.peek().is_some() => {
+                                    index.extend(chars.next());
+                                }
+                                Some(c) => index.push(c),
+                                None => {
+                                    return Err(Error::QueryError(format!(
+                                        "Unclosed quote in path {}",
+                                        path
+                                    )));
+                                }
+                            }
+                        }
+                    }
+                    loop {
+                        match chars.next() {
+                            Some(']') => break,
+                            Some(c) if !quoted => index.push(c),
+                            Some(_) => {
+                                return Err(Error::QueryError(format!(
+                                    "Expected ']' after quoted field name in path {}",
+                                    path
+                                )));
+                            }
+                            None => {
+                                return Err(Error::QueryError(format!(
+                                    "Unclosed bracket in path {}",
+                                    path
+                                )));
+                            }
+                        }
+                    }
+                    if quoted {
+                        segments.push(PathSegment::Field(index));
+                    } else if index.trim() == "*" {
+                        segments.push(PathSegment::Wildcard);
+                    } else {
+                        let index = index.trim();
+                        match index.parse::<usize>() {
+                            Ok(i) => segments.push(PathSegment::Index(i)),
+                            Err(_) => {
+                                return Err(Error::QueryError(format!(
+                                    "Invalid array index {:?} in path {}",
+                                    index, path
+                                )));
+                            }
+                        }
+                    }
+                    after_bracket = true;
+                    if let Some(&next) = chars.peek() {
+                        if next != '.' && next != '[' {
+                            return Err(Error::QueryError(format!(
+                                "Expected '.' or '[' after ']' in path {}",
+                                path
+                            )));
+                        }
+                    }
+                }
+                ']' => {
+                    return Err(Error::QueryError(format!(
+                        "Unexpected ']' in path {}",
+                        path
+                    )));
+                }
+                _ => name.push(c),
+            }
+        }
+
+        if !name.is_empty() {
+            segments.push(Path::field_segment(&name));
+        } else if !after_bracket && !path.is_empty() {
+            return Err(Error::QueryError(format!(
+                "Path {} ends with an empty field name",
+                path
+            )));
+        }
+
+        Ok(Path { segments })
+    }
+
+    fn field_segment(name: &str) -> PathSegment {
+        if name == "*" {
+            PathSegment::Wildcard
+        } else {
+            PathSegment::Field(name.to_string())
+        }
+    }
+
+    /// Returns true if the path can match more than one value
+    pub fn has_wildcard(&self) -> bool {
+        self.segments.contains(&PathSegment::Wildcard)
+    }
+
+    /// Collects every value matching this path, starting at `root`
+    pub fn select<'a>(&self, root: &'a ObjectValue) -> Vec<&'a ObjectValue> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for value in current {
+                select_segment(value, segment, &mut next);
+            }
+            if next.is_empty() {
+                return next;
+            }
+            current = next;
+        }
+        current
+    }
+
+    /// Collects every value matching this path, starting at the fields of
+    /// `map`. The empty path matches nothing, as `map` is not a value.
+    pub fn select_in_map<'a>(
+        &self,
+        map: &'a OrderedMap<String, ObjectValue>,
+    ) -> Vec<&'a ObjectValue> {
+        let (first, rest) = match self.segments.split_first() {
+            Some(s) => s,
+            None => return Vec::new(),
+        };
+
+        let mut current = Vec::new();
+        select_in_map(map, first, &mut current);
+        for segment in rest {
+            let mut next = Vec::new();
+            for value in current {
+                select_segment(value, segment, &mut next);
+            }
+            current = next;
+        }
+        current
+    }
+
+    /// Collects every node matching this path, starting at `object`. The
+    /// empty path selects `object` itself.
+    pub fn select_in_object<'a>(&self, object: &'a EngineObject) -> Vec<Selection<'a>> {
+        if self.segments.is_empty() {
+            return vec![Selection::Object(object)];
+        }
+        self.select_in_map(&object.map)
+            .into_iter()
+            .map(Selection::Value)
+            .collect()
+    }
+}
+
+/// A node selected from an `EngineObject`, which is either the engine
+/// object itself or a value within it
+#[derive(Debug, Clone, Copy)]
+pub enum Selection<'a> {
+    Object(&'a EngineObject),
+    Value(&'a ObjectValue),
+}
+
+impl<'a> Selection<'a> {
+    /// The selected value, `None` for the engine object itself
+    pub fn value(self) -> Option<&'a ObjectValue> {
+        match self {
+            Selection::Value(value) => Some(value),
+            Selection::Object(_) => None,
+        }
+    }
+
+    /// The fields of the selected engine object or map
+    pub fn fields(self) -> Option<&'a OrderedMap<String, ObjectValue>> {
+        match self {
+            Selection::Object(object) => Some(&object.map),
+            Selection::Value(ObjectValue::Map(ref map)) => Some(map),
+            Selection::Value(ObjectValue::EngineObject(ref object)) => Some(&object.map),
+            Selection::Value(_) => None,
+        }
+    }
+
+    /// Converts the selected value to `T`
+    pub fn get<T: FromObjectValue>(self) -> Result<T> {
+        match self {
+            Selection::Value(value) => T::from_object_value(value),
+            Selection::Object(_) => Err(Error::QueryError(
+                "An engine object can not be converted to a plain value".to_string(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Field(ref name) => write_field(f, name, i == 0)?,
+                PathSegment::Index(idx) => write!(f, "[{}]", idx)?,
+                PathSegment::Wildcard => write!(f, "[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a field segment the way `Path::parse` reads it back, quoting names
+/// the plain syntax can't express
+fn write_field<W: fmt::Write>(out: &mut W, name: &str, first: bool) -> fmt::Result {
+    let plain = !name.is_empty()
+        && name != "*"
+        && !name.contains(['.', '[', ']', '"']);
+    if plain {
+        if !first {
+            out.write_char('.')?;
+        }
+        return out.write_str(name);
+    }
+    out.write_str("[\"")?;
+    for c in name.chars() {
+        if c == '"' || c == '\\' {
+            out.write_char('\\')?;
+        }
+        out.write_char(c)?;
+    }
+    out.write_str("\"]")
+}
+
+/// Appends a field to a path
+pub(crate) fn join_field(path: &str, field: &str) -> String {
+    let mut res = path.to_string();
+    // writing to a string can't fail
+    let _ = write_field(&mut res, field, path.is_empty());
+    res
+}
+
+fn select_in_map<'a>(
+    map: &'a OrderedMap<String, ObjectValue>,
+    segment: &PathSegment,
+    out: &mut Vec<&'a ObjectValue>,
+) {
+    match segment {
+        PathSegment::Field(ref name) => {
+            if let Some(v) = map.get(name) {
+                out.push(v);
+            }
+        }
+        PathSegment::Wildcard => out.extend(map.values()),
+        PathSegment::Index(_) => {}
+    }
+}
+
+fn select_segment<'a>(
+    value: &'a ObjectValue,
+    segment: &PathSegment,
+    out: &mut Vec<&'a ObjectValue>,
+) {
+    match value {
+        ObjectValue::Map(ref map) => select_in_map(map, segment, out),
+        ObjectValue::EngineObject(ref engine_object) => {
+            select_in_map(&engine_object.map, segment, out)
+        }
+        ObjectValue::Array(ref array) => match segment {
+            PathSegment::Index(idx) => {
+                if let Some(v) = array.get(*idx) {
+                    out.push(v);
+                }
+            }
+            PathSegment::Wildcard => out.extend(array.iter()),
+            PathSegment::Field(_) => {}
+        },
+        ObjectValue::Pair((ref first, ref second)) => match segment {
+            PathSegment::Field(ref name) if name == "first" => out.push(first),
+            PathSegment::Field(ref name) if name == "second" => out.push(second),
+            PathSegment::Index(0) => out.push(first),
+            PathSegment::Index(1) => out.push(second),
+            PathSegment::Wildcard => {
+                out.push(first);
+                out.push(second);
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+}
+
+/// Conversion of a borrowed `ObjectValue` into a plain rust value
+pub trait FromObjectValue: Sized {
+    fn from_object_value(value: &ObjectValue) -> Result<Self>;
+}
+
+macro_rules! impl_from_object_value {
+    ($t: ty, $method: ident) => {
+        impl FromObjectValue for $t {
+            fn from_object_value(value: &ObjectValue) -> Result<Self> {
+                value.$method()
+            }
+        }
+    };
+}
+
+impl_from_object_value!(bool, to_bool);
+impl_from_object_value!(u8, to_u8);
+impl_from_object_value!(i8, to_i8);
+impl_from_object_value!(u16, to_u16);
+impl_from_object_value!(i16, to_i16);
+impl_from_object_value!(u32, to_u32);
+impl_from_object_value!(i32, to_i32);
+impl_from_object_value!(u64, to_u64);
+impl_from_object_value!(i64, to_i64);
+impl_from_object_value!(f32, to_f32);
+impl_from_object_value!(String, to_string);
+
+impl FromObjectValue for Vec<u8> {
+    fn from_object_value(value: &ObjectValue) -> Result<Self> {
+        match value {
+            ObjectValue::U8Array(ref data) => Ok(data.clone()),
+            _ => Err(Error::ObjectError(format!(
+                "ObjectValue is not u8 array variant but {:?}",
+                value
+            ))),
+        }
+    }
+}
+
+fn first_match<'a>(matches: Vec<&'a ObjectValue>, path: &str) -> Result<&'a ObjectValue> {
+    match matches.into_iter().next() {
+        Some(v) => Ok(v),
+        None => Err(Error::QueryError(format!("No value found at path {}", path))),
+    }
+}
+
+fn convert_all<T: FromObjectValue>(matches: Vec<&ObjectValue>) -> Result<Vec<T>> {
+    matches.into_iter().map(T::from_object_value).collect()
+}
+
+impl ObjectValue {
+    /// Returns every value matching `path`
+    pub fn query(&self, path: &str) -> Result<Vec<&ObjectValue>> {
+        Ok(Path::parse(path)?.select(self))
+    }
+
+    /// Returns the first value matching `path`, or an error if there is none
+    pub fn query_one(&self, path: &str) -> Result<&ObjectValue> {
+        first_match(self.query(path)?, path)
+    }
+
+    /// Returns the first value matching `path` converted to `T`
+    pub fn get<T: FromObjectValue>(&self, path: &str) -> Result<T> {
+        T::from_object_value(self.query_one(path)?)
+    }
+
+    /// Returns every value matching `path` converted to `T`
+    pub fn get_all<T: FromObjectValue>(&self, path: &str) -> Result<Vec<T>> {
+        convert_all(self.query(path)?)
+    }
+}
+
+impl EngineObject {
+    /// Returns every node matching `path`, the empty path selects the
+    /// object itself
+    pub fn query(&self, path: &str) -> Result<Vec<Selection<'_>>> {
+        Ok(Path::parse(path)?.select_in_object(self))
+    }
+
+    /// Returns the first node matching `path`, or an error if there is none
+    pub fn query_one(&self, path: &str) -> Result<Selection<'_>> {
+        match self.query(path)?.into_iter().next() {
+            Some(selection) => Ok(selection),
+            None => Err(Error::QueryError(format!(
+                "No value found at path {}",
+                path
+            ))),
+        }
+    }
+
+    /// Returns the first value matching `path` converted to `T`
+    pub fn get<T: FromObjectValue>(&self, path: &str) -> Result<T> {
+        self.query_one(path)?.get()
+    }
+
+    /// Returns every value matching `path` converted to `T`
+    pub fn get_all<T: FromObjectValue>(&self, path: &str) -> Result<Vec<T>> {
+        self.query(path)?
+            .into_iter()
+            .map(|selection| selection.get())
+            .collect()
+    }
+}
+
+impl Asset {
+    /// Reads every object of the asset (optionally only those of `type_name`) and
+    /// calls `f` with each value that matches `path`.
+    pub fn query_objects<F>(
+        &self,
+        signature: &mut Signature,
+        type_name: Option<&str>,
+        path: &str,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(i64, &ObjectValue) -> Result<()>,
+    {
+        let path = Path::parse(path)?;
+
+        let mut path_ids: Vec<i64> = self
+            .objects
+            .values()
+            .filter(|obj| match type_name {
+                Some(t) => obj.type_name == t,
+                None => true,
+            })
+            .map(|obj| obj.path_id)
+            .collect();
+        path_ids.sort();
+
+        for path_id in path_ids {
+            let value = self.objects[&path_id].read_signature(self, signature)?;
+            for matched in path.select(&value) {
+                f(path_id, matched)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the values matching `path` from every object of the asset
+    /// (optionally only those of `type_name`) converted to `T`, keyed by path id.
+    pub fn query_objects_as<T: FromObjectValue>(
+        &self,
+        signature: &mut Signature,
+        type_name: Option<&str>,
+        path: &str,
+    ) -> Result<Vec<(i64, T)>> {
+        let mut result = Vec::new();
+        self.query_objects(signature, type_name, path, |path_id, value| {
+            result.push((path_id, T::from_object_value(value)?));
+            Ok(())
+        })?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn channel(dimension: u8) -> ObjectValue {
+        let mut map = OrderedMap::default();
+        map.insert("stream".to_string(), ObjectValue::U8(0));
+        map.insert("dimension".to_string(), ObjectValue::U8(dimension));
+        ObjectValue::Map(map)
+    }
+
+    fn sample() -> ObjectValue {
+        let mut vertex_data = OrderedMap::default();
+        vertex_data.insert(
+            "m_Channels".to_string(),
+            ObjectValue::Array(vec![channel(3), channel(3), channel(2)]),
+        );
+        let mut map = OrderedMap::default();
+        map.insert(
+            "m_Name".to_string(),
+            ObjectValue::String(OsString::from("cube")),
+        );
+        map.insert("m_VertexData".to_string(), ObjectValue::Map(vertex_data));
+        map.insert(
+            "m_Pair".to_string(),
+            ObjectValue::Pair((
+                Box::new(ObjectValue::I32(1)),
+                Box::new(ObjectValue::I32(2)),
+            )),
+        );
+        ObjectValue::Map(map)
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let path = Path::parse("m_VertexData.m_Channels[2].dimension").unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                PathSegment::Field("m_VertexData".to_string()),
+                PathSegment::Field("m_Channels".to_string()),
+                PathSegment::Index(2),
+                PathSegment::Field("dimension".to_string()),
+            ]
+        );
+        assert_eq!(path.to_string(), "m_VertexData.m_Channels[2].dimension");
+        assert!(Path::parse("m_Component[*].component")
+            .unwrap()
+            .has_wildcard());
+        assert!(Path::parse("a..b").is_err());
+        assert!(Path::parse("a[x]").is_err());
+        assert!(Path::parse("a[1").is_err());
+        assert!(Path::parse("a[1]b").is_err());
+
+        let path = Path::parse(r#"m_Skin[0]["weight[0]"].x["a\"b"]["*"]"#).unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                PathSegment::Field("m_Skin".to_string()),
+                PathSegment::Index(0),
+                PathSegment::Field("weight[0]".to_string()),
+                PathSegment::Field("x".to_string()),
+                PathSegment::Field("a\"b".to_string()),
+                PathSegment::Field("*".to_string()),
+            ]
+        );
+        assert_eq!(Path::parse(&path.to_string()).unwrap(), path);
+        assert_eq!(join_field("", "data[0]"), r#"["data[0]"]"#);
+        assert_eq!(
+            join_field("m_Skin[0]", "boneIndex[0]"),
+            r#"m_Skin[0]["boneIndex[0]"]"#
+        );
+        assert!(Path::parse(r#"a["b"c]"#).is_err());
+        assert!(Path::parse(r#"a["b]"#).is_err());
+    }
+
+    #[test]
+    fn test_query_values() {
+        let value = sample();
+        assert_eq!(value.get::<String>("m_Name").unwrap(), "cube");
+        assert_eq!(
+            value
+                .get::<u8>("m_VertexData.m_Channels[2].dimension")
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            value
+                .get_all::<u8>("m_VertexData.m_Channels[*].dimension")
+                .unwrap(),
+            vec![3, 3, 2]
+        );
+        assert_eq!(value.get::<i32>("m_Pair.second").unwrap(), 2);
+        assert!(value.query("m_VertexData.m_Missing").unwrap().is_empty());
+        assert!(value.query_one("m_VertexData.m_Channels[5]").is_err());
+        assert!(value.get::<u32>("m_Name").is_err());
+    }
+
+    #[test]
+    fn test_query_root() {
+        let value = sample();
+        assert_eq!(value.query("").unwrap().len(), 1);
+
+        let object = match value {
+            ObjectValue::Map(map) => EngineObject::new(map),
+            _ => unreachable!(),
+        };
+        let root = object.query_one("").unwrap();
+        assert_eq!(root.fields().unwrap().len(), 3);
+        assert!(root.value().is_none());
+        assert!(object.get::<u32>("").is_err());
+        assert_eq!(
+            object
+                .get_all::<u8>("m_VertexData.m_Channels[*].dimension")
+                .unwrap(),
+            vec![3, 3, 2]
+        );
+    }
+}
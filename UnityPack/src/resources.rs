@@ -5,20 +5,23 @@
  * All rights reserved 2017
  */
 
-use binaryreader::Endianness;
 use error::{Error, Result};
 use serde_json;
 use std;
 use std::collections::HashMap;
 use std::io;
-use std::io::{BufReader, Cursor};
-use typetree::TypeMetadata;
+use std::sync::{Arc, RwLock};
+use typedb::{TypeDatabase, TypeLookup};
+use typetree::TypeNode;
 
 lazy_static! {
-    static ref DEFAULT_TYPE_METADATA: Result<TypeMetadata> = {
+    static ref TYPE_DATABASE: RwLock<TypeDatabase> = {
+        let mut database = TypeDatabase::new();
         let data: &[u8] = include_bytes!("../res/structs.dat");
-        let mut bin_reader = BufReader::new(Cursor::new(data));
-        TypeMetadata::new(&mut bin_reader, 15, Endianness::Big)
+        if let Err(err) = database.load_from_bytes(data) {
+            eprintln!("Resource import error: Failed to read structs.dat: {}", err);
+        }
+        RwLock::new(database)
     };
 }
 
@@ -51,20 +54,21 @@ lazy_static! {
     };
 }
 
-pub fn default_type_metadata() -> Result<&'static TypeMetadata> {
-    match DEFAULT_TYPE_METADATA.as_ref() {
-        Ok(ref d) => Ok(d),
-        Err(err) => {
-            eprintln!("Failed to read structs.dat");
-            match err {
-                Error::IOError(ref e) => Err(Error::IOError(Box::new(io::Error::new(
-                    e.kind(),
-                    std::error::Error::description(e),
-                )))),
-                Error::ResourceError(ref s) => Err(Error::ResourceError(s.clone())),
-                _ => Err(Error::ResourceError("Unknown".to_string())),
-            }
-        }
+/// The process wide type tree database, initialized with the built-in `structs.dat`
+pub fn type_database() -> &'static RwLock<TypeDatabase> {
+    &TYPE_DATABASE
+}
+
+/// Finds the built-in (or user loaded) type tree of a class for a Unity version
+pub fn default_type_tree(version: &str, class_id: i64) -> Option<Arc<TypeNode>> {
+    default_type_lookup(version, class_id).map(|found| found.tree)
+}
+
+/// Like `default_type_tree`, but also returns the Unity version the type tree was taken from
+pub fn default_type_lookup(version: &str, class_id: i64) -> Option<TypeLookup> {
+    match TYPE_DATABASE.read() {
+        Ok(database) => database.lookup(version, class_id),
+        Err(_) => None,
     }
 }
 
@@ -101,3 +105,27 @@ pub fn get_unity_class(type_id: i64) -> Result<String> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_type_tree() {
+        // structs.dat is stored little endian, reading it as big endian
+        // left the database empty
+        let tree = default_type_tree("5.5.2f1", 28).unwrap();
+        assert_eq!(tree.type_name, "Texture2D");
+        assert_eq!(get_unity_class(28).unwrap(), "Texture2D");
+        let fields: Vec<&str> = tree
+            .children
+            .iter()
+            .map(|child| child.field_name.as_str())
+            .collect();
+        assert!(fields.contains(&"m_Width"));
+        assert!(fields.contains(&"m_TextureFormat"));
+
+        let tree = default_type_tree("5.5.2f1", 1).unwrap();
+        assert_eq!(tree.type_name, "GameObject");
+    }
+}
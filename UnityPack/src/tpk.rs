@@ -0,0 +1,492 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Reader for type tree packages (TPK files).
+//!
+//! A type tree package holds the type trees of every Unity class across many
+//! Unity versions. Nodes and strings are shared between versions, each class
+//! lists the versions in which its layout changed. This is the format of the
+//! community maintained class databases (`uncompressed.tpk`, `lz4.tpk`, ...).
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use error::{Error, Result};
+use extras::lzma::decompress_raw;
+use lz4_compress;
+use lzma;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use typetree::TypeNode;
+use version::UnityVersion;
+
+const TPK_MAGIC: u32 = 0x2A4B_5054; // "TPK*"
+const TPK_VERSION: u8 = 1;
+
+const COMPRESSION_NONE: u8 = 0;
+const COMPRESSION_LZ4: u8 = 1;
+const COMPRESSION_LZMA: u8 = 2;
+const COMPRESSION_BROTLI: u8 = 3;
+
+const DATA_TYPE_TYPE_TREE_INFORMATION: u8 = 0;
+
+const CLASS_FLAG_HAS_EDITOR_ROOT_NODE: u8 = 64;
+const CLASS_FLAG_HAS_RELEASE_ROOT_NODE: u8 = 128;
+
+/// Class layout valid from a Unity version on
+pub struct TpkClass {
+    pub name: u16,
+    pub base: u16,
+    pub flags: u8,
+    pub editor_root_node: Option<u16>,
+    pub release_root_node: Option<u16>,
+}
+
+pub struct TpkClassInformation {
+    pub class_id: i32,
+    /// (packed version, class) pairs in ascending version order,
+    /// `None` means the class does not exist from that version on
+    pub versions: Vec<(u64, Option<TpkClass>)>,
+}
+
+pub struct TpkNode {
+    pub type_name: u16,
+    pub name: u16,
+    pub byte_size: i32,
+    pub version: i16,
+    pub type_flags: u8,
+    pub meta_flag: u32,
+    pub sub_nodes: Vec<u16>,
+}
+
+/// Contents of a type tree package
+pub struct TypePackage {
+    /// creation time as .NET `DateTime` binary value
+    pub creation_time: i64,
+    /// packed Unity versions covered by the package
+    pub versions: Vec<u64>,
+    pub classes: HashMap<i32, TpkClassInformation>,
+    /// packed versions and the number of common strings used by them
+    pub common_string_versions: Vec<(u64, u8)>,
+    pub common_string_indices: Vec<u16>,
+    pub nodes: Vec<TpkNode>,
+    pub strings: Vec<String>,
+    /// type trees which have already been built, keyed by class id and root node
+    cache: Mutex<HashMap<(i32, u16), Arc<TypeNode>>>,
+}
+
+impl TypePackage {
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<TypePackage> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        TypePackage::load_from_bytes(&data)
+    }
+
+    pub fn load_from_bytes(data: &[u8]) -> Result<TypePackage> {
+        let mut reader = Cursor::new(data);
+        let magic = reader.read_u32::<LittleEndian>()?;
+        if magic != TPK_MAGIC {
+            return Err(Error::ResourceError(
+                "Type package has an invalid signature".to_string(),
+            ));
+        }
+        let version = reader.read_u8()?;
+        if version != TPK_VERSION {
+            return Err(Error::ResourceError(format!(
+                "Unsupported type package version {}",
+                version
+            )));
+        }
+        let compression = reader.read_u8()?;
+        let data_type = reader.read_u8()?;
+        if data_type != DATA_TYPE_TYPE_TREE_INFORMATION {
+            return Err(Error::ResourceError(format!(
+                "Type package does not contain type trees but data of type {}",
+                data_type
+            )));
+        }
+        reader.read_u8()?; // reserved
+        reader.read_u32::<LittleEndian>()?; // reserved
+        let compressed_size = reader.read_i32::<LittleEndian>()? as usize;
+        let uncompressed_size = reader.read_i32::<LittleEndian>()? as usize;
+
+        let mut compressed = vec![0; compressed_size];
+        reader.read_exact(compressed.as_mut_slice())?;
+
+        let blob = match compression {
+            COMPRESSION_NONE => compressed,
+            COMPRESSION_LZ4 => match lz4_compress::decompress(&compressed) {
+                Ok(data) => data,
+                Err(err) => return Err(Error::LZ4DecompressionError(Box::new(err))),
+            },
+            COMPRESSION_LZMA => match decompress_raw(&compressed, uncompressed_size) {
+                Ok(data) => data,
+                // fall back to streams with a complete lzma alone header
+                Err(_) => match lzma::decompress(&compressed) {
+                    Ok(data) => data,
+                    Err(err) => return Err(Error::LZMADecompressionError(Box::new(err))),
+                },
+            },
+            COMPRESSION_BROTLI => return Err(Error::CompressionNotImplementedError),
+            _ => {
+                return Err(Error::ResourceError(format!(
+                    "Unknown type package compression {}",
+                    compression
+                )))
+            }
+        };
+
+        if blob.len() != uncompressed_size {
+            return Err(Error::ResourceError(format!(
+                "Type package should be {} bytes but is {} bytes",
+                uncompressed_size,
+                blob.len()
+            )));
+        }
+
+        TypePackage::read_type_tree_blob(&mut Cursor::new(blob.as_slice()))
+    }
+
+    fn read_type_tree_blob<R: Read>(reader: &mut R) -> Result<TypePackage> {
+        let creation_time = reader.read_i64::<LittleEndian>()?;
+
+        let version_count = read_count(reader)?;
+        let mut versions = Vec::with_capacity(version_count);
+        for _ in 0..version_count {
+            versions.push(reader.read_u64::<LittleEndian>()?);
+        }
+
+        let class_count = read_count(reader)?;
+        let mut classes = HashMap::with_capacity(class_count);
+        for _ in 0..class_count {
+            let class_id = reader.read_i32::<LittleEndian>()?;
+            let count = read_count(reader)?;
+            let mut class_versions = Vec::with_capacity(count);
+            for _ in 0..count {
+                let version = reader.read_u64::<LittleEndian>()?;
+                let class = if reader.read_u8()? != 0 {
+                    Some(TypePackage::read_class(reader)?)
+                } else {
+                    None
+                };
+                class_versions.push((version, class));
+            }
+            classes.insert(
+                class_id,
+                TpkClassInformation {
+                    class_id,
+                    versions: class_versions,
+                },
+            );
+        }
+
+        let count = read_count(reader)?;
+        let mut common_string_versions = Vec::with_capacity(count);
+        for _ in 0..count {
+            let version = reader.read_u64::<LittleEndian>()?;
+            common_string_versions.push((version, reader.read_u8()?));
+        }
+        let count = read_count(reader)?;
+        let mut common_string_indices = Vec::with_capacity(count);
+        for _ in 0..count {
+            common_string_indices.push(reader.read_u16::<LittleEndian>()?);
+        }
+
+        let count = read_count(reader)?;
+        let mut nodes = Vec::with_capacity(count);
+        for _ in 0..count {
+            let type_name = reader.read_u16::<LittleEndian>()?;
+            let name = reader.read_u16::<LittleEndian>()?;
+            let byte_size = reader.read_i32::<LittleEndian>()?;
+            let version = reader.read_i16::<LittleEndian>()?;
+            let type_flags = reader.read_u8()?;
+            let meta_flag = reader.read_u32::<LittleEndian>()?;
+            let sub_node_count = reader.read_u16::<LittleEndian>()?;
+            let mut sub_nodes = Vec::with_capacity(sub_node_count as usize);
+            for _ in 0..sub_node_count {
+                sub_nodes.push(reader.read_u16::<LittleEndian>()?);
+            }
+            nodes.push(TpkNode {
+                type_name,
+                name,
+                byte_size,
+                version,
+                type_flags,
+                meta_flag,
+                sub_nodes,
+            });
+        }
+
+        let count = read_count(reader)?;
+        let mut strings = Vec::with_capacity(count);
+        for _ in 0..count {
+            strings.push(read_dotnet_string(reader)?);
+        }
+
+        Ok(TypePackage {
+            creation_time,
+            versions,
+            classes,
+            common_string_versions,
+            common_string_indices,
+            nodes,
+            strings,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn read_class<R: Read>(reader: &mut R) -> Result<TpkClass> {
+        let name = reader.read_u16::<LittleEndian>()?;
+        let base = reader.read_u16::<LittleEndian>()?;
+        let flags = reader.read_u8()?;
+        let editor_root_node = if flags & CLASS_FLAG_HAS_EDITOR_ROOT_NODE != 0 {
+            Some(reader.read_u16::<LittleEndian>()?)
+        } else {
+            None
+        };
+        let release_root_node = if flags & CLASS_FLAG_HAS_RELEASE_ROOT_NODE != 0 {
+            Some(reader.read_u16::<LittleEndian>()?)
+        } else {
+            None
+        };
+        Ok(TpkClass {
+            name,
+            base,
+            flags,
+            editor_root_node,
+            release_root_node,
+        })
+    }
+
+    /// Returns the class layout which is valid for the packed Unity version
+    pub fn class_for_version(&self, class_id: i32, version: u64) -> Option<&TpkClass> {
+        let info = self.classes.get(&class_id)?;
+        let mut result = None;
+        for (v, class) in &info.versions {
+            if *v > version {
+                break;
+            }
+            result = class.as_ref();
+        }
+        result
+    }
+
+    /// Builds the release (player) type tree of a class for a Unity version,
+    /// e.g. `2019.4.31f1`. Classes which only have an editor layout return `None`.
+    pub fn get(&self, version: &str, class_id: i64) -> Option<Arc<TypeNode>> {
+        self.get_tree(version, class_id, false)
+    }
+
+    /// Builds the editor type tree of a class for a Unity version
+    pub fn get_editor(&self, version: &str, class_id: i64) -> Option<Arc<TypeNode>> {
+        self.get_tree(version, class_id, true)
+    }
+
+    fn get_tree(&self, version: &str, class_id: i64, editor: bool) -> Option<Arc<TypeNode>> {
+        let class_id = class_id as i32;
+        let class = self.class_for_version(class_id, pack_version(version))?;
+        let root = if editor {
+            class.editor_root_node?
+        } else {
+            class.release_root_node?
+        };
+
+        if let Ok(cache) = self.cache.lock() {
+            if let Some(tree) = cache.get(&(class_id, root)) {
+                return Some(tree.clone());
+            }
+        }
+
+        let tree = Arc::new(self.build_node(root, 0)?);
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert((class_id, root), tree.clone());
+        }
+        Some(tree)
+    }
+
+    fn build_node(&self, index: u16, depth: usize) -> Option<TypeNode> {
+        // node graphs of valid packages are trees, this guards against cycles
+        if depth > 64 {
+            return None;
+        }
+        let node = self.nodes.get(index as usize)?;
+        let mut children = Vec::with_capacity(node.sub_nodes.len());
+        for sub_node in &node.sub_nodes {
+            children.push(self.build_node(*sub_node, depth + 1)?);
+        }
+        Some(TypeNode::from_parts(
+            self.strings.get(node.type_name as usize)?.clone(),
+            self.strings.get(node.name as usize)?.clone(),
+            node.byte_size,
+            node.version,
+            node.type_flags & 1 != 0,
+            node.meta_flag as i32,
+            children,
+        ))
+    }
+}
+
+fn read_count<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let count = reader.read_i32::<LittleEndian>()?;
+    if count < 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Negative element count in type package",
+        ));
+    }
+    Ok(count as usize)
+}
+
+/// Reads a string prefixed with its 7-bit encoded length (.NET `BinaryWriter` format)
+fn read_dotnet_string<R: Read>(reader: &mut R) -> Result<String> {
+    let mut length: usize = 0;
+    let mut shift = 0;
+    loop {
+        let b = reader.read_u8()?;
+        length |= ((b & 0x7f) as usize) << shift;
+        if b & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 28 {
+            return Err(Error::ResourceError(
+                "Invalid string length in type package".to_string(),
+            ));
+        }
+    }
+    let mut data = vec![0; length];
+    reader.read_exact(data.as_mut_slice())?;
+    match String::from_utf8(data) {
+        Ok(s) => Ok(s),
+        Err(err) => Err(Error::ResourceError(format!("{}", err))),
+    }
+}
+
+/// Packs a Unity version string into the 64-bit representation used by type packages:
+/// major (16 bits), minor (16 bits), build (16 bits), type (8 bits), type number (8 bits).
+/// Invalid versions pack to 0.
+pub fn pack_version(version: &str) -> u64 {
+    match UnityVersion::parse(version) {
+        Ok(version) => version.to_packed(),
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    fn write_string(out: &mut Vec<u8>, s: &str) {
+        out.push(s.len() as u8);
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    fn write_node(out: &mut Vec<u8>, type_name: u16, name: u16, size: i32, children: &[u16]) {
+        out.write_u16::<LittleEndian>(type_name).unwrap();
+        out.write_u16::<LittleEndian>(name).unwrap();
+        out.write_i32::<LittleEndian>(size).unwrap();
+        out.write_i16::<LittleEndian>(1).unwrap();
+        out.push(0);
+        out.write_u32::<LittleEndian>(0).unwrap();
+        out.write_u16::<LittleEndian>(children.len() as u16)
+            .unwrap();
+        for c in children {
+            out.write_u16::<LittleEndian>(*c).unwrap();
+        }
+    }
+
+    pub fn sample_package() -> Vec<u8> {
+        let mut blob = Vec::new();
+        blob.write_i64::<LittleEndian>(0).unwrap();
+        blob.write_i32::<LittleEndian>(1).unwrap();
+        blob.write_u64::<LittleEndian>(pack_version("5.0.0f1"))
+            .unwrap();
+
+        // class 1 exists from 5.0.0f1 on and is removed in 2020.1.0f1
+        blob.write_i32::<LittleEndian>(2).unwrap();
+        blob.write_i32::<LittleEndian>(1).unwrap();
+        blob.write_i32::<LittleEndian>(2).unwrap();
+        blob.write_u64::<LittleEndian>(pack_version("5.0.0f1"))
+            .unwrap();
+        blob.push(1);
+        blob.write_u16::<LittleEndian>(0).unwrap();
+        blob.write_u16::<LittleEndian>(0).unwrap();
+        blob.push(CLASS_FLAG_HAS_RELEASE_ROOT_NODE);
+        blob.write_u16::<LittleEndian>(0).unwrap();
+        blob.write_u64::<LittleEndian>(pack_version("2020.1.0f1"))
+            .unwrap();
+        blob.push(0);
+
+        // class 2 only has an editor layout
+        blob.write_i32::<LittleEndian>(2).unwrap();
+        blob.write_i32::<LittleEndian>(1).unwrap();
+        blob.write_u64::<LittleEndian>(pack_version("5.0.0f1"))
+            .unwrap();
+        blob.push(1);
+        blob.write_u16::<LittleEndian>(0).unwrap();
+        blob.write_u16::<LittleEndian>(0).unwrap();
+        blob.push(CLASS_FLAG_HAS_EDITOR_ROOT_NODE);
+        blob.write_u16::<LittleEndian>(0).unwrap();
+
+        // common strings
+        blob.write_i32::<LittleEndian>(0).unwrap();
+        blob.write_i32::<LittleEndian>(0).unwrap();
+
+        // nodes
+        blob.write_i32::<LittleEndian>(2).unwrap();
+        write_node(&mut blob, 0, 1, -1, &[1]);
+        write_node(&mut blob, 2, 3, 4, &[]);
+
+        // strings
+        blob.write_i32::<LittleEndian>(4).unwrap();
+        for s in &["GameObject", "Base", "int", "m_Layer"] {
+            write_string(&mut blob, s);
+        }
+
+        let mut file = Vec::new();
+        file.write_u32::<LittleEndian>(TPK_MAGIC).unwrap();
+        file.push(TPK_VERSION);
+        file.push(COMPRESSION_NONE);
+        file.push(DATA_TYPE_TYPE_TREE_INFORMATION);
+        file.push(0);
+        file.write_u32::<LittleEndian>(0).unwrap();
+        file.write_i32::<LittleEndian>(blob.len() as i32).unwrap();
+        file.write_i32::<LittleEndian>(blob.len() as i32).unwrap();
+        file.extend(blob);
+        file
+    }
+
+    #[test]
+    fn test_pack_version() {
+        assert_eq!(pack_version("2019.4.31f1"), 0x07E3_0004_001F_0301);
+        assert!(pack_version("5.6.0p3") < pack_version("2017.1.0b2"));
+        assert!(pack_version("2017.1.0b2") < pack_version("2017.1.0f1"));
+    }
+
+    #[test]
+    fn test_load_package() {
+        let package = TypePackage::load_from_bytes(&sample_package()).unwrap();
+        let tree = package.get("5.6.0f3", 1).unwrap();
+        assert_eq!(tree.type_name, "GameObject");
+        assert_eq!(tree.children.len(), 1);
+        assert_eq!(tree.children[0].field_name, "m_Layer");
+        assert_eq!(tree.children[0].size, 4);
+
+        assert!(package.get("4.7.2f1", 1).is_none());
+        assert!(package.get("2020.3.1f1", 1).is_none());
+        assert!(package.get("5.6.0f3", 3).is_none());
+
+        // editor layouts are only returned on request
+        assert!(package.get("5.6.0f3", 2).is_none());
+        assert!(package.get_editor("5.6.0f3", 1).is_none());
+        let tree = package.get_editor("5.6.0f3", 2).unwrap();
+        assert_eq!(tree.type_name, "GameObject");
+    }
+}
@@ -0,0 +1,304 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Database of built-in type trees keyed by Unity version and class id.
+//!
+//! Bundles which are built without type trees only store class ids, the layout
+//! of the classes has to come from a type tree of the same Unity version. The
+//! database holds one set of type trees per Unity version and picks the set
+//! closest to the version of an asset.
+
+use binaryreader::Endianness;
+use error::{Error, Result};
+use resources;
+use std::cmp::Ordering;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+use tpk::TypePackage;
+use typetree::{TypeMetadata, TypeNode};
+use version::UnityVersion;
+
+/// Serialized format of the type tree database files (same as `structs.dat`)
+const DATABASE_FORMAT: u32 = 15;
+
+/// Type trees of a single Unity version
+pub struct TypeDatabaseEntry {
+    pub version: String,
+    pub metadata: TypeMetadata,
+}
+
+/// Type tree found for a class, see `TypeDatabase::lookup`
+#[derive(Clone)]
+pub struct TypeLookup {
+    pub tree: Arc<TypeNode>,
+    /// Unity version the type tree was taken from, which differs from the
+    /// requested version when the database fell back to another version
+    pub version: String,
+}
+
+impl TypeLookup {
+    /// Whether the type tree belongs to another Unity version than `version`
+    pub fn is_fallback(&self, version: &str) -> bool {
+        self.version != version
+    }
+}
+
+#[derive(Default)]
+pub struct TypeDatabase {
+    entries: Vec<TypeDatabaseEntry>,
+    /// type tree packages consulted when no entry matches the exact version
+    packages: Vec<TypePackage>,
+}
+
+impl TypeDatabase {
+    pub fn new() -> TypeDatabase {
+        TypeDatabase::default()
+    }
+
+    /// Adds the type trees of a Unity version, replacing a previous entry of the same version
+    pub fn insert(&mut self, version: &str, metadata: TypeMetadata) {
+        self.entries.retain(|e| e.version != version);
+        self.entries.push(TypeDatabaseEntry {
+            version: version.to_string(),
+            metadata,
+        });
+        self.entries
+            .sort_by(|a, b| compare_versions(&a.version, &b.version));
+    }
+
+    /// Reads a database in the `structs.dat` format, the version is taken from its header
+    pub fn load_from_bytes(&mut self, data: &[u8]) -> Result<()> {
+        let mut reader = BufReader::new(Cursor::new(data));
+        let metadata = TypeMetadata::new(&mut reader, DATABASE_FORMAT, Endianness::Little)?;
+        if metadata.generator_version.is_empty() {
+            return Err(Error::ResourceError(
+                "Type database does not specify a Unity version".to_string(),
+            ));
+        }
+        let version = metadata.generator_version.clone();
+        self.insert(&version, metadata);
+        Ok(())
+    }
+
+    /// Reads a database file in the `structs.dat` format
+    pub fn load_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        self.load_from_bytes(&data)
+    }
+
+    /// Reads every `.dat` file of a directory, returns the number of loaded databases
+    pub fn load_from_directory<P: AsRef<Path>>(&mut self, path: P) -> Result<usize> {
+        let mut count = 0;
+        for entry in fs::read_dir(path)? {
+            let file_path = entry?.path();
+            if file_path.extension().is_some_and(|ext| ext == "dat") {
+                self.load_from_file(&file_path)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Adds a type tree package as an additional source of type trees
+    pub fn add_package(&mut self, package: TypePackage) {
+        self.packages.push(package);
+    }
+
+    pub fn num_packages(&self) -> usize {
+        self.packages.len()
+    }
+
+    pub fn versions(&self) -> Vec<&str> {
+        self.entries.iter().map(|e| e.version.as_str()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the type trees registered for exactly `version`
+    pub fn get_version(&self, version: &str) -> Option<&TypeMetadata> {
+        self.entries
+            .iter()
+            .find(|e| e.version == version)
+            .map(|e| &e.metadata)
+    }
+
+    /// Finds the type tree of a class for the given Unity version, see `lookup`
+    pub fn get(&self, version: &str, class_id: i64) -> Option<Arc<TypeNode>> {
+        self.lookup(version, class_id).map(|found| found.tree)
+    }
+
+    /// Finds the type tree of a class for the given Unity version, together
+    /// with the version it was taken from.
+    ///
+    /// An exact version match is preferred, then the type tree packages, then
+    /// the newest older version which knows the class and finally the oldest
+    /// newer version.
+    pub fn lookup(&self, version: &str, class_id: i64) -> Option<TypeLookup> {
+        if let Some(metadata) = self.get_version(version) {
+            if let Some(tree) = metadata.type_trees.get(&class_id) {
+                return Some(TypeLookup {
+                    tree: tree.clone(),
+                    version: version.to_string(),
+                });
+            }
+        }
+        // packages describe every version they cover
+        for package in &self.packages {
+            if let Some(tree) = package.get(version, class_id) {
+                return Some(TypeLookup {
+                    tree,
+                    version: version.to_string(),
+                });
+            }
+        }
+        self.find(version, class_id).map(|entry| TypeLookup {
+            tree: entry.metadata.type_trees[&class_id].clone(),
+            version: entry.version.clone(),
+        })
+    }
+
+    /// Finds the entry which knows the class and whose version is closest to
+    /// `version`. Type tree packages are not consulted, use `lookup` for the
+    /// same search as `get`.
+    pub fn find(&self, version: &str, class_id: i64) -> Option<&TypeDatabaseEntry> {
+        let candidates: Vec<&TypeDatabaseEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.metadata.type_trees.contains_key(&class_id))
+            .collect();
+
+        if let Some(entry) = candidates.iter().find(|e| e.version == version) {
+            return Some(entry);
+        }
+        if let Some(entry) = candidates
+            .iter()
+            .rev()
+            .find(|e| compare_versions(&e.version, version) != Ordering::Greater)
+        {
+            return Some(entry);
+        }
+        candidates.into_iter().next()
+    }
+}
+
+/// Loads an additional database file into the process wide type tree database
+pub fn load_type_database<P: AsRef<Path>>(path: P) -> Result<()> {
+    let mut database = match resources::type_database().write() {
+        Ok(database) => database,
+        Err(_) => {
+            return Err(Error::ResourceError(
+                "Type database lock is poisoned".to_string(),
+            ))
+        }
+    };
+    database.load_from_file(path)
+}
+
+/// Loads a type tree package (TPK) into the process wide type tree database
+pub fn load_type_package<P: AsRef<Path>>(path: P) -> Result<()> {
+    let package = TypePackage::load_from_file(path)?;
+    match resources::type_database().write() {
+        Ok(mut database) => {
+            database.add_package(package);
+            Ok(())
+        }
+        Err(_) => Err(Error::ResourceError(
+            "Type database lock is poisoned".to_string(),
+        )),
+    }
+}
+
+/// Adds type trees for a Unity version to the process wide type tree database
+pub fn register_type_metadata(version: &str, metadata: TypeMetadata) -> Result<()> {
+    match resources::type_database().write() {
+        Ok(mut database) => {
+            database.insert(version, metadata);
+            Ok(())
+        }
+        Err(_) => Err(Error::ResourceError(
+            "Type database lock is poisoned".to_string(),
+        )),
+    }
+}
+
+/// Finds a type tree for a class in the process wide type tree database
+pub fn lookup_type_tree(version: &str, class_id: i64) -> Option<Arc<TypeNode>> {
+    resources::default_type_tree(version, class_id)
+}
+
+/// Orders version strings as Unity versions, unparseable versions come first
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |version: &str| UnityVersion::parse(version).unwrap_or_default();
+    parse(a).cmp(&parse(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tpk::tests::sample_package;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("5.6.0p3", "2017.1.0f1"), Ordering::Less);
+        assert_eq!(compare_versions("5.5.2f1", "5.5.2f1"), Ordering::Equal);
+        assert_eq!(
+            compare_versions("2019.4.31f1", "2019.4.3f1"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_builtin_database() {
+        let mut database = TypeDatabase::new();
+        database
+            .load_from_bytes(include_bytes!("../res/structs.dat"))
+            .unwrap();
+        assert_eq!(database.versions(), vec!["5.5.2f1"]);
+
+        // Texture2D
+        let tree = database.get("5.5.2f1", 28).unwrap();
+        assert_eq!(tree.type_name, "Texture2D");
+        assert_eq!(database.find("2019.4.31f1", 28).unwrap().version, "5.5.2f1");
+        assert_eq!(database.find("4.7.2f1", 28).unwrap().version, "5.5.2f1");
+        assert!(database.get("5.5.2f1", 1_000_000).is_none());
+
+        let found = database.lookup("5.5.2f1", 28).unwrap();
+        assert!(!found.is_fallback("5.5.2f1"));
+        let found = database.lookup("2019.4.31f1", 28).unwrap();
+        assert_eq!(found.version, "5.5.2f1");
+        assert!(found.is_fallback("2019.4.31f1"));
+        assert!(database.lookup("2019.4.31f1", 1_000_000).is_none());
+    }
+
+    #[test]
+    fn test_package_lookup() {
+        let mut database = TypeDatabase::new();
+        database
+            .load_from_bytes(include_bytes!("../res/structs.dat"))
+            .unwrap();
+        database.add_package(TypePackage::load_from_bytes(&sample_package()).unwrap());
+
+        // the package covers the requested version, the entry is only a fallback
+        let found = database.lookup("5.6.0f3", 1).unwrap();
+        assert_eq!(found.version, "5.6.0f3");
+        assert_eq!(found.tree.children.len(), 1);
+        assert!(Arc::ptr_eq(
+            &database.get("5.6.0f3", 1).unwrap(),
+            &found.tree
+        ));
+        assert_eq!(database.find("5.6.0f3", 1).unwrap().version, "5.5.2f1");
+
+        // versions the package does not cover fall back to the entries
+        assert_eq!(database.lookup("2020.3.1f1", 1).unwrap().version, "5.5.2f1");
+    }
+}
@@ -0,0 +1,508 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Rendering of type trees for documentation purposes.
+//!
+//! Type trees can be dumped as indented text or as JSON, or turned into C# or
+//! Rust struct definitions which mirror the serialized layout of the classes.
+
+use serde_json;
+use std::collections::HashMap;
+use std::fmt::Write;
+use typetree::{TypeMetadata, TypeNode};
+
+const INDENT: &str = "  ";
+
+/// Target language of the generated struct definitions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    CSharp,
+    Rust,
+}
+
+/// Renders a type tree as indented text, one node per line
+pub fn dump_type_tree(node: &TypeNode) -> String {
+    let mut output = String::new();
+    dump_node(node, 0, &mut output);
+    output
+}
+
+/// Renders every type tree of the metadata as indented text, ordered by class id
+pub fn dump_type_metadata(metadata: &TypeMetadata) -> String {
+    let mut output = String::new();
+    let _ = writeln!(output, "Unity version {}", metadata.generator_version);
+    for class_id in sorted_class_ids(metadata) {
+        let _ = writeln!(output, "\nClass {}:", class_id);
+        dump_node(&metadata.type_trees[&class_id], 1, &mut output);
+    }
+    output
+}
+
+fn dump_node(node: &TypeNode, depth: usize, output: &mut String) {
+    let mut attributes = format!(
+        "size={}, index={}, version={}, flags=0x{:x}",
+        node.size,
+        node.index(),
+        node.version(),
+        node.flags()
+    );
+    if node.is_array {
+        attributes.push_str(", array");
+    }
+    if node.post_align() {
+        attributes.push_str(", aligned");
+    }
+    let _ = writeln!(
+        output,
+        "{}{} {} ({})",
+        INDENT.repeat(depth),
+        node.type_name,
+        node.field_name,
+        attributes
+    );
+    for child in &node.children {
+        dump_node(child, depth + 1, output);
+    }
+}
+
+pub fn type_tree_to_json(node: &TypeNode) -> serde_json::Value {
+    let children: Vec<serde_json::Value> = node.children.iter().map(type_tree_to_json).collect();
+    json!({
+        "type": node.type_name,
+        "name": node.field_name,
+        "size": node.size,
+        "index": node.index(),
+        "version": node.version(),
+        "is_array": node.is_array,
+        "flags": node.flags(),
+        "aligned": node.post_align(),
+        "children": children,
+    })
+}
+
+pub fn type_metadata_to_json(metadata: &TypeMetadata) -> serde_json::Value {
+    let classes: Vec<serde_json::Value> = sorted_class_ids(metadata)
+        .into_iter()
+        .map(|class_id| {
+            json!({
+                "class_id": class_id,
+                "type_tree": type_tree_to_json(&metadata.type_trees[&class_id]),
+            })
+        })
+        .collect();
+    json!({
+        "version": metadata.generator_version,
+        "classes": classes,
+    })
+}
+
+/// Generates struct definitions for a single type tree and the types it uses
+pub fn generate_type_structs(node: &TypeNode, language: Language) -> String {
+    let mut generator = StructGenerator::new(language);
+    generator.type_ref(node);
+    generator.output
+}
+
+/// Generates struct definitions for every type tree of the metadata.
+/// Types shared between classes are only generated once.
+pub fn generate_structs(metadata: &TypeMetadata, language: Language) -> String {
+    let mut generator = StructGenerator::new(language);
+    let _ = writeln!(
+        generator.output,
+        "// Generated from the type trees of Unity {}",
+        metadata.generator_version
+    );
+    for class_id in sorted_class_ids(metadata) {
+        generator.type_ref(&metadata.type_trees[&class_id]);
+    }
+    generator.output
+}
+
+fn sorted_class_ids(metadata: &TypeMetadata) -> Vec<i64> {
+    let mut class_ids: Vec<i64> = metadata.type_trees.keys().cloned().collect();
+    class_ids.sort();
+    class_ids
+}
+
+struct StructGenerator {
+    language: Language,
+    names: StructNames,
+    output: String,
+}
+
+impl StructGenerator {
+    fn new(language: Language) -> StructGenerator {
+        StructGenerator {
+            language,
+            names: StructNames::default(),
+            output: String::new(),
+        }
+    }
+
+    /// Returns the type to use for a node, generating the structs it depends on
+    fn type_ref(&mut self, node: &TypeNode) -> String {
+        let t = node.type_name.as_str();
+        if let Some(primitive) = primitive_type(t, self.language) {
+            return primitive.to_string();
+        }
+        if node.is_array {
+            return self.list_ref(&node.children[1]);
+        }
+        if let Some(first_child) = node.children.first() {
+            if first_child.is_array {
+                let element = &first_child.children[1];
+                if t == "map" && element.type_name == "pair" && element.children.len() == 2 {
+                    let key = self.type_ref(&element.children[0]);
+                    let value = self.type_ref(&element.children[1]);
+                    return match self.language {
+                        Language::CSharp => format!("Dictionary<{}, {}>", key, value),
+                        Language::Rust => format!("Vec<({}, {})>", key, value),
+                    };
+                }
+                return self.list_ref(element);
+            }
+        }
+        if t == "pair" && node.children.len() == 2 {
+            let first = self.type_ref(&node.children[0]);
+            let second = self.type_ref(&node.children[1]);
+            return match self.language {
+                Language::CSharp => format!("KeyValuePair<{}, {}>", first, second),
+                Language::Rust => format!("({}, {})", first, second),
+            };
+        }
+        if t.starts_with("PPtr<") {
+            // all pointers share the same layout
+            return self.emit_struct("PPtr".to_string(), node);
+        }
+        self.emit_struct(type_identifier(t), node)
+    }
+
+    fn list_ref(&mut self, element: &TypeNode) -> String {
+        let element = self.type_ref(element);
+        match self.language {
+            Language::CSharp if element == "byte" => "byte[]".to_string(),
+            Language::CSharp => format!("List<{}>", element),
+            Language::Rust => format!("Vec<{}>", element),
+        }
+    }
+
+    /// Generates the struct of a node unless a struct with the same layout was
+    /// generated before
+    fn emit_struct(&mut self, base_name: String, node: &TypeNode) -> String {
+        let (name, generated) = self.names.name(base_name, node);
+        if generated {
+            return name;
+        }
+        // nested types are generated first, so every struct follows its dependencies
+        let fields: Vec<(&TypeNode, String)> = node
+            .children
+            .iter()
+            .map(|child| (child, self.type_ref(child)))
+            .collect();
+
+        let out = &mut self.output;
+        let _ = writeln!(out);
+        match self.language {
+            Language::CSharp => {
+                let _ = writeln!(out, "// {} (version {})", node.type_name, node.version());
+                let _ = writeln!(out, "public class {}\n{{", name);
+                for (child, type_ref) in fields {
+                    let _ = writeln!(
+                        out,
+                        "    public {} {};",
+                        type_ref,
+                        csharp_field_identifier(&child.field_name)
+                    );
+                }
+                let _ = writeln!(out, "}}");
+            }
+            Language::Rust => {
+                let _ = writeln!(out, "/// {} (version {})", node.type_name, node.version());
+                let _ = writeln!(out, "#[allow(non_snake_case)]");
+                let _ = writeln!(out, "#[derive(Debug, Clone)]");
+                let _ = writeln!(out, "pub struct {} {{", name);
+                for (child, type_ref) in fields {
+                    let _ = writeln!(
+                        out,
+                        "    pub {}: {},",
+                        rust_field_identifier(&child.field_name),
+                        type_ref
+                    );
+                }
+                let _ = writeln!(out, "}}");
+            }
+        }
+        name
+    }
+}
+
+/// Names of the generated structs. Types with the same name but a different
+/// layout get a numbered suffix.
+#[derive(Default)]
+pub(crate) struct StructNames {
+    /// struct names by base name, with the layout each of them was generated for
+    variants: HashMap<String, Vec<(String, String)>>,
+}
+
+impl StructNames {
+    /// Returns the struct name for a node and whether a struct with the same
+    /// layout was named before. New names are reserved, so the caller has to
+    /// generate the struct.
+    pub(crate) fn name(&mut self, base_name: String, node: &TypeNode) -> (String, bool) {
+        let layout = layout_signature(node);
+        let variants = self.variants.entry(base_name.clone()).or_default();
+        if let Some((_, name)) = variants.iter().find(|v| v.0 == layout) {
+            return (name.clone(), true);
+        }
+        let name = match variants.len() {
+            0 => base_name,
+            n => format!("{}_{}", base_name, n + 1),
+        };
+        variants.push((layout, name.clone()));
+        (name, false)
+    }
+}
+
+/// Returns the type a primitive Unity type maps to
+pub(crate) fn primitive_type(type_name: &str, language: Language) -> Option<&'static str> {
+    let (csharp, rust) = match type_name {
+        "bool" => ("bool", "bool"),
+        "UInt8" | "char" => ("byte", "u8"),
+        "SInt8" => ("sbyte", "i8"),
+        "UInt16" => ("ushort", "u16"),
+        "SInt16" => ("short", "i16"),
+        "UInt32" | "unsigned int" => ("uint", "u32"),
+        "SInt32" | "int" => ("int", "i32"),
+        "UInt64" | "unsigned long long" | "FileSize" => ("ulong", "u64"),
+        "SInt64" | "long long" => ("long", "i64"),
+        "float" => ("float", "f32"),
+        "double" => ("double", "f64"),
+        "string" => ("string", "String"),
+        _ => return None,
+    };
+    Some(match language {
+        Language::CSharp => csharp,
+        Language::Rust => rust,
+    })
+}
+
+/// Describes everything about a type tree which affects the generated structs.
+/// The field name of the node itself is left out, so a type used by several
+/// fields is generated once, and pointers to all classes share one layout.
+pub(crate) fn layout_signature(node: &TypeNode) -> String {
+    let children: Vec<String> = node
+        .children
+        .iter()
+        .map(|child| format!("{} {}", child.field_name, layout_signature(child)))
+        .collect();
+    let type_name = if node.type_name.starts_with("PPtr<") {
+        "PPtr"
+    } else {
+        node.type_name.as_str()
+    };
+    format!(
+        "{} {} {} {:x}({})",
+        type_name,
+        node.size,
+        node.is_array,
+        node.flags(),
+        children.join(",")
+    )
+}
+
+/// Turns a Unity type or field name into a valid identifier
+fn sanitize_identifier(name: &str) -> String {
+    let mut result: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if result.is_empty() || result.starts_with(|c: char| c.is_ascii_digit()) {
+        result.insert(0, '_');
+    }
+    result
+}
+
+pub(crate) fn type_identifier(type_name: &str) -> String {
+    sanitize_identifier(type_name.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+}
+
+fn csharp_field_identifier(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "async",
+        "await",
+        "base",
+        "bool",
+        "byte",
+        "char",
+        "class",
+        "const",
+        "default",
+        "double",
+        "dyn",
+        "event",
+        "fixed",
+        "float",
+        "int",
+        "internal",
+        "lock",
+        "long",
+        "namespace",
+        "new",
+        "object",
+        "operator",
+        "out",
+        "override",
+        "params",
+        "private",
+        "public",
+        "ref",
+        "short",
+        "string",
+        "struct",
+        "this",
+        "try",
+        "type",
+        "uint",
+        "ulong",
+        "ushort",
+        "value",
+        "virtual",
+        "void",
+    ];
+    let name = sanitize_identifier(name);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("@{}", name)
+    } else {
+        name
+    }
+}
+
+pub(crate) fn rust_field_identifier(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "do", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type",
+        "unsafe", "use", "where", "while",
+    ];
+    let name = sanitize_identifier(name);
+    match name.as_str() {
+        // cannot be used as raw identifiers
+        "self" | "Self" | "super" | "crate" => format!("{}_", name),
+        keyword if KEYWORDS.contains(&keyword) => format!("r#{}", name),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use typedb::TypeDatabase;
+    use typetree::tests::{node, pointer, sample_tree};
+
+    #[test]
+    fn test_dump_type_tree() {
+        let dump = dump_type_tree(&sample_tree());
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 22);
+        assert_eq!(
+            lines[0],
+            "Sample Base (size=-1, index=0, version=1, flags=0x0)"
+        );
+        assert_eq!(
+            lines[7],
+            "    Array Array (size=-1, index=0, version=1, flags=0x4000, array, aligned)"
+        );
+
+        let json = type_tree_to_json(&sample_tree());
+        assert_eq!(json["children"][2]["name"], "m_Weights");
+        assert_eq!(json["children"][2]["children"][0]["aligned"], true);
+    }
+
+    #[test]
+    fn test_generate_structs() {
+        let csharp = generate_type_structs(&sample_tree(), Language::CSharp);
+        assert!(csharp.contains("public class Sample\n{"));
+        assert!(csharp.contains("    public List<float> m_Weights;"));
+        assert!(csharp.contains("    public Dictionary<int, string> m_Table;"));
+        assert!(csharp.contains("    public PPtr m_Texture;"));
+        // dependencies come first
+        assert!(csharp.find("class PPtr").unwrap() < csharp.find("class Sample").unwrap());
+
+        let rust = generate_type_structs(&sample_tree(), Language::Rust);
+        assert!(rust.contains("pub struct Sample {"));
+        assert!(rust.contains("    pub m_Weights: Vec<f32>,"));
+        assert!(rust.contains("    pub m_Table: Vec<(i32, String)>,"));
+        assert!(rust.contains("    pub m_PathID: i64,"));
+    }
+
+    #[test]
+    fn test_generate_shared_structs() {
+        let float = |name: &str| node("float", name, 4, Vec::new());
+        let vector = |name: &str| {
+            node(
+                "Vector3f",
+                name,
+                12,
+                vec![float("x"), float("y"), float("z")],
+            )
+        };
+        let tree = node(
+            "Shared",
+            "Base",
+            -1,
+            vec![
+                vector("m_Center"),
+                vector("m_Extent"),
+                // a different layout with the same name
+                node("Vector3f", "m_Flat", 8, vec![float("x"), float("y")]),
+                pointer("PPtr<Texture2D>", "m_Texture"),
+                pointer("PPtr<Mesh>", "m_Mesh"),
+            ],
+        );
+        let rust = generate_type_structs(&tree, Language::Rust);
+        assert_eq!(rust.matches("pub struct Vector3f {").count(), 1);
+        assert_eq!(rust.matches("pub struct Vector3f_2 {").count(), 1);
+        assert_eq!(rust.matches("pub struct PPtr").count(), 1);
+        assert!(rust.contains("    pub m_Extent: Vector3f,"));
+        assert!(rust.contains("    pub m_Flat: Vector3f_2,"));
+        assert!(rust.contains("    pub m_Mesh: PPtr,"));
+    }
+
+    #[test]
+    fn test_field_identifiers() {
+        assert_eq!(csharp_field_identifier("type"), "@type");
+        assert_eq!(csharp_field_identifier("try"), "@try");
+        assert_eq!(csharp_field_identifier("m_Name"), "m_Name");
+        assert_eq!(rust_field_identifier("type"), "r#type");
+        for keyword in &["async", "await", "dyn", "try"] {
+            assert_eq!(rust_field_identifier(keyword), format!("r#{}", keyword));
+        }
+        assert_eq!(rust_field_identifier("self"), "self_");
+        assert_eq!(rust_field_identifier("m_Local Scale"), "m_Local_Scale");
+    }
+
+    #[test]
+    fn test_export_metadata() {
+        let mut database = TypeDatabase::new();
+        database
+            .load_from_bytes(include_bytes!("../res/structs.dat"))
+            .unwrap();
+        let metadata = database.get_version("5.5.2f1").unwrap();
+
+        let dump = dump_type_metadata(metadata);
+        assert!(dump.starts_with("Unity version 5.5.2f1\n"));
+        assert!(dump.contains("\nClass 28:\n  Texture2D Base"));
+
+        let csharp = generate_structs(metadata, Language::CSharp);
+        assert_eq!(csharp.matches("public class Texture2D\n").count(), 1);
+        let json = type_metadata_to_json(metadata);
+        assert_eq!(
+            json["classes"].as_array().unwrap().len(),
+            metadata.type_trees.len()
+        );
+    }
+}
@@ -7,17 +7,20 @@
 
 use binaryreader::Teller;
 use binaryreader::{Endianness, ReadExtras};
-use enums::{get_runtime_platform, RuntimePlatform};
+use enums::{get_build_target, BuildTarget, PlatformHints};
 use error::{Error, Result};
+use extras::md4::Md4;
 use resources;
 use std::collections::HashMap;
 use std::fmt;
 use std::io::{BufReader, Cursor, Read, Seek};
 use std::sync::Arc;
+use version::UnityVersion;
 
 pub struct TypeMetadata {
-    generator_version: String,
-    target_platform: RuntimePlatform,
+    pub generator_version: String,
+    /// Platform the asset was built for
+    pub target_platform: BuildTarget,
     pub class_ids: Vec<i32>,
     hashes: HashMap<i32, Vec<u8>>,
     pub type_trees: HashMap<i64, Arc<TypeNode>>,
@@ -31,14 +34,14 @@ impl TypeMetadata {
     ) -> Result<TypeMetadata> {
         let mut result = TypeMetadata {
             generator_version: String::new(),
-            target_platform: RuntimePlatform::OSXEditor,
+            target_platform: BuildTarget::NoTarget,
             class_ids: Vec::new(),
             hashes: HashMap::new(),
             type_trees: HashMap::new(),
         };
 
         result.generator_version = try!(buffer.read_string());
-        result.target_platform = get_runtime_platform(try!(buffer.read_u32(endianness)));
+        result.target_platform = get_build_target(try!(buffer.read_u32(endianness)));
 
         if format >= 13 {
             let has_type_trees = try!(buffer.read_bool());
@@ -85,6 +88,66 @@ impl TypeMetadata {
 
         Ok(result)
     }
+
+    /// Parsed `generator_version`, `None` if it is stripped or invalid
+    pub fn version(&self) -> Option<UnityVersion> {
+        match UnityVersion::parse(&self.generator_version) {
+            Ok(version) if !version.is_stripped() => Some(version),
+            _ => None,
+        }
+    }
+
+    /// Decoding hints for the platform the asset was built for
+    pub fn platform_hints(&self) -> PlatformHints {
+        self.target_platform.hints()
+    }
+
+    /// Hashes stored for each class, 16 bytes or 32 bytes for scripted classes
+    pub fn hashes(&self) -> &HashMap<i32, Vec<u8>> {
+        &self.hashes
+    }
+
+    /// Stored hash of the type tree of a class. `None` if the class has no
+    /// hash or the hash was stripped (all zeroes).
+    pub fn type_hash(&self, class_id: i32) -> Option<&[u8]> {
+        let hash = self.hashes.get(&class_id)?;
+        // scripted classes store the hash of the script first
+        let type_hash = &hash[hash.len() - 16..];
+        if type_hash.iter().all(|b| *b == 0) {
+            None
+        } else {
+            Some(type_hash)
+        }
+    }
+
+    /// Hash of the script of a scripted class (class ids below 0)
+    pub fn script_hash(&self, class_id: i32) -> Option<&[u8]> {
+        match self.hashes.get(&class_id) {
+            Some(hash) if hash.len() == 32 => Some(&hash[..16]),
+            _ => None,
+        }
+    }
+
+    /// Compares the hash of `tree` with the hash stored for the class.
+    /// Returns `None` if there is no stored hash to compare with.
+    pub fn verify_type_tree(&self, class_id: i32, tree: &TypeNode) -> Option<bool> {
+        self.type_hash(class_id)
+            .map(|hash| hash == tree.compute_hash())
+    }
+
+    /// Returns the classes whose stored type tree does not match the stored hash
+    pub fn verify_hashes(&self) -> Vec<i32> {
+        let mut mismatched: Vec<i32> = self
+            .type_trees
+            .iter()
+            .filter(|&(class_id, tree)| {
+                self.verify_type_tree(*class_id as i32, tree) == Some(false)
+            })
+            .map(|(class_id, _)| *class_id as i32)
+            .collect();
+        mismatched.sort();
+        mismatched
+    }
 }
 
 pub struct TypeNode {
@@ -93,6 +156,7 @@ pub struct TypeNode {
     pub size: i32,
     index: u32,
     pub is_array: bool,
+    version: i16,
     flags: i32,
     pub children: Vec<TypeNode>,
 }
@@ -105,6 +169,7 @@ lazy_static! {
             size: 0,
             index: 0,
             is_array: false,
+            version: 0,
             flags: 0,
             children: Vec::new(),
         }
@@ -124,6 +189,28 @@ impl TypeNode {
         }
     }
 
+    /// Creates a type tree node from its components, `index` is left at 0
+    pub fn from_parts(
+        type_name: String,
+        field_name: String,
+        size: i32,
+        version: i16,
+        is_array: bool,
+        flags: i32,
+        children: Vec<TypeNode>,
+    ) -> TypeNode {
+        TypeNode {
+            type_name,
+            field_name,
+            size,
+            index: 0,
+            is_array,
+            version,
+            flags,
+            children,
+        }
+    }
+
     fn load_blob<R: Read + Seek + Teller>(
         buffer: &mut R,
         endianness: Endianness,
@@ -145,7 +232,7 @@ impl TypeNode {
 
         for _ in 0..num_nodes {
             // create root element
-            let _ = try!(buf.read_i16(endianness)); // version, unused
+            let version = try!(buf.read_i16(endianness));
             let depth = try!(buf.read_u8()).into();
 
             let is_array = try!(buf.read_u8()) == 1;
@@ -169,6 +256,7 @@ impl TypeNode {
                 size,
                 index,
                 is_array,
+                version,
                 flags,
                 children: Vec::new(),
             };
@@ -215,7 +303,7 @@ impl TypeNode {
         let size = try!(buffer.read_i32(endianness));
         let index = try!(buffer.read_u32(endianness));
         let is_array = try!(buffer.read_i32(endianness)) == 1;
-        let _ = try!(buffer.read_i32(endianness)); // version, unused
+        let version = try!(buffer.read_i32(endianness)) as i16;
         let flags = try!(buffer.read_i32(endianness));
 
         let mut result = TypeNode {
@@ -224,6 +312,7 @@ impl TypeNode {
             size,
             index,
             is_array,
+            version,
             flags,
             children: Vec::new(),
         };
@@ -257,6 +346,41 @@ impl TypeNode {
         Ok(result)
     }
 
+    /// Position of the node in the flattened type tree, the root has index 0
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Version of the serialized layout of this node
+    pub fn version(&self) -> i16 {
+        self.version
+    }
+
+    /// Meta flags of the node, see `post_align`
+    pub fn flags(&self) -> i32 {
+        self.flags
+    }
+
+    /// Computes the hash Unity stores for the type tree, the MD4 digest of all
+    /// nodes in depth first order
+    pub fn compute_hash(&self) -> [u8; 16] {
+        let mut hasher = Md4::default();
+        self.update_hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn update_hash(&self, hasher: &mut Md4) {
+        hasher.update(self.type_name.as_bytes());
+        hasher.update(self.field_name.as_bytes());
+        hasher.update(&self.size.to_le_bytes());
+        hasher.update(&(self.is_array as i32).to_le_bytes());
+        hasher.update(&i32::from(self.version).to_le_bytes());
+        hasher.update(&(self.flags & 0x4000).to_le_bytes());
+        for child in &self.children {
+            child.update_hash(hasher);
+        }
+    }
+
     pub fn post_align(&self) -> bool {
         (self.flags & 0x4000) != 0
     }
@@ -266,8 +390,98 @@ impl fmt::Display for TypeNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "<{} {} (size={}, index={}, is_array={}, flags={})>",
-            self.type_name, self.field_name, self.size, self.index, self.is_array, self.flags
+            "<{} {} (size={}, index={}, version={}, is_array={}, flags={})>",
+            self.type_name,
+            self.field_name,
+            self.size,
+            self.index,
+            self.version,
+            self.is_array,
+            self.flags
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn node(type_name: &str, field_name: &str, size: i32, children: Vec<TypeNode>) -> TypeNode {
+        TypeNode::from_parts(
+            type_name.to_string(),
+            field_name.to_string(),
+            size,
+            1,
+            false,
+            0,
+            children,
+        )
+    }
+
+    pub fn array(element: TypeNode) -> TypeNode {
+        TypeNode::from_parts(
+            "Array".to_string(),
+            "Array".to_string(),
+            -1,
+            1,
+            true,
+            0x4000,
+            vec![node("int", "size", 4, Vec::new()), element],
+        )
+    }
+
+    pub fn pointer(type_name: &str, field_name: &str) -> TypeNode {
+        node(
+            type_name,
+            field_name,
+            12,
+            vec![
+                node("int", "m_FileID", 4, Vec::new()),
+                node("SInt64", "m_PathID", 8, Vec::new()),
+            ],
+        )
+    }
+
+    /// A class with a string, an aligned bool, a vector, a map and a pointer
+    pub fn sample_tree() -> TypeNode {
+        let string = |name: &str| {
+            node(
+                "string",
+                name,
+                -1,
+                vec![array(node("char", "data", 1, Vec::new()))],
+            )
+        };
+        let pair = node(
+            "pair",
+            "data",
+            -1,
+            vec![node("int", "first", 4, Vec::new()), string("second")],
+        );
+        node(
+            "Sample",
+            "Base",
+            -1,
+            vec![
+                string("m_Name"),
+                TypeNode::from_parts(
+                    "bool".to_string(),
+                    "m_Enabled".to_string(),
+                    1,
+                    1,
+                    false,
+                    0x4000,
+                    Vec::new(),
+                ),
+                node(
+                    "vector",
+                    "m_Weights",
+                    -1,
+                    vec![array(node("float", "data", 4, Vec::new()))],
+                ),
+                node("map", "m_Table", -1, vec![array(pair)]),
+                pointer("PPtr<Texture2D>", "m_Texture"),
+            ],
         )
     }
 }
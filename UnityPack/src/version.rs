@@ -0,0 +1,368 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Unity editor versions such as `2019.4.31f1`.
+//!
+//! Versions are ordered by their numeric components first and by the release
+//! type afterwards, so `2017.1.0b2` < `2017.1.0f1` < `2017.1.0p1`. Releases
+//! for China append a `c` number, `2017.4.40f1c1` follows `2017.4.40f1`.
+//! Stripped builds write `0.0.0` as their version, which parses into a version
+//! for which `is_stripped` returns true.
+
+use error::{Error, Result};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Release type of a Unity version, in release order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VersionType {
+    Alpha,
+    Beta,
+    China,
+    Final,
+    Patch,
+    Experimental,
+}
+
+impl VersionType {
+    fn from_char(c: char) -> Option<VersionType> {
+        match c {
+            'a' => Some(VersionType::Alpha),
+            'b' => Some(VersionType::Beta),
+            'c' => Some(VersionType::China),
+            'f' => Some(VersionType::Final),
+            'p' => Some(VersionType::Patch),
+            'x' => Some(VersionType::Experimental),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            VersionType::Alpha => 'a',
+            VersionType::Beta => 'b',
+            VersionType::China => 'c',
+            VersionType::Final => 'f',
+            VersionType::Patch => 'p',
+            VersionType::Experimental => 'x',
+        }
+    }
+}
+
+/// A Unity version. Versions are equal if their components are, no matter
+/// how they were written.
+#[derive(Debug, Clone, Copy)]
+pub struct UnityVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub version_type: VersionType,
+    pub type_number: u8,
+    /// Number of the China release, `1` for `2017.4.40f1c1`
+    pub china_number: Option<u8>,
+    /// whether the parsed string had a build number and a release type,
+    /// `Display` writes the version the same way
+    has_build: bool,
+    has_type: bool,
+}
+
+impl Default for UnityVersion {
+    /// The stripped version `0.0.0`
+    fn default() -> UnityVersion {
+        UnityVersion::new(0, 0, 0)
+    }
+}
+
+impl UnityVersion {
+    /// Creates the final release version `major.minor.build`, e.g. for comparisons.
+    /// As a bound of a `VersionRange` it includes the alpha and beta releases.
+    pub fn new(major: u16, minor: u16, build: u16) -> UnityVersion {
+        UnityVersion {
+            major,
+            minor,
+            build,
+            version_type: VersionType::Final,
+            type_number: 0,
+            china_number: None,
+            has_build: true,
+            has_type: false,
+        }
+    }
+
+    pub fn parse(version: &str) -> Result<UnityVersion> {
+        let invalid = || Error::VersionError(format!("Invalid Unity version {:?}", version));
+
+        let mut parts = version.trim().splitn(3, '.');
+        let major = parse_number(parts.next()).ok_or_else(invalid)?;
+        let minor = parse_number(parts.next()).ok_or_else(invalid)?;
+        let mut result = UnityVersion::new(major, minor, 0);
+        result.has_build = false;
+
+        // build number followed by the release type, e.g. `31f1`, `0p3` or `1f1c1`
+        let (build, suffix) = split_digits(parts.next().unwrap_or(""));
+        if !build.is_empty() {
+            result.build = parse_number(Some(build)).ok_or_else(invalid)?;
+            result.has_build = true;
+        } else if !suffix.is_empty() {
+            return Err(invalid());
+        }
+        let mut chars = suffix.chars();
+        if let Some(c) = chars.next() {
+            result.version_type = VersionType::from_char(c).ok_or_else(invalid)?;
+            result.has_type = true;
+            let (number, mut suffix) = split_digits(chars.as_str());
+            if !number.is_empty() {
+                result.type_number = number.parse().map_err(|_| invalid())?;
+            }
+            if suffix.starts_with('c') {
+                let (number, rest) = split_digits(&suffix[1..]);
+                result.china_number = Some(parse_number(Some(number)).ok_or_else(invalid)?);
+                suffix = rest;
+            }
+            if !suffix.is_empty() {
+                return Err(invalid());
+            }
+        }
+        Ok(result)
+    }
+
+    /// Stripped builds do not store their Unity version
+    pub fn is_stripped(&self) -> bool {
+        self.major == 0 && self.minor == 0 && self.build == 0
+    }
+
+    /// Returns true if this version is `major.minor` or newer
+    pub fn is_at_least(&self, major: u16, minor: u16) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+
+    /// 64-bit representation used by type tree packages: major (16 bits),
+    /// minor (16 bits), build (16 bits), type (8 bits), type number (8 bits).
+    /// China releases share the value of the release they are based on.
+    pub fn to_packed(&self) -> u64 {
+        u64::from(self.major) << 48
+            | u64::from(self.minor) << 32
+            | u64::from(self.build) << 16
+            | (self.version_type as u64) << 8
+            | u64::from(self.type_number)
+    }
+
+    /// The first release of the version's build if it was given without a
+    /// release type, e.g. `2019.1.0a0` for `2019.1.0`
+    fn lowest_release(&self) -> UnityVersion {
+        let mut result = *self;
+        if !self.has_type {
+            result.version_type = VersionType::Alpha;
+            result.type_number = 0;
+        }
+        result
+    }
+
+    fn key(&self) -> (u16, u16, u16, VersionType, u8, Option<u8>) {
+        (
+            self.major,
+            self.minor,
+            self.build,
+            self.version_type,
+            self.type_number,
+            self.china_number,
+        )
+    }
+}
+
+impl PartialEq for UnityVersion {
+    fn eq(&self, other: &UnityVersion) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for UnityVersion {}
+
+impl PartialOrd for UnityVersion {
+    fn partial_cmp(&self, other: &UnityVersion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for UnityVersion {
+    fn cmp(&self, other: &UnityVersion) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for UnityVersion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// Splits a string after its leading digits
+fn split_digits(s: &str) -> (&str, &str) {
+    let digits = s.chars().take_while(|c| c.is_ascii_digit()).count();
+    s.split_at(digits)
+}
+
+fn parse_number<T: FromStr>(part: Option<&str>) -> Option<T> {
+    match part {
+        Some(s) if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+impl FromStr for UnityVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<UnityVersion> {
+        UnityVersion::parse(s)
+    }
+}
+
+/// Writes the components the version was parsed from, so `2017.1` stays
+/// `2017.1`. A changed release type is always written.
+impl fmt::Display for UnityVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        let has_type = self.has_type
+            || self.version_type != VersionType::Final
+            || self.type_number != 0
+            || self.china_number.is_some();
+        if self.has_build || has_type {
+            write!(f, ".{}", self.build)?;
+        }
+        if has_type {
+            write!(f, "{}{}", self.version_type.to_char(), self.type_number)?;
+        }
+        if let Some(number) = self.china_number {
+            write!(f, "c{}", number)?;
+        }
+        Ok(())
+    }
+}
+
+/// Range of Unity versions in which a field or a feature exists. Bounds
+/// without a release type start at the first alpha of their build, so
+/// `2019.1.0` includes `2019.1.0b3`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct VersionRange {
+    /// first version of the range
+    pub from: Option<UnityVersion>,
+    /// first version after the range
+    pub until: Option<UnityVersion>,
+}
+
+impl VersionRange {
+    /// All versions starting with `version`
+    pub fn since(version: UnityVersion) -> VersionRange {
+        VersionRange {
+            from: Some(version),
+            until: None,
+        }
+    }
+
+    /// All versions before `version`
+    pub fn before(version: UnityVersion) -> VersionRange {
+        VersionRange {
+            from: None,
+            until: Some(version),
+        }
+    }
+
+    pub fn between(from: UnityVersion, until: UnityVersion) -> VersionRange {
+        VersionRange {
+            from: Some(from),
+            until: Some(until),
+        }
+    }
+
+    pub fn contains(&self, version: &UnityVersion) -> bool {
+        self.from
+            .is_none_or(|from| *version >= from.lowest_release())
+            && self
+                .until
+                .is_none_or(|until| *version < until.lowest_release())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let version: UnityVersion = "2019.4.31f1".parse().unwrap();
+        assert_eq!((version.major, version.minor, version.build), (2019, 4, 31));
+        assert_eq!(version.version_type, VersionType::Final);
+        assert_eq!(version.type_number, 1);
+        assert_eq!(version.to_string(), "2019.4.31f1");
+        assert_eq!(version.to_packed(), 0x07E3_0004_001F_0301);
+
+        let version = UnityVersion::parse("2022.3.0b2").unwrap();
+        assert_eq!(version.version_type, VersionType::Beta);
+        assert_eq!(
+            UnityVersion::parse("5.6.0p3").unwrap().to_string(),
+            "5.6.0p3"
+        );
+        let china = UnityVersion::parse("2017.4.40f1c1").unwrap();
+        assert_eq!(china.china_number, Some(1));
+        assert_eq!(china.to_string(), "2017.4.40f1c1");
+        assert!(china > UnityVersion::parse("2017.4.40f1").unwrap());
+        assert!(china < UnityVersion::parse("2017.4.40f2").unwrap());
+        assert_eq!(
+            UnityVersion::parse("2017.1").unwrap(),
+            UnityVersion::new(2017, 1, 0)
+        );
+        // versions are written the way they were parsed
+        for version in &[
+            "2017.1",
+            "5.6.0",
+            "2017.1.0f0",
+            "2022.3.0b2",
+            "2018.4.2f1c3",
+        ] {
+            assert_eq!(UnityVersion::parse(version).unwrap().to_string(), *version);
+        }
+        assert_eq!(UnityVersion::new(2017, 1, 0).to_string(), "2017.1.0");
+
+        assert!(UnityVersion::parse("0.0.0").unwrap().is_stripped());
+        assert!(UnityVersion::parse("").is_err());
+        assert!(UnityVersion::parse("5.x.2").is_err());
+        assert!(UnityVersion::parse("5.6.0q1").is_err());
+        assert!(UnityVersion::parse("2019.4.31f1-custom").is_err());
+        assert!(UnityVersion::parse("2017.4.40f1c").is_err());
+    }
+
+    #[test]
+    fn test_ordering() {
+        let parse = |s: &str| UnityVersion::parse(s).unwrap();
+        assert!(parse("5.6.0p3") < parse("2017.1.0f1"));
+        assert!(parse("2017.1.0b2") < parse("2017.1.0f1"));
+        assert!(parse("2017.1.0f1") < parse("2017.1.0p1"));
+        assert!(parse("2019.4.31f1") > parse("2019.4.3f1"));
+        assert!(parse("0.0.0") < parse("3.4.0f5"));
+        assert!(parse("2018.3.0f2").is_at_least(2018, 3));
+        assert!(!parse("2018.2.9f1").is_at_least(2018, 3));
+
+        let range =
+            VersionRange::between(UnityVersion::new(5, 5, 0), UnityVersion::new(2018, 3, 0));
+        assert!(range.contains(&parse("2017.4.1f1")));
+        assert!(!range.contains(&parse("2018.3.0f2")));
+        assert!(!range.contains(&parse("5.4.6f1")));
+        assert!(VersionRange::since(UnityVersion::new(2019, 1, 0)).contains(&parse("2022.3.0b2")));
+        assert!(VersionRange::before(UnityVersion::new(5, 0, 0)).contains(&parse("4.7.2f1")));
+
+        // untyped bounds include the pre-releases of their build
+        let range = VersionRange::since(UnityVersion::new(2019, 1, 0));
+        assert!(range.contains(&parse("2019.1.0b3")));
+        assert!(!range.contains(&parse("2018.4.36f1")));
+        assert!(!VersionRange::since(parse("2019.1.0f1")).contains(&parse("2019.1.0b3")));
+        let range = VersionRange::before(UnityVersion::new(2018, 3, 0));
+        assert!(!range.contains(&parse("2018.3.0b1")));
+        assert!(range.contains(&parse("2018.2.21f1")));
+    }
+}
@@ -0,0 +1,312 @@
+/*
+ * This file is part of the UnityPack rust package.
+ * (c) Istvan Fehervari <gooksl@gmail.com>
+ *
+ * All rights reserved 2017
+ */
+
+//! Streaming decoding of object data.
+//!
+//! `TypeTreeWalker` reads an object following its type tree and reports every
+//! value to a `Visitor` as it is decoded, without building an `ObjectValue`
+//! tree. Every visitor method has an empty default implementation, so a visitor
+//! only needs to implement the events it is interested in.
+
+use asset::Asset;
+use binaryreader::{BinaryReader, ReadExtras, Teller};
+use error::{Error, Result};
+use std::io::{Read, Seek, SeekFrom};
+use typetree::{TypeNode, DEFAULT_TYPENODE};
+
+/// Primitive value decoded from the object data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    Bool(bool),
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    Float(f32),
+}
+
+#[allow(unused_variables)]
+pub trait Visitor {
+    /// Called before the fields of a structure are visited
+    fn enter_struct(&mut self, node: &TypeNode) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after the last field of a structure has been visited
+    fn exit_struct(&mut self, node: &TypeNode) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before the value of a structure field is visited
+    fn field(&mut self, node: &TypeNode) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returning true skips the value of the field without decoding it,
+    /// `field` is not called for skipped fields
+    fn skip_field(&mut self, node: &TypeNode) -> bool {
+        false
+    }
+
+    /// Called before the elements of an array are visited
+    fn array_begin(&mut self, node: &TypeNode, len: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after the last element of an array has been visited
+    fn array_end(&mut self, node: &TypeNode) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for arrays of `UInt8` or `char`, which are not visited element by element
+    fn byte_array(&mut self, node: &TypeNode, data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before the two values of a pair are visited
+    fn enter_pair(&mut self, node: &TypeNode) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called after the second value of a pair has been visited
+    fn exit_pair(&mut self, node: &TypeNode) -> Result<()> {
+        Ok(())
+    }
+
+    fn primitive(&mut self, node: &TypeNode, value: Primitive) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with the raw bytes of a string, which are not guaranteed to be utf-8
+    fn string(&mut self, node: &TypeNode, data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for object pointers, a pointer with file_id and path_id of 0 is a null pointer
+    fn pptr(&mut self, node: &TypeNode, file_id: i32, path_id: i64) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Drives a `Visitor` over object data described by a type tree
+pub struct TypeTreeWalker {
+    long_ids: bool,
+    /// reused buffer for strings and byte arrays
+    scratch: Vec<u8>,
+}
+
+impl TypeTreeWalker {
+    pub fn new(asset: &Asset) -> TypeTreeWalker {
+        TypeTreeWalker {
+            long_ids: asset.format >= 14,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn walk<R: Read + Seek, V: Visitor>(
+        &mut self,
+        typetree: &TypeNode,
+        buffer: &mut BinaryReader<R>,
+        visitor: &mut V,
+    ) -> Result<()> {
+        let mut align = false;
+        let expected_size = typetree.size;
+        let pos_before = buffer.tell();
+        let t = &typetree.type_name;
+
+        if t == "bool" {
+            visitor.primitive(typetree, Primitive::Bool(buffer.read_u8()? != 0))?;
+        } else if t == "UInt8" {
+            visitor.primitive(typetree, Primitive::U8(buffer.read_u8()?))?;
+        } else if t == "SInt8" {
+            visitor.primitive(typetree, Primitive::I8(buffer.read_i8()?))?;
+        } else if t == "UInt16" {
+            visitor.primitive(typetree, Primitive::U16(buffer.read_u16()?))?;
+        } else if t == "SInt16" {
+            visitor.primitive(typetree, Primitive::I16(buffer.read_i16()?))?;
+        } else if t == "UInt32" || t == "unsigned int" {
+            visitor.primitive(typetree, Primitive::U32(buffer.read_u32()?))?;
+        } else if t == "SInt32" || t == "int" {
+            visitor.primitive(typetree, Primitive::I32(buffer.read_i32()?))?;
+        } else if t == "UInt64" {
+            visitor.primitive(typetree, Primitive::U64(buffer.read_u64()?))?;
+        } else if t == "SInt64" {
+            visitor.primitive(typetree, Primitive::I64(buffer.read_i64()?))?;
+        } else if t == "float" {
+            visitor.primitive(typetree, Primitive::Float(buffer.read_f32()?))?;
+        } else if t == "string" {
+            let size = buffer.read_u32()? as usize;
+            self.read_scratch(buffer, size)?;
+            visitor.string(typetree, &self.scratch)?;
+            align = typetree.children[0].post_align();
+        } else {
+            let first_child: &TypeNode = if typetree.is_array {
+                typetree
+            } else {
+                match typetree.children.len() {
+                    x if x > 0 => &typetree.children[0],
+                    _ => &DEFAULT_TYPENODE,
+                }
+            };
+
+            if t.contains("PPtr<") {
+                let (file_id, path_id) = self.read_pointer(buffer);
+                visitor.pptr(typetree, file_id, path_id)?;
+            } else if first_child.is_array {
+                align = first_child.post_align();
+                let size = buffer.read_u32()? as usize;
+                let element = &first_child.children[1];
+                if element.type_name == "char" || element.type_name == "UInt8" {
+                    self.read_scratch(buffer, size)?;
+                    visitor.byte_array(typetree, &self.scratch)?;
+                } else {
+                    visitor.array_begin(typetree, size)?;
+                    for _ in 0..size {
+                        self.walk(element, buffer, visitor)?;
+                    }
+                    visitor.array_end(typetree)?;
+                }
+            } else if t == "pair" {
+                if typetree.children.len() != 2 {
+                    return Err(Error::ObjectError(format!(
+                        "Type pair needs exactly 2 elements not {}",
+                        typetree.children.len()
+                    )));
+                }
+
+                visitor.enter_pair(typetree)?;
+                self.walk(&typetree.children[0], buffer, visitor)?;
+                self.walk(&typetree.children[1], buffer, visitor)?;
+                visitor.exit_pair(typetree)?;
+            } else {
+                visitor.enter_struct(typetree)?;
+                for type_child in &typetree.children {
+                    if visitor.skip_field(type_child) {
+                        self.skip(type_child, buffer)?;
+                        continue;
+                    }
+                    visitor.field(type_child)?;
+                    self.walk(type_child, buffer, visitor)?;
+                }
+                visitor.exit_struct(typetree)?;
+            }
+        }
+
+        // Check to make sure we read at least as many bytes the tree says.
+        // We allow reading more for the case of alignment.
+        let pos_after = buffer.tell();
+        let actual_size = pos_after - pos_before;
+        if (expected_size > 0) && (actual_size < expected_size as u64) {
+            return Err(Error::ObjectError(format!(
+                "Expected read_value({}) to read {} bytes, but only read {} bytes",
+                typetree, expected_size, actual_size
+            )));
+        }
+        if align || typetree.post_align() {
+            buffer.align();
+        }
+
+        Ok(())
+    }
+
+    /// Moves the reader past the value of `typetree` without decoding it.
+    /// Values of fixed size are skipped with a single seek, arrays and strings
+    /// are skipped using their length prefix.
+    pub fn skip<R: Read + Seek>(
+        &mut self,
+        typetree: &TypeNode,
+        buffer: &mut BinaryReader<R>,
+    ) -> Result<()> {
+        let mut align = false;
+        let t = &typetree.type_name;
+
+        if typetree.size > 0 && !typetree.is_array && is_fixed_size(typetree) {
+            buffer.seek(SeekFrom::Current(i64::from(typetree.size)))?;
+        } else if t == "string" {
+            let size = buffer.read_u32()?;
+            buffer.seek(SeekFrom::Current(i64::from(size)))?;
+            align = typetree.children[0].post_align();
+        } else {
+            let first_child: &TypeNode = if typetree.is_array {
+                typetree
+            } else {
+                match typetree.children.len() {
+                    x if x > 0 => &typetree.children[0],
+                    _ => &DEFAULT_TYPENODE,
+                }
+            };
+
+            if t.contains("PPtr<") {
+                self.read_pointer(buffer);
+            } else if first_child.is_array {
+                align = first_child.post_align();
+                let size = buffer.read_u32()?;
+                let element = &first_child.children[1];
+                if element.size > 0 && !element.post_align() && is_fixed_size(element) {
+                    buffer.seek(SeekFrom::Current(i64::from(size) * i64::from(element.size)))?;
+                } else {
+                    for _ in 0..size {
+                        self.skip(element, buffer)?;
+                    }
+                }
+            } else {
+                for type_child in &typetree.children {
+                    self.skip(type_child, buffer)?;
+                }
+            }
+        }
+
+        if align || typetree.post_align() {
+            buffer.align();
+        }
+        Ok(())
+    }
+
+    fn read_scratch<R: Read + Seek>(
+        &mut self,
+        buffer: &mut BinaryReader<R>,
+        size: usize,
+    ) -> Result<()> {
+        self.scratch.clear();
+        self.scratch.resize(size, 0);
+        buffer.read_exact(self.scratch.as_mut_slice())?;
+        Ok(())
+    }
+
+    /// Reads a file id and path id, a pointer which fails to load is
+    /// reported as a null pointer by both `walk` and `skip`
+    fn read_pointer<R: Read + Seek>(&self, buffer: &mut BinaryReader<R>) -> (i32, i64) {
+        let file_id = match buffer.read_i32() {
+            Ok(file_id) => file_id,
+            Err(_) => return (0, 0),
+        };
+        let path_id = if self.long_ids {
+            buffer.read_i64()
+        } else {
+            buffer.read_i32().map(i64::from)
+        };
+        match path_id {
+            Ok(path_id) => (file_id, path_id),
+            Err(_) => (0, 0),
+        }
+    }
+}
+
+/// Returns true if neither the node nor any of its children have a variable
+/// size or require alignment, so that `size` is the exact size of its data.
+fn is_fixed_size(node: &TypeNode) -> bool {
+    if node.is_array || node.type_name == "string" {
+        return false;
+    }
+    node.children
+        .iter()
+        .all(|child| child.size > 0 && !child.post_align() && is_fixed_size(child))
+}
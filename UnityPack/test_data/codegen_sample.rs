@@ -0,0 +1,51 @@
+// Generated by unitypack::codegen from Unity type trees, do not edit.
+#[allow(unused_imports)]
+use crate::binaryreader::{BinaryReader, Teller};
+#[allow(unused_imports)]
+use crate::codegen::{read_array, read_byte_array, ReadTypeTree};
+use crate::error::Result;
+use std::io::{Read, Seek};
+
+/// PPtr<Texture2D> (version 1)
+#[allow(dead_code, non_snake_case)]
+#[derive(Debug, Clone)]
+pub struct PPtr {
+    pub m_FileID: i32,
+    pub m_PathID: i64,
+}
+
+#[allow(non_snake_case)]
+impl ReadTypeTree for PPtr {
+    fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<PPtr> {
+        let m_FileID = i32::read_type_tree(reader)?;
+        let m_PathID = i64::read_type_tree(reader)?;
+        Ok(PPtr { m_FileID, m_PathID })
+    }
+}
+
+/// Sample (version 1)
+#[allow(dead_code, non_snake_case)]
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub m_Name: String,
+    pub m_Enabled: bool,
+    pub m_Weights: Vec<f32>,
+    pub m_Table: Vec<(i32, String)>,
+    pub m_Texture: PPtr,
+}
+
+#[allow(non_snake_case)]
+impl ReadTypeTree for Sample {
+    fn read_type_tree<R: Read + Seek>(reader: &mut BinaryReader<R>) -> Result<Sample> {
+        let m_Name = String::read_type_tree(reader)?;
+        reader.align();
+        let m_Enabled = bool::read_type_tree(reader)?;
+        reader.align();
+        let m_Weights = read_array(reader, f32::read_type_tree)?;
+        reader.align();
+        let m_Table = read_array(reader, |reader| Ok((i32::read_type_tree(reader)?, { let value = String::read_type_tree(reader)?; reader.align(); value })))?;
+        reader.align();
+        let m_Texture = PPtr::read_type_tree(reader)?;
+        Ok(Sample { m_Name, m_Enabled, m_Weights, m_Table, m_Texture })
+    }
+}